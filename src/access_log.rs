@@ -0,0 +1,105 @@
+//! Access logging for the local development server.
+
+#![cfg_attr(not(feature = "local"), allow(dead_code))]
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use http::header::USER_AGENT;
+use http::{Method, Request, Response, Version};
+use serde_json::json;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// The format of the lines written by the local development server's access log.
+///
+/// See [`Builder::access_log`](struct.Builder.html#method.access_log).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessLogFormat {
+    /// The Common Log Format used by web servers such as Apache and nginx, followed by the time
+    /// taken to respond in seconds.
+    #[default]
+    Common,
+    /// A JSON object per line, with the fields an API Gateway access log is commonly configured to
+    /// record: `request_id`, `ip`, `time`, `method`, `path`, `status`, `duration_ms`, and
+    /// `user_agent`. The request ID is the one API Gateway would respond with, and `null` for
+    /// other profiles.
+    Json,
+}
+
+/// The parts of a request that are logged once its response is ready.
+pub(crate) struct Entry {
+    client_ip: IpAddr,
+    time: DateTime<Utc>,
+    start: Instant,
+    method: Method,
+    target: String,
+    version: Version,
+    user_agent: Option<String>,
+}
+
+impl Entry {
+    pub(crate) fn new<B>(client_ip: IpAddr, request: &Request<B>) -> Entry {
+        Entry {
+            client_ip,
+            time: Utc::now(),
+            start: Instant::now(),
+            method: request.method().clone(),
+            target: request
+                .uri()
+                .path_and_query()
+                .map_or_else(|| request.uri().path().to_owned(), ToString::to_string),
+            version: request.version(),
+            user_agent: request
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned),
+        }
+    }
+
+    /// Write the log line for `response` to standard output.
+    pub(crate) fn write(&self, format: AccessLogFormat, response: &Response<Vec<u8>>) {
+        let elapsed = self.start.elapsed();
+        match format {
+            AccessLogFormat::Common => println!("{}", self.common(response, elapsed)),
+            AccessLogFormat::Json => println!("{}", self.json(response, elapsed)),
+        }
+    }
+
+    /// Format the entry in the Common Log Format, followed by the time taken to respond in
+    /// seconds.
+    fn common(&self, response: &Response<Vec<u8>>, elapsed: Duration) -> String {
+        let size = match response.body().len() {
+            0 => "-".to_owned(),
+            size => size.to_string(),
+        };
+        format!(
+            "{} - - [{}] \"{} {} {:?}\" {} {} {:.3}",
+            self.client_ip,
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.method,
+            self.target,
+            self.version,
+            response.status().as_u16(),
+            size,
+            elapsed.as_secs_f64()
+        )
+    }
+
+    fn json(&self, response: &Response<Vec<u8>>, elapsed: Duration) -> String {
+        let request_id = ["x-amzn-requestid", "apigw-requestid"]
+            .iter()
+            .find_map(|name| response.headers().get(*name))
+            .and_then(|value| value.to_str().ok());
+        json!({
+            "request_id": request_id,
+            "ip": self.client_ip.to_string(),
+            "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "method": self.method.as_str(),
+            "path": self.target.split('?').next(),
+            "status": response.status().as_u16(),
+            "duration_ms": elapsed.as_secs_f64() * 1000.0,
+            "user_agent": self.user_agent,
+        })
+        .to_string()
+    }
+}
@@ -0,0 +1,158 @@
+use crate::service::Service;
+use crate::{InvocationError, TraceContext};
+use actix_http::header::{HeaderName, HeaderValue};
+use actix_http::{Payload, Version};
+use actix_service::IntoServiceFactory;
+use actix_web::body::MessageBody;
+use actix_web::dev::{AppConfig, Service as _, ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{App, HttpMessage, HttpResponse};
+use futures::channel::{mpsc, oneshot};
+use futures::{executor, FutureExt, StreamExt};
+use http::{Request, Response};
+use lambda_http::request::RequestContext;
+use lambda_runtime::Context;
+use std::rc::Rc;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+type Invocation = (
+    Request<Vec<u8>>,
+    oneshot::Sender<Result<Response<Vec<u8>>, InvocationError>>,
+);
+
+/// Wrap an actix-web application as a handler.
+///
+/// The application is built when the first execution environment is started, and fails it with
+/// an init error if it can not be; `factory` can only be called once, so any later execution
+/// environments share it.
+pub(crate) fn service<F, S, B>(factory: F) -> Service
+where
+    F: FnOnce() -> App<S> + Send + 'static,
+    S: ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<B>,
+            Error = actix_web::Error,
+            InitError = (),
+        > + 'static,
+    B: MessageBody + 'static,
+{
+    let factory = Mutex::new(Some(factory));
+    let app = OnceLock::new();
+    Service::per_instance(move || {
+        app.get_or_init(|| {
+            let factory = factory.lock().unwrap().take();
+            start(factory.expect("the actix-web application was already built"))
+        })
+        .clone()
+    })
+}
+
+/// Build and run the application, returning a handler sending requests to it.
+///
+/// actix-web services can only be used from the thread they were created on, so the application
+/// is built and run on an actix system on a thread of its own, and requests are sent to it over
+/// a channel.
+fn start<F, S, B>(factory: F) -> Result<Service, String>
+where
+    F: FnOnce() -> App<S> + Send + 'static,
+    S: ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<B>,
+            Error = actix_web::Error,
+            InitError = (),
+        > + 'static,
+    B: MessageBody + 'static,
+{
+    let (tx, mut rx) = mpsc::unbounded::<Invocation>();
+    let (init_tx, init_rx) = oneshot::channel();
+    thread::spawn(move || {
+        actix_rt::System::new().block_on(async move {
+            let app = factory()
+                .into_factory()
+                .new_service(AppConfig::default())
+                .await;
+            let _ = init_tx.send(app.is_ok());
+            let Ok(app) = app else { return };
+            let app = Rc::new(app);
+            while let Some((request, response_tx)) = rx.next().await {
+                let app = app.clone();
+                actix_rt::spawn(async move {
+                    let response = match app.call(into_actix(request)).await {
+                        Ok(response) => from_actix(response.into_parts().1).await,
+                        Err(err) => from_actix(err.error_response()).await,
+                    };
+                    // The receiver is gone if the invocation timed out.
+                    let _ = response_tx.send(response);
+                });
+            }
+        });
+    });
+    match executor::block_on(init_rx) {
+        Ok(true) => {}
+        Ok(false) => return Err("the actix-web application failed to start".to_owned()),
+        Err(_) => return Err("building the actix-web application panicked".to_owned()),
+    }
+
+    Ok(Service::new(
+        move |request| {
+            let (response_tx, response_rx) = oneshot::channel();
+            let sent = tx.unbounded_send((request, response_tx));
+            async move {
+                sent.map_err(|_| InvocationError::new("actix-web application stopped"))?;
+                response_rx
+                    .await
+                    .map_err(|_| InvocationError::new("handler panicked"))?
+            }
+            .boxed()
+        },
+        false,
+    ))
+}
+
+fn into_actix(request: Request<Vec<u8>>) -> actix_http::Request {
+    let (parts, body) = request.into_parts();
+    let mut request = actix_http::Request::with_payload(Payload::from(body));
+    let head = request.head_mut();
+    head.method = parts.method.as_str().parse().expect("invalid method");
+    head.uri = parts.uri.to_string().parse().expect("invalid URI");
+    head.version = match parts.version {
+        http::Version::HTTP_09 => Version::HTTP_09,
+        http::Version::HTTP_10 => Version::HTTP_10,
+        http::Version::HTTP_2 => Version::HTTP_2,
+        _ => Version::HTTP_11,
+    };
+    for (name, value) in &parts.headers {
+        head.headers.append(
+            HeaderName::from_bytes(name.as_str().as_bytes()).expect("invalid header name"),
+            HeaderValue::from_bytes(value.as_bytes()).expect("invalid header value"),
+        );
+    }
+    if let Some(context) = parts.extensions.get::<Context>() {
+        request.extensions_mut().insert(context.clone());
+    }
+    if let Some(context) = parts.extensions.get::<RequestContext>() {
+        request.extensions_mut().insert(context.clone());
+    }
+    if let Some(context) = parts.extensions.get::<TraceContext>() {
+        request.extensions_mut().insert(context.clone());
+    }
+    request
+}
+
+async fn from_actix<B: MessageBody>(
+    response: HttpResponse<B>,
+) -> Result<Response<Vec<u8>>, InvocationError> {
+    let mut builder = Response::builder();
+    builder.status(response.status().as_u16());
+    for (name, value) in response.headers() {
+        builder.header(name.as_str(), value.as_bytes());
+    }
+    let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .map_err(|err| InvocationError::new(err.into()))?;
+    Ok(builder
+        .body(body.to_vec())
+        .expect("failed to convert response"))
+}
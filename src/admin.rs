@@ -0,0 +1,330 @@
+//! Endpoints the local development server serves under `/__lambda/` instead of invoking the
+//! function.
+
+use crate::har::Har;
+use crate::Profile;
+use chrono::{SecondsFormat, Utc};
+use futures01::sync::mpsc::{self, UnboundedSender};
+use futures01::Stream;
+use http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use http::{HeaderMap, Request, Response, StatusCode};
+use lambda_runtime::Context;
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// The path prefix of the endpoints.
+pub(crate) const PREFIX: &str = "/__lambda/";
+
+/// The number of bytes of request and response bodies kept in the invocation history.
+const BODY_PREVIEW_LIMIT: usize = 4096;
+
+/// The upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Invocation counts and latencies for the `/__lambda/metrics` endpoint.
+#[derive(Default)]
+pub(crate) struct Stats {
+    invocations: AtomicU64,
+    errors: AtomicU64,
+    /// The number of invocations that completed in at most each of `BUCKETS`.
+    buckets: [AtomicU64; BUCKETS.len()],
+    duration_micros: AtomicU64,
+}
+
+impl Stats {
+    /// Record an invocation that took `duration`, and whether it failed or timed out.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn record(&self, duration: Duration, error: bool) {
+        self.invocations.fetch_add(1, Ordering::Relaxed);
+        if error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        let seconds = duration.as_secs_f64();
+        for (bucket, le) in self.buckets.iter().zip(&BUCKETS) {
+            if seconds <= *le {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.duration_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Format the statistics in the Prometheus text exposition format.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn response(&self) -> Response<Vec<u8>> {
+        let invocations = self.invocations.load(Ordering::Relaxed);
+        let mut body = String::new();
+        let _ = write!(
+            body,
+            "# HELP lambda_invocations_total The number of times the function was invoked.\n\
+             # TYPE lambda_invocations_total counter\n\
+             lambda_invocations_total {}\n\
+             # HELP lambda_errors_total The number of invocations that failed or timed out.\n\
+             # TYPE lambda_errors_total counter\n\
+             lambda_errors_total {}\n\
+             # HELP lambda_duration_seconds The time taken by invocations.\n\
+             # TYPE lambda_duration_seconds histogram\n",
+            invocations,
+            self.errors.load(Ordering::Relaxed)
+        );
+        for (bucket, le) in self.buckets.iter().zip(&BUCKETS) {
+            let _ = writeln!(
+                body,
+                "lambda_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let _ = write!(
+            body,
+            "lambda_duration_seconds_bucket{{le=\"+Inf\"}} {}\n\
+             lambda_duration_seconds_sum {}\n\
+             lambda_duration_seconds_count {}\n",
+            invocations,
+            self.duration_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+            invocations
+        );
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(body.into_bytes())
+            .expect("failed to build response")
+    }
+}
+
+/// The `/__lambda/health` response: the function the server runs, and for how long it has been
+/// running.
+pub(crate) fn health(context: &Context, profile: Profile, started: Instant) -> Response<Vec<u8>> {
+    let body = json!({
+        "status": "ok",
+        "function_name": context.function_name,
+        "function_version": context.function_version,
+        "memory_size": context.memory_limit_in_mb,
+        "profile": format!("{profile:?}"),
+        "uptime_seconds": started.elapsed().as_secs_f64(),
+        "version": env!("CARGO_PKG_VERSION"),
+    });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string().into_bytes())
+        .expect("failed to build response")
+}
+
+/// The `/__lambda/ready` response: `200 OK` once the handler is ready to receive requests, and
+/// `503 Service Unavailable` while it is still initializing.
+pub(crate) fn ready(ready: bool) -> Response<Vec<u8>> {
+    let (status, body) = if ready {
+        (StatusCode::OK, json!({"status": "ready"}))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({"status": "initializing"}),
+        )
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CACHE_CONTROL, "no-store")
+        .body(body.to_string().into_bytes())
+        .expect("failed to build response")
+}
+
+/// The most recent invocations, for the `/__lambda/invocations` endpoint.
+pub(crate) struct History {
+    capacity: usize,
+    entries: Mutex<VecDeque<Value>>,
+}
+
+impl History {
+    pub(crate) fn new(capacity: usize) -> History {
+        History {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Add an entry, dropping the oldest if the history is full.
+    pub(crate) fn push(&self, entry: Value) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// The entries as a JSON array, most recent first.
+    pub(crate) fn response(&self) -> Response<Vec<u8>> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let body = Value::Array(entries.iter().rev().cloned().collect());
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string().into_bytes())
+            .expect("failed to build response")
+    }
+}
+
+/// The clients of the `/__lambda/events` endpoint, which are sent a summary of each invocation
+/// as a server-sent event.
+#[derive(Default)]
+pub(crate) struct EventStream {
+    clients: Mutex<Vec<UnboundedSender<String>>>,
+}
+
+impl EventStream {
+    /// Start streaming events to a new client.
+    pub(crate) fn subscribe(&self) -> Response<hyper::Body> {
+        let (tx, rx) = mpsc::unbounded();
+        // Sending a comment right away lets clients know the stream is open.
+        let _ = tx.unbounded_send(": connected\n\n".to_owned());
+        self.clients
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(tx);
+        let body = rx.map_err(|()| io::Error::other("event stream closed"));
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .body(hyper::Body::wrap_stream(body))
+            .expect("failed to build response")
+    }
+
+    /// Send an `invocation` event summarizing the history entry `entry` to each client, dropping
+    /// the clients that have disconnected.
+    pub(crate) fn send(&self, entry: &Value) {
+        let summary = json!({
+            "request_id": entry["request_id"],
+            "time": entry["time"],
+            "method": entry["request"]["method"],
+            "uri": entry["request"]["uri"],
+            "status": entry["response"]["status"],
+            "duration_ms": entry["duration_ms"],
+        });
+        let message = format!("event: invocation\ndata: {summary}\n\n");
+        self.clients
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|client| client.unbounded_send(message.clone()).is_ok());
+    }
+}
+
+/// The parts of a request that are kept in the history, or sent to event stream clients, once
+/// its response is ready.
+pub(crate) struct Record {
+    time: String,
+    start: Instant,
+    request_id: Option<String>,
+    request: Value,
+    event: Option<Value>,
+    /// A copy of the whole request, if it is written to a HAR file.
+    har_request: Option<Request<Vec<u8>>>,
+}
+
+impl Record {
+    pub(crate) fn new(request: &Request<Vec<u8>>, har: bool) -> Record {
+        let har_request = if har {
+            Some(copy_request(request))
+        } else {
+            None
+        };
+        Record {
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            start: Instant::now(),
+            request_id: None,
+            event: None,
+            har_request,
+            request: json!({
+                "method": request.method().as_str(),
+                "uri": request.uri().to_string(),
+                "headers": headers(request.headers()),
+                "body": preview(request.body()),
+            }),
+        }
+    }
+
+    /// Set the ID of the Lambda invocation made for the request.
+    pub(crate) fn set_request_id(&mut self, request_id: &str) {
+        self.request_id = Some(request_id.to_owned());
+    }
+
+    /// Set the event the function was invoked with for the request.
+    pub(crate) fn set_event(&mut self, event: &Value) {
+        self.event = Some(event.clone());
+    }
+
+    /// Add the request and `response` to `har`, if the record keeps a copy of the request.
+    pub(crate) fn add_to_har(&self, har: &Har, response: &Response<Vec<u8>>) {
+        if let Some(request) = &self.har_request {
+            har.push(&self.time, self.start.elapsed(), request, response);
+        }
+    }
+
+    /// The history entry for `response`.
+    pub(crate) fn finish(self, response: &Response<Vec<u8>>) -> Value {
+        json!({
+            "request_id": self.request_id,
+            "time": self.time,
+            "duration_ms": self.start.elapsed().as_secs_f64() * 1000.0,
+            "request": self.request,
+            "event": self.event,
+            "response": {
+                "status": response.status().as_u16(),
+                "headers": headers(response.headers()),
+                "body": preview(response.body()),
+            },
+        })
+    }
+}
+
+/// The dashboard page, which shows the invocation history.
+#[cfg(feature = "dashboard")]
+pub(crate) fn dashboard() -> Response<Vec<u8>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(include_bytes!("dashboard.html").to_vec())
+        .expect("failed to build response")
+}
+
+/// Copy a request, without its extensions.
+pub(crate) fn copy_request(request: &Request<Vec<u8>>) -> Request<Vec<u8>> {
+    let mut copy = Request::new(request.body().clone());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    copy
+}
+
+/// Headers as a JSON object, with the values of repeated headers joined by commas.
+fn headers(headers: &HeaderMap) -> Value {
+    let mut map = Map::new();
+    for name in headers.keys() {
+        let values = headers
+            .get_all(name)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()))
+            .collect::<Vec<_>>();
+        map.insert(name.as_str().to_owned(), values.join(", ").into());
+    }
+    Value::Object(map)
+}
+
+/// The start of a body as text, with its full size.
+fn preview(body: &[u8]) -> Value {
+    let truncated = body.len() > BODY_PREVIEW_LIMIT;
+    json!({
+        "text": String::from_utf8_lossy(&body[..body.len().min(BODY_PREVIEW_LIMIT)]),
+        "size": body.len(),
+        "truncated": truncated,
+    })
+}
@@ -0,0 +1,768 @@
+#[cfg(feature = "local")]
+use crate::Profile;
+#[cfg(feature = "local")]
+use chrono::Utc;
+#[cfg(feature = "local")]
+use http::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+use http::Request;
+#[cfg(feature = "local")]
+use http::{Response, StatusCode};
+#[cfg(feature = "local")]
+use ring::{hmac, signature};
+#[cfg(feature = "local")]
+use serde_json::json;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// An emulated JWT authorizer for an API Gateway HTTP API.
+///
+/// When configured with [`Builder::jwt_authorizer`], the local development server validates the
+/// token in each request's `Authorization` header before invoking the function, as API Gateway
+/// does. Requests without a valid token receive a `401 Unauthorized` response; for other requests,
+/// the token's claims are passed to the function in the `authorizer` field of the request
+/// context.
+///
+/// The token must not have expired, must be issued by the configured issuer, and, if any
+/// audiences are configured, must have an `aud` or `client_id` claim matching one of them.
+///
+/// Token signatures are checked with the public keys configured with [`jwks`](#method.jwks), as
+/// API Gateway checks them with the keys the issuer publishes, or with a shared secret configured
+/// with [`hs256_secret`](#method.hs256_secret). The local development server does not fetch keys
+/// from the issuer, so until some are configured, every token is rejected, unless signatures are
+/// not checked at all with [`skip_signature_verification`](#method.skip_signature_verification).
+///
+/// [`Builder::jwt_authorizer`]: struct.Builder.html#method.jwt_authorizer
+///
+/// # Example
+///
+/// ```rust
+/// # use http::Response;
+/// use lambda_http_local::{JwtAuthorizer, Profile, Runtime};
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// let server = Runtime::builder()
+///     .listen_addr("localhost:0")
+///     .quiet(true)
+///     .profile(Profile::HttpApi)
+///     .jwt_authorizer(JwtAuthorizer::new("https://auth.example.com/").audience("my-api"))
+///     .build()
+///     .spawn(|request| Response::new("Hello, world!"))
+///     .unwrap();
+///
+/// let mut stream = TcpStream::connect(server.addr()).unwrap();
+/// stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).unwrap();
+/// assert!(response.starts_with("HTTP/1.0 401 Unauthorized"));
+/// ```
+#[derive(Clone)]
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+pub struct JwtAuthorizer {
+    issuer: String,
+    audiences: Vec<String>,
+    secret: Option<Vec<u8>>,
+    keys: Vec<Jwk>,
+    skip_signature_verification: bool,
+}
+
+/// A public key from a JSON Web Key Set.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+struct Jwk {
+    /// The `kid` of the key, which tokens signed with it name in their header.
+    id: Option<String>,
+    key: PublicKey,
+}
+
+#[derive(Clone)]
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+enum PublicKey {
+    /// An RSA key for RS256, with its big-endian modulus and exponent.
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    /// A P-256 key for ES256, as an uncompressed point.
+    P256(Vec<u8>),
+}
+
+impl JwtAuthorizer {
+    /// Create an authorizer accepting tokens issued by `issuer`, the value of their `iss` claim.
+    #[must_use]
+    pub fn new<T: Into<String>>(issuer: T) -> JwtAuthorizer {
+        JwtAuthorizer {
+            issuer: issuer.into(),
+            audiences: Vec::new(),
+            secret: None,
+            keys: Vec::new(),
+            skip_signature_verification: false,
+        }
+    }
+
+    /// Add an audience the authorizer accepts tokens for.
+    ///
+    /// By default, tokens for any audience are accepted.
+    #[must_use]
+    pub fn audience<T: Into<String>>(mut self, audience: T) -> JwtAuthorizer {
+        self.audiences.push(audience.into());
+        self
+    }
+
+    /// Accept tokens signed using HS256 with `secret`.
+    #[must_use]
+    pub fn hs256_secret<T: Into<Vec<u8>>>(mut self, secret: T) -> JwtAuthorizer {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Accept tokens signed with the keys in `jwks`, a JSON Web Key Set such as the one the
+    /// issuer publishes at the `jwks_uri` of its `/.well-known/openid-configuration`.
+    ///
+    /// RSA keys are used for tokens signed using RS256, and P-256 keys for those signed using
+    /// ES256; other keys, and those only for encryption, are ignored. If a token's header has a
+    /// `kid`, only the key with that ID is used.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use lambda_http_local::JwtAuthorizer;
+    /// use std::fs;
+    ///
+    /// let jwks = serde_json::from_slice(&fs::read("jwks.json").unwrap()).unwrap();
+    /// let authorizer = JwtAuthorizer::new("https://auth.example.com/").jwks(&jwks);
+    /// ```
+    #[must_use]
+    pub fn jwks(mut self, jwks: &Value) -> JwtAuthorizer {
+        let keys = jwks.get("keys").and_then(Value::as_array);
+        self.keys
+            .extend(keys.into_iter().flatten().filter_map(Jwk::parse));
+        self
+    }
+
+    /// Accept tokens without checking their signatures, for tokens from an issuer whose keys are
+    /// not available locally.
+    ///
+    /// Anyone can then forge a token that the authorizer accepts, so the local development server
+    /// prints a warning when it starts.
+    #[must_use]
+    pub fn skip_signature_verification(mut self) -> JwtAuthorizer {
+        self.skip_signature_verification = true;
+        self
+    }
+
+    /// A warning to print when the local development server starts, if token signatures are not
+    /// checked, or no token can be accepted.
+    #[cfg(feature = "local")]
+    pub(crate) fn warning(&self) -> Option<&'static str> {
+        if self.skip_signature_verification {
+            Some("Warning: the JWT authorizer does not check token signatures")
+        } else if self.secret.is_none() && self.keys.is_empty() {
+            Some(
+                "Warning: the JWT authorizer has no keys to check token signatures with, so it \
+                 rejects every token; configure them with JwtAuthorizer::jwks or \
+                 JwtAuthorizer::hs256_secret",
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Validate the token in `headers`, returning the `authorizer` field of the request context.
+    #[cfg(feature = "local")]
+    pub(crate) fn authorize(&self, headers: &HeaderMap) -> Result<Value, Rejected> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.strip_prefix("Bearer ").unwrap_or(value).trim())
+            .filter(|token| !token.is_empty())
+            .ok_or(Rejected::MissingToken)?;
+        let claims = self.validate(token).map_err(Rejected::InvalidToken)?;
+
+        // HTTP APIs pass every claim to the function as a string.
+        let claims = claims
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(value) => value,
+                    Value::Array(values) => format!(
+                        "[{}]",
+                        values
+                            .iter()
+                            .map(|value| value
+                                .as_str()
+                                .map_or_else(|| value.to_string(), str::to_owned))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ),
+                    value => value.to_string(),
+                };
+                (name, Value::String(value))
+            })
+            .collect::<Map<_, _>>();
+        Ok(json!({ "claims": claims, "scopes": null }))
+    }
+
+    #[cfg(feature = "local")]
+    fn validate(&self, token: &str) -> Result<Map<String, Value>, &'static str> {
+        let invalid = "the token is not a valid JWT";
+        let (signed, signature) = token.rsplit_once('.').ok_or(invalid)?;
+        let (header, payload) = signed.split_once('.').ok_or(invalid)?;
+        let header = decode_segment(header).ok_or(invalid)?;
+        let claims = decode_segment(payload).ok_or(invalid)?;
+        let signature =
+            base64::decode_config(signature, base64::URL_SAFE_NO_PAD).map_err(|_| invalid)?;
+        if !self.skip_signature_verification {
+            self.verify(&header, signed.as_bytes(), &signature)?;
+        }
+
+        let now = Utc::now().timestamp();
+        let time = |name| claims.get(name).and_then(Value::as_i64);
+        match time("exp") {
+            Some(exp) if exp > now => {}
+            Some(_) => return Err("the token has expired"),
+            None => return Err("the token has no exp claim"),
+        }
+        if time("nbf").is_some_and(|nbf| nbf > now) {
+            return Err("the token is not valid yet");
+        }
+        if time("iat").is_some_and(|iat| iat > now) {
+            return Err("the token was issued in the future");
+        }
+        if claims.get("iss").and_then(Value::as_str) != Some(self.issuer.as_str()) {
+            return Err("the token has an invalid issuer");
+        }
+        if !self.audiences.is_empty() {
+            let mut audiences = match claims.get("aud") {
+                Some(Value::String(aud)) => vec![aud.as_str()],
+                Some(Value::Array(aud)) => aud.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            audiences.extend(claims.get("client_id").and_then(Value::as_str));
+            if !audiences
+                .iter()
+                .any(|aud| self.audiences.iter().any(|expected| expected == aud))
+            {
+                return Err("the token has an invalid audience");
+            }
+        }
+        Ok(claims)
+    }
+
+    /// Check the `signature` of the token with `header` over `message` with the configured keys.
+    #[cfg(feature = "local")]
+    fn verify(
+        &self,
+        header: &Map<String, Value>,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), &'static str> {
+        let no_key = "no key matches the token";
+        let kid = header.get("kid").and_then(Value::as_str);
+        let verified = match header.get("alg").and_then(Value::as_str) {
+            Some("HS256") => {
+                let secret = self.secret.as_ref().ok_or(no_key)?;
+                hmac::verify(
+                    &hmac::Key::new(hmac::HMAC_SHA256, secret),
+                    message,
+                    signature,
+                )
+                .is_ok()
+            }
+            Some(alg @ ("RS256" | "ES256")) => {
+                let mut keys = self
+                    .keys
+                    .iter()
+                    .filter(|key| key.matches(alg, kid))
+                    .peekable();
+                keys.peek().ok_or(no_key)?;
+                keys.any(|key| key.verify(message, signature))
+            }
+            _ => return Err("the token is not signed using a supported algorithm"),
+        };
+        if verified {
+            Ok(())
+        } else {
+            Err("the token signature is invalid")
+        }
+    }
+}
+
+impl Jwk {
+    /// Parse a key of a JSON Web Key Set, if it is one that can check token signatures.
+    fn parse(key: &Value) -> Option<Jwk> {
+        let field = |name| {
+            let value = key.get(name)?.as_str()?;
+            base64::decode_config(value, base64::URL_SAFE_NO_PAD).ok()
+        };
+        if key.get("use").is_some_and(|usage| usage != "sig") {
+            return None;
+        }
+        let public_key = match (key.get("kty")?.as_str()?, key.get("crv")) {
+            ("RSA", _) => PublicKey::Rsa {
+                n: field("n")?,
+                e: field("e")?,
+            },
+            ("EC", Some(crv)) if crv == "P-256" => {
+                let mut point = vec![4];
+                point.extend(field("x")?);
+                point.extend(field("y")?);
+                PublicKey::P256(point)
+            }
+            _ => return None,
+        };
+        Some(Jwk {
+            id: key.get("kid").and_then(Value::as_str).map(str::to_owned),
+            key: public_key,
+        })
+    }
+
+    /// Whether the key can check the signature of a token signed using `alg` with the key `kid`.
+    #[cfg(feature = "local")]
+    fn matches(&self, alg: &str, kid: Option<&str>) -> bool {
+        let alg_matches = match self.key {
+            PublicKey::Rsa { .. } => alg == "RS256",
+            PublicKey::P256(_) => alg == "ES256",
+        };
+        alg_matches && kid.is_none_or(|kid| self.id.as_deref() == Some(kid))
+    }
+
+    #[cfg(feature = "local")]
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match &self.key {
+            PublicKey::Rsa { n, e } => signature::RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, signature)
+                .is_ok(),
+            PublicKey::P256(point) => {
+                signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                    .verify(message, signature)
+                    .is_ok()
+            }
+        }
+    }
+}
+
+/// A Lambda authorizer function, called with the request before the handler.
+pub(crate) type LambdaAuthorizer = Arc<dyn Fn(&Request<()>) -> Authorization + Send + Sync>;
+
+/// The result of an emulated Lambda authorizer, set with [`Builder::lambda_authorizer`].
+///
+/// [`Builder::lambda_authorizer`]: struct.Builder.html#method.lambda_authorizer
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+pub struct Authorization {
+    effect: Effect,
+    principal_id: String,
+    context: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Effect {
+    Allow,
+    Deny,
+    Unauthorized,
+}
+
+impl Authorization {
+    /// Allow the request, on behalf of the user identified by `principal_id`.
+    #[must_use]
+    pub fn allow<T: Into<String>>(principal_id: T) -> Authorization {
+        Authorization {
+            effect: Effect::Allow,
+            principal_id: principal_id.into(),
+            context: Map::new(),
+        }
+    }
+
+    /// Deny the request with a policy, so the client receives a `403 Forbidden` response.
+    #[must_use]
+    pub fn deny() -> Authorization {
+        Authorization {
+            effect: Effect::Deny,
+            ..Authorization::allow("")
+        }
+    }
+
+    /// Reject the request as unauthenticated, as authorizers do by failing with the error
+    /// `Unauthorized`, so the client receives a `401 Unauthorized` response.
+    #[must_use]
+    pub fn unauthorized() -> Authorization {
+        Authorization {
+            effect: Effect::Unauthorized,
+            ..Authorization::allow("")
+        }
+    }
+
+    /// Add a value to the context passed to the function in the `authorizer` field of the
+    /// request context.
+    #[must_use]
+    pub fn context<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Authorization {
+        self.context.insert(key.into(), Value::String(value.into()));
+        self
+    }
+
+    /// Convert the result into the `authorizer` field of the request context.
+    #[cfg(feature = "local")]
+    pub(crate) fn into_context(self) -> Result<Value, Rejected> {
+        match self.effect {
+            Effect::Allow => {
+                let mut context = self.context;
+                context.insert("principalId".into(), self.principal_id.into());
+                context.insert("integrationLatency".into(), 0.into());
+                Ok(Value::Object(context))
+            }
+            Effect::Deny => Err(Rejected::Denied),
+            Effect::Unauthorized => Err(Rejected::MissingToken),
+        }
+    }
+}
+
+/// The header local requests use to select a set of claims configured with
+/// [`Builder::cognito_user`](struct.Builder.html#method.cognito_user).
+#[cfg(feature = "local")]
+const COGNITO_USER_HEADER: &str = "x-local-cognito-user";
+
+/// Canned claims passed to the function as if the request was authorized by a Cognito user pool.
+#[derive(Clone, Default)]
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+pub(crate) struct CognitoClaims {
+    pub(crate) default: Option<Map<String, Value>>,
+    pub(crate) users: HashMap<String, Map<String, Value>>,
+}
+
+impl CognitoClaims {
+    #[cfg(feature = "local")]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.default.is_none() && self.users.is_empty()
+    }
+
+    /// Select the claims for `headers`, removing the header used to select them, and return the
+    /// `authorizer` field of the request context.
+    #[cfg(feature = "local")]
+    pub(crate) fn authorize(&self, headers: &mut HeaderMap) -> Result<Value, Rejected> {
+        let claims = match headers.remove(COGNITO_USER_HEADER) {
+            Some(user) => user
+                .to_str()
+                .ok()
+                .and_then(|user| self.users.get(user))
+                .ok_or(Rejected::InvalidToken("unknown Cognito user"))?,
+            None => self.default.as_ref().ok_or(Rejected::MissingToken)?,
+        };
+        Ok(json!({ "claims": claims }))
+    }
+}
+
+/// Why a request was rejected by an authorizer, or for its API key.
+#[cfg(feature = "local")]
+#[derive(Debug)]
+pub(crate) enum Rejected {
+    MissingToken,
+    InvalidToken(&'static str),
+    Denied,
+    InvalidApiKey,
+    QuotaExceeded,
+}
+
+#[cfg(feature = "local")]
+impl Rejected {
+    /// The response API Gateway returns for the rejected request.
+    pub(crate) fn response(&self, profile: Profile) -> Response<Vec<u8>> {
+        let (status, body): (_, &[u8]) = match self {
+            Rejected::MissingToken | Rejected::InvalidToken(_) => {
+                (StatusCode::UNAUTHORIZED, br#"{"message":"Unauthorized"}"#)
+            }
+            Rejected::Denied if profile == Profile::RestApi => (
+                StatusCode::FORBIDDEN,
+                br#"{"Message":"User is not authorized to access this resource with an explicit deny"}"#,
+            ),
+            Rejected::Denied | Rejected::InvalidApiKey => {
+                (StatusCode::FORBIDDEN, br#"{"message":"Forbidden"}"#)
+            }
+            Rejected::QuotaExceeded => (
+                StatusCode::TOO_MANY_REQUESTS,
+                br#"{"message":"Limit Exceeded"}"#,
+            ),
+        };
+
+        let mut response = Response::builder();
+        response
+            .status(status)
+            .header(CONTENT_TYPE, "application/json");
+        if let Rejected::InvalidToken(reason) = self {
+            response.header(
+                WWW_AUTHENTICATE,
+                format!("Bearer scope=\"\" error=\"invalid_token\" error_description=\"{reason}\""),
+            );
+        }
+        response
+            .body(body.to_vec())
+            .expect("failed to build response")
+    }
+}
+
+#[cfg(feature = "local")]
+fn decode_segment(segment: &str) -> Option<Map<String, Value>> {
+    let json = base64::decode_config(segment, base64::URL_SAFE_NO_PAD).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+#[cfg(all(test, feature = "local"))]
+mod tests {
+    use super::JwtAuthorizer;
+    use chrono::Utc;
+    use ring::hmac;
+    use ring::rand::SystemRandom;
+    use ring::signature::{self, EcdsaKeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents};
+    use serde_json::{json, Value};
+
+    const ISSUER: &str = "https://auth.example.com/";
+    const SECRET: &[u8] = b"secret";
+
+    /// A 2048-bit RSA private key, in PKCS #8 DER encoding, for tests only.
+    const RSA_KEY: &str = "
+MIIEvAIBADANBgkqhkiG9w0BAQEFAASCBKYwggSiAgEAAoIBAQDPreSL8flFul/xR449w4c5RWcFJeIBrZiRCR5e
+W1jSP4vRThWWw/GKyH4X4DxghGbqAldXCecfQctutNEBR6ok1HGdBtEUbm3z7eNos5MESVYKySTEQKlmGYw4HLXj
+k6OdUuCF6oM7lag2oIfxImSp/XHUsBwFFAkVTJDmBeQsJoHCgl08ec8GW+8ZyfhWFQaUA0nkma4DPwnkJ2heMJVY
+akuygZJOpMqxA0IdCutJfwhRsXQRqGFGcbN0L6MH1Sba416G4q8ZznX2PFDHa7bt5qj7VRxOndU6YZClFq3kMBm0
+Mv7i7YfXj8xrvPLgfsyMLSRTw1FcZWrTINCLWNQ7AgMBAAECggEAUpMnzmUV4h2qzDrRWcmG2MS5o5hedqBZi0F+
+67GSbXaK11wexWV/gtkZGdE08IN0kBMCOz9awFje1FPczo96HU7DJdZyB5eo7FZoF1dfW1L1fTBhCA9Vy5zY3e68
+e5JWedVcJKMMLwLgcWf+5Wfxco2tVw329iXzPp7E9lDdWpf1h4BNlS9gZUYXJ1A4gATMCQJivDgNpFiTOhzQrtNT
+Z6RMAXob/wbDu7qTnMxYdDHnacbJhrgo2M31gL2ditCKv0mQs4YHlOhFvkCOihalqQmCopV2kQfa1jsQyMtU1K9a
+XyQ7BVHfVyfXntC/4bfSkOdXHcqAqbzGmQgzp58PkQKBgQDuGM1bCRrzZxuxuMjHlekgJjGB52q+37KRm6kBDTa4
+vDDmXrNpuobe/+vVoKk0eNoxqs/igV9o1G859Va2ViLR54Y9cMOfSvSyrnfW9o4qwJTLgRbyTzsRwOdG4/NJ0CbX
+MUOqojorclvH6qqi8nxgVx45CCvLnyUASatmpgVM1wKBgQDfS5KnBGGw/4YQ9abKJNtwZwleh5lfDXs5AiHU8E4w
+1XGI88g1z3yyjJTiwZWDzWD08/XLMouVRR/nrWI0qYqqdmV63odTSpQaQk1g7z/9KKvWt67CF3N3UPGfe2yf3lDA
+D3RyKYGz5kg1Zxy9HJlR+CsnXoRhXhl9PoLx2GkDPQKBgESlU/euPV312U85FnIsr4T8XgO1A9q6RhKjDWvnMmK6
+p2KT5ShThfUfVhBGfOJLAaWWOM92O1rO/K56JKD3XpykItjt/QidJMWrdZO9jCmZUo0Fz+bWnxI0SrnumMMsRzZ9
+1qOImDrUgs+1wbKvNNrV4znQOEhBvvpBQzCaJycLAoGAaThRWMZlymP+Jsu61pJbnTHQZmz7RqExqYFps8+w6K4L
+m/9UHpcxKWm5VsjlzX/f6k1zS1b8E1DJYgB/wFUL9Jtp4mYt2n/QHjeUAtN6ka6ymrFfdjJaxKSt1vrwCr1Vy8r8
+kjcg2UbwS8nU7wAk9007+Ic/VipjVHM6fP//K/kCgYAzCFS0XdVJ9nEy1g8AXMGa8bUmcgW2EOnw+cBw7AUd+6cb
+a41Ukjh1uZjz0RsoGqFuCB7JZ5IL28ijg5iIOIMD9KdOmRwOehRkdsKDU0zmLYfKGVvTtUbVA6GzJT81SWKZKd0j
+zFYzEMaT80kIAj3E6L56xr+Bu+rxGOJPvCZpKg==";
+
+    fn base64url(bytes: &[u8]) -> String {
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// A token with `header` and `claims`, with the signature `sign` returns.
+    fn token(header: &Value, claims: &Value, sign: impl Fn(&[u8]) -> Vec<u8>) -> String {
+        let signed = format!(
+            "{}.{}",
+            base64url(header.to_string().as_bytes()),
+            base64url(claims.to_string().as_bytes())
+        );
+        let signature = sign(signed.as_bytes());
+        format!("{}.{}", signed, base64url(&signature))
+    }
+
+    fn hs256(secret: &[u8], claims: &Value) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+        token(&json!({ "alg": "HS256" }), claims, |message| {
+            hmac::sign(&key, message).as_ref().to_vec()
+        })
+    }
+
+    fn claims() -> Value {
+        json!({
+            "iss": ISSUER,
+            "aud": "my-api",
+            "sub": "user",
+            "exp": Utc::now().timestamp() + 60,
+        })
+    }
+
+    fn with(claim: &str, value: Value) -> Value {
+        let mut claims = claims();
+        claims[claim] = value;
+        claims
+    }
+
+    fn authorizer() -> JwtAuthorizer {
+        JwtAuthorizer::new(ISSUER)
+            .audience("my-api")
+            .hs256_secret(SECRET)
+    }
+
+    #[test]
+    fn valid() {
+        let claims = authorizer().validate(&hs256(SECRET, &claims())).unwrap();
+        assert_eq!(claims["sub"], "user");
+    }
+
+    #[test]
+    fn bad_signature() {
+        assert_eq!(
+            authorizer().validate(&hs256(b"other", &claims())),
+            Err("the token signature is invalid")
+        );
+    }
+
+    #[test]
+    fn tampered_claims() {
+        let token = hs256(SECRET, &claims());
+        let forged = hs256(SECRET, &with("sub", json!("admin")));
+        let mut parts = token.split('.').collect::<Vec<_>>();
+        parts[1] = forged.split('.').nth(1).unwrap();
+        assert_eq!(
+            authorizer().validate(&parts.join(".")),
+            Err("the token signature is invalid")
+        );
+    }
+
+    #[test]
+    fn expired() {
+        let now = Utc::now().timestamp();
+        assert_eq!(
+            authorizer().validate(&hs256(SECRET, &with("exp", json!(now - 1)))),
+            Err("the token has expired")
+        );
+        assert_eq!(
+            authorizer().validate(&hs256(SECRET, &with("exp", Value::Null))),
+            Err("the token has no exp claim")
+        );
+        assert_eq!(
+            authorizer().validate(&hs256(SECRET, &with("nbf", json!(now + 60)))),
+            Err("the token is not valid yet")
+        );
+    }
+
+    #[test]
+    fn wrong_audience() {
+        assert_eq!(
+            authorizer().validate(&hs256(SECRET, &with("aud", json!("other")))),
+            Err("the token has an invalid audience")
+        );
+        let mut claims = with("aud", Value::Null);
+        claims["client_id"] = json!("my-api");
+        assert!(authorizer().validate(&hs256(SECRET, &claims)).is_ok());
+    }
+
+    #[test]
+    fn wrong_issuer() {
+        assert_eq!(
+            authorizer().validate(&hs256(SECRET, &with("iss", json!("https://evil.example/")))),
+            Err("the token has an invalid issuer")
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        for token in &["", "abc", "a.b", "a.b.c", "e30.e30.!!"] {
+            assert_eq!(
+                authorizer().validate(token),
+                Err("the token is not a valid JWT"),
+                "{token}"
+            );
+        }
+    }
+
+    #[test]
+    fn unsigned() {
+        let token = token(&json!({ "alg": "none" }), &claims(), |_| Vec::new());
+        assert_eq!(
+            authorizer().validate(&token),
+            Err("the token is not signed using a supported algorithm")
+        );
+    }
+
+    #[test]
+    fn no_keys() {
+        let authorizer = JwtAuthorizer::new(ISSUER);
+        assert!(authorizer.warning().is_some());
+        assert_eq!(
+            authorizer.validate(&hs256(SECRET, &claims())),
+            Err("no key matches the token")
+        );
+    }
+
+    #[test]
+    fn skip_signature_verification() {
+        let authorizer = JwtAuthorizer::new(ISSUER).skip_signature_verification();
+        assert!(authorizer.warning().is_some());
+        assert!(authorizer.validate(&hs256(b"other", &claims())).is_ok());
+        assert_eq!(
+            authorizer.validate(&hs256(b"other", &with("iss", json!("other")))),
+            Err("the token has an invalid issuer")
+        );
+    }
+
+    #[test]
+    fn rs256() {
+        let der = base64::decode(&RSA_KEY.split_whitespace().collect::<String>()).unwrap();
+        let key_pair = RsaKeyPair::from_pkcs8(&der).unwrap();
+        let public = RsaPublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+        let authorizer = JwtAuthorizer::new(ISSUER).jwks(&json!({
+            "keys": [
+                { "kty": "RSA", "kid": "enc", "use": "enc", "n": "AQAB", "e": "AQAB" },
+                { "kty": "RSA", "kid": "sig", "n": base64url(&public.n), "e": base64url(&public.e) },
+            ]
+        }));
+        assert!(authorizer.warning().is_none());
+        let rs256 = |kid: &str, claims: &Value| {
+            token(&json!({ "alg": "RS256", "kid": kid }), claims, |message| {
+                let mut signature = vec![0; key_pair.public().modulus_len()];
+                key_pair
+                    .sign(
+                        &signature::RSA_PKCS1_SHA256,
+                        &SystemRandom::new(),
+                        message,
+                        &mut signature,
+                    )
+                    .unwrap();
+                signature
+            })
+        };
+        assert!(authorizer.validate(&rs256("sig", &claims())).is_ok());
+        assert_eq!(
+            authorizer.validate(&rs256("other", &claims())),
+            Err("no key matches the token")
+        );
+        assert_eq!(
+            authorizer.validate(&rs256("enc", &claims())),
+            Err("no key matches the token")
+        );
+        let token = rs256("sig", &claims());
+        let forged = rs256("sig", &with("sub", json!("admin")));
+        let signature = forged.rsplit('.').next().unwrap();
+        let (signed, _) = token.rsplit_once('.').unwrap();
+        assert_eq!(
+            authorizer.validate(&format!("{signed}.{signature}")),
+            Err("the token signature is invalid")
+        );
+        // An HS256 token is not accepted by an authorizer with only public keys.
+        assert_eq!(
+            authorizer.validate(&hs256(SECRET, &claims())),
+            Err("no key matches the token")
+        );
+    }
+
+    #[test]
+    fn es256() {
+        let rng = SystemRandom::new();
+        let algorithm = &signature::ECDSA_P256_SHA256_FIXED_SIGNING;
+        let key_pair = |rng| {
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(algorithm, rng).unwrap();
+            EcdsaKeyPair::from_pkcs8(algorithm, pkcs8.as_ref(), rng).unwrap()
+        };
+        let (key, other) = (key_pair(&rng), key_pair(&rng));
+        let point = key.public_key().as_ref();
+        let authorizer = JwtAuthorizer::new(ISSUER).jwks(&json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": base64url(&point[1..33]),
+                "y": base64url(&point[33..]),
+            }]
+        }));
+        let es256 = |key: &EcdsaKeyPair| {
+            token(&json!({ "alg": "ES256" }), &claims(), |message| {
+                key.sign(&rng, message).unwrap().as_ref().to_vec()
+            })
+        };
+        assert!(authorizer.validate(&es256(&key)).is_ok());
+        assert_eq!(
+            authorizer.validate(&es256(&other)),
+            Err("the token signature is invalid")
+        );
+        let rs256 = token(&json!({ "alg": "RS256" }), &claims(), |_| vec![0; 256]);
+        assert_eq!(authorizer.validate(&rs256), Err("no key matches the token"));
+    }
+}
@@ -0,0 +1,165 @@
+//! Injecting latency and failures into the local development server's responses, for testing how
+//! clients calling the function cope with them.
+
+use crate::context::random;
+use crate::runtime::ErrorMatcher;
+use crate::Runtime;
+use futures01::stream;
+use http::header::{HeaderValue, CONTENT_LENGTH};
+use http::{Request, Response};
+use std::io;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// How often, and how, requests are disrupted.
+pub(crate) struct Chaos {
+    /// The fraction of requests delayed, and the range of the delay.
+    latency: Option<(f64, RangeInclusive<Duration>)>,
+    /// The fraction of requests whose connection is closed without a response.
+    drop_rate: f64,
+    /// The fraction of responses whose body is cut off partway through.
+    truncate_rate: f64,
+    /// The fraction of matching requests that receive a service error instead of invoking the
+    /// function.
+    errors: Option<(f64, ErrorMatcher)>,
+    seed: u64,
+    /// The number of requests decided so far, which together with the seed determines what
+    /// happens to the next one.
+    requests: AtomicU64,
+}
+
+/// What happens to a request.
+#[derive(Debug, Default)]
+pub(crate) struct Disruption {
+    /// How long to delay the response.
+    pub(crate) latency: Option<Duration>,
+    /// Whether to close the connection instead of responding.
+    pub(crate) drop: bool,
+    /// Whether to cut off the response's body.
+    pub(crate) truncate: bool,
+    /// Whether to return a service error instead of invoking the function.
+    pub(crate) error: bool,
+}
+
+/// A request extension marking a request that receives a service error instead of invoking the
+/// function.
+#[derive(Clone, Copy)]
+pub(crate) struct InjectedError;
+
+impl Chaos {
+    /// The disruptions `runtime` injects, chosen from its seed or a random one, or `None` if
+    /// there are none.
+    pub(crate) fn new(runtime: &Runtime) -> Option<Chaos> {
+        let latency = runtime
+            .injected_latency
+            .clone()
+            .filter(|(rate, _)| *rate > 0.0);
+        let errors = runtime
+            .injected_errors
+            .clone()
+            .filter(|(rate, _)| *rate > 0.0);
+        let (drop_rate, truncate_rate) = (
+            runtime.dropped_connection_rate,
+            runtime.truncated_response_rate,
+        );
+        let seed = runtime.simulation_seed.unwrap_or_else(random);
+        (latency.is_some() || drop_rate > 0.0 || truncate_rate > 0.0 || errors.is_some()).then(
+            || Chaos {
+                latency,
+                drop_rate,
+                truncate_rate,
+                errors,
+                seed,
+                requests: AtomicU64::new(0),
+            },
+        )
+    }
+
+    /// The seed the disruptions are chosen from, which reproduces them when set with
+    /// `Builder::simulation_seed`.
+    pub(crate) fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Decide what happens to `request`, the next request to arrive.
+    ///
+    /// Each request's choices are drawn from its own generator, seeded from the seed and the
+    /// number of requests that arrived before it, so they do not depend on how the handling of
+    /// concurrent requests interleaves.
+    pub(crate) fn disrupt<B>(&self, request: &Request<B>) -> Disruption {
+        let sequence = self.requests.fetch_add(1, Ordering::Relaxed);
+        let mut rng = Rng::for_request(self.seed, sequence);
+        let latency = self
+            .latency
+            .as_ref()
+            .filter(|(rate, _)| rng.chance(*rate))
+            .map(|(_, range)| rng.duration(range));
+        let drop = rng.chance(self.drop_rate);
+        let truncate = !drop && rng.chance(self.truncate_rate);
+        let error = self.errors.as_ref().is_some_and(|(rate, matcher)| {
+            matcher(request.method(), request.uri().path()) && rng.chance(*rate)
+        });
+        Disruption {
+            latency,
+            drop,
+            truncate,
+            error,
+        }
+    }
+}
+
+/// Cut off the body of `response` halfway through, closing the connection, while it still
+/// declares its full length.
+pub(crate) fn truncate(response: Response<Vec<u8>>) -> Response<hyper::Body> {
+    let (mut parts, mut body) = response.into_parts();
+    parts
+        .headers
+        .insert(CONTENT_LENGTH, HeaderValue::from(body.len() as u64));
+    body.truncate(body.len() / 2);
+    // hyper closes the connection when a body ends before its declared length.
+    let chunks = stream::once::<_, io::Error>(Ok(body));
+    Response::from_parts(parts, hyper::Body::wrap_stream(chunks))
+}
+
+/// A small, fast pseudorandom number generator (`SplitMix64`), which gives the same sequence of
+/// numbers for the same seed.
+struct Rng(u64);
+
+impl Rng {
+    const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    /// The generator for the request with `sequence` requests before it, seeded with the number
+    /// at that position in the sequence `seed` generates.
+    fn for_request(seed: u64, sequence: u64) -> Rng {
+        let mut rng = Rng(seed.wrapping_add(sequence.wrapping_mul(Rng::GAMMA)));
+        Rng(rng.next())
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(Rng::GAMMA);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, 1)`.
+    #[allow(clippy::cast_precision_loss)]
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Whether an event with `probability` happens.
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.unit() < probability
+    }
+
+    fn duration(&mut self, range: &RangeInclusive<Duration>) -> Duration {
+        let (start, end) = (*range.start(), *range.end());
+        if end <= start {
+            return start;
+        }
+        start + end.saturating_sub(start).mul_f64(self.unit())
+    }
+}
@@ -0,0 +1,188 @@
+use chrono::NaiveDateTime;
+#[cfg(feature = "tls")]
+use chrono::{TimeZone, Utc};
+use serde_json::{json, Value};
+
+/// The format of validity times in API Gateway events.
+const TIME_FORMAT: &str = "%b %e %H:%M:%S %Y GMT";
+
+/// The client certificate a request was authenticated with, when the API requires mutual TLS.
+///
+/// Handlers can get it with
+/// [`RequestExt::client_cert`](trait.RequestExt.html#tymethod.client_cert), which reads it from
+/// the `clientCert` field of the request context in API Gateway events. With the `tls` feature,
+/// the local development server sends it for certificates validated against
+/// [`Builder::tls_client_ca`](struct.Builder.html#method.tls_client_ca).
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Request, Response};
+/// use lambda_http_local::{fixtures, testing, RequestExt};
+///
+/// let handler = |request: Request<&[u8]>| {
+///     let subject = request.client_cert().map(|cert| cert.subject_dn().to_owned());
+///     Response::new(subject.unwrap_or_else(|| "anonymous".to_owned()))
+/// };
+/// let event = serde_json::json!({
+///     "httpMethod": "GET", "path": "/", "headers": {"host": "api.example.com"},
+///     "requestContext": {
+///         "httpMethod": "GET", "path": "/", "stage": "prod", "accountId": "", "resourceId": "",
+///         "resourcePath": "/", "requestId": "", "apiId": "",
+///         "identity": {
+///             "sourceIp": "192.0.2.1",
+///             "clientCert": {
+///                 "clientCertPem": "-----BEGIN CERTIFICATE-----\n...",
+///                 "subjectDN": "CN=client.example.com",
+///                 "issuerDN": "CN=Example CA",
+///                 "serialNumber": "01:02:03",
+///                 "validity": {
+///                     "notBefore": "May 28 12:30:02 2024 GMT",
+///                     "notAfter": "Aug  5 09:36:04 2025 GMT"
+///                 }
+///             }
+///         }
+///     }
+/// });
+/// let request = fixtures::from_value(event).unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(response.body(), b"CN=client.example.com");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCert {
+    pem: String,
+    subject_dn: String,
+    issuer_dn: String,
+    serial_number: String,
+    not_before: String,
+    not_after: String,
+}
+
+impl ClientCert {
+    /// The PEM-encoded certificate.
+    #[must_use]
+    pub fn pem(&self) -> &str {
+        &self.pem
+    }
+
+    /// The distinguished name of the certificate's subject, such as `CN=client.example.com`.
+    #[must_use]
+    pub fn subject_dn(&self) -> &str {
+        &self.subject_dn
+    }
+
+    /// The distinguished name of the certificate's issuer.
+    #[must_use]
+    pub fn issuer_dn(&self) -> &str {
+        &self.issuer_dn
+    }
+
+    /// The certificate's serial number, as colon-separated hex bytes.
+    #[must_use]
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    /// The start of the certificate's validity period, such as `May 28 12:30:02 2024 GMT`.
+    #[must_use]
+    pub fn not_before(&self) -> &str {
+        &self.not_before
+    }
+
+    /// The end of the certificate's validity period.
+    #[must_use]
+    pub fn not_after(&self) -> &str {
+        &self.not_after
+    }
+
+    /// Read the `clientCert` field of an API Gateway request context.
+    pub(crate) fn from_event(client_cert: &Value) -> Option<ClientCert> {
+        let field = |pointer| {
+            client_cert
+                .pointer(pointer)
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+        };
+        Some(ClientCert {
+            pem: field("/clientCertPem")?,
+            subject_dn: field("/subjectDN").unwrap_or_default(),
+            issuer_dn: field("/issuerDN").unwrap_or_default(),
+            serial_number: field("/serialNumber").unwrap_or_default(),
+            not_before: field("/validity/notBefore").unwrap_or_default(),
+            not_after: field("/validity/notAfter").unwrap_or_default(),
+        })
+    }
+
+    /// The `clientCert` field of an API Gateway request context.
+    pub(crate) fn to_event(&self) -> Value {
+        json!({
+            "clientCertPem": self.pem,
+            "subjectDN": self.subject_dn,
+            "issuerDN": self.issuer_dn,
+            "serialNumber": self.serial_number,
+            "validity": {
+                "notBefore": self.not_before,
+                "notAfter": self.not_after,
+            },
+        })
+    }
+
+    /// Read the details of a DER-encoded certificate.
+    #[cfg(feature = "tls")]
+    pub(crate) fn from_der(der: &[u8]) -> Option<ClientCert> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+        let time = |time: x509_parser::time::ASN1Time| {
+            Utc.timestamp_opt(time.timestamp(), 0)
+                .single()
+                .map(|time| time.format(TIME_FORMAT).to_string())
+        };
+        let base64 = base64::encode(der);
+        let mut pem = "-----BEGIN CERTIFICATE-----\n".to_owned();
+        for line in base64.as_bytes().chunks(64) {
+            pem.push_str(std::str::from_utf8(line).ok()?);
+            pem.push('\n');
+        }
+        pem.push_str("-----END CERTIFICATE-----\n");
+        Some(ClientCert {
+            pem,
+            subject_dn: cert.subject().to_string().replace(", ", ","),
+            issuer_dn: cert.issuer().to_string().replace(", ", ","),
+            serial_number: cert.raw_serial_as_string(),
+            not_before: time(cert.validity().not_before)?,
+            not_after: time(cert.validity().not_after)?,
+        })
+    }
+
+    /// The headers ALB adds to requests authenticated with mutual TLS.
+    pub(crate) fn alb_headers(&self) -> Vec<(&'static str, String)> {
+        let time = |time: &str| {
+            NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+                .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+                .unwrap_or_default()
+        };
+        vec![
+            (
+                "x-amzn-mtls-clientcert-serial-number",
+                self.serial_number.replace(':', "").to_ascii_uppercase(),
+            ),
+            ("x-amzn-mtls-clientcert-issuer", self.issuer_dn.clone()),
+            ("x-amzn-mtls-clientcert-subject", self.subject_dn.clone()),
+            (
+                "x-amzn-mtls-clientcert-validity",
+                format!(
+                    "NotBefore={};NotAfter={}",
+                    time(&self.not_before),
+                    time(&self.not_after)
+                ),
+            ),
+            (
+                "x-amzn-mtls-clientcert-leaf",
+                percent_encoding::utf8_percent_encode(
+                    &self.pem,
+                    percent_encoding::USERINFO_ENCODE_SET,
+                )
+                .to_string(),
+            ),
+        ]
+    }
+}
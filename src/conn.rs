@@ -0,0 +1,190 @@
+//! Closing connections to the local development server that sit idle, or send requests too
+//! slowly.
+
+use futures01::{Async, Future, Poll};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::timer::Delay;
+
+/// How long connections may go without making progress.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Timeouts {
+    /// How long a connection may sit idle, with no request in flight, before it is closed.
+    pub(crate) keep_alive: Option<Duration>,
+    /// How long a client has to send a whole request, from its first byte.
+    pub(crate) request_read: Option<Duration>,
+}
+
+/// When the body of a request must have been received by, set as a request extension.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline(pub(crate) Instant);
+
+/// What a connection is doing, shared between its stream and the service handling its requests.
+#[derive(Clone)]
+pub(crate) struct Activity(Arc<Mutex<State>>);
+
+struct State {
+    timeouts: Timeouts,
+    in_flight: usize,
+    /// When the connection last had no request in flight.
+    idle_since: Instant,
+    /// When the first byte of the next request was received.
+    request_started: Option<Instant>,
+}
+
+impl Activity {
+    fn new(timeouts: Timeouts) -> Activity {
+        Activity(Arc::new(Mutex::new(State {
+            timeouts,
+            in_flight: 0,
+            idle_since: Instant::now(),
+            request_started: None,
+        })))
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Mark a request as in flight until the returned guard is dropped, returning it with the
+    /// deadline for receiving the request's body, if there is one.
+    pub(crate) fn start(&self) -> (InFlight, Option<Deadline>) {
+        let mut state = self.state();
+        state.in_flight += 1;
+        let started = state.request_started.take().unwrap_or_else(Instant::now);
+        let deadline = state
+            .timeouts
+            .request_read
+            .map(|timeout| Deadline(started + timeout));
+        (InFlight(self.clone()), deadline)
+    }
+
+    /// When the connection should be closed, if it makes no progress before then.
+    fn deadline(&self) -> Option<Instant> {
+        let state = self.state();
+        if state.in_flight > 0 {
+            return None;
+        }
+        match state.request_started {
+            Some(started) => state.timeouts.request_read.map(|timeout| started + timeout),
+            None => state
+                .timeouts
+                .keep_alive
+                .map(|timeout| state.idle_since + timeout),
+        }
+    }
+
+    fn sent(&self) {
+        let mut state = self.state();
+        if state.in_flight == 0 && state.request_started.is_none() {
+            state.idle_since = Instant::now();
+        }
+    }
+
+    fn received(&self) {
+        let mut state = self.state();
+        if state.in_flight == 0 && state.request_started.is_none() {
+            state.request_started = Some(Instant::now());
+        }
+    }
+}
+
+/// Marks a request as in flight while it is held.
+pub(crate) struct InFlight(Activity);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut state = self.0.state();
+        state.in_flight -= 1;
+        if state.in_flight == 0 {
+            state.idle_since = Instant::now();
+        }
+    }
+}
+
+/// A connection that fails reads with a timeout once its deadline passes.
+pub(crate) struct TimedStream<T> {
+    inner: T,
+    activity: Activity,
+    delay: Option<Delay>,
+}
+
+impl<T> TimedStream<T> {
+    pub(crate) fn new(inner: T, timeouts: Timeouts) -> TimedStream<T> {
+        TimedStream {
+            inner,
+            activity: Activity::new(timeouts),
+            delay: None,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub(crate) fn activity(&self) -> &Activity {
+        &self.activity
+    }
+
+    /// Whether the deadline has passed, arranging for the current task to be woken when it does
+    /// if not.
+    fn timed_out(&mut self) -> bool {
+        let Some(deadline) = self.activity.deadline() else {
+            self.delay = None;
+            return false;
+        };
+        let delay = match &mut self.delay {
+            Some(delay) => {
+                if delay.deadline() != deadline {
+                    delay.reset(deadline);
+                }
+                delay
+            }
+            None => self.delay.get_or_insert_with(|| Delay::new(deadline)),
+        };
+        matches!(delay.poll(), Ok(Async::Ready(())))
+    }
+}
+
+impl<T: Read> Read for TimedStream<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Ok(read) => {
+                if read > 0 {
+                    self.activity.received();
+                }
+                Ok(read)
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock && self.timed_out() => Err(
+                io::Error::new(io::ErrorKind::TimedOut, "connection timed out"),
+            ),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<T: Write> Write for TimedStream<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Writing the last response counts as activity, so it is not cut off by the keep-alive
+        // timeout.
+        let written = self.inner.write(buf)?;
+        if written > 0 {
+            self.activity.sent();
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for TimedStream<T> {}
+
+impl<T: AsyncWrite> AsyncWrite for TimedStream<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
@@ -0,0 +1,214 @@
+use chrono::Utc;
+use lambda_runtime::Context;
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A builder for the simulated [`Context`] local requests receive.
+///
+/// When running locally, a `Context` is created for each request, so handlers can read it from
+/// the request extensions the same way they do in AWS Lambda. Use this builder with
+/// [`Builder::context`] to make it match what your deployed function reports.
+///
+/// [`Context`]: ../lambda_runtime_core/context/struct.Context.html
+/// [`Builder::context`]: struct.Builder.html#method.context
+///
+/// # Example
+///
+/// ```rust
+/// use lambda_http_local::ContextBuilder;
+/// use std::time::Duration;
+///
+/// let context = ContextBuilder::new()
+///     .function_name("hello")
+///     .memory_limit_in_mb(512)
+///     .timeout(Duration::from_secs(30))
+///     .build();
+/// assert_eq!(context.function_name, "hello");
+/// assert_eq!(
+///     context.invoked_function_arn,
+///     "arn:aws:lambda:us-east-1:123456789012:function:hello",
+/// );
+/// ```
+#[derive(Clone)]
+pub struct ContextBuilder {
+    function_name: String,
+    function_version: String,
+    memory_limit_in_mb: i32,
+    region: String,
+    invoked_function_arn: Option<String>,
+    log_group_name: Option<String>,
+    log_stream_name: String,
+    timeout: Duration,
+    request_id: Arc<dyn Fn() -> String + Send + Sync>,
+}
+
+impl ContextBuilder {
+    /// Create a builder with default values.
+    ///
+    /// The function name is taken from `AWS_LAMBDA_FUNCTION_NAME`, or the name of the executable
+    /// if unset. The region is taken from `AWS_REGION`, or `us-east-1` if unset. The memory limit
+    /// is 128 MB and the timeout is three seconds, Lambda's defaults.
+    #[must_use]
+    pub fn new() -> ContextBuilder {
+        let function_name = env::var("AWS_LAMBDA_FUNCTION_NAME")
+            .ok()
+            .or_else(|| {
+                env::current_exe()
+                    .ok()?
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| "local".into());
+        let function_version = "$LATEST".to_owned();
+        ContextBuilder {
+            log_stream_name: log_stream_name(&function_version),
+            function_name,
+            function_version,
+            memory_limit_in_mb: 128,
+            region: env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".into()),
+            invoked_function_arn: None,
+            log_group_name: None,
+            timeout: Duration::from_secs(3),
+            request_id: Arc::new(request_id),
+        }
+    }
+
+    /// Set the function name.
+    ///
+    /// Unless set explicitly, the function ARN and log group name are derived from this.
+    #[must_use]
+    pub fn function_name<T: Into<String>>(mut self, function_name: T) -> ContextBuilder {
+        self.function_name = function_name.into();
+        self
+    }
+
+    /// Set the function version. Defaults to `$LATEST`.
+    #[must_use]
+    pub fn function_version<T: Into<String>>(mut self, function_version: T) -> ContextBuilder {
+        self.function_version = function_version.into();
+        self
+    }
+
+    /// Set the function's memory limit in megabytes.
+    #[must_use]
+    pub fn memory_limit_in_mb(mut self, memory_limit_in_mb: i32) -> ContextBuilder {
+        self.memory_limit_in_mb = memory_limit_in_mb;
+        self
+    }
+
+    /// Set the ARN of the invoked function.
+    #[must_use]
+    pub fn invoked_function_arn<T: Into<String>>(mut self, arn: T) -> ContextBuilder {
+        self.invoked_function_arn = Some(arn.into());
+        self
+    }
+
+    /// Set the log group name. Defaults to `/aws/lambda/` followed by the function name.
+    #[must_use]
+    pub fn log_group_name<T: Into<String>>(mut self, log_group_name: T) -> ContextBuilder {
+        self.log_group_name = Some(log_group_name.into());
+        self
+    }
+
+    /// Set the log stream name.
+    #[must_use]
+    pub fn log_stream_name<T: Into<String>>(mut self, log_stream_name: T) -> ContextBuilder {
+        self.log_stream_name = log_stream_name.into();
+        self
+    }
+
+    /// Set the function timeout, which determines each invocation's deadline.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> ContextBuilder {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set a function that generates request IDs.
+    ///
+    /// By default, request IDs are random UUIDs, like those AWS Lambda generates.
+    #[must_use]
+    pub fn request_id<F>(mut self, f: F) -> ContextBuilder
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        self.request_id = Arc::new(f);
+        self
+    }
+
+    /// A log stream name for another execution environment of the function.
+    #[cfg(feature = "local")]
+    pub(crate) fn new_log_stream_name(&self) -> String {
+        log_stream_name(&self.function_version)
+    }
+
+    #[cfg(feature = "local")]
+    pub(crate) fn function_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Create the `Context` for a new invocation.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn build(&self) -> Context {
+        Context {
+            memory_limit_in_mb: self.memory_limit_in_mb,
+            function_name: self.function_name.clone(),
+            function_version: self.function_version.clone(),
+            invoked_function_arn: self.invoked_function_arn.clone().unwrap_or_else(|| {
+                format!(
+                    "arn:aws:lambda:{}:123456789012:function:{}",
+                    self.region, self.function_name
+                )
+            }),
+            aws_request_id: (self.request_id)(),
+            xray_trace_id: None,
+            log_stream_name: self.log_stream_name.clone(),
+            log_group_name: self
+                .log_group_name
+                .clone()
+                .unwrap_or_else(|| format!("/aws/lambda/{}", self.function_name)),
+            client_context: None,
+            identity: None,
+            deadline: Utc::now().timestamp_millis() + self.timeout.as_millis() as i64,
+        }
+    }
+}
+
+impl Default for ContextBuilder {
+    fn default() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+}
+
+/// Generate a log stream name in the format Lambda uses, which is unique to each execution
+/// environment.
+fn log_stream_name(function_version: &str) -> String {
+    format!(
+        "{}/[{}]{:016x}{:016x}",
+        Utc::now().format("%Y/%m/%d"),
+        function_version,
+        random(),
+        random()
+    )
+}
+
+pub(crate) fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Generate a random request ID in the same format as AWS request IDs (a UUID).
+pub(crate) fn request_id() -> String {
+    let (a, b) = (random(), random());
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        a >> 32,
+        (a >> 16) & 0xffff,
+        a & 0xfff,
+        0x8000 | (b >> 48) & 0x3fff,
+        b & 0xffff_ffff_ffff,
+    )
+}
@@ -0,0 +1,295 @@
+use http::header::{HeaderMap, HeaderValue, COOKIE};
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+/// A cookie to set with a `Set-Cookie` response header.
+///
+/// Append cookies to a response with [`Cookie::to_header_value`]. A response may set any number
+/// of cookies, locally and with every service: for function URLs and HTTP APIs using the 2.0
+/// payload format, which only accept more than one `Set-Cookie` header in the response's
+/// `cookies` field, they are moved there when the response is returned to the Lambda Runtime
+/// API.
+///
+/// Read the cookies a request sent with [`RequestExt::cookie`].
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Request, Response};
+/// use http::header::SET_COOKIE;
+/// use lambda_http_local::{testing, Cookie, RequestExt, SameSite};
+/// use std::time::Duration;
+///
+/// let handler = |request: Request<&[u8]>| {
+///     let visits = request
+///         .cookie("visits")
+///         .and_then(|visits| visits.parse::<u32>().ok())
+///         .unwrap_or_default();
+///     let cookie = Cookie::new("visits", (visits + 1).to_string())
+///         .path("/")
+///         .max_age(Duration::from_secs(3600))
+///         .http_only()
+///         .same_site(SameSite::Lax);
+///     let mut response = Response::new(format!("Visits: {}", visits + 1));
+///     response
+///         .headers_mut()
+///         .append(SET_COOKIE, cookie.to_header_value());
+///     response
+/// };
+///
+/// let request = Request::get("/")
+///     .header("cookie", "theme=dark; visits=2")
+///     .body(Vec::new())
+///     .unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(response.body(), b"Visits: 3");
+/// assert_eq!(
+///     response.headers()["set-cookie"],
+///     "visits=3; Path=/; Max-Age=3600; HttpOnly; SameSite=Lax",
+/// );
+/// ```
+///
+/// [`Cookie::to_header_value`]: #method.to_header_value
+/// [`RequestExt::cookie`]: trait.RequestExt.html#tymethod.cookie
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+/// The `SameSite` attribute of a cookie, which controls whether it is sent with requests from
+/// other sites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    /// Only send the cookie with requests from the same site.
+    Strict,
+    /// Also send the cookie when navigating to the site from another one. Browsers treat cookies
+    /// without a `SameSite` attribute this way.
+    Lax,
+    /// Send the cookie with all requests, which browsers only allow for `Secure` cookies.
+    None,
+}
+
+impl Cookie {
+    /// Create a cookie that sets `name` to `value` until the browser is closed.
+    ///
+    /// This is meant for literal names and values. For values from elsewhere, such as request
+    /// data, use [`try_new`](#method.try_new).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` or `value` is invalid, as described for [`try_new`](#method.try_new).
+    #[must_use]
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> Cookie {
+        Cookie::try_new(name, value).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Create a cookie that sets `name` to `value` until the browser is closed, if they are
+    /// valid.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `name` is empty or contains characters other than the printable ASCII characters
+    /// allowed in a header name, or `value` contains control characters, whitespace, double
+    /// quotes, commas, semicolons, or backslashes.
+    ///
+    /// ```rust
+    /// use lambda_http_local::Cookie;
+    ///
+    /// assert!(Cookie::try_new("name", "Ferris").is_ok());
+    /// assert!(Cookie::try_new("name", "Ferris the crab").is_err());
+    /// ```
+    pub fn try_new<N: Into<String>, V: Into<String>>(
+        name: N,
+        value: V,
+    ) -> Result<Cookie, InvalidCookie> {
+        let (name, value) = (name.into(), value.into());
+        if name.is_empty() || !name.bytes().all(is_token) {
+            return Err(InvalidCookie(format!("invalid cookie name {name:?}")));
+        }
+        if !value.bytes().all(is_cookie_octet) {
+            return Err(InvalidCookie(format!("invalid cookie value {value:?}")));
+        }
+        Ok(Cookie {
+            name,
+            value,
+            path: None,
+            domain: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        })
+    }
+
+    /// Create a cookie that removes the cookie `name` from the browser.
+    ///
+    /// Set the same path and domain as the cookie was set with, if any.
+    #[must_use]
+    pub fn removal<N: Into<String>>(name: N) -> Cookie {
+        Cookie::new(name, "").max_age(Duration::from_secs(0))
+    }
+
+    /// The name of the cookie.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the cookie.
+    #[must_use]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Only send the cookie with requests for `path` and the paths below it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` contains control characters or semicolons.
+    #[must_use]
+    pub fn path<P: Into<String>>(mut self, path: P) -> Cookie {
+        let path = path.into();
+        assert!(is_attribute_value(&path), "invalid cookie path {:?}", path);
+        self.path = Some(path);
+        self
+    }
+
+    /// Send the cookie with requests for `domain` and its subdomains, rather than only the host
+    /// that set it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `domain` contains control characters or semicolons.
+    #[must_use]
+    pub fn domain<D: Into<String>>(mut self, domain: D) -> Cookie {
+        let domain = domain.into();
+        assert!(
+            is_attribute_value(&domain),
+            "invalid cookie domain {:?}",
+            domain
+        );
+        self.domain = Some(domain);
+        self
+    }
+
+    /// Keep the cookie for `max_age`, rather than until the browser is closed.
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Cookie {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Only send the cookie over HTTPS.
+    ///
+    /// Browsers also accept `Secure` cookies from `http://localhost`.
+    #[must_use]
+    pub fn secure(mut self) -> Cookie {
+        self.secure = true;
+        self
+    }
+
+    /// Hide the cookie from JavaScript.
+    #[must_use]
+    pub fn http_only(mut self) -> Cookie {
+        self.http_only = true;
+        self
+    }
+
+    /// Set whether the cookie is sent with requests from other sites.
+    #[must_use]
+    pub fn same_site(mut self, same_site: SameSite) -> Cookie {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// The value of the `Set-Cookie` header that sets the cookie.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn to_header_value(&self) -> HeaderValue {
+        // The name, value, and attributes were checked when the cookie was built.
+        HeaderValue::from_str(&self.to_string()).expect("invalid cookie")
+    }
+}
+
+impl Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={path}")?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={domain}")?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => f.write_str("; SameSite=Strict"),
+            Some(SameSite::Lax) => f.write_str("; SameSite=Lax"),
+            Some(SameSite::None) => f.write_str("; SameSite=None"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The error returned by [`Cookie::try_new`] for an invalid cookie name or value.
+///
+/// [`Cookie::try_new`]: struct.Cookie.html#method.try_new
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCookie(String);
+
+impl Display for InvalidCookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidCookie {}
+
+/// The cookies sent in the `Cookie` headers of a request, in order, with any quotes around their
+/// values removed.
+pub(crate) fn parse(headers: &HeaderMap) -> Vec<(&str, &str)> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| {
+            let (name, value) = cookie.split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            Some((name.trim(), value))
+        })
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&byte)
+}
+
+fn is_cookie_octet(byte: u8) -> bool {
+    byte.is_ascii_graphic() && !b"\",;\\".contains(&byte)
+}
+
+fn is_attribute_value(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|byte| byte.is_ascii() && !byte.is_ascii_control() && byte != b';')
+}
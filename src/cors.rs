@@ -0,0 +1,543 @@
+use crate::service::{OptionsRoutes, Service};
+use futures::future::{self, FutureExt};
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS,
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use http::{Method, Response, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A CORS configuration, like the one attached to an API Gateway HTTP API or a Lambda function
+/// URL.
+///
+/// When configured with [`Builder::cors`], responses to cross-origin requests from allowed
+/// origins get the CORS headers browsers require, as the service adds them to responses. As with
+/// HTTP APIs and function URLs, any CORS headers the handler sets are replaced. Responses to
+/// requests from other origins, or without an `Origin` header, get no CORS headers, so browsers
+/// block them.
+///
+/// Preflight requests, `OPTIONS` requests with an `Access-Control-Request-Method` header, are
+/// answered with a `204 No Content` response without calling the handler, unless it is a
+/// [`Router`] with an `OPTIONS` route for the request's path. If the requested method and headers
+/// are allowed, the response to a preflight request also lists the allowed methods and headers,
+/// and how long the browser may cache them.
+///
+/// The same headers are added in AWS Lambda, where HTTP APIs and function URLs with a CORS
+/// configuration override them, and REST APIs, which do not add CORS headers to responses from
+/// proxy integrations, pass them on.
+///
+/// [`Builder::cors`]: struct.Builder.html#method.cors
+/// [`Router`]: struct.Router.html
+///
+/// # Example
+///
+/// ```rust
+/// # use http::Response;
+/// use lambda_http_local::{Cors, Runtime};
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// let server = Runtime::builder()
+///     .listen_addr("localhost:0")
+///     .quiet(true)
+///     .cors(
+///         Cors::new()
+///             .allow_origins(vec!["http://localhost:3000"])
+///             .allow_methods(vec!["GET", "POST"])
+///             .allow_credentials(),
+///     )
+///     .build()
+///     .spawn(|request| Response::new("Hello, world!"))
+///     .unwrap();
+///
+/// let mut stream = TcpStream::connect(server.addr()).unwrap();
+/// stream
+///     .write_all(b"GET / HTTP/1.0\r\nOrigin: http://localhost:3000\r\n\r\n")
+///     .unwrap();
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).unwrap();
+/// assert!(response.contains("access-control-allow-origin: http://localhost:3000"));
+/// assert!(response.contains("access-control-allow-credentials: true"));
+///
+/// let mut stream = TcpStream::connect(server.addr()).unwrap();
+/// stream
+///     .write_all(
+///         b"OPTIONS / HTTP/1.0\r\n\
+///           Origin: http://localhost:3000\r\n\
+///           Access-Control-Request-Method: POST\r\n\r\n",
+///     )
+///     .unwrap();
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).unwrap();
+/// assert!(response.starts_with("HTTP/1.0 204 No Content"));
+/// assert!(response.contains("access-control-allow-methods: GET,POST"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Cors {
+    origins: Vec<String>,
+    methods: Vec<String>,
+    headers: Vec<String>,
+    expose_headers: Vec<String>,
+    max_age: Option<Duration>,
+    credentials: bool,
+}
+
+impl Cors {
+    /// Create a CORS configuration that allows no origins.
+    #[must_use]
+    pub fn new() -> Cors {
+        Cors::default()
+    }
+
+    /// Set the origins allowed to make requests, such as `https://example.com`, or `*` for any.
+    ///
+    /// Origins are compared exactly, including the scheme and any port.
+    #[must_use]
+    pub fn allow_origins<I>(mut self, origins: I) -> Cors
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.origins = origins.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the methods preflight requests may ask for, such as `PUT`, or `*` for any.
+    ///
+    /// Browsers do not send preflight requests for `GET`, `HEAD`, and most `POST` requests. By
+    /// default, preflight requests are not allowed.
+    #[must_use]
+    pub fn allow_methods<I>(mut self, methods: I) -> Cors
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.methods = methods.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the request headers preflight requests may ask for, such as `Authorization`, or `*`
+    /// for any.
+    ///
+    /// By default, only preflight requests without headers beyond those browsers always allow
+    /// are allowed.
+    #[must_use]
+    pub fn allow_headers<I>(mut self, headers: I) -> Cors
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the response headers, beyond those browsers always expose, that scripts may read.
+    #[must_use]
+    pub fn expose_headers<I>(mut self, headers: I) -> Cors
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.expose_headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Let browsers cache the response to a preflight request for `max_age`.
+    ///
+    /// By default, browsers cache them for five seconds.
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Cors {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Allow requests with credentials, such as cookies.
+    ///
+    /// Browsers do not accept `*` for credentialed requests, so the request's origin, method, and
+    /// headers are returned instead.
+    #[must_use]
+    pub fn allow_credentials(mut self) -> Cors {
+        self.credentials = true;
+        self
+    }
+
+    /// The CORS headers for the response to a request with `headers`, if its origin is allowed.
+    fn response_headers(&self, method: &Method, headers: &HeaderMap) -> Option<HeaderMap> {
+        let origin = headers.get(ORIGIN)?;
+        let origin_str = origin.to_str().ok()?;
+        let any_origin = self.origins.iter().any(|allowed| allowed == "*");
+        if !any_origin && !self.origins.iter().any(|allowed| allowed == origin_str) {
+            return None;
+        }
+
+        let mut cors = HeaderMap::new();
+        if any_origin && !self.credentials {
+            cors.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        } else {
+            cors.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+            cors.insert(VARY, HeaderValue::from_static("origin"));
+        }
+        if self.credentials {
+            cors.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+
+        match preflight(method, headers) {
+            Some((requested_method, requested_headers)) => {
+                if !self.allows_method(requested_method) || !self.allows_headers(&requested_headers)
+                {
+                    return None;
+                }
+                let methods = self.allowed(&self.methods, requested_method);
+                let headers = self.allowed(&self.headers, &requested_headers.join(","));
+                insert_list(&mut cors, ACCESS_CONTROL_ALLOW_METHODS, &methods);
+                insert_list(&mut cors, ACCESS_CONTROL_ALLOW_HEADERS, &headers);
+                if let Some(max_age) = self.max_age {
+                    cors.insert(ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+                }
+            }
+            None => insert_list(
+                &mut cors,
+                ACCESS_CONTROL_EXPOSE_HEADERS,
+                &self.expose_headers.join(","),
+            ),
+        }
+        Some(cors)
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.methods
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(method))
+    }
+
+    fn allows_headers(&self, headers: &[&str]) -> bool {
+        headers.iter().all(|header| {
+            self.headers
+                .iter()
+                .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(header))
+        })
+    }
+
+    /// The configured methods or headers, or the requested ones if any are allowed and the
+    /// response can not say so with `*`.
+    fn allowed(&self, configured: &[String], requested: &str) -> String {
+        if self.credentials && configured.iter().any(|allowed| allowed == "*") {
+            requested.to_owned()
+        } else {
+            configured.join(",")
+        }
+    }
+}
+
+/// The requested method and headers, if `method` and `headers` are those of a preflight request.
+fn preflight<'a>(method: &Method, headers: &'a HeaderMap) -> Option<(&'a str, Vec<&'a str>)> {
+    if method != Method::OPTIONS {
+        return None;
+    }
+    let requested_method = headers.get(ACCESS_CONTROL_REQUEST_METHOD)?.to_str().ok()?;
+    let requested_headers = headers
+        .get_all(ACCESS_CONTROL_REQUEST_HEADERS)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .collect();
+    Some((requested_method, requested_headers))
+}
+
+/// Insert a header with a comma-separated list of values, unless the list is empty.
+fn insert_list(headers: &mut HeaderMap, name: HeaderName, values: &str) {
+    if let (false, Ok(value)) = (values.is_empty(), HeaderValue::from_str(values)) {
+        headers.insert(name, value);
+    }
+}
+
+/// Wrap `service` so that its responses get the CORS headers of `cors`, and preflight requests
+/// are answered without calling it unless `options_routes` says it has a route for them.
+pub(crate) fn apply(
+    service: Service,
+    cors: Arc<Cors>,
+    options_routes: Option<OptionsRoutes>,
+) -> Service {
+    let blocking = service.is_blocking();
+    Service::new(
+        move |request| {
+            let cors_headers = cors.response_headers(request.method(), request.headers());
+            let routed = options_routes
+                .as_ref()
+                .is_some_and(|routes| routes(request.uri()));
+            if preflight(request.method(), request.headers()).is_some() && !routed {
+                let mut response = Response::new(Vec::new());
+                *response.status_mut() = StatusCode::NO_CONTENT;
+                let response = with_headers(response, cors_headers.as_ref());
+                return future::ready(Ok(response)).boxed();
+            }
+            service
+                .call(request)
+                .map(move |result| {
+                    result.map(|response| with_headers(response, cors_headers.as_ref()))
+                })
+                .boxed()
+        },
+        blocking,
+    )
+}
+
+/// Replace the CORS headers of `response` with `cors_headers`.
+fn with_headers(
+    mut response: Response<Vec<u8>>,
+    cors_headers: Option<&HeaderMap>,
+) -> Response<Vec<u8>> {
+    let headers = response.headers_mut();
+    let names = headers
+        .keys()
+        .filter(|name| name.as_str().starts_with("access-control-"))
+        .cloned()
+        .collect::<Vec<_>>();
+    for name in names {
+        headers.remove(name);
+    }
+    for (name, value) in cors_headers.into_iter().flatten() {
+        headers.append(name, value.clone());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, Cors};
+    use crate::service::Service;
+    use futures::executor::block_on;
+    use http::header::{HeaderMap, HeaderValue};
+    use http::{Method, Request, Response, StatusCode};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (*name, HeaderValue::from_static(value)))
+            .fold(HeaderMap::new(), |mut headers, (name, value)| {
+                headers.append(name, value);
+                headers
+            })
+    }
+
+    /// The CORS headers of the response to a `GET` request from `origin`.
+    fn simple(cors: &Cors, origin: &'static str) -> Option<HeaderMap> {
+        cors.response_headers(&Method::GET, &headers(&[("origin", origin)]))
+    }
+
+    /// The CORS headers of the response to a preflight request from `origin` for `method` with
+    /// `request_headers`.
+    fn preflight(
+        cors: &Cors,
+        method: &'static str,
+        request_headers: &'static str,
+    ) -> Option<HeaderMap> {
+        let mut pairs = vec![
+            ("origin", "https://example.com"),
+            ("access-control-request-method", method),
+        ];
+        if !request_headers.is_empty() {
+            pairs.push(("access-control-request-headers", request_headers));
+        }
+        cors.response_headers(&Method::OPTIONS, &headers(&pairs))
+    }
+
+    fn get<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+        headers.get(name).map(|value| value.to_str().unwrap())
+    }
+
+    #[test]
+    fn allowed_origins() {
+        let cors = Cors::new()
+            .allow_origins(vec!["https://example.com"])
+            .expose_headers(vec!["x-request-id"]);
+        let headers = simple(&cors, "https://example.com").unwrap();
+        assert_eq!(
+            get(&headers, "access-control-allow-origin"),
+            Some("https://example.com")
+        );
+        assert_eq!(get(&headers, "vary"), Some("origin"));
+        assert_eq!(
+            get(&headers, "access-control-expose-headers"),
+            Some("x-request-id")
+        );
+        assert_eq!(get(&headers, "access-control-allow-credentials"), None);
+    }
+
+    #[test]
+    fn disallowed_origins() {
+        let cors = Cors::new().allow_origins(vec!["https://example.com"]);
+        assert!(simple(&cors, "https://evil.example").is_none());
+        assert!(simple(&cors, "http://example.com").is_none());
+        assert!(simple(&cors, "https://example.com:8443").is_none());
+        assert!(cors
+            .response_headers(&Method::GET, &HeaderMap::new())
+            .is_none());
+        assert!(simple(&Cors::new(), "https://example.com").is_none());
+    }
+
+    #[test]
+    fn any_origin() {
+        let cors = Cors::new().allow_origins(vec!["*"]);
+        let headers = simple(&cors, "https://example.com").unwrap();
+        assert_eq!(get(&headers, "access-control-allow-origin"), Some("*"));
+        assert_eq!(get(&headers, "vary"), None);
+    }
+
+    #[test]
+    fn credentials_with_any_origin_methods_and_headers() {
+        let cors = Cors::new()
+            .allow_origins(vec!["*"])
+            .allow_methods(vec!["*"])
+            .allow_headers(vec!["*"])
+            .allow_credentials();
+        let headers = simple(&cors, "https://example.com").unwrap();
+        assert_eq!(
+            get(&headers, "access-control-allow-origin"),
+            Some("https://example.com")
+        );
+        assert_eq!(get(&headers, "vary"), Some("origin"));
+        assert_eq!(
+            get(&headers, "access-control-allow-credentials"),
+            Some("true")
+        );
+
+        let headers = preflight(&cors, "DELETE", "authorization, x-custom").unwrap();
+        assert_eq!(
+            get(&headers, "access-control-allow-origin"),
+            Some("https://example.com")
+        );
+        assert_eq!(
+            get(&headers, "access-control-allow-methods"),
+            Some("DELETE")
+        );
+        assert_eq!(
+            get(&headers, "access-control-allow-headers"),
+            Some("authorization,x-custom")
+        );
+    }
+
+    #[test]
+    fn any_methods_and_headers_without_credentials() {
+        let cors = Cors::new()
+            .allow_origins(vec!["*"])
+            .allow_methods(vec!["*"])
+            .allow_headers(vec!["*"]);
+        let headers = preflight(&cors, "DELETE", "x-custom").unwrap();
+        assert_eq!(get(&headers, "access-control-allow-origin"), Some("*"));
+        assert_eq!(get(&headers, "access-control-allow-methods"), Some("*"));
+        assert_eq!(get(&headers, "access-control-allow-headers"), Some("*"));
+    }
+
+    #[test]
+    fn preflight_requests() {
+        let cors = Cors::new()
+            .allow_origins(vec!["https://example.com"])
+            .allow_methods(vec!["GET", "PUT"])
+            .allow_headers(vec!["Authorization"])
+            .max_age(Duration::from_secs(90));
+        let headers = preflight(&cors, "put", "authorization").unwrap();
+        assert_eq!(
+            get(&headers, "access-control-allow-methods"),
+            Some("GET,PUT")
+        );
+        assert_eq!(
+            get(&headers, "access-control-allow-headers"),
+            Some("Authorization")
+        );
+        assert_eq!(get(&headers, "access-control-max-age"), Some("90"));
+        assert_eq!(get(&headers, "access-control-expose-headers"), None);
+        assert!(preflight(&cors, "PUT", "").is_some());
+    }
+
+    #[test]
+    fn disallowed_preflight_requests() {
+        let cors = Cors::new()
+            .allow_origins(vec!["https://example.com"])
+            .allow_methods(vec!["PUT"])
+            .allow_headers(vec!["Authorization"]);
+        assert!(preflight(&cors, "DELETE", "").is_none());
+        assert!(preflight(&cors, "PUT", "authorization, x-custom").is_none());
+        let no_methods = Cors::new().allow_origins(vec!["https://example.com"]);
+        assert!(preflight(&no_methods, "PUT", "").is_none());
+    }
+
+    fn call(service: &Service, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+        block_on(service.call(request)).unwrap()
+    }
+
+    #[test]
+    fn wrapped_handlers() {
+        let handler = Service::from_sync(|_| {
+            Response::builder()
+                .header("access-control-allow-origin", "https://evil.example")
+                .body("handled")
+                .unwrap()
+        });
+        let cors = Cors::new()
+            .allow_origins(vec!["https://example.com"])
+            .allow_methods(vec!["PUT"]);
+        let service = apply(handler, Arc::new(cors), None);
+
+        let request = Request::get("/")
+            .header("origin", "https://example.com")
+            .body(Vec::new())
+            .unwrap();
+        let response = call(&service, request);
+        assert_eq!(response.body(), b"handled");
+        assert_eq!(
+            get(response.headers(), "access-control-allow-origin"),
+            Some("https://example.com")
+        );
+
+        let request = Request::get("/")
+            .header("origin", "https://evil.example")
+            .body(Vec::new())
+            .unwrap();
+        let response = call(&service, request);
+        assert_eq!(get(response.headers(), "access-control-allow-origin"), None);
+
+        let request = Request::options("/")
+            .header("origin", "https://example.com")
+            .header("access-control-request-method", "PUT")
+            .body(Vec::new())
+            .unwrap();
+        let response = call(&service, request);
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(response.body().is_empty());
+        assert_eq!(
+            get(response.headers(), "access-control-allow-methods"),
+            Some("PUT")
+        );
+    }
+
+    #[test]
+    fn routed_preflight_requests() {
+        let handler = Service::from_sync(|_| Response::new("handled"));
+        let routes = Arc::new(|uri: &http::Uri| uri.path() == "/routed");
+        let cors = Cors::new().allow_origins(vec!["*"]);
+        let service = apply(handler, Arc::new(cors), Some(routes));
+        let preflight = |path| {
+            Request::options(path)
+                .header("origin", "https://example.com")
+                .header("access-control-request-method", "PUT")
+                .body(Vec::new())
+                .unwrap()
+        };
+        assert_eq!(call(&service, preflight("/routed")).body(), b"handled");
+        assert_eq!(
+            call(&service, preflight("/other")).status(),
+            StatusCode::NO_CONTENT
+        );
+    }
+}
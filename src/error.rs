@@ -0,0 +1,78 @@
+use std::fmt::{self, Display};
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// An error that prevented the runtime from starting or kept it from running.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The listen address could not be resolved.
+    Resolve(io::Error),
+    /// The listen address resolved to no socket addresses.
+    NoAddresses,
+    /// The local development server could not bind to the listen address.
+    Bind(SocketAddr, io::Error),
+    /// The local development server could not bind to the Unix socket path.
+    BindUnix(PathBuf, io::Error),
+    /// The socket passed to the process by a service manager could not be used.
+    Inherit(io::Error),
+    /// The directory to record invocations in could not be created.
+    Record(PathBuf, io::Error),
+    /// The HAR file could not be written on shutdown.
+    Har(PathBuf, io::Error),
+    /// The TLS certificate or private key could not be loaded.
+    Tls(PathBuf, io::Error),
+    /// The URL to mirror requests to is not an absolute `http` or `https` URL.
+    Shadow(String),
+    /// In one-shot mode, the event could not be read from standard input, or the response could
+    /// not be written to standard output.
+    Event(io::Error),
+    /// The local development server failed while serving requests.
+    Server(Box<dyn std::error::Error + Send + Sync>),
+    /// The function failed to initialize, with the message of the error its init closure
+    /// returned.
+    Init(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Resolve(err) => write!(f, "failed to resolve listen address: {err}"),
+            Error::NoAddresses => f.write_str("listen address resolved to no addresses"),
+            Error::Bind(addr, err) => write!(f, "failed to bind to {addr}: {err}"),
+            Error::BindUnix(path, err) => {
+                write!(f, "failed to bind to {}: {}", path.display(), err)
+            }
+            Error::Inherit(err) => write!(f, "failed to use inherited socket: {err}"),
+            Error::Record(dir, err) => {
+                write!(f, "failed to create {}: {}", dir.display(), err)
+            }
+            Error::Har(path, err) => write!(f, "failed to write {}: {}", path.display(), err),
+            Error::Tls(path, err) => write!(f, "failed to load {}: {}", path.display(), err),
+            Error::Shadow(url) => {
+                write!(f, "shadow URL must be an absolute http or https URL: {url}")
+            }
+            Error::Event(err) => write!(f, "failed to invoke function with event: {err}"),
+            Error::Server(err) => write!(f, "server error: {err}"),
+            Error::Init(message) => write!(f, "function failed to initialize: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Resolve(err)
+            | Error::Bind(_, err)
+            | Error::BindUnix(_, err)
+            | Error::Inherit(err)
+            | Error::Record(_, err)
+            | Error::Har(_, err)
+            | Error::Tls(_, err)
+            | Error::Event(err) => Some(err),
+            Error::NoAddresses | Error::Shadow(_) | Error::Init(_) => None,
+            Error::Server(err) => Some(&**err),
+        }
+    }
+}
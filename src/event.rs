@@ -0,0 +1,802 @@
+//! Conversions between `http::Request` values and Lambda proxy integration events, and the
+//! encoding rules the emulated services apply to bodies.
+
+use crate::context::request_id;
+use crate::ext::{PathParameters, QueryStringParameters, RawPath, RawQueryString, StageVariables};
+use crate::{ClientCert, Profile};
+use chrono::Utc;
+use http::header::{HeaderName, HeaderValue, CONTENT_TYPE, COOKIE, HOST, USER_AGENT};
+use http::request::Parts;
+#[cfg(feature = "local")]
+use http::Response;
+use http::{HeaderMap, Request, Uri, Version};
+use serde_json::{json, Map, Value};
+use std::net::{IpAddr, Ipv4Addr};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PORT: &str = "x-forwarded-port";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// Settings for converting requests into events.
+#[derive(Clone, Default)]
+pub(crate) struct Options {
+    pub(crate) profile: Profile,
+    pub(crate) stage: Option<String>,
+    pub(crate) binary_media_types: Vec<String>,
+    pub(crate) stage_variables: Map<String, Value>,
+}
+
+impl Options {
+    /// The stage requests are served under, if the profile has stages.
+    pub(crate) fn stage(&self) -> Option<&str> {
+        match self.profile {
+            Profile::RestApi | Profile::HttpApi => self.stage.as_deref(),
+            Profile::Alb | Profile::AlbMultiValueHeaders | Profile::FunctionUrl => None,
+        }
+    }
+
+    /// Whether API Gateway treats a body with `content_type` as binary. Only REST APIs are
+    /// configured with binary media types.
+    fn is_binary(&self, content_type: Option<&str>) -> bool {
+        self.profile == Profile::RestApi
+            && content_type.is_some_and(|content_type| {
+                self.binary_media_types
+                    .iter()
+                    .any(|pattern| media_type_matches(pattern, content_type))
+            })
+    }
+
+    /// Whether the emulated service would corrupt the body of `request`, by passing it to the
+    /// function as text even though it is not valid UTF-8.
+    #[cfg(feature = "local")]
+    pub(crate) fn corrupts_request_body(&self, request: &Request<Vec<u8>>) -> bool {
+        self.profile == Profile::RestApi
+            && !self.is_binary(header_str(request.headers(), CONTENT_TYPE))
+            && std::str::from_utf8(request.body()).is_err()
+    }
+
+    /// Convert the body of a response from the function to what the client receives.
+    ///
+    /// Binary bodies are sent by the function base64-encoded, which REST APIs only decode if the
+    /// request's `Accept` header or the response's `Content-Type` is a binary media type.
+    /// Returns `true` if the client receives the body still base64-encoded.
+    #[cfg(feature = "local")]
+    pub(crate) fn encode_response_body(
+        &self,
+        accept: Option<&str>,
+        response: &mut Response<Vec<u8>>,
+    ) -> bool {
+        if self.profile != Profile::RestApi || std::str::from_utf8(response.body()).is_ok() {
+            return false;
+        }
+        let accept = accept.and_then(|accept| accept.split(',').next());
+        if self.is_binary(accept) || self.is_binary(header_str(response.headers(), CONTENT_TYPE)) {
+            return false;
+        }
+        let encoded = base64::encode(response.body());
+        *response.body_mut() = encoded.into_bytes();
+        true
+    }
+
+    /// The path of a resource in the API, without the stage a request path starts with, if any.
+    #[cfg(feature = "openapi")]
+    pub(crate) fn resource_path<'a>(&self, path: &'a str) -> &'a str {
+        self.stage()
+            .and_then(|stage| strip_stage(path, stage))
+            .unwrap_or(path)
+    }
+
+    /// Whether a request path is under the configured stage, if any.
+    #[cfg(feature = "local")]
+    pub(crate) fn matches_stage(&self, path: &str) -> bool {
+        self.stage()
+            .is_none_or(|stage| strip_stage(path, stage).is_some())
+    }
+}
+
+/// The client connection a request is received on.
+#[derive(Clone, Copy)]
+pub(crate) struct Connection {
+    pub(crate) client_ip: IpAddr,
+    pub(crate) port: u16,
+    pub(crate) proto: &'static str,
+}
+
+impl Default for Connection {
+    /// A local client connected over HTTPS, as requests to AWS services are.
+    fn default() -> Connection {
+        Connection {
+            client_ip: Ipv4Addr::LOCALHOST.into(),
+            port: 443,
+            proto: "https",
+        }
+    }
+}
+
+/// Build the proxy integration event the emulated service would send for `request`: an event in
+/// the 2.0 payload format for HTTP APIs and function URLs, and in the 1.0 format otherwise.
+pub(crate) fn to_event(
+    options: &Options,
+    connection: Connection,
+    request: Request<Vec<u8>>,
+) -> Value {
+    match options.profile {
+        Profile::HttpApi | Profile::FunctionUrl => to_v2_event(options, connection, request),
+        Profile::RestApi | Profile::Alb | Profile::AlbMultiValueHeaders => {
+            to_v1_event(options, connection, request)
+        }
+    }
+}
+
+/// Build the event in the 1.0 payload format the emulated service would send for `request`.
+pub(crate) fn to_v1_event(
+    options: &Options,
+    connection: Connection,
+    request: Request<Vec<u8>>,
+) -> Value {
+    let profile = options.profile;
+    let (mut parts, body) = request.into_parts();
+    let client_cert = prepare(profile, connection, &mut parts);
+    let (headers, multi_value_headers) = headers(profile, &parts);
+    let (query, multi_value_query) = query(profile, &parts.uri);
+    let (body, is_base64_encoded) = event_body(options, &parts, body);
+
+    let full_path = parts.uri.path();
+    let path = match (profile, options.stage()) {
+        (Profile::RestApi, Some(stage)) => strip_stage(full_path, stage).unwrap_or(full_path),
+        _ => full_path,
+    };
+    let method = parts.method.as_str();
+    let mut event = json!({
+        "path": path,
+        "httpMethod": method,
+        "headers": headers,
+        "queryStringParameters": non_empty(query),
+        "body": body,
+        "isBase64Encoded": is_base64_encoded,
+    });
+    if profile.is_alb() {
+        event["requestContext"] = json!({
+            "elb": {
+                "targetGroupArn":
+                    "arn:aws:elasticloadbalancing:us-east-1:123456789012:targetgroup/local/0123456789abcdef",
+            },
+        });
+        if profile == Profile::AlbMultiValueHeaders {
+            // With multi-value headers enabled, ALB only sends the multi-value fields.
+            let event = event.as_object_mut().expect("events are objects");
+            event.remove("headers");
+            event.remove("queryStringParameters");
+            event.insert("multiValueHeaders".into(), multi_value_headers.into());
+            event.insert(
+                "multiValueQueryStringParameters".into(),
+                non_empty(multi_value_query),
+            );
+        }
+        return event;
+    }
+
+    let stage = options.stage().unwrap_or(if profile == Profile::RestApi {
+        "local"
+    } else {
+        "$default"
+    });
+    event["resource"] = "/{proxy+}".into();
+    if !multi_value_headers.is_empty() {
+        // Unlike other fields, `lambda_http` does not accept `null` here.
+        event["multiValueHeaders"] = Value::Object(multi_value_headers);
+    }
+    event["multiValueQueryStringParameters"] = non_empty(multi_value_query);
+    event["pathParameters"] = Value::Null;
+    event["stageVariables"] = non_empty(options.stage_variables.clone());
+    event["requestContext"] = json!({
+        "accountId": "123456789012",
+        "resourceId": "local",
+        "stage": stage,
+        "path": full_path,
+        "requestId": request_id(),
+        "resourcePath": "/{proxy+}",
+        "httpMethod": method,
+        "apiId": "local",
+        "identity": {
+            "sourceIp": connection.client_ip.to_string(),
+            "userAgent": event["headers"].get("user-agent").cloned().unwrap_or(Value::Null),
+        },
+    });
+    if let Some(client_cert) = client_cert {
+        event["requestContext"]["identity"]["clientCert"] = client_cert.to_event();
+    }
+    if profile != Profile::RestApi {
+        event["version"] = "1.0".into();
+    }
+    event
+}
+
+/// Build the event in the 2.0 payload format an HTTP API or function URL would send for
+/// `request`. Repeated headers and query parameters are combined into a single comma-separated
+/// value, and cookies are sent in the `cookies` field rather than as a header.
+fn to_v2_event(options: &Options, connection: Connection, request: Request<Vec<u8>>) -> Value {
+    let profile = options.profile;
+    let (mut parts, body) = request.into_parts();
+    let client_cert = prepare(profile, connection, &mut parts);
+    let mut headers = Map::new();
+    let mut cookies = Vec::new();
+    for (name, values) in header_values(&parts) {
+        if name == COOKIE.as_str() {
+            let values = values.iter().flat_map(|value| value.split(';'));
+            cookies.extend(values.map(str::trim).filter(|cookie| !cookie.is_empty()));
+        } else {
+            headers.insert(name.into(), values.join(",").into());
+        }
+    }
+    let mut query = Map::new();
+    for (key, value) in query_pairs(profile, &parts.uri) {
+        add_comma_separated(&mut query, key, value);
+    }
+    let (body, is_base64_encoded) = event_body(options, &parts, body);
+
+    let path = parts.uri.path();
+    let domain_name = headers
+        .get(HOST.as_str())
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let domain_prefix = domain_name.split('.').next().unwrap_or_default();
+    let now = Utc::now();
+    let mut event = json!({
+        "version": "2.0",
+        "routeKey": "$default",
+        "rawPath": path,
+        "rawQueryString": parts.uri.query().unwrap_or_default(),
+        "headers": headers,
+        "requestContext": {
+            "accountId": if profile == Profile::FunctionUrl { "anonymous" } else { "123456789012" },
+            "apiId": "local",
+            "domainName": domain_name,
+            "domainPrefix": domain_prefix,
+            "http": {
+                "method": parts.method.as_str(),
+                "path": path,
+                "protocol": format!("{:?}", parts.version),
+                "sourceIp": connection.client_ip.to_string(),
+                "userAgent": headers.get(USER_AGENT.as_str()),
+            },
+            "requestId": request_id(),
+            "routeKey": "$default",
+            "stage": options.stage().unwrap_or("$default"),
+            "time": now.format("%d/%b/%Y:%H:%M:%S %z").to_string(),
+            "timeEpoch": now.timestamp_millis(),
+        },
+        "isBase64Encoded": is_base64_encoded,
+    });
+    if !cookies.is_empty() {
+        event["cookies"] = cookies.into();
+    }
+    if !query.is_empty() {
+        event["queryStringParameters"] = query.into();
+    }
+    if !body.is_null() {
+        event["body"] = body;
+    }
+    if !options.stage_variables.is_empty() {
+        event["stageVariables"] = Value::Object(options.stage_variables.clone());
+    }
+    if let Some(client_cert) = client_cert {
+        event["requestContext"]["authentication"] = json!({ "clientCert": client_cert.to_event() });
+    }
+    event
+}
+
+/// Add the headers the emulated service adds to `parts`, returning the client certificate of the
+/// request, if any.
+fn prepare(profile: Profile, connection: Connection, parts: &mut Parts) -> Option<ClientCert> {
+    add_forwarded_headers(&mut parts.headers, connection);
+    if parts.version == Version::HTTP_2 {
+        combine_cookies(&mut parts.headers);
+    }
+    let client_cert = parts.extensions.remove::<ClientCert>();
+    if let Some(client_cert) = client_cert.as_ref().filter(|_| profile.is_alb()) {
+        for (name, value) in client_cert.alb_headers() {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                parts.headers.insert(name, value);
+            }
+        }
+    }
+    client_cert
+}
+
+/// Convert a request body into the `body` and `isBase64Encoded` fields of an event.
+fn event_body(options: &Options, parts: &Parts, body: Vec<u8>) -> (Value, bool) {
+    if body.is_empty() {
+        (Value::Null, false)
+    } else if options.is_binary(header_str(&parts.headers, CONTENT_TYPE)) {
+        (Value::from(base64::encode(&body)), true)
+    } else if options.profile == Profile::RestApi {
+        // REST APIs pass other bodies as text, replacing anything that is not valid UTF-8.
+        (Value::from(String::from_utf8_lossy(&body)), false)
+    } else {
+        match String::from_utf8(body) {
+            Ok(body) => (Value::from(body), false),
+            Err(err) => (Value::from(base64::encode(err.as_bytes())), true),
+        }
+    }
+}
+
+/// Convert `request` to the request `lambda_http` would produce for the equivalent event from the
+/// emulated service.
+pub(crate) fn to_lambda_request(
+    options: &Options,
+    connection: Connection,
+    request: Request<Vec<u8>>,
+) -> lambda_http::Request {
+    from_event(to_event(options, connection, request)).expect("failed to parse synthesized event")
+}
+
+/// Parse a proxy integration event into the request `lambda_http` produces for it.
+///
+/// `lambda_http` requires the `headers` and `queryStringParameters` fields, which ALB leaves out
+/// when multi-value headers are enabled, so they are filled in from the multi-value fields first.
+/// Events in the 2.0 payload format, which `lambda_http` does not understand, are converted to
+/// the equivalent 1.0 event.
+pub(crate) fn from_event(mut event: Value) -> Result<lambda_http::Request, serde_json::Error> {
+    if event.get("version").and_then(Value::as_str) == Some("2.0") {
+        event = from_v2(&event);
+    }
+    let raw_path = ["/rawPath", "/requestContext/path", "/path"]
+        .iter()
+        .find_map(|pointer| event.pointer(pointer)?.as_str())
+        .unwrap_or("/")
+        .to_owned();
+    let raw_query_string = match event.get("rawQueryString").and_then(Value::as_str) {
+        Some(raw_query_string) => raw_query_string.to_owned(),
+        None => build_query_string(&event),
+    };
+    let client_cert = event
+        .pointer("/requestContext/identity/clientCert")
+        .and_then(ClientCert::from_event);
+
+    if let Some(event) = event.as_object_mut() {
+        for (field, multi_value_field) in [
+            ("headers", "multiValueHeaders"),
+            ("queryStringParameters", "multiValueQueryStringParameters"),
+        ] {
+            if event.get(field).is_none_or(Value::is_null) {
+                let values = last_values(event.get(multi_value_field));
+                event.insert(field.into(), values);
+            }
+        }
+    }
+    let mut request = lambda_http::request::from_str(&event.to_string())?;
+    // `lambda_http` keeps its parameter extensions private, so copy them into ours.
+    let query_string_parameters = lambda_http::RequestExt::query_string_parameters(&request);
+    let path_parameters = lambda_http::RequestExt::path_parameters(&request);
+    let stage_variables = lambda_http::RequestExt::stage_variables(&request);
+    let extensions = request.extensions_mut();
+    extensions.insert(RawPath(raw_path));
+    extensions.insert(RawQueryString(raw_query_string));
+    extensions.insert(QueryStringParameters(query_string_parameters));
+    extensions.insert(PathParameters(path_parameters));
+    extensions.insert(StageVariables(stage_variables));
+    if let Some(client_cert) = client_cert {
+        extensions.insert(client_cert);
+    }
+    Ok(request)
+}
+
+/// Parse an event saved to a file, which is either the event itself or an invocation saved by the
+/// local development server, with the event in its `event` field.
+pub(crate) fn from_saved_event(
+    mut event: Value,
+) -> Result<lambda_http::Request, serde_json::Error> {
+    if let Some(saved) = event.get_mut("event") {
+        event = saved.take();
+    }
+    from_event(event)
+}
+
+/// Convert an event in the 2.0 payload format, as sent by HTTP APIs and function URLs, into the
+/// equivalent 1.0 event. As in 2.0 events, repeated headers and query parameters are combined
+/// into a single comma-separated value.
+fn from_v2(event: &Value) -> Value {
+    let context = &event["requestContext"];
+    let http = &context["http"];
+    let method = http["method"].as_str().unwrap_or("GET");
+    let path = event["rawPath"]
+        .as_str()
+        .or_else(|| http["path"].as_str())
+        .unwrap_or("/");
+    let mut headers = event["headers"].as_object().cloned().unwrap_or_default();
+    if let Some(cookies) = event["cookies"].as_array() {
+        let cookies = cookies.iter().filter_map(Value::as_str).collect::<Vec<_>>();
+        headers.insert(COOKIE.as_str().into(), cookies.join("; ").into());
+    }
+    // Routes are keyed by method and path, as in `GET /users/{id}`.
+    let resource = match event["routeKey"].as_str() {
+        Some(route_key) => route_key
+            .split_once(' ')
+            .map_or(route_key, |(_, path)| path),
+        None => path,
+    };
+    json!({
+        "version": "1.0",
+        "resource": resource,
+        "path": path,
+        "rawPath": path,
+        "rawQueryString": event["rawQueryString"].as_str().unwrap_or_default(),
+        "httpMethod": method,
+        "headers": headers,
+        "queryStringParameters": event["queryStringParameters"],
+        "pathParameters": event["pathParameters"],
+        "stageVariables": event["stageVariables"],
+        "body": event["body"],
+        "isBase64Encoded": event["isBase64Encoded"].as_bool().unwrap_or(false),
+        "requestContext": {
+            "accountId": context["accountId"].as_str().unwrap_or_default(),
+            "resourceId": "",
+            "stage": context["stage"].as_str().unwrap_or("$default"),
+            "path": path,
+            "requestId": context["requestId"].as_str().unwrap_or_default(),
+            "resourcePath": resource,
+            "httpMethod": method,
+            "apiId": context["apiId"].as_str().unwrap_or_default(),
+            "authorizer": context.get("authorizer").cloned().unwrap_or_else(|| json!({})),
+            "identity": {
+                "sourceIp": http["sourceIp"].as_str().unwrap_or("127.0.0.1"),
+                "userAgent": http["userAgent"],
+                "clientCert": context.pointer("/authentication/clientCert"),
+            },
+        },
+    })
+}
+
+/// Rebuild a query string from the query parameters of an event.
+fn build_query_string(event: &Value) -> String {
+    let mut pairs = Vec::new();
+    let multi_value = event
+        .get("multiValueQueryStringParameters")
+        .and_then(Value::as_object)
+        .filter(|multi_value| !multi_value.is_empty());
+    if let Some(multi_value) = multi_value {
+        for (key, values) in multi_value {
+            for value in values.as_array().into_iter().flatten() {
+                pairs.push((key.as_str(), value.as_str().unwrap_or_default()));
+            }
+        }
+    } else if let Some(query) = event
+        .get("queryStringParameters")
+        .and_then(Value::as_object)
+    {
+        for (key, value) in query {
+            pairs.push((key.as_str(), value.as_str().unwrap_or_default()));
+        }
+    }
+
+    if event.pointer("/requestContext/elb").is_some() {
+        // ALB passes query parameters without decoding them.
+        pairs
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join("&")
+    } else {
+        serde_urlencoded::to_string(pairs).unwrap_or_default()
+    }
+}
+
+/// Take the last value of each entry in a multi-value field of an event.
+fn last_values(multi_value: Option<&Value>) -> Value {
+    match multi_value {
+        Some(Value::Object(multi_value)) => multi_value
+            .iter()
+            .filter_map(|(key, values)| Some((key.clone(), values.as_array()?.last()?.clone())))
+            .collect::<Map<_, _>>()
+            .into(),
+        _ => Value::Object(Map::new()),
+    }
+}
+
+/// Add the `X-Forwarded-*` headers AWS services add to requests.
+fn add_forwarded_headers(headers: &mut HeaderMap, connection: Connection) {
+    let client_ip = connection.client_ip.to_string();
+    let forwarded_for = match headers
+        .get(X_FORWARDED_FOR)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(forwarded_for) => format!("{forwarded_for}, {client_ip}"),
+        None => client_ip,
+    };
+    let values = [
+        (X_FORWARDED_FOR, forwarded_for),
+        (X_FORWARDED_PROTO, connection.proto.to_owned()),
+        (X_FORWARDED_PORT, connection.port.to_string()),
+    ];
+    for (name, value) in values {
+        headers.insert(
+            HeaderName::from_static(name),
+            HeaderValue::from_str(&value).expect("invalid forwarded header"),
+        );
+    }
+}
+
+/// Combine the `Cookie` headers of an HTTP/2 request, which may send each cookie separately, into
+/// the single header an HTTP/1.1 request has.
+fn combine_cookies(headers: &mut HeaderMap) {
+    let cookies = headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>();
+    if cookies.len() > 1 {
+        if let Ok(value) = HeaderValue::from_str(&cookies.join("; ")) {
+            headers.insert(COOKIE, value);
+        }
+    }
+}
+
+/// Convert request headers into the `headers` and `multiValueHeaders` of a 1.0 event.
+fn headers(profile: Profile, parts: &Parts) -> (Map<String, Value>, Map<String, Value>) {
+    let mut headers = Map::new();
+    let mut multi_value_headers = Map::new();
+    for (name, values) in header_values(parts) {
+        headers.insert(name.into(), values[values.len() - 1].into());
+        if profile != Profile::Alb {
+            multi_value_headers.insert(name.into(), values.into());
+        }
+    }
+    (headers, multi_value_headers)
+}
+
+/// The values of each request header, adding the Host header if the request has none.
+fn header_values(parts: &Parts) -> Vec<(&str, Vec<&str>)> {
+    let mut header_values = parts
+        .headers
+        .keys()
+        .map(|name| {
+            let values = parts
+                .headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect::<Vec<_>>();
+            (name.as_str(), values)
+        })
+        .filter(|(_, values)| !values.is_empty())
+        .collect::<Vec<_>>();
+    if !parts.headers.contains_key(HOST) {
+        // `lambda_http` builds the request URI from the Host header.
+        let host = parts
+            .uri
+            .authority_part()
+            .map_or("localhost", |a| a.as_str());
+        header_values.push((HOST.as_str(), vec![host]));
+    }
+    header_values
+}
+
+/// Convert the query string into the `queryStringParameters` and
+/// `multiValueQueryStringParameters` of a 1.0 event.
+fn query(profile: Profile, uri: &Uri) -> (Map<String, Value>, Map<String, Value>) {
+    let mut query = Map::new();
+    let mut multi_value_query = Map::new();
+    for (key, value) in query_pairs(profile, uri) {
+        query.insert(key.clone(), Value::from(value.clone()));
+        if profile != Profile::Alb {
+            multi_value_query
+                .entry(key)
+                .or_insert_with(|| Value::Array(Vec::new()))
+                .as_array_mut()
+                .expect("multi-value query parameters are arrays")
+                .push(Value::from(value));
+        }
+    }
+    (query, multi_value_query)
+}
+
+/// The query parameters of `uri`, decoded as the emulated service decodes them.
+fn query_pairs(profile: Profile, uri: &Uri) -> Vec<(String, String)> {
+    let raw_query = uri.query().unwrap_or_default();
+    if profile.is_alb() {
+        // ALB passes query parameters as they appear in the request, without decoding them.
+        raw_query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let mut split = pair.splitn(2, '=');
+                let key = split.next().unwrap_or_default();
+                (key.into(), split.next().unwrap_or_default().into())
+            })
+            .collect()
+    } else {
+        serde_urlencoded::from_str(raw_query).unwrap_or_default()
+    }
+}
+
+/// Add a value to a map of comma-separated values, as 2.0 events combine repeated headers and
+/// query parameters.
+pub(crate) fn add_comma_separated(map: &mut Map<String, Value>, key: String, value: String) {
+    match map.get_mut(&key) {
+        Some(Value::String(existing)) => {
+            existing.push(',');
+            existing.push_str(&value);
+        }
+        _ => {
+            map.insert(key, Value::String(value));
+        }
+    }
+}
+
+/// Whether `media_type` (which may have parameters) matches `pattern`, such as `image/png`,
+/// `image/*` or `*/*`, ignoring case, as API Gateway matches binary media types and the content
+/// types of request and response models.
+pub(crate) fn media_type_matches(pattern: &str, media_type: &str) -> bool {
+    let media_type = media_type.split(';').next().unwrap_or_default().trim();
+    let mut pattern_parts = pattern.splitn(2, '/');
+    let mut parts = media_type.splitn(2, '/');
+    let matches = |pattern: Option<&str>, part: Option<&str>| match (pattern, part) {
+        (Some("*"), Some(_)) => true,
+        (Some(pattern), Some(part)) => pattern.eq_ignore_ascii_case(part),
+        _ => false,
+    };
+    matches(pattern_parts.next(), parts.next()) && matches(pattern_parts.next(), parts.next())
+}
+
+fn header_str(headers: &HeaderMap, name: HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Remove a leading `/{stage}` segment from `path`, or return `None` if it is not there.
+fn strip_stage<'a>(path: &'a str, stage: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix('/')?.strip_prefix(stage)?;
+    if rest.is_empty() {
+        Some("/")
+    } else if rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+fn non_empty(map: Map<String, Value>) -> Value {
+    if map.is_empty() {
+        Value::Null
+    } else {
+        Value::Object(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_event, to_event, Connection, Options};
+    use crate::{Profile, RequestExt};
+    use http::Request;
+    use serde_json::{json, Value};
+
+    /// The example HTTP API event in the AWS documentation of the 2.0 payload format, with the Host
+    /// header every request has.
+    fn fixture() -> Value {
+        json!({
+            "version": "2.0",
+            "routeKey": "$default",
+            "rawPath": "/my/path",
+            "rawQueryString": "parameter1=value1&parameter1=value2&parameter2=value",
+            "cookies": ["cookie1", "cookie2"],
+            "headers": {
+                "header1": "value1",
+                "header2": "value1,value2",
+                "host": "id.execute-api.us-east-1.amazonaws.com",
+            },
+            "queryStringParameters": {"parameter1": "value1,value2", "parameter2": "value"},
+            "requestContext": {
+                "accountId": "123456789012",
+                "apiId": "api-id",
+                "authorizer": {"jwt": {"claims": {"claim1": "value1"}, "scopes": ["scope1"]}},
+                "domainName": "id.execute-api.us-east-1.amazonaws.com",
+                "domainPrefix": "id",
+                "http": {
+                    "method": "POST",
+                    "path": "/my/path",
+                    "protocol": "HTTP/1.1",
+                    "sourceIp": "192.0.2.1",
+                    "userAgent": "agent",
+                },
+                "requestId": "id",
+                "routeKey": "$default",
+                "stage": "$default",
+                "time": "12/Mar/2020:19:03:58 +0000",
+                "timeEpoch": 1_583_348_638_390_u64,
+            },
+            "body": "Hello from Lambda",
+            "pathParameters": {"parameter1": "value1"},
+            "isBase64Encoded": false,
+            "stageVariables": {"stageVariable1": "value1"},
+        })
+    }
+
+    /// Assert that `event` has every field of `fixture`, other than those the service only sends
+    /// for some routes and authorizers, with a value of the same type.
+    fn assert_same_shape(fixture: &Value, event: &Value, path: &str) {
+        const OPTIONAL: &[&str] = &[
+            "/pathParameters",
+            "/stageVariables",
+            "/requestContext/authorizer",
+        ];
+        let (Some(fixture), Some(event)) = (fixture.as_object(), event.as_object()) else {
+            return;
+        };
+        for (key, expected) in fixture {
+            let path = format!("{path}/{key}");
+            if OPTIONAL.contains(&path.as_str()) {
+                continue;
+            }
+            let actual = event.get(key).unwrap_or_else(|| panic!("missing {}", path));
+            let same_type = match expected {
+                Value::Object(_) => actual.is_object(),
+                Value::Array(_) => actual.is_array(),
+                Value::String(_) => actual.is_string(),
+                Value::Number(_) => actual.is_number(),
+                Value::Bool(_) => actual.is_boolean(),
+                Value::Null => actual.is_null(),
+            };
+            assert!(
+                same_type,
+                "{} is {}, expected a value like {}",
+                path, actual, expected
+            );
+            assert_same_shape(expected, actual, &path);
+        }
+    }
+
+    #[test]
+    fn synthesizes_v2_events() {
+        let fixture = fixture();
+        for profile in [Profile::HttpApi, Profile::FunctionUrl] {
+            let request =
+                Request::post("/my/path?parameter1=value1&parameter1=value2&parameter2=value")
+                    .header("host", "id.execute-api.us-east-1.amazonaws.com")
+                    .header("header1", "value1")
+                    .header("header2", "value1")
+                    .header("header2", "value2")
+                    .header("cookie", "cookie1; cookie2")
+                    .header("user-agent", "agent")
+                    .body(b"Hello from Lambda".to_vec())
+                    .unwrap();
+            let options = Options {
+                profile,
+                ..Options::default()
+            };
+            let event = to_event(&options, Connection::default(), request);
+            assert_same_shape(&fixture, &event, "");
+            for field in [
+                "version",
+                "routeKey",
+                "rawPath",
+                "rawQueryString",
+                "cookies",
+                "body",
+            ] {
+                assert_eq!(event[field], fixture[field], "{field}");
+            }
+            assert_eq!(
+                event["queryStringParameters"],
+                fixture["queryStringParameters"]
+            );
+            assert_eq!(event["headers"]["header2"], "value1,value2");
+            assert_eq!(event["requestContext"]["http"], {
+                let mut http = fixture["requestContext"]["http"].clone();
+                http["sourceIp"] = "127.0.0.1".into();
+                http
+            });
+
+            let expected = from_event(fixture.clone()).unwrap();
+            let actual = from_event(event).unwrap();
+            assert_eq!(actual.method(), expected.method());
+            assert_eq!(actual.uri(), expected.uri());
+            assert_eq!(actual.headers()["header2"], expected.headers()["header2"]);
+            assert_eq!(actual.headers()["cookie"], expected.headers()["cookie"]);
+            assert_eq!(
+                actual.query_string_parameters().get_all("parameter1"),
+                expected.query_string_parameters().get_all("parameter1")
+            );
+            assert_eq!(actual.body(), expected.body());
+        }
+    }
+}
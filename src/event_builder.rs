@@ -0,0 +1,374 @@
+use crate::context::request_id;
+use crate::event::{self, add_comma_separated, Connection, Options};
+use crate::Profile;
+use chrono::Utc;
+use http::header::{COOKIE, HOST, USER_AGENT};
+use http::{Method, Request};
+use serde_json::{json, Map, Value};
+use std::net::{IpAddr, Ipv4Addr};
+
+/// A builder for realistic sample events, like those `sam local generate-event` creates.
+///
+/// Start with the service sending the event, set the parts of the request that matter to the
+/// test, and [`build`](#method.build) the event, or convert it straight to the request a handler
+/// would receive with [`to_request`](#method.to_request). Everything else is filled in the way
+/// the service fills it in.
+///
+/// # Example
+///
+/// ```rust
+/// use lambda_http_local::fixtures::EventBuilder;
+/// use lambda_http_local::RequestExt;
+///
+/// let event = EventBuilder::http_api_v2()
+///     .method("POST")
+///     .route("/users/{id}")
+///     .path("/users/42?notify=true")
+///     .path_parameter("id", "42")
+///     .header("content-type", "application/json")
+///     .body(r#"{"name":"Ferris"}"#)
+///     .build();
+/// assert_eq!(event["routeKey"], "POST /users/{id}");
+/// assert_eq!(event["requestContext"]["http"]["method"], "POST");
+///
+/// let request = EventBuilder::rest_api().path("/users/42").to_request();
+/// assert_eq!(request.uri().path(), "/users/42");
+/// ```
+#[derive(Clone)]
+pub struct EventBuilder {
+    format: Format,
+    method: Method,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    route: Option<String>,
+    path_parameters: Map<String, Value>,
+    stage: Option<String>,
+    stage_variables: Map<String, Value>,
+    source_ip: IpAddr,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    /// An event in the 1.0 payload format, or from an ALB.
+    V1(Profile),
+    /// An event in the 2.0 payload format, from an HTTP API or a function URL.
+    V2 { function_url: bool },
+}
+
+impl EventBuilder {
+    /// Build an event from an API Gateway REST API with a Lambda proxy integration.
+    #[must_use]
+    pub fn rest_api() -> EventBuilder {
+        EventBuilder::new(Format::V1(Profile::RestApi))
+    }
+
+    /// Build an event from an API Gateway HTTP API using payload format version 1.0.
+    #[must_use]
+    pub fn http_api() -> EventBuilder {
+        EventBuilder::new(Format::V1(Profile::HttpApi))
+    }
+
+    /// Build an event from an API Gateway HTTP API using payload format version 2.0.
+    #[must_use]
+    pub fn http_api_v2() -> EventBuilder {
+        EventBuilder::new(Format::V2 {
+            function_url: false,
+        })
+    }
+
+    /// Build an event from an Application Load Balancer target group.
+    #[must_use]
+    pub fn alb() -> EventBuilder {
+        EventBuilder::new(Format::V1(Profile::Alb))
+    }
+
+    /// Build an event from an Application Load Balancer target group with multi-value headers
+    /// enabled.
+    #[must_use]
+    pub fn alb_multi_value_headers() -> EventBuilder {
+        EventBuilder::new(Format::V1(Profile::AlbMultiValueHeaders))
+    }
+
+    /// Build an event from a Lambda function URL, which uses payload format version 2.0.
+    #[must_use]
+    pub fn function_url() -> EventBuilder {
+        EventBuilder::new(Format::V2 { function_url: true })
+    }
+
+    fn new(format: Format) -> EventBuilder {
+        EventBuilder {
+            format,
+            method: Method::GET,
+            path: "/".to_owned(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            route: None,
+            path_parameters: Map::new(),
+            stage: None,
+            stage_variables: Map::new(),
+            source_ip: Ipv4Addr::new(192, 0, 2, 1).into(),
+        }
+    }
+
+    /// Set the request method. Defaults to `GET`.
+    ///
+    /// # Panics
+    ///
+    /// If `method` is not a valid HTTP method, this function will panic.
+    #[must_use]
+    pub fn method(mut self, method: &str) -> EventBuilder {
+        self.method = method.parse().expect("invalid HTTP method");
+        self
+    }
+
+    /// Set the request path, which may include a query string. Defaults to `/`.
+    #[must_use]
+    pub fn path<T: Into<String>>(mut self, path: T) -> EventBuilder {
+        self.path = path.into();
+        self
+    }
+
+    /// Add a query parameter, after any in the path.
+    #[must_use]
+    pub fn query<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> EventBuilder {
+        self.query.push((key.into(), value.into()));
+        self
+    }
+
+    /// Add a request header.
+    ///
+    /// A `Host` header for the service's default domain and a `User-Agent` header are sent unless
+    /// they are set.
+    #[must_use]
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> EventBuilder {
+        self.headers
+            .push((name.into().to_ascii_lowercase(), value.into()));
+        self
+    }
+
+    /// Set the request body. Bodies that are not valid UTF-8 are base64-encoded, except by REST
+    /// APIs, which are not configured with binary media types.
+    #[must_use]
+    pub fn body<T: Into<Vec<u8>>>(mut self, body: T) -> EventBuilder {
+        self.body = body.into();
+        self
+    }
+
+    /// Set the route or resource that matched the request, such as `/users/{id}`. Defaults to
+    /// `/{proxy+}` for REST APIs and HTTP APIs using payload format version 1.0, and `$default`
+    /// for payload format version 2.0.
+    #[must_use]
+    pub fn route<T: Into<String>>(mut self, route: T) -> EventBuilder {
+        self.route = Some(route.into());
+        self
+    }
+
+    /// Add a path parameter from the route. ALBs and function URLs do not have path parameters.
+    #[must_use]
+    pub fn path_parameter<K: Into<String>, V: Into<String>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> EventBuilder {
+        self.path_parameters
+            .insert(key.into(), Value::String(value.into()));
+        self
+    }
+
+    /// Set the API Gateway stage. Defaults to `local` for REST APIs and `$default` for HTTP
+    /// APIs.
+    #[must_use]
+    pub fn stage<T: Into<String>>(mut self, stage: T) -> EventBuilder {
+        self.stage = Some(stage.into());
+        self
+    }
+
+    /// Add an API Gateway stage variable.
+    #[must_use]
+    pub fn stage_variable<K: Into<String>, V: Into<String>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> EventBuilder {
+        self.stage_variables
+            .insert(key.into(), Value::String(value.into()));
+        self
+    }
+
+    /// Set the IP address of the client. Defaults to `192.0.2.1`.
+    #[must_use]
+    pub fn source_ip(mut self, source_ip: IpAddr) -> EventBuilder {
+        self.source_ip = source_ip;
+        self
+    }
+
+    /// Build the event.
+    ///
+    /// # Panics
+    ///
+    /// If a header name or value is invalid, this function will panic.
+    #[must_use]
+    pub fn build(&self) -> Value {
+        match self.format {
+            Format::V1(profile) => self.build_v1(profile),
+            Format::V2 { function_url } => self.build_v2(function_url),
+        }
+    }
+
+    /// Build the event and parse it into the request a handler would receive for it.
+    ///
+    /// # Panics
+    ///
+    /// If a header name or value is invalid, this function will panic.
+    #[must_use]
+    pub fn to_request(&self) -> Request<Vec<u8>> {
+        crate::fixtures::from_value(self.build()).expect("failed to parse built event")
+    }
+
+    fn build_v1(&self, profile: Profile) -> Value {
+        let mut request = Request::builder();
+        request.method(self.method.clone()).uri(self.uri().as_str());
+        for (name, value) in self.headers_with_defaults() {
+            request.header(name.as_str(), value.as_str());
+        }
+        let request = request.body(self.body.clone()).expect("invalid request");
+        let options = Options {
+            profile,
+            stage: self.stage.clone(),
+            binary_media_types: Vec::new(),
+            stage_variables: self.stage_variables.clone(),
+        };
+        let connection = Connection {
+            client_ip: self.source_ip,
+            port: 443,
+            proto: "https",
+        };
+        let mut event = event::to_v1_event(&options, connection, request);
+        if !profile.is_alb() {
+            if let Some(route) = &self.route {
+                event["resource"] = route.as_str().into();
+                event["requestContext"]["resourcePath"] = route.as_str().into();
+            }
+            if !self.path_parameters.is_empty() {
+                event["pathParameters"] = Value::Object(self.path_parameters.clone());
+            }
+        }
+        event
+    }
+
+    fn build_v2(&self, function_url: bool) -> Value {
+        let uri = self.uri();
+        let (path, raw_query) = uri.split_once('?').unwrap_or((&uri, ""));
+        let query: Vec<(String, String)> =
+            serde_urlencoded::from_str(raw_query).unwrap_or_default();
+
+        let mut headers = Map::new();
+        let mut cookies = Vec::new();
+        for (name, value) in self.headers_with_defaults() {
+            if name == COOKIE.as_str() {
+                cookies.extend(value.split(';').map(|cookie| cookie.trim().to_owned()));
+            } else {
+                add_comma_separated(&mut headers, name, value);
+            }
+        }
+        let mut query_string_parameters = Map::new();
+        for (key, value) in query {
+            add_comma_separated(&mut query_string_parameters, key, value);
+        }
+        let (body, is_base64_encoded) = match std::str::from_utf8(&self.body) {
+            Ok(body) => (body.into(), false),
+            Err(_) => (base64::encode(&self.body).into(), true),
+        };
+        let route_key = match (&self.route, function_url) {
+            (Some(route), false) => format!("{} {}", self.method, route),
+            _ => "$default".to_owned(),
+        };
+        let domain_name = headers
+            .get(HOST.as_str())
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned();
+        let now = Utc::now();
+
+        let mut event = json!({
+            "version": "2.0",
+            "routeKey": route_key,
+            "rawPath": path,
+            "rawQueryString": raw_query,
+            "headers": headers,
+            "requestContext": {
+                "accountId": if function_url { "anonymous" } else { "123456789012" },
+                "apiId": domain_name.split('.').next(),
+                "domainName": domain_name,
+                "domainPrefix": domain_name.split('.').next(),
+                "http": {
+                    "method": self.method.as_str(),
+                    "path": path,
+                    "protocol": "HTTP/1.1",
+                    "sourceIp": self.source_ip.to_string(),
+                    "userAgent": headers.get(USER_AGENT.as_str()),
+                },
+                "requestId": request_id(),
+                "routeKey": route_key,
+                "stage": self.stage.as_deref().unwrap_or("$default"),
+                "time": now.format("%d/%b/%Y:%H:%M:%S %z").to_string(),
+                "timeEpoch": now.timestamp_millis(),
+            },
+            "isBase64Encoded": is_base64_encoded,
+        });
+        if !cookies.is_empty() {
+            event["cookies"] = cookies.into();
+        }
+        if !query_string_parameters.is_empty() {
+            event["queryStringParameters"] = query_string_parameters.into();
+        }
+        if !self.body.is_empty() {
+            event["body"] = body;
+        }
+        if !function_url {
+            if !self.path_parameters.is_empty() {
+                event["pathParameters"] = Value::Object(self.path_parameters.clone());
+            }
+            if !self.stage_variables.is_empty() {
+                event["stageVariables"] = Value::Object(self.stage_variables.clone());
+            }
+        }
+        event
+    }
+
+    /// The path with the added query parameters.
+    fn uri(&self) -> String {
+        if self.query.is_empty() {
+            return self.path.clone();
+        }
+        let separator = if self.path.contains('?') { '&' } else { '?' };
+        let query = serde_urlencoded::to_string(&self.query).unwrap_or_default();
+        format!("{}{}{}", self.path, separator, query)
+    }
+
+    fn headers_with_defaults(&self) -> Vec<(String, String)> {
+        let mut headers = Vec::new();
+        for (name, value) in [(HOST, self.default_host()), (USER_AGENT, "curl/8.5.0")] {
+            if !self.headers.iter().any(|(set, _)| set == name.as_str()) {
+                headers.push((name.as_str().to_owned(), value.to_owned()));
+            }
+        }
+        headers.extend(self.headers.iter().cloned());
+        headers
+    }
+
+    fn default_host(&self) -> &'static str {
+        match self.format {
+            Format::V1(Profile::Alb | Profile::AlbMultiValueHeaders) => {
+                "lambda-alb-123578498.us-east-1.elb.amazonaws.com"
+            }
+            Format::V2 { function_url: true } => {
+                "abcdefghij0123456789klmnopqrstuv.lambda-url.us-east-1.on.aws"
+            }
+            _ => "abcdef1234.execute-api.us-east-1.amazonaws.com",
+        }
+    }
+}
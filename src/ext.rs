@@ -0,0 +1,197 @@
+use crate::{cookie, ClientCert, TraceContext};
+use http::Request;
+use lambda_http::request::RequestContext;
+use lambda_http::StrMap;
+use std::collections::HashMap;
+
+/// The path the client requested, before any decoding or stage removal.
+#[derive(Debug, Clone)]
+pub(crate) struct RawPath(pub(crate) String);
+
+/// The query string the client requested.
+#[derive(Debug, Clone)]
+pub(crate) struct RawQueryString(pub(crate) String);
+
+/// The query parameters `lambda_http` parsed from the event.
+#[derive(Debug, Clone)]
+pub(crate) struct QueryStringParameters(pub(crate) StrMap);
+
+/// The path parameters `lambda_http` parsed from the event.
+#[derive(Debug, Clone)]
+pub(crate) struct PathParameters(pub(crate) StrMap);
+
+/// The stage variables `lambda_http` parsed from the event.
+#[derive(Debug, Clone)]
+pub(crate) struct StageVariables(pub(crate) StrMap);
+
+/// Extension methods for requests received by handlers, which work the same way locally and in
+/// AWS Lambda.
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Request, Response};
+/// use lambda_http_local::{testing, RequestExt};
+///
+/// let handler = |request: Request<&[u8]>| {
+///     Response::new(format!("{}?{}", request.raw_path(), request.raw_query_string()))
+/// };
+/// let request = Request::get("/files/a%2Fb?download=1").body(Vec::new()).unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(response.body(), b"/files/a%2Fb?download=1");
+/// ```
+///
+/// The accessors `lambda_http::RequestExt` provides are also available here, since that trait is
+/// only implemented for `lambda_http::Request`, not the request types handlers receive:
+///
+/// ```rust
+/// # use http::{Request, Response};
+/// use lambda_http_local::{testing, RequestExt};
+///
+/// let handler = |request: Request<&[u8]>| {
+///     let query = request.query_string_parameters();
+///     Response::new(query.get_all("tag").unwrap_or_default().join(","))
+/// };
+/// let request = Request::get("/?tag=a&tag=b").body(Vec::new()).unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(response.body(), b"a,b");
+/// ```
+pub trait RequestExt {
+    /// Return the path the client requested, like the `rawPath` field of HTTP API events.
+    ///
+    /// This is the path as it appeared in the request line. Unlike the path of the request URI,
+    /// it still includes the stage when a REST API is served under one.
+    fn raw_path(&self) -> &str;
+
+    /// Return the query string the client requested, without the leading `?`, like the
+    /// `rawQueryString` field of HTTP API events.
+    ///
+    /// Function URLs send the query string as it appeared in the request line. Other services
+    /// only send the parsed query parameters, so the query string is rebuilt from them, which may
+    /// change the order and encoding of parameters.
+    fn raw_query_string(&self) -> &str;
+
+    /// Return the query parameters of the request, like
+    /// `lambda_http::RequestExt::query_string_parameters`.
+    ///
+    /// For requests that did not come from an event, such as those built in tests, the parameters
+    /// are parsed from the request URI.
+    fn query_string_parameters(&self) -> StrMap;
+
+    /// Return the path parameters matched by the API's route, like
+    /// `lambda_http::RequestExt::path_parameters`.
+    fn path_parameters(&self) -> StrMap;
+
+    /// Return the stage variables of the API, like `lambda_http::RequestExt::stage_variables`.
+    fn stage_variables(&self) -> StrMap;
+
+    /// Return the request context of the event, like `lambda_http::RequestExt::request_context`.
+    fn request_context(&self) -> RequestContext;
+
+    /// Return the distributed tracing context of the request: the X-Ray trace of the invocation
+    /// if the function has active tracing, or else the trace of its `traceparent` or
+    /// `X-Amzn-Trace-Id` header.
+    ///
+    /// See [`TraceContext`](struct.TraceContext.html) for details.
+    ///
+    /// Other Lambda runtimes set the `_X_AMZN_TRACE_ID` environment variable to the invocation's
+    /// trace header, where the X-Ray SDKs look for it. Changing the environment while other
+    /// threads may read it is undefined behavior, so this runtime does not; pass the trace header
+    /// from here, or from `Context::xray_trace_id`, to the SDK instead. Both are set alike in AWS
+    /// Lambda and locally.
+    fn trace_context(&self) -> Option<TraceContext>;
+
+    /// Return the client certificate the request was authenticated with, when the API requires
+    /// mutual TLS.
+    ///
+    /// See [`ClientCert`](struct.ClientCert.html) for details.
+    fn client_cert(&self) -> Option<ClientCert>;
+
+    /// Return the value of the cookie `name` the request sent, or the first one if it sent more
+    /// than one.
+    ///
+    /// Function URLs and HTTP APIs using the 2.0 payload format send cookies in the event's
+    /// `cookies` field rather than its headers, and they are combined into a `Cookie` header
+    /// before the handler is called, so cookies are read the same way for every service.
+    fn cookie(&self, name: &str) -> Option<&str>;
+
+    /// Return the names and values of all of the cookies the request sent, in order.
+    ///
+    /// See [`cookie`](#tymethod.cookie) for details.
+    fn cookies(&self) -> Vec<(&str, &str)>;
+}
+
+impl<B> RequestExt for Request<B> {
+    fn raw_path(&self) -> &str {
+        self.extensions()
+            .get::<RawPath>()
+            .map_or_else(|| self.uri().path(), |raw_path| &raw_path.0)
+    }
+
+    fn raw_query_string(&self) -> &str {
+        self.extensions().get::<RawQueryString>().map_or_else(
+            || self.uri().query().unwrap_or_default(),
+            |raw_query_string| &raw_query_string.0,
+        )
+    }
+
+    fn query_string_parameters(&self) -> StrMap {
+        match self.extensions().get::<QueryStringParameters>() {
+            Some(parameters) => parameters.0.clone(),
+            None => parse_query(self.uri().query().unwrap_or_default()),
+        }
+    }
+
+    fn path_parameters(&self) -> StrMap {
+        self.extensions()
+            .get::<PathParameters>()
+            .map(|parameters| parameters.0.clone())
+            .unwrap_or_default()
+    }
+
+    fn stage_variables(&self) -> StrMap {
+        self.extensions()
+            .get::<StageVariables>()
+            .map(|variables| variables.0.clone())
+            .unwrap_or_default()
+    }
+
+    fn request_context(&self) -> RequestContext {
+        self.extensions()
+            .get::<RequestContext>()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn trace_context(&self) -> Option<TraceContext> {
+        match self.extensions().get::<TraceContext>() {
+            Some(trace_context) => Some(trace_context.clone()),
+            None => TraceContext::from_headers(self.headers()),
+        }
+    }
+
+    fn client_cert(&self) -> Option<ClientCert> {
+        self.extensions().get::<ClientCert>().cloned()
+    }
+
+    fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies()
+            .into_iter()
+            .find(|(cookie, _)| *cookie == name)
+            .map(|(_, value)| value)
+    }
+
+    fn cookies(&self) -> Vec<(&str, &str)> {
+        cookie::parse(self.headers())
+    }
+}
+
+fn parse_query(query: &str) -> StrMap {
+    let mut parameters = HashMap::<String, Vec<String>>::new();
+    for (key, value) in
+        serde_urlencoded::from_str::<Vec<(String, String)>>(query).unwrap_or_default()
+    {
+        parameters.entry(key).or_default().push(value);
+    }
+    parameters.into()
+}
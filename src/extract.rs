@@ -0,0 +1,320 @@
+//! Typed values extracted from requests, which reject requests they cannot be extracted from.
+
+use crate::headers::Header;
+use crate::{IntoResponse, InvocationError, RequestExt};
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{self, Debug};
+
+/// A JSON request body, or a response serialized as JSON.
+///
+/// [`Json::from_request`] deserializes the body of a request with a JSON `Content-Type`, and
+/// returning a `Json` from a handler serializes it as the response body, with a `Content-Type` of
+/// `application/json`.
+///
+/// # Example
+///
+/// ```rust
+/// use http::Request;
+/// use lambda_http_local::{testing, Json, Rejection};
+/// use serde_json::{json, Value};
+///
+/// let handler = |request: Request<&[u8]>| -> Result<Json<Value>, Rejection> {
+///     let Json(order): Json<Value> = Json::from_request(&request)?;
+///     Ok(Json(json!({ "total": order["quantity"].as_u64().unwrap_or(0) * 3 })))
+/// };
+///
+/// let request = Request::post("/orders")
+///     .header("content-type", "application/json")
+///     .body(br#"{"item":"apple","quantity":2}"#.to_vec())
+///     .unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(response.body(), br#"{"total":6}"#);
+///
+/// let request = Request::post("/orders")
+///     .header("content-type", "application/json")
+///     .body(br#"{"item":"apple","#.to_vec())
+///     .unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(response.status(), 400);
+/// ```
+///
+/// [`Json::from_request`]: #method.from_request
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+impl<T: DeserializeOwned> Json<T> {
+    /// Deserialize the body of `request` as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Rejects the request with `415 Unsupported Media Type` if its `Content-Type` is not
+    /// `application/json` or another JSON media type, and with `400 Bad Request` if its body
+    /// cannot be deserialized as a `T`.
+    pub fn from_request<B: AsRef<[u8]>>(request: &Request<B>) -> Result<Json<T>, Rejection> {
+        if !has_content_type(request, is_json) {
+            return Err(Rejection::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Expected a request body with Content-Type: application/json",
+            ));
+        }
+        serde_json::from_slice(request.body().as_ref())
+            .map(Json)
+            .map_err(|err| {
+                Rejection::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to parse the request body as JSON: {err}"),
+                )
+            })
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        let body = serde_json::to_vec(&self.0).map_err(InvocationError::new)?;
+        let mut response = Response::new(body);
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(response)
+    }
+}
+
+/// The query parameters of a request, deserialized into a `T`.
+///
+/// The parameters are those [`RequestExt::query_string_parameters`] returns: the ones API Gateway
+/// decoded from the query string, or, for requests that did not come from an event, the ones
+/// decoded from the request URI in the same way. A parameter given more than once takes its first
+/// value.
+///
+/// # Example
+///
+/// ```rust
+/// use http::Request;
+/// use lambda_http_local::{testing, Query, Rejection};
+/// use std::collections::HashMap;
+///
+/// let handler = |request: Request<&[u8]>| -> Result<String, Rejection> {
+///     let Query(query): Query<HashMap<String, String>> = Query::from_request(&request)?;
+///     Ok(format!("Hello, {}!", query["name"]))
+/// };
+///
+/// let request = Request::get("/?name=Ferris+the%20crab").body(Vec::new()).unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(response.body(), b"Hello, Ferris the crab!");
+/// ```
+///
+/// [`RequestExt::query_string_parameters`]: trait.RequestExt.html#tymethod.query_string_parameters
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Query<T>(pub T);
+
+impl<T: DeserializeOwned> Query<T> {
+    /// Deserialize the query parameters of `request`.
+    ///
+    /// # Errors
+    ///
+    /// Rejects the request with `400 Bad Request` if its query parameters cannot be deserialized
+    /// as a `T`.
+    pub fn from_request<B>(request: &Request<B>) -> Result<Query<T>, Rejection> {
+        let parameters = request.query_string_parameters();
+        // Encoding the decoded parameters again is lossless, and lets them be deserialized with
+        // the same conversions from strings as forms.
+        serde_urlencoded::to_string(parameters.iter().collect::<Vec<_>>())
+            .map_err(|err| err.to_string())
+            .and_then(|query| serde_urlencoded::from_str(&query).map_err(|err| err.to_string()))
+            .map(Query)
+            .map_err(|err| {
+                Rejection::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to deserialize the query string: {err}"),
+                )
+            })
+    }
+}
+
+/// A form submitted as an `application/x-www-form-urlencoded` request body, deserialized into a
+/// `T`.
+///
+/// # Example
+///
+/// ```rust
+/// use http::Request;
+/// use lambda_http_local::{testing, Form, Rejection};
+/// use std::collections::HashMap;
+///
+/// let handler = |request: Request<&[u8]>| -> Result<String, Rejection> {
+///     let Form(form): Form<HashMap<String, String>> = Form::from_request(&request)?;
+///     Ok(format!("Subscribed {}", form["email"]))
+/// };
+///
+/// let request = Request::post("/subscribe")
+///     .header("content-type", "application/x-www-form-urlencoded")
+///     .body(b"email=ferris%40example.com".to_vec())
+///     .unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(response.body(), b"Subscribed ferris@example.com");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Form<T>(pub T);
+
+impl<T: DeserializeOwned> Form<T> {
+    /// Deserialize the body of `request` as a form.
+    ///
+    /// # Errors
+    ///
+    /// Rejects the request with `415 Unsupported Media Type` if its `Content-Type` is not
+    /// `application/x-www-form-urlencoded`, and with `400 Bad Request` if its body cannot be
+    /// deserialized as a `T`.
+    pub fn from_request<B: AsRef<[u8]>>(request: &Request<B>) -> Result<Form<T>, Rejection> {
+        if !has_content_type(request, |media_type| {
+            media_type == "application/x-www-form-urlencoded"
+        }) {
+            return Err(Rejection::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Expected a request body with Content-Type: application/x-www-form-urlencoded",
+            ));
+        }
+        serde_urlencoded::from_bytes(request.body().as_ref())
+            .map(Form)
+            .map_err(|err| {
+                Rejection::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to deserialize the form: {err}"),
+                )
+            })
+    }
+}
+
+/// A typed request header.
+///
+/// See the [`headers`](headers/index.html) module for the headers that can be extracted, and an
+/// example.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypedHeader<T>(pub T);
+
+impl<T: Header> TypedHeader<T> {
+    /// Parse the header from `request`.
+    ///
+    /// # Errors
+    ///
+    /// Rejects the request with `400 Bad Request` if it does not have the header, or its value is
+    /// malformed.
+    pub fn from_request<B>(request: &Request<B>) -> Result<TypedHeader<T>, Rejection> {
+        TypedHeader::optional(request)?.ok_or_else(|| {
+            Rejection::new(
+                StatusCode::BAD_REQUEST,
+                format!("Missing request header: {}", T::name()),
+            )
+        })
+    }
+
+    /// Parse the header from `request`, or return `None` if it does not have the header.
+    ///
+    /// # Errors
+    ///
+    /// Rejects the request with `400 Bad Request` if the header's value is malformed.
+    pub fn optional<B>(request: &Request<B>) -> Result<Option<TypedHeader<T>>, Rejection> {
+        let name = T::name();
+        let values = request.headers().get_all(&name).iter().collect::<Vec<_>>();
+        if values.is_empty() {
+            return Ok(None);
+        }
+        T::decode(&values)
+            .map(|header| Some(TypedHeader(header)))
+            .ok_or_else(|| {
+                Rejection::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid request header: {name}"),
+                )
+            })
+    }
+}
+
+/// The response to a request a typed value could not be extracted from, such as a
+/// `400 Bad Request` with a message saying what was wrong with it.
+///
+/// Handlers can return `Result<R, Rejection>` to respond with the rejection when extraction
+/// fails, using the `?` operator. Unlike other errors a handler returns, a rejection is a
+/// response rather than a failed invocation.
+pub struct Rejection {
+    status: StatusCode,
+    message: String,
+}
+
+impl Rejection {
+    pub(crate) fn new<T: Into<String>>(status: StatusCode, message: T) -> Rejection {
+        Rejection {
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// The status of the response.
+    #[must_use]
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// The message explaining why the request was rejected, which is the body of the response.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Debug for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rejection")
+            .field("status", &self.status)
+            .field("message", &self.message)
+            .finish()
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        (self.status, self.message).into_response()
+    }
+}
+
+// `Rejection` does not implement `Display`, so this does not overlap with the implementation for
+// other errors, which fail the invocation.
+impl<R: IntoResponse> IntoResponse for Result<R, Rejection> {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        match self {
+            Ok(response) => response.into_response(),
+            Err(rejection) => rejection.into_response(),
+        }
+    }
+}
+
+/// Whether the `Content-Type` of `request` is a media type `matches` accepts.
+pub(crate) fn has_content_type<B>(request: &Request<B>, matches: fn(&str) -> bool) -> bool {
+    request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| matches(&media_type.trim().to_ascii_lowercase()))
+}
+
+/// The value of the parameter `name` of a header value such as a media type, without any quotes.
+pub(crate) fn parameter<'v>(value: &'v str, name: &str) -> Option<&'v str> {
+    value.split(';').skip(1).find_map(|parameter| {
+        let (key, value) = parameter.split_once('=')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| {
+            let value = value.trim();
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value)
+        })
+    })
+}
+
+/// Whether `media_type`, in lowercase and without parameters, is a JSON media type.
+pub(crate) fn is_json(media_type: &str) -> bool {
+    media_type == "application/json" || media_type.ends_with("+json")
+}
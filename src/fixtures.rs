@@ -0,0 +1,162 @@
+//! Conversions between Lambda proxy integration events and requests, and between responses and
+//! the payloads functions return, for tests and tools that work with saved events.
+//!
+//! These are the conversions the crate itself uses, in the Lambda runtime and the local
+//! development server, so tools like load generators and proxies built on them treat events and
+//! responses exactly as deployed functions do.
+//!
+//! Events from API Gateway REST APIs, HTTP APIs (in both payload formats), Application Load
+//! Balancers, and function URLs are all accepted. The requests they are parsed into are the same
+//! requests handlers receive, including the parameters available through
+//! [`RequestExt`](../trait.RequestExt.html) and the request context. To create sample events from
+//! scratch, use [`EventBuilder`](struct.EventBuilder.html).
+//!
+//! # Example
+//!
+//! ```rust
+//! use lambda_http_local::fixtures;
+//! use lambda_http_local::RequestExt;
+//!
+//! let request = fixtures::from_str(
+//!     r#"{
+//!         "version": "2.0",
+//!         "routeKey": "GET /users/{id}",
+//!         "rawPath": "/users/42",
+//!         "rawQueryString": "fields=name",
+//!         "headers": {"host": "api.example.com"},
+//!         "queryStringParameters": {"fields": "name"},
+//!         "pathParameters": {"id": "42"},
+//!         "isBase64Encoded": false,
+//!         "requestContext": {
+//!             "http": {"method": "GET", "path": "/users/42", "sourceIp": "192.0.2.1"},
+//!             "stage": "$default"
+//!         }
+//!     }"#,
+//! )
+//! .unwrap();
+//! assert_eq!(request.uri(), "https://api.example.com/users/42");
+//! assert_eq!(request.path_parameters().get("id"), Some("42"));
+//! assert_eq!(request.query_string_parameters().get("fields"), Some("name"));
+//! ```
+
+pub use crate::event_builder::EventBuilder;
+
+use crate::event::{self, Connection, Options};
+use crate::{lambda, Profile};
+use http::{Request, Response};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Parse the JSON text of an event into the request a handler would receive for it.
+///
+/// The text may also be an invocation saved with
+/// [`Builder::record`](../struct.Builder.html#method.record), in which case its event is parsed.
+///
+/// # Errors
+///
+/// Returns an error if `json` is not a valid event.
+pub fn from_str(json: &str) -> Result<Request<Vec<u8>>, serde_json::Error> {
+    from_value(serde_json::from_str(json)?)
+}
+
+/// Parse an event into the request a handler would receive for it.
+///
+/// See [`from_str`](fn.from_str.html) for details.
+///
+/// # Errors
+///
+/// Returns an error if `event` is not a valid event.
+pub fn from_value(event: Value) -> Result<Request<Vec<u8>>, serde_json::Error> {
+    let (parts, body) = event::from_saved_event(event)?.into_parts();
+    Ok(Request::from_parts(parts, body.to_vec()))
+}
+
+/// Read an event from a JSON file and parse it into the request a handler would receive for it.
+///
+/// See [`from_str`](fn.from_str.html) for details.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be read, or does not contain a valid event.
+pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Request<Vec<u8>>> {
+    let json = fs::read_to_string(path)?;
+    from_str(&json).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Build the event the service described by `profile` would send the function for `request`.
+///
+/// The request is treated as the local development server treats requests it receives: the
+/// client is `127.0.0.1`, connected over HTTPS, and no stage, binary media types, or stage
+/// variables are configured.
+///
+/// # Example
+///
+/// ```rust
+/// use http::Request;
+/// use lambda_http_local::{fixtures, Profile};
+///
+/// let request = Request::post("/orders?dry_run=true")
+///     .header("host", "api.example.com")
+///     .body(b"{}".to_vec())
+///     .unwrap();
+/// let event = fixtures::to_event(request, Profile::Alb);
+/// assert_eq!(event["httpMethod"], "POST");
+/// assert_eq!(event["queryStringParameters"]["dry_run"], "true");
+/// ```
+#[must_use]
+pub fn to_event(request: Request<Vec<u8>>, profile: Profile) -> Value {
+    let options = Options {
+        profile,
+        ..Options::default()
+    };
+    event::to_event(&options, Connection::default(), request)
+}
+
+/// Build the payload a function returns for `response`, as sent to the service described by
+/// `profile`.
+///
+/// For HTTP APIs and function URLs, which use the 2.0 payload format, `Set-Cookie` headers are
+/// sent in the payload's `cookies` field.
+///
+/// # Example
+///
+/// ```rust
+/// use http::Response;
+/// use lambda_http_local::{fixtures, Profile};
+///
+/// let response = Response::builder()
+///     .status(201)
+///     .header("location", "/orders/7")
+///     .body(b"created".to_vec())
+///     .unwrap();
+/// let payload = fixtures::to_response_event(&response, Profile::HttpApi);
+/// assert_eq!(payload["statusCode"], 201);
+/// assert_eq!(payload["headers"]["location"], "/orders/7");
+/// assert_eq!(payload["body"], "created");
+///
+/// let parsed = fixtures::from_response_event(payload).unwrap();
+/// assert_eq!(parsed.status(), 201);
+/// assert_eq!(parsed.body(), b"created");
+/// ```
+#[must_use]
+pub fn to_response_event(response: &Response<Vec<u8>>, profile: Profile) -> Value {
+    let mut payload = lambda::response_event(profile.is_alb(), response);
+    if matches!(profile, Profile::HttpApi | Profile::FunctionUrl) {
+        lambda::move_cookies(&mut payload);
+    }
+    payload
+}
+
+/// Parse the payload a function returned into the response the service would send for it.
+///
+/// Payloads in both the 1.0 and 2.0 payload formats are accepted. As HTTP APIs do, a payload
+/// without a `statusCode` field is treated as a JSON response body with status 200.
+///
+/// # Errors
+///
+/// Returns an error if `event` has a `statusCode` field but is not otherwise a valid response.
+pub fn from_response_event(event: Value) -> Result<Response<Vec<u8>>, serde_json::Error> {
+    lambda::from_response_event(event)
+}
@@ -0,0 +1,118 @@
+//! Handlers with any of the signatures [`Runtime::run_handler`] accepts.
+//!
+//! [`Runtime::run_handler`]: ../struct.Runtime.html#method.run_handler
+
+use crate::service::Service;
+use crate::IntoResponse;
+use http::Request;
+use lambda_runtime::Context;
+use std::future::Future;
+
+/// A function that can handle requests, which [`Runtime::run_handler`] accepts.
+///
+/// This is implemented for synchronous functions taking a `Request<&[u8]>`, and asynchronous
+/// functions taking a `Request<Vec<u8>>`, either of which can also take the Lambda [`Context`]
+/// of the invocation as a second argument, rather than getting it from the request's extensions.
+/// The type parameter distinguishes the signatures, and is inferred.
+///
+/// ```rust,no_run
+/// use http::{Request, Response};
+/// use lambda_http_local::Runtime;
+/// use lambda_runtime::Context;
+///
+/// Runtime::builder()
+///     .build()
+///     .run_handler(|_: Request<&[u8]>, context: Context| {
+///         Response::new(context.aws_request_id)
+///     });
+/// ```
+///
+/// [`Runtime::run_handler`]: struct.Runtime.html#method.run_handler
+/// [`Context`]: ../lambda_runtime_core/context/struct.Context.html
+pub trait Handler<Signature>: private::Sealed<Signature> {}
+
+impl<T: private::Sealed<Signature>, Signature> Handler<Signature> for T {}
+
+/// The signatures a handler can have.
+pub mod signature {
+    /// `Fn(Request<&[u8]>) -> impl IntoResponse`
+    #[derive(Debug)]
+    pub struct Sync(());
+    /// `Fn(Request<&[u8]>, Context) -> impl IntoResponse`
+    #[derive(Debug)]
+    pub struct SyncWithContext(());
+    /// `Fn(Request<Vec<u8>>) -> impl Future<Output = impl IntoResponse>`
+    #[derive(Debug)]
+    pub struct Async(());
+    /// `Fn(Request<Vec<u8>>, Context) -> impl Future<Output = impl IntoResponse>`
+    #[derive(Debug)]
+    pub struct AsyncWithContext(());
+}
+
+pub(crate) mod private {
+    use crate::service::Service;
+
+    pub trait Sealed<Signature> {
+        fn into_service(self) -> Boxed;
+    }
+
+    /// A handler with its signature normalized, which the crate's users cannot name.
+    pub struct Boxed(pub(crate) Service);
+}
+
+/// The context of the invocation `request` is for.
+fn context<B>(request: &Request<B>) -> Context {
+    request
+        .extensions()
+        .get::<Context>()
+        .cloned()
+        .unwrap_or_default()
+}
+
+impl<F, R> private::Sealed<signature::Sync> for F
+where
+    F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    fn into_service(self) -> private::Boxed {
+        private::Boxed(Service::from_sync(self))
+    }
+}
+
+impl<F, R> private::Sealed<signature::SyncWithContext> for F
+where
+    F: Fn(Request<&[u8]>, Context) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    fn into_service(self) -> private::Boxed {
+        private::Boxed(Service::from_sync(move |request: Request<&[u8]>| {
+            let context = context(&request);
+            self(request, context)
+        }))
+    }
+}
+
+impl<F, R> private::Sealed<signature::Async> for F
+where
+    F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+    R: Future + Send + 'static,
+    R::Output: IntoResponse,
+{
+    fn into_service(self) -> private::Boxed {
+        private::Boxed(Service::from_async(self))
+    }
+}
+
+impl<F, R> private::Sealed<signature::AsyncWithContext> for F
+where
+    F: Fn(Request<Vec<u8>>, Context) -> R + Send + Sync + 'static,
+    R: Future + Send + 'static,
+    R::Output: IntoResponse,
+{
+    fn into_service(self) -> private::Boxed {
+        private::Boxed(Service::from_async(move |request: Request<Vec<u8>>| {
+            let context = context(&request);
+            self(request, context)
+        }))
+    }
+}
@@ -0,0 +1,246 @@
+//! Capturing the local development server's traffic as an HTTP Archive (HAR) file, and reading
+//! requests back from HAR files.
+
+#[cfg(feature = "local")]
+use http::header::CONTENT_TYPE;
+use http::header::HOST;
+#[cfg(feature = "local")]
+use http::{HeaderMap, Response, Version};
+use http::{Request, Uri};
+#[cfg(feature = "local")]
+use serde_json::json;
+use serde_json::Value;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::Path;
+#[cfg(feature = "local")]
+use std::path::PathBuf;
+#[cfg(feature = "local")]
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// The requests and responses to write to a HAR file on shutdown.
+#[cfg(feature = "local")]
+pub(crate) struct Har {
+    path: PathBuf,
+    entries: Mutex<Vec<Value>>,
+}
+
+#[cfg(feature = "local")]
+impl Har {
+    pub(crate) fn new(path: PathBuf) -> Har {
+        Har {
+            path,
+            entries: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add an entry for a request made at `started` (an RFC 3339 timestamp) that took
+    /// `duration`.
+    pub(crate) fn push(
+        &self,
+        started: &str,
+        duration: Duration,
+        request: &Request<Vec<u8>>,
+        response: &Response<Vec<u8>>,
+    ) {
+        let host = request
+            .headers()
+            .get(HOST)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("localhost");
+        let query_string = request
+            .uri()
+            .query()
+            .map(|query| {
+                serde_urlencoded::from_str::<Vec<(String, String)>>(query).unwrap_or_default()
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect::<Vec<_>>();
+        let mut har_request = json!({
+            "method": request.method().as_str(),
+            "url": format!("http://{}{}", host, request.uri()),
+            "httpVersion": http_version(request.version()),
+            "cookies": [],
+            "headers": headers(request.headers()),
+            "queryString": query_string,
+            "headersSize": -1,
+            "bodySize": request.body().len(),
+        });
+        if !request.body().is_empty() {
+            let mut post_data = content(request.headers(), request.body());
+            if let Some(post_data) = post_data.as_object_mut() {
+                post_data.remove("size");
+            }
+            har_request["postData"] = post_data;
+        }
+
+        let status = response.status();
+        let time = duration.as_secs_f64() * 1000.0;
+        let entry = json!({
+            "startedDateTime": started,
+            "time": time,
+            "request": har_request,
+            "response": {
+                "status": status.as_u16(),
+                "statusText": status.canonical_reason().unwrap_or_default(),
+                "httpVersion": http_version(response.version()),
+                "cookies": [],
+                "headers": headers(response.headers()),
+                "content": content(response.headers(), response.body()),
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": response.body().len(),
+            },
+            "cache": {},
+            "timings": { "send": 0, "wait": time, "receive": 0 },
+        });
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(entry);
+    }
+
+    /// Write the file, replacing it if it exists.
+    pub(crate) fn write(&self) -> io::Result<()> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "entries": *entries,
+            },
+        });
+        let contents = serde_json::to_vec_pretty(&har).map_err(io::Error::from)?;
+        fs::write(&self.path, contents)
+    }
+}
+
+#[cfg(feature = "local")]
+fn http_version(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_2 => "HTTP/2",
+        _ => "HTTP/1.1",
+    }
+}
+
+#[cfg(feature = "local")]
+fn headers(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.as_str(),
+                "value": String::from_utf8_lossy(value.as_bytes()),
+            })
+        })
+        .collect()
+}
+
+/// A body as HAR content, base64-encoded if it is not valid UTF-8.
+#[cfg(feature = "local")]
+fn content(headers: &HeaderMap, body: &[u8]) -> Value {
+    let mime_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let mut content = json!({ "size": body.len(), "mimeType": mime_type });
+    if let Ok(text) = std::str::from_utf8(body) {
+        content["text"] = text.into();
+    } else {
+        content["text"] = base64::encode(body).into();
+        content["encoding"] = "base64".into();
+    }
+    content
+}
+
+/// A request read from a HAR file, with the status and time recorded for its response.
+pub(crate) struct Entry {
+    pub(crate) request: Request<Vec<u8>>,
+    pub(crate) status: u16,
+    pub(crate) time: Duration,
+}
+
+/// Read the requests from the HAR file at `path`.
+///
+/// HTTP/2 pseudo-headers, which browsers include in HAR files, are dropped, and the `Host` header
+/// is set from the URL if it is missing.
+pub(crate) fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    let invalid = |message: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), message),
+        )
+    };
+    let har: Value =
+        serde_json::from_slice(&fs::read(path)?).map_err(|err| invalid(err.to_string()))?;
+    let entries = har
+        .pointer("/log/entries")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("missing log.entries".to_owned()))?;
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            read_entry(entry).map_err(|message| invalid(format!("entry {index}: {message}")))
+        })
+        .collect()
+}
+
+fn read_entry(entry: &Value) -> Result<Entry, String> {
+    let har_request = &entry["request"];
+    let url = har_request["url"]
+        .as_str()
+        .ok_or("missing request.url")?
+        .parse::<Uri>()
+        .map_err(|err| format!("invalid request.url: {err}"))?;
+    let mut request = Request::builder();
+    request
+        .method(har_request["method"].as_str().unwrap_or("GET"))
+        .uri(url.path_and_query().map_or("/", |path| path.as_str()));
+    let mut has_host = false;
+    for header in har_request["headers"].as_array().into_iter().flatten() {
+        let name = header["name"].as_str().unwrap_or_default();
+        if name.is_empty() || name.starts_with(':') {
+            continue;
+        }
+        has_host |= name.eq_ignore_ascii_case(HOST.as_str());
+        request.header(name, header["value"].as_str().unwrap_or_default());
+    }
+    if let (false, Some(authority)) = (has_host, url.authority_part()) {
+        request.header(HOST, authority.as_str());
+    }
+    let post_data = &har_request["postData"];
+    let text = post_data["text"].as_str().unwrap_or_default();
+    let body = if post_data["encoding"] == "base64" {
+        base64::decode(text).map_err(|err| format!("invalid request.postData: {err}"))?
+    } else {
+        text.as_bytes().to_vec()
+    };
+    let request = request
+        .body(body)
+        .map_err(|err| format!("invalid request: {err}"))?;
+
+    let status = entry["response"]["status"]
+        .as_u64()
+        .and_then(|status| u16::try_from(status).ok())
+        .unwrap_or_default();
+    let time = entry["time"].as_f64().unwrap_or_default().max(0.0) / 1000.0;
+    Ok(Entry {
+        request,
+        status,
+        time: Duration::from_secs_f64(time),
+    })
+}
@@ -0,0 +1,231 @@
+//! Typed request headers, extracted with [`TypedHeader`].
+//!
+//! [`TypedHeader`]: ../struct.TypedHeader.html
+//!
+//! # Example
+//!
+//! ```rust
+//! use http::{Request, StatusCode};
+//! use lambda_http_local::headers::{Authorization, IfNoneMatch};
+//! use lambda_http_local::{testing, Rejection, TypedHeader};
+//!
+//! let handler = |request: Request<&[u8]>| -> Result<(StatusCode, &'static str), Rejection> {
+//!     let TypedHeader(authorization): TypedHeader<Authorization> =
+//!         TypedHeader::from_request(&request)?;
+//!     if authorization.bearer() != Some("secret") {
+//!         return Ok((StatusCode::FORBIDDEN, "Forbidden"));
+//!     }
+//!     let if_none_match = TypedHeader::<IfNoneMatch>::optional(&request)?;
+//!     if if_none_match.is_some_and(|TypedHeader(tags)| tags.matches("\"v1\"")) {
+//!         return Ok((StatusCode::NOT_MODIFIED, ""));
+//!     }
+//!     Ok((StatusCode::OK, "Hello, world!"))
+//! };
+//!
+//! let request = Request::get("/")
+//!     .header("authorization", "Bearer secret")
+//!     .header("if-none-match", "W/\"v1\"")
+//!     .body(Vec::new())
+//!     .unwrap();
+//! let response = testing::invoke(handler, request).unwrap();
+//! assert_eq!(response.status(), 304);
+//!
+//! let request = Request::get("/").body(Vec::new()).unwrap();
+//! let response = testing::invoke(handler, request).unwrap();
+//! assert_eq!(response.status(), 400);
+//! assert_eq!(response.body(), b"Missing request header: authorization");
+//! ```
+
+use crate::extract::parameter;
+use http::header::{
+    HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, IF_NONE_MATCH, USER_AGENT,
+};
+
+/// A header that can be extracted from a request with [`TypedHeader`].
+///
+/// [`TypedHeader`]: ../struct.TypedHeader.html
+pub trait Header: Sized {
+    /// The name of the header.
+    fn name() -> HeaderName;
+
+    /// Parse the values the header was sent with, of which there is at least one, or return
+    /// `None` if they are malformed.
+    fn decode(values: &[&HeaderValue]) -> Option<Self>;
+}
+
+/// The only value of a header that cannot be repeated, as text.
+fn single<'v>(values: &[&'v HeaderValue]) -> Option<&'v str> {
+    match values {
+        [value] => value.to_str().ok(),
+        _ => None,
+    }
+}
+
+/// The `Content-Type` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType(String);
+
+impl ContentType {
+    /// The media type, in lowercase and without parameters, such as `text/html`.
+    #[must_use]
+    pub fn media_type(&self) -> String {
+        self.0
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+    }
+
+    /// The value of the parameter `name`, such as `charset`.
+    #[must_use]
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        parameter(&self.0, name)
+    }
+
+    /// The whole header value.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Header for ContentType {
+    fn name() -> HeaderName {
+        CONTENT_TYPE
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<ContentType> {
+        single(values)
+            .filter(|value| value.split(';').next().unwrap_or_default().contains('/'))
+            .map(|value| ContentType(value.to_owned()))
+    }
+}
+
+/// The `Authorization` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authorization {
+    scheme: String,
+    credentials: String,
+}
+
+impl Authorization {
+    /// The authentication scheme, such as `Bearer` or `Basic`.
+    #[must_use]
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// The credentials following the scheme.
+    #[must_use]
+    pub fn credentials(&self) -> &str {
+        &self.credentials
+    }
+
+    /// The token, if the scheme is `Bearer`.
+    #[must_use]
+    pub fn bearer(&self) -> Option<&str> {
+        self.scheme
+            .eq_ignore_ascii_case("Bearer")
+            .then_some(self.credentials.as_str())
+    }
+
+    /// The user name and password, if the scheme is `Basic` and the credentials are valid.
+    #[must_use]
+    pub fn basic(&self) -> Option<(String, String)> {
+        if !self.scheme.eq_ignore_ascii_case("Basic") {
+            return None;
+        }
+        let decoded = String::from_utf8(base64::decode(&self.credentials).ok()?).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+        Some((user.to_owned(), password.to_owned()))
+    }
+}
+
+impl Header for Authorization {
+    fn name() -> HeaderName {
+        AUTHORIZATION
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Authorization> {
+        let (scheme, credentials) = single(values)?.trim().split_once(' ')?;
+        Some(Authorization {
+            scheme: scheme.to_owned(),
+            credentials: credentials.trim().to_owned(),
+        })
+    }
+}
+
+/// The `If-None-Match` header, which makes a request conditional on the resource not having any
+/// of the entity tags it lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IfNoneMatch {
+    /// The entity tags, or `None` for `*`, which matches any.
+    tags: Option<Vec<String>>,
+}
+
+impl IfNoneMatch {
+    /// Whether the header lists `etag`, such as `"v1"`, using the weak comparison the HTTP
+    /// specification requires for `If-None-Match`, or is `*`.
+    ///
+    /// A server should respond to `GET` requests whose `If-None-Match` matches the resource's
+    /// entity tag with `304 Not Modified`.
+    #[must_use]
+    pub fn matches(&self, etag: &str) -> bool {
+        let opaque = |tag: &str| tag.strip_prefix("W/").unwrap_or(tag).to_owned();
+        self.tags
+            .as_ref()
+            .is_none_or(|tags| tags.iter().any(|tag| opaque(tag) == opaque(etag)))
+    }
+
+    /// The entity tags the header lists, or `None` if it is `*`.
+    #[must_use]
+    pub fn tags(&self) -> Option<&[String]> {
+        self.tags.as_deref()
+    }
+}
+
+impl Header for IfNoneMatch {
+    fn name() -> HeaderName {
+        IF_NONE_MATCH
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<IfNoneMatch> {
+        if single(values).is_some_and(|value| value.trim() == "*") {
+            return Some(IfNoneMatch { tags: None });
+        }
+        let mut tags = Vec::new();
+        for value in values {
+            for tag in value.to_str().ok()?.split(',').map(str::trim) {
+                let opaque = tag.strip_prefix("W/").unwrap_or(tag);
+                if opaque.len() < 2 || !opaque.starts_with('"') || !opaque.ends_with('"') {
+                    return None;
+                }
+                tags.push(tag.to_owned());
+            }
+        }
+        Some(IfNoneMatch { tags: Some(tags) })
+    }
+}
+
+/// The `User-Agent` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAgent(String);
+
+impl UserAgent {
+    /// The whole header value.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Header for UserAgent {
+    fn name() -> HeaderName {
+        USER_AGENT
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<UserAgent> {
+        single(values).map(|value| UserAgent(value.to_owned()))
+    }
+}
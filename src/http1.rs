@@ -0,0 +1,112 @@
+//! Adapters for frameworks built on version 1 of the `http` crate and Tokio 1.
+
+use crate::service::Service;
+use crate::{InvocationError, TraceContext};
+use bytes::Bytes;
+use futures::future::{self, FutureExt};
+use http::{Request, Response};
+use http_body_util::BodyExt;
+use lambda_http::request::RequestContext;
+use lambda_runtime::Context;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// Wrap an `axum::Router` as a handler.
+#[cfg(feature = "axum")]
+pub(crate) fn axum(router: axum::Router) -> Service {
+    service::<_, axum::body::Body, _>(router)
+}
+
+/// Wrap a warp filter as a handler.
+#[cfg(feature = "warp")]
+pub(crate) fn warp<F>(filter: F) -> Service
+where
+    F: warp::Filter<Error = warp::Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: warp::Reply,
+    F::Future: Send,
+{
+    service::<_, http_body_util::Full<Bytes>, _>(warp::service(filter))
+}
+
+/// Wrap a service as a handler. The service is run on a Tokio 1 runtime of its own, and requests
+/// and responses are converted between the two `http` versions.
+fn service<S, B, R>(service: S) -> Service
+where
+    S: tower_service::Service<http1::Request<B>, Response = http1::Response<R>, Error = Infallible>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send,
+    B: From<Bytes> + Send + 'static,
+    R: http_body::Body<Data = Bytes> + Send + 'static,
+    R::Error: Display,
+{
+    let runtime = Arc::new(
+        tokio1::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start Tokio runtime"),
+    );
+    Service::new(
+        move |request| {
+            let mut service = service.clone();
+            let response = runtime.spawn(async move {
+                future::poll_fn(|cx| service.poll_ready(cx))
+                    .await
+                    .unwrap_or_else(|err| match err {});
+                let response = service
+                    .call(into_http1(request))
+                    .await
+                    .unwrap_or_else(|err| match err {});
+                from_http1(response).await
+            });
+            async move { response.await.map_err(InvocationError::new)? }.boxed()
+        },
+        false,
+    )
+}
+
+fn into_http1<B: From<Bytes>>(request: Request<Vec<u8>>) -> http1::Request<B> {
+    let (parts, body) = request.into_parts();
+    let mut builder = http1::Request::builder()
+        .method(parts.method.as_str())
+        .uri(parts.uri.to_string());
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    if let Some(context) = parts.extensions.get::<Context>() {
+        builder = builder.extension(context.clone());
+    }
+    if let Some(context) = parts.extensions.get::<RequestContext>() {
+        builder = builder.extension(context.clone());
+    }
+    if let Some(context) = parts.extensions.get::<TraceContext>() {
+        builder = builder.extension(context.clone());
+    }
+    builder
+        .body(B::from(Bytes::from(body)))
+        .expect("failed to convert request")
+}
+
+async fn from_http1<B>(response: http1::Response<B>) -> Result<Response<Vec<u8>>, InvocationError>
+where
+    B: http_body::Body<Data = Bytes>,
+    B::Error: Display,
+{
+    let (parts, body) = response.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map_err(InvocationError::new)?
+        .to_bytes();
+    let mut builder = Response::builder();
+    builder.status(parts.status.as_u16());
+    for (name, value) in &parts.headers {
+        builder.header(name.as_str(), value.as_bytes());
+    }
+    Ok(builder
+        .body(body.to_vec())
+        .expect("failed to convert response"))
+}
@@ -0,0 +1,240 @@
+//! Serving HTTP/3 from the local development server, over QUIC.
+//!
+//! QUIC is only implemented for Tokio 1, so the listener runs on a Tokio 1 runtime of its own,
+//! and hands each request to the server's Tokio 0.1 runtime to be handled like any other.
+
+use crate::event::Connection;
+use crate::tls;
+use crate::{ClientCert, Error};
+use bytes::{Buf, Bytes};
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use futures01::Future;
+use http::{Request, Response, Version};
+use quinn::crypto::rustls::QuicServerConfig;
+use quinn::rustls::pki_types::pem::PemObject;
+use quinn::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use quinn::rustls::server::WebPkiClientVerifier;
+use quinn::rustls::{self, RootCertStore};
+use std::convert::TryFrom;
+use std::io;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use tokio::runtime::TaskExecutor;
+
+/// Handles a request received over HTTP/3, resolving to the complete response.
+pub(crate) type Handler = Arc<
+    dyn Fn(
+            Connection,
+            Request<hyper::Body>,
+        ) -> Box<dyn Future<Item = Response<Vec<u8>>, Error = hyper::Error> + Send>
+        + Send
+        + Sync,
+>;
+
+/// Headers that are specific to HTTP/1.1 connections, which HTTP/3 responses must not include.
+const CONNECTION_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Build the QUIC configuration for a PEM-encoded certificate chain and private key, requiring
+/// clients to present a certificate signed by the PEM-encoded CA certificates at
+/// `client_ca_path`, if set.
+pub(crate) fn server_config(
+    cert_path: &Path,
+    key_path: &Path,
+    client_ca_path: Option<&Path>,
+) -> Result<quinn::ServerConfig, Error> {
+    let certs = read_certs(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|err| tls::invalid(key_path, &err.to_string()))?;
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = match client_ca_path {
+        Some(client_ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(client_ca_path)? {
+                roots
+                    .add(cert)
+                    .map_err(|err| tls::invalid(client_ca_path, &err.to_string()))?;
+            }
+            WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map_err(|err| tls::invalid(client_ca_path, &err.to_string()))?
+        }
+        None => WebPkiClientVerifier::no_client_auth(),
+    };
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .and_then(|builder| {
+            builder
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(certs, key)
+        })
+        .map_err(|err| tls::invalid(cert_path, &err.to_string()))?;
+    config.alpn_protocols = vec![b"h3".to_vec()];
+    let config = QuicServerConfig::try_from(config)
+        .map_err(|err| tls::invalid(cert_path, &err.to_string()))?;
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(config)))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .map_err(|err| tls::invalid(path, &err.to_string()))?;
+    if certs.is_empty() {
+        return Err(tls::invalid(path, "no certificates found"));
+    }
+    Ok(certs)
+}
+
+/// Serve HTTP/3 from `socket` on a thread of its own until `shutdown` resolves, handling requests
+/// with `handler` on `executor`.
+pub(crate) fn spawn(
+    socket: UdpSocket,
+    config: quinn::ServerConfig,
+    executor: TaskExecutor,
+    handler: Handler,
+    shutdown: oneshot::Receiver<()>,
+) -> io::Result<JoinHandle<()>> {
+    let port = socket.local_addr()?.port();
+    let runtime = tokio1::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let endpoint = {
+        let _guard = runtime.enter();
+        quinn::Endpoint::new(
+            quinn::EndpointConfig::default(),
+            Some(config),
+            socket,
+            Arc::new(quinn::TokioRuntime),
+        )?
+    };
+    Ok(thread::spawn(move || {
+        runtime.block_on(async move {
+            let accept = async {
+                while let Some(incoming) = endpoint.accept().await {
+                    let handler = handler.clone();
+                    let executor = executor.clone();
+                    tokio1::spawn(async move {
+                        // Like the HTTP/1.1 and HTTP/2 listeners, failed connections are dropped.
+                        let _ = serve_connection(incoming, port, executor, handler).await;
+                    });
+                }
+            };
+            futures::pin_mut!(accept);
+            if let Either::Left(_) = future::select(accept, shutdown).await {
+                return;
+            }
+            endpoint.close(0_u32.into(), b"shutting down");
+            endpoint.wait_idle().await;
+        });
+    }))
+}
+
+async fn serve_connection(
+    incoming: quinn::Incoming,
+    port: u16,
+    executor: TaskExecutor,
+    handler: Handler,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let connection = incoming.await?;
+    let client_cert = connection
+        .peer_identity()
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+        .and_then(|certs| ClientCert::from_der(certs.first()?));
+    let info = Connection {
+        client_ip: connection.remote_address().ip(),
+        port,
+        proto: "https",
+    };
+    let mut connection = h3::server::Connection::new(h3_quinn::Connection::new(connection)).await?;
+    while let Some(resolver) = connection.accept().await? {
+        let handler = handler.clone();
+        let executor = executor.clone();
+        let client_cert = client_cert.clone();
+        tokio1::spawn(async move {
+            let _ = serve_request(resolver, info, client_cert, executor, handler).await;
+        });
+    }
+    Ok(())
+}
+
+async fn serve_request(
+    resolver: h3::server::RequestResolver<h3_quinn::Connection, Bytes>,
+    connection: Connection,
+    client_cert: Option<ClientCert>,
+    executor: TaskExecutor,
+    handler: Handler,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (request, mut stream) = resolver.resolve_request().await?;
+    let mut body = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await? {
+        while chunk.has_remaining() {
+            let bytes = chunk.chunk();
+            body.extend_from_slice(bytes);
+            let len = bytes.len();
+            chunk.advance(len);
+        }
+    }
+    let mut request = from_http1(request, body);
+    if let Some(client_cert) = client_cert {
+        request.extensions_mut().insert(client_cert);
+    }
+
+    let (sender, receiver) = oneshot::channel();
+    executor.spawn(handler(connection, request).then(move |result| {
+        let _ = sender.send(result);
+        Ok(())
+    }));
+    let (response, body) = match receiver.await {
+        Ok(Ok(response)) => into_http1(response),
+        _ => (
+            http1::Response::builder()
+                .status(http1::StatusCode::INTERNAL_SERVER_ERROR)
+                .body(())?,
+            Vec::new(),
+        ),
+    };
+    stream.send_response(response).await?;
+    if !body.is_empty() {
+        stream.send_data(Bytes::from(body)).await?;
+    }
+    stream.finish().await?;
+    Ok(())
+}
+
+/// Convert an HTTP/3 request to the request the other listeners receive.
+///
+/// Version 0.1 of the `http` crate predates HTTP/3, so the request is marked as HTTP/2, whose
+/// header semantics are the same.
+fn from_http1(request: http1::Request<()>, body: Vec<u8>) -> Request<hyper::Body> {
+    let (parts, ()) = request.into_parts();
+    let mut builder = Request::builder();
+    builder
+        .method(parts.method.as_str())
+        .uri(parts.uri.to_string())
+        .version(Version::HTTP_2);
+    for (name, value) in &parts.headers {
+        builder.header(name.as_str(), value.as_bytes());
+    }
+    builder
+        .body(hyper::Body::from(body))
+        .expect("failed to convert request")
+}
+
+fn into_http1(response: Response<Vec<u8>>) -> (http1::Response<()>, Vec<u8>) {
+    let (parts, body) = response.into_parts();
+    let mut builder = http1::Response::builder().status(parts.status.as_u16());
+    for (name, value) in &parts.headers {
+        if !CONNECTION_HEADERS.contains(&name.as_str()) {
+            builder = builder.header(name.as_str(), value.as_bytes());
+        }
+    }
+    (builder.body(()).expect("failed to convert response"), body)
+}
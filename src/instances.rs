@@ -0,0 +1,292 @@
+//! Simulating the execution environments AWS Lambda runs a function in.
+//!
+//! Each execution environment handles one invocation at a time. Invocations go to an idle
+//! environment if there is one, and otherwise start a new one, up to a limit, past which they
+//! wait for an environment to become idle. Environments that sit idle for too long are stopped,
+//! unless they were provisioned in advance.
+
+use crate::service::Service;
+use crate::ContextBuilder;
+use futures01::future::{self, Future};
+use futures01::sync::oneshot;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Acquire = Box<dyn Future<Item = Lease, Error = ()> + Send>;
+
+/// The execution environments of the function.
+pub(crate) struct Instances {
+    /// The handler the first environment uses, once it has been created.
+    first: Mutex<Option<Service>>,
+    /// The handler later environments use if they do not create their own.
+    shared: Service,
+    max: Option<usize>,
+    idle_timeout: Option<Duration>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The number of environments, idle or busy.
+    count: usize,
+    /// The number of environments started so far.
+    started: usize,
+    /// Idle environments, with the most recently used last.
+    idle: Vec<Instance>,
+    /// Invocations waiting for an environment, oldest first. They are sent `None` when an
+    /// environment stops, so that they start a new one in its place.
+    waiting: VecDeque<oneshot::Sender<Option<Instance>>>,
+}
+
+/// An execution environment.
+struct Instance {
+    id: usize,
+    service: Service,
+    /// The log stream name, if it is not the one the context is configured with.
+    log_stream_name: Option<String>,
+    /// When the environment last finished an invocation.
+    last_used: Instant,
+    /// Whether the environment was started in advance, and so is never stopped.
+    provisioned: bool,
+    /// Whether the environment failed to start, and so is stopped after its first invocation.
+    failed: bool,
+}
+
+impl Instances {
+    pub(crate) fn new(
+        service: Service,
+        max: Option<usize>,
+        idle_timeout: Option<Duration>,
+    ) -> Instances {
+        Instances {
+            first: Mutex::new((!service.is_per_instance()).then(|| service.clone())),
+            shared: service,
+            max,
+            idle_timeout,
+            state: Mutex::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Create the handler for the first execution environment, which starts with the first
+    /// invocation, and then start `provisioned` environments in advance, returning how many were
+    /// started, or the message of the init error the first handler failed with.
+    pub(crate) fn initialize(
+        &self,
+        provisioned: usize,
+        context: &ContextBuilder,
+    ) -> Result<usize, String> {
+        if let Some(service) = self.shared.new_instance() {
+            *self.first.lock().unwrap_or_else(PoisonError::into_inner) = Some(service?);
+        }
+        Ok(self.provision(provisioned, context))
+    }
+
+    /// Start `count` execution environments in advance, as provisioned concurrency does, or as
+    /// many as the maximum allows, returning how many were started. Those that fail to
+    /// initialize are stopped.
+    fn provision(&self, count: usize, context: &ContextBuilder) -> usize {
+        let count = self.max.map_or(count, |max| count.min(max));
+        let mut provisioned = 0;
+        for _ in 0..count {
+            let id = {
+                let mut state = self.state();
+                state.count += 1;
+                state.started += 1;
+                state.started
+            };
+            let mut instance = self.start(id, context);
+            if instance.failed {
+                self.release(None);
+                continue;
+            }
+            instance.provisioned = true;
+            self.state().idle.push(instance);
+            provisioned += 1;
+        }
+        provisioned
+    }
+
+    /// Take an idle execution environment, start a new one, or wait for one to become idle.
+    pub(crate) fn acquire(self: &Arc<Self>, context: &ContextBuilder) -> Acquire {
+        let mut state = self.state();
+        if let Some(idle_timeout) = self.idle_timeout {
+            let idle = state.idle.len();
+            state.idle.retain(|instance| {
+                instance.provisioned || instance.last_used.elapsed() < idle_timeout
+            });
+            state.count -= idle - state.idle.len();
+        }
+        if let Some(instance) = state.idle.pop() {
+            return Box::new(future::ok(self.lease(instance, None)));
+        }
+        if self.max.is_some_and(|max| state.count >= max) {
+            let (sender, receiver) = oneshot::channel();
+            state.waiting.push_back(sender);
+            let instances = self.clone();
+            let context = context.clone();
+            return Box::new(
+                receiver
+                    .map_err(|_| ())
+                    .and_then(move |instance| -> Acquire {
+                        match instance {
+                            Some(instance) => Box::new(future::ok(instances.lease(instance, None))),
+                            None => instances.create(&context),
+                        }
+                    }),
+            );
+        }
+        state.count += 1;
+        drop(state);
+        self.create(context)
+    }
+
+    /// Start a new execution environment, already counted, for an invocation.
+    ///
+    /// Creating the handler may be slow, so it happens on its own thread, without holding the
+    /// lock. If it panics, the invocation fails and the environment is stopped.
+    fn create(self: &Arc<Self>, context: &ContextBuilder) -> Acquire {
+        let id = {
+            let mut state = self.state();
+            state.started += 1;
+            state.started
+        };
+        let init_start = Instant::now();
+        let (sender, receiver) = oneshot::channel();
+        let instances = self.clone();
+        let context = context.clone();
+        thread::spawn(move || {
+            let _ = sender.send(instances.start(id, &context));
+        });
+        let instances = self.clone();
+        Box::new(receiver.then(move |instance| {
+            let instance = instance.unwrap_or_else(|_| Instance {
+                failed: true,
+                ..Instance::new(id, Service::failing("init panicked".to_owned()), None)
+            });
+            Ok(instances.lease(instance, Some(init_start.elapsed())))
+        }))
+    }
+
+    /// Create the execution environment numbered `id`.
+    ///
+    /// If its handler fails to initialize, as in AWS Lambda, the first invocation fails with the
+    /// error, and the environment is then stopped, so that the next invocation starts another.
+    fn start(&self, id: usize, context: &ContextBuilder) -> Instance {
+        let first = self
+            .first
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(service) = first {
+            return Instance::new(id, service, None);
+        }
+        let log_stream_name = Some(context.new_log_stream_name());
+        match self.shared.new_instance() {
+            Some(Ok(service)) => Instance::new(id, service, log_stream_name),
+            Some(Err(message)) => Instance {
+                failed: true,
+                ..Instance::new(
+                    id,
+                    Service::failing(format!("init failed: {message}")),
+                    log_stream_name,
+                )
+            },
+            None => Instance::new(id, self.shared.clone(), log_stream_name),
+        }
+    }
+
+    fn lease(self: &Arc<Self>, instance: Instance, init_duration: Option<Duration>) -> Lease {
+        Lease {
+            instance: Some(instance),
+            instances: self.clone(),
+            init_duration,
+        }
+    }
+
+    /// Hand an execution environment that has finished an invocation, or the place of one that
+    /// has stopped (`None`), to the next waiting invocation, or keep it for later.
+    fn release(&self, mut instance: Option<Instance>) {
+        let mut state = self.state();
+        while let Some(waiting) = state.waiting.pop_front() {
+            // Invocations that stopped waiting have dropped their receivers.
+            match waiting.send(instance) {
+                Ok(()) => return,
+                Err(returned) => instance = returned,
+            }
+        }
+        match instance {
+            Some(mut instance) => {
+                instance.last_used = Instant::now();
+                state.idle.push(instance);
+            }
+            None => state.count -= 1,
+        }
+    }
+}
+
+impl Instance {
+    fn new(id: usize, service: Service, log_stream_name: Option<String>) -> Instance {
+        Instance {
+            id,
+            service,
+            log_stream_name,
+            last_used: Instant::now(),
+            provisioned: false,
+            failed: false,
+        }
+    }
+}
+
+/// An execution environment, held for the duration of an invocation.
+pub(crate) struct Lease {
+    instance: Option<Instance>,
+    instances: Arc<Instances>,
+    /// How long the execution environment took to start, if it was started for this invocation.
+    init_duration: Option<Duration>,
+}
+
+impl Lease {
+    fn instance(&self) -> &Instance {
+        self.instance.as_ref().expect("lease already released")
+    }
+
+    /// A number identifying the execution environment, starting from 1.
+    pub(crate) fn id(&self) -> usize {
+        self.instance().id
+    }
+
+    pub(crate) fn service(&self) -> &Service {
+        &self.instance().service
+    }
+
+    pub(crate) fn log_stream_name(&self) -> Option<&str> {
+        self.instance().log_stream_name.as_deref()
+    }
+
+    /// How long the execution environment took to start, if it was started for this invocation.
+    pub(crate) fn init_duration(&self) -> Option<Duration> {
+        self.init_duration
+    }
+
+    /// Count `duration` towards the time the execution environment took to start.
+    pub(crate) fn add_init_duration(&mut self, duration: Duration) {
+        if let Some(init_duration) = &mut self.init_duration {
+            *init_duration += duration;
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if let Some(instance) = self.instance.take() {
+            self.instances
+                .release((!instance.failed).then_some(instance));
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::event;
+use crate::service::{BoxFuture, Service};
+use crate::TraceContext;
+use futures::TryFutureExt;
+use http::header::{CONTENT_TYPE, SET_COOKIE};
+use http::{Request, Response};
+use lambda_http::request::RequestContext;
+use lambda_runtime::error::HandlerError;
+use lambda_runtime::Context;
+use lambda_runtime_client::error::ErrorResponse;
+use lambda_runtime_client::RuntimeClient;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::env;
+
+pub(crate) fn start(service: Service) {
+    let mut runtime = tokio::runtime::Runtime::new().expect("failed to start Tokio runtime");
+    lambda_runtime::start(
+        move |event: Value, context: Context| {
+            let is_v2 = event["version"] == "2.0";
+            let request = event::from_event(event)
+                .map_err(|err| HandlerError::from(err.to_string().as_str()))?;
+            let is_alb = request
+                .extensions()
+                .get::<RequestContext>()
+                .is_some_and(RequestContext::is_alb);
+            runtime
+                .block_on(invoke(&service, request, context).compat())
+                .map(|response| {
+                    let mut payload = response_event(is_alb, &response);
+                    if is_v2 {
+                        move_cookies(&mut payload);
+                    }
+                    payload
+                })
+                .map_err(|err| HandlerError::from(err.message()))
+        },
+        None,
+    );
+}
+
+/// Report to the Runtime API that the function failed to initialize with `message`, so that AWS
+/// Lambda records an init error rather than a crash.
+pub(crate) fn fail_init(message: &str) {
+    let Ok(endpoint) = env::var("AWS_LAMBDA_RUNTIME_API") else {
+        return;
+    };
+    let Ok(client) = RuntimeClient::new(&endpoint, None, None) else {
+        return;
+    };
+    client.fail_init(&ErrorResponse {
+        error_message: message.to_owned(),
+        error_type: "InitError".to_owned(),
+        stack_trace: None,
+    });
+}
+
+/// Build the response payload sent to the Runtime API for `response`, in the same format as
+/// `lambda_http`.
+///
+/// Bodies that are valid UTF-8 are sent as text, so that API Gateway REST APIs without binary
+/// media types configured return them unchanged; other bodies are base64-encoded.
+pub(crate) fn response_event(is_alb: bool, response: &Response<Vec<u8>>) -> Value {
+    let mut headers = Map::new();
+    let mut multi_value_headers = Map::new();
+    for name in response.headers().keys() {
+        let values = response
+            .headers()
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>();
+        if let Some(first) = values.first() {
+            headers.insert(name.to_string(), (*first).into());
+        }
+        multi_value_headers.insert(name.to_string(), values.into());
+    }
+
+    let status = response.status();
+    let mut event = json!({
+        "statusCode": status.as_u16(),
+        "headers": headers,
+        "multiValueHeaders": multi_value_headers,
+        "isBase64Encoded": false,
+    });
+    if is_alb {
+        // ALB requires a status description, but API Gateway rejects responses with one.
+        event["statusDescription"] = format!(
+            "{} {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or_default()
+        )
+        .into();
+    }
+    if !response.body().is_empty() {
+        if let Ok(text) = std::str::from_utf8(response.body()) {
+            event["body"] = text.into();
+        } else {
+            event["body"] = base64::encode(response.body()).into();
+            event["isBase64Encoded"] = true.into();
+        }
+    }
+    event
+}
+
+/// Move the `Set-Cookie` headers of a response payload into its `cookies` field, for the 2.0
+/// payload format. Function URLs and HTTP APIs ignore `multiValueHeaders` in 2.0 responses, so
+/// only the first cookie in `headers` would otherwise be set.
+pub(crate) fn move_cookies(payload: &mut Value) {
+    let cookies = payload["multiValueHeaders"]
+        .as_object_mut()
+        .and_then(|headers| headers.remove(SET_COOKIE.as_str()));
+    if let Some(headers) = payload["headers"].as_object_mut() {
+        headers.remove(SET_COOKIE.as_str());
+    }
+    if let Some(cookies) = cookies {
+        payload["cookies"] = cookies;
+    }
+}
+
+/// Parse a response payload returned by a function into the response API Gateway or ALB would
+/// send for it.
+///
+/// Headers in `multiValueHeaders` take precedence over those in `headers`, and the `cookies` of
+/// a 2.0 payload format response are sent as `Set-Cookie` headers. As HTTP APIs do for 2.0
+/// responses, a payload without a `statusCode` is sent as a JSON body with status 200.
+pub(crate) fn from_response_event(event: Value) -> Result<Response<Vec<u8>>, serde_json::Error> {
+    let mut event = match event {
+        Value::Object(event) if event.contains_key("statusCode") => event,
+        event => {
+            return Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(event.to_string().into_bytes())
+                .map_err(serde::de::Error::custom)
+        }
+    };
+    let mut field = |name: &str| event.remove(name).unwrap_or(Value::Null);
+    let status: u16 = serde_json::from_value(field("statusCode"))?;
+    let headers: Option<BTreeMap<String, String>> = serde_json::from_value(field("headers"))?;
+    let multi_value_headers: Option<BTreeMap<String, Vec<String>>> =
+        serde_json::from_value(field("multiValueHeaders"))?;
+    let cookies: Option<Vec<String>> = serde_json::from_value(field("cookies"))?;
+    let body: Option<String> = serde_json::from_value(field("body"))?;
+    let is_base64_encoded: Option<bool> = serde_json::from_value(field("isBase64Encoded"))?;
+
+    let mut builder = Response::builder();
+    builder.status(status);
+    let multi_value_headers = multi_value_headers.unwrap_or_default();
+    for (name, value) in headers.unwrap_or_default() {
+        if !multi_value_headers.contains_key(&name) {
+            builder.header(name.as_str(), value.as_str());
+        }
+    }
+    for (name, values) in &multi_value_headers {
+        for value in values {
+            builder.header(name.as_str(), value.as_str());
+        }
+    }
+    for cookie in cookies.unwrap_or_default() {
+        builder.header(SET_COOKIE, cookie.as_str());
+    }
+    let body = match body {
+        Some(body) if is_base64_encoded.unwrap_or_default() => {
+            base64::decode(&body).map_err(serde::de::Error::custom)?
+        }
+        Some(body) => body.into_bytes(),
+        None => Vec::new(),
+    };
+    builder.body(body).map_err(serde::de::Error::custom)
+}
+
+/// Call `service` with a request received from `lambda_http`.
+pub(crate) fn invoke(
+    service: &Service,
+    request: lambda_http::Request,
+    context: Context,
+) -> BoxFuture {
+    service.invoke(into_request(request, context), false)
+}
+
+/// Convert a request received from `lambda_http` into the form handlers are called with.
+pub(crate) fn into_request(request: lambda_http::Request, context: Context) -> Request<Vec<u8>> {
+    let (mut parts, body) = request.into_parts();
+    // Prefer the trace Lambda continued for the invocation, whose parent is the function's
+    // segment, over the trace the request was sent with.
+    let trace_context = context
+        .xray_trace_id
+        .as_deref()
+        .and_then(TraceContext::parse_xray)
+        .or_else(|| TraceContext::from_headers(&parts.headers));
+    if let Some(trace_context) = trace_context {
+        parts.extensions.insert(trace_context);
+    }
+    parts.extensions.insert(context);
+    Request::from_parts(parts, body.to_vec())
+}
@@ -1,10 +1,114 @@
 //! A small wrapper for locally developing HTTP handlers for AWS Lambda.
 //!
-//! For details, see the documentation for [`run`](fn.run.html).
+//! For details, see the documentation for [`run`](fn.run.html) and
+//! [`run_async`](fn.run_async.html). To configure the local development server, use
+//! [`Runtime::builder`](struct.Runtime.html#method.builder).
+//!
+//! With the `tracing` feature, each invocation runs in an `invocation` span with the request ID,
+//! method, path, and response status, both in AWS Lambda and locally, and the local development
+//! server emits events for what it would otherwise only write to standard error.
+//!
+//! With the `log` feature, the local development server logs its diagnostic messages with the
+//! [`log`](https://docs.rs/log/0.4/log/) crate instead of writing them to standard error.
 
 #![warn(clippy::pedantic)]
 
-use http::{Request, Response};
+/// Write a diagnostic message to standard error, or with the `log` feature, log it at `$level`.
+macro_rules! diagnostic {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::$level!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        eprintln!($($arg)+);
+    }};
+}
+
+mod access_log;
+#[cfg(feature = "actix")]
+mod actix;
+#[cfg(feature = "local")]
+mod admin;
+mod authorizer;
+#[cfg(feature = "local")]
+mod chaos;
+mod client_cert;
+#[cfg(feature = "local")]
+mod conn;
+mod context;
+mod cookie;
+mod cors;
+mod error;
+mod event;
+mod event_builder;
+mod ext;
+mod extract;
+pub mod fixtures;
+mod handler;
+mod har;
+pub mod headers;
+#[cfg(any(feature = "axum", feature = "warp"))]
+mod http1;
+#[cfg(feature = "http3")]
+mod http3;
+#[cfg(feature = "local")]
+mod instances;
+mod lambda;
+#[cfg(feature = "local")]
+mod local;
+pub mod metrics;
+mod middleware;
+pub mod multipart;
+#[cfg(feature = "local")]
+mod one_shot;
+#[cfg(feature = "openapi")]
+mod openapi;
+mod profile;
+#[cfg(feature = "local")]
+mod record;
+#[cfg(feature = "local")]
+mod report;
+mod response;
+mod router;
+mod runtime;
+#[cfg(feature = "local")]
+mod server;
+mod service;
+#[cfg(feature = "shadow")]
+mod shadow;
+pub mod testing;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "tower")]
+mod tower;
+mod trace;
+mod trace_context;
+#[cfg(feature = "local")]
+mod xray;
+
+pub use crate::access_log::AccessLogFormat;
+pub use crate::authorizer::{Authorization, JwtAuthorizer};
+pub use crate::client_cert::ClientCert;
+pub use crate::context::ContextBuilder;
+pub use crate::cookie::{Cookie, InvalidCookie, SameSite};
+pub use crate::cors::Cors;
+pub use crate::error::Error;
+pub use crate::ext::RequestExt;
+pub use crate::extract::{Form, Json, Query, Rejection, TypedHeader};
+pub use crate::handler::{signature, Handler};
+pub use crate::middleware::Next;
+pub use crate::multipart::Multipart;
+#[cfg(feature = "openapi")]
+pub use crate::openapi::ResponseValidation;
+pub use crate::profile::Profile;
+pub use crate::response::{IntoResponse, InvocationError};
+pub use crate::router::Router;
+pub use crate::runtime::{Builder, Runtime};
+#[cfg(feature = "local")]
+pub use crate::server::{LocalServer, ServerHandle};
+pub use crate::trace_context::TraceContext;
+
+use http::Request;
+use std::future::Future;
 use std::net::ToSocketAddrs;
 
 /// Run an HTTP handler in either AWS Lambda or as a local development HTTP server.
@@ -12,7 +116,11 @@ use std::net::ToSocketAddrs;
 /// If the `AWS_LAMBDA_RUNTIME_API` environment variable is present (as it is when running in
 /// Lambda), requests are received via the [AWS Lambda Runtime Interface][lambda-interface].
 ///
-/// If not, an HTTP server is started on `listen_addr`.
+/// If not, an HTTP server is started on `listen_addr`. Each request it receives is converted
+/// into the event an API Gateway REST API would send and back, so the handler sees requests the
+/// same way it would in AWS Lambda; see [`Builder::profile`] to emulate a different service.
+///
+/// [`Builder::profile`]: struct.Builder.html#method.profile
 ///
 /// If you need to decrease the size of the binary you deploy to Lambda, you can build the crate
 /// with `--no-default-features` for your production build, which disables the HTTP server
@@ -25,7 +133,7 @@ use std::net::ToSocketAddrs;
 /// ```rust,no_run
 /// # use http::Response;
 /// lambda_http_local::run(
-///     |request| Response::new(b"Hello, world!".to_vec()),
+///     |request| Response::new("Hello, world!"),
 ///     "localhost:3000",
 /// );
 /// ```
@@ -33,7 +141,8 @@ use std::net::ToSocketAddrs;
 /// # Panics
 ///
 /// If `AWS_LAMBDA_RUNTIME_API` is not present and `listen_addr`'s [`to_socket_addrs`] method fails
-/// or resolves to no [`SocketAddr`] values, this function will panic.
+/// or resolves to no [`SocketAddr`] values, or the server fails to bind, this function will panic.
+/// Use [`try_run`](fn.try_run.html) to handle these errors instead.
 ///
 /// [`to_socket_addrs`]: https://doc.rust-lang.org/std/net/addr/trait.ToSocketAddrs.html#tymethod.to_socket_addrs
 /// [`SocketAddr`]: https://doc.rust-lang.org/std/net/addr/enum.SocketAddr.html
@@ -43,54 +152,347 @@ use std::net::ToSocketAddrs;
 /// When running in AWS Lambda, the [`Context`] object for the request is available as a [request
 /// extension].
 ///
+/// When running locally, a simulated `Context` is inserted instead. It has a random request ID, a
+/// function name taken from `AWS_LAMBDA_FUNCTION_NAME` (or the executable name if unset), and a
+/// deadline three seconds from the start of the request, Lambda's default function timeout. These
+/// can be changed with [`Builder::context`](struct.Builder.html#method.context).
+///
+/// ```rust,no_run
+/// # use http::Response;
+/// use lambda_runtime::Context;
+///
+/// lambda_http_local::run(
+///     |request| {
+///         let context = request.extensions().get::<Context>().unwrap();
+///         Response::new(context.aws_request_id.clone())
+///     },
+///     "localhost:3000",
+/// );
+/// ```
+///
 /// [`Context`]: ../lambda_runtime_core/context/struct.Context.html
 /// [request extension]: ../http/request/struct.Request.html#method.extensions
-#[cfg_attr(not(feature = "local"), allow(unused_variables))]
-pub fn run<F, T>(handler: F, listen_addr: T)
+///
+/// # Handler errors
+///
+/// The handler may return any type implementing [`IntoResponse`], including
+/// `Result<Response<Vec<u8>>, E>` for any error type `E` that implements `Display`:
+///
+/// ```rust,no_run
+/// # use http::Response;
+/// lambda_http_local::run(
+///     |request| {
+///         let name = std::str::from_utf8(request.body())?;
+///         Ok::<_, std::str::Utf8Error>(Response::new(format!("Hello, {}!", name)))
+///     },
+///     "localhost:3000",
+/// );
+/// ```
+///
+/// [`IntoResponse`]: trait.IntoResponse.html
+pub fn run<F, R, T>(handler: F, listen_addr: T)
 where
-    F: Fn(Request<&[u8]>) -> Response<Vec<u8>> + Send + Sync + 'static,
+    F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+    R: IntoResponse,
     T: ToSocketAddrs,
 {
-    #[cfg(feature = "local")]
-    {
-        if is_lambda() {
-            // AWS Lambda mode
-            lambda(handler)
-        } else {
-            use hyper::rt::{Future, Stream};
-            use std::sync::Arc;
-
-            // Hyper server mode
-            let listen_addr = listen_addr
-                .to_socket_addrs()
-                .expect("listen_addr.to_socket_addrs() failed")
-                .next()
-                .expect("listen_addr.to_socket_addrs() resolved to no addresses");
-            let handler = Arc::new(handler);
-            let make_service = move || {
-                let handler = handler.clone();
-                hyper::service::service_fn(move |request: Request<hyper::Body>| {
-                    let handler = handler.clone();
-                    let (parts, body) = request.into_parts();
-                    body.concat2().map(move |chunk| {
-                        handler(Request::from_parts(parts, &chunk)).map(hyper::Body::from)
-                    })
-                })
-            };
-            let server = hyper::Server::bind(&listen_addr).serve(make_service);
-            eprintln!("Listening on http://{}", listen_addr);
-            hyper::rt::run(server.map_err(|e| {
-                eprintln!("Hyper error: {}", e);
-            }));
-        }
-    }
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .run(handler);
+}
 
-    #[cfg(not(feature = "local"))]
-    {
-        lambda(handler)
-    }
+/// Run an asynchronous HTTP handler in either AWS Lambda or as a local development HTTP server.
+///
+/// This behaves like [`run`](fn.run.html), except the handler returns a [`Future`] that resolves
+/// to the response (or any other [`IntoResponse`](trait.IntoResponse.html) value). As the future
+/// may outlive the call to the handler, the request body is passed as an owned `Vec<u8>`.
+///
+/// In local mode, the future is driven on the HTTP server's event loop, so the handler should
+/// avoid blocking. In Lambda mode, each future is run to completion on a Tokio runtime before the
+/// next event is received.
+///
+/// [`Future`]: https://doc.rust-lang.org/std/future/trait.Future.html
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use http::Response;
+/// lambda_http_local::run_async(
+///     |request| async move { Response::new(request.into_body()) },
+///     "localhost:3000",
+/// );
+/// ```
+///
+/// # Panics
+///
+/// See [`run`](fn.run.html#panics).
+pub fn run_async<F, R, T>(handler: F, listen_addr: T)
+where
+    F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+    R: Future + Send + 'static,
+    R::Output: IntoResponse,
+    T: ToSocketAddrs,
+{
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .run_async(handler);
 }
 
+/// Run an HTTP handler like [`run`](fn.run.html), returning an error instead of panicking if the
+/// local development server fails to start.
+///
+/// In AWS Lambda, this function does not return.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use http::Response;
+/// if let Err(err) = lambda_http_local::try_run(
+///     |request| Response::new("Hello, world!"),
+///     "localhost:3000",
+/// ) {
+///     eprintln!("{}", err);
+///     std::process::exit(1);
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `listen_addr` cannot be resolved or bound, or if the server fails while
+/// running.
+pub fn try_run<F, R, T>(handler: F, listen_addr: T) -> Result<(), Error>
+where
+    F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+    T: ToSocketAddrs,
+{
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .try_run(handler)
+}
+
+/// Run a [`tower::Service`] in either AWS Lambda or as a local development HTTP server.
+///
+/// This behaves like [`run_async`](fn.run_async.html), so middleware built with tower can be used
+/// the same way in both modes. Each request is sent to a clone of `service` once it is ready.
+/// Errors returned by the service are handled like handler errors.
+///
+/// This function requires the `tower` feature.
+///
+/// [`tower::Service`]: https://docs.rs/tower-service/0.3/tower_service/trait.Service.html
+///
+/// # Panics
+///
+/// See [`run`](fn.run.html#panics).
+#[cfg(feature = "tower")]
+pub fn run_service<S, T>(service: S, listen_addr: T)
+where
+    S: tower_service::Service<Request<bytes::Bytes>, Response = http::Response<bytes::Bytes>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: std::fmt::Display,
+    S::Future: Send + 'static,
+    T: ToSocketAddrs,
+{
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .run_service(service);
+}
+
+/// Run an [`axum::Router`] in either AWS Lambda or as a local development HTTP server.
+///
+/// The router is run on a Tokio 1 runtime, so handlers can use Tokio 1 APIs. The Lambda
+/// [`Context`] and `lambda_http`'s request context are available as request extensions, as for
+/// other handlers.
+///
+/// This function requires the `axum` feature.
+///
+/// [`axum::Router`]: https://docs.rs/axum/0.8/axum/struct.Router.html
+/// [`Context`]: ../lambda_runtime_core/context/struct.Context.html
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use axum::routing::get;
+///
+/// let router = axum::Router::new().route("/", get(|| async { "Hello, world!" }));
+/// lambda_http_local::run_axum(router, "localhost:3000");
+/// ```
+///
+/// # Panics
+///
+/// See [`run`](fn.run.html#panics).
+#[cfg(feature = "axum")]
+pub fn run_axum<T: ToSocketAddrs>(router: ::axum::Router, listen_addr: T) {
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .run_axum(router);
+}
+
+/// Run an [`axum::Router`] like [`run_axum`](fn.run_axum.html), returning an error instead of
+/// panicking if the local development server fails to start.
+///
+/// This function requires the `axum` feature.
+///
+/// [`axum::Router`]: https://docs.rs/axum/0.8/axum/struct.Router.html
+///
+/// # Errors
+///
+/// See [`try_run`](fn.try_run.html#errors).
+#[cfg(feature = "axum")]
+pub fn try_run_axum<T: ToSocketAddrs>(router: ::axum::Router, listen_addr: T) -> Result<(), Error> {
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .try_run_axum(router)
+}
+
+/// Run a [warp] filter in either AWS Lambda or as a local development HTTP server.
+///
+/// The filter is run on a Tokio 1 runtime, so it can use Tokio 1 APIs. The Lambda [`Context`]
+/// and `lambda_http`'s request context are available as request extensions, which filters can
+/// read with `warp::ext::get`.
+///
+/// This function requires the `warp` feature.
+///
+/// [warp]: https://docs.rs/warp/0.4/warp/
+/// [`Context`]: ../lambda_runtime_core/context/struct.Context.html
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use warp::Filter;
+///
+/// let hello = warp::path!("hello" / String).map(|name| format!("Hello, {}!", name));
+/// lambda_http_local::run_warp(hello, "localhost:3000");
+/// ```
+///
+/// # Panics
+///
+/// See [`run`](fn.run.html#panics).
+#[cfg(feature = "warp")]
+pub fn run_warp<F, T>(filter: F, listen_addr: T)
+where
+    F: ::warp::Filter<Error = ::warp::Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: ::warp::Reply,
+    F::Future: Send,
+    T: ToSocketAddrs,
+{
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .run_warp(filter);
+}
+
+/// Run a [warp] filter like [`run_warp`](fn.run_warp.html), returning an error instead of
+/// panicking if the local development server fails to start.
+///
+/// This function requires the `warp` feature.
+///
+/// [warp]: https://docs.rs/warp/0.4/warp/
+///
+/// # Errors
+///
+/// See [`try_run`](fn.try_run.html#errors).
+#[cfg(feature = "warp")]
+pub fn try_run_warp<F, T>(filter: F, listen_addr: T) -> Result<(), Error>
+where
+    F: ::warp::Filter<Error = ::warp::Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: ::warp::Reply,
+    F::Future: Send,
+    T: ToSocketAddrs,
+{
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .try_run_warp(filter)
+}
+
+/// Run an [actix-web] application in either AWS Lambda or as a local development HTTP server.
+///
+/// actix-web applications are not `Send`, so `factory` is called once, when the function
+/// initializes, to build the application, which is run on an actix system on a thread of its own. The Lambda [`Context`] and
+/// `lambda_http`'s request context are available as request extensions, which handlers can read
+/// with `HttpRequest::extensions` or the `web::ReqData` extractor.
+///
+/// This function requires the `actix` feature.
+///
+/// [actix-web]: https://docs.rs/actix-web/4/actix_web/
+/// [`Context`]: ../lambda_runtime_core/context/struct.Context.html
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use actix_web::{web, App};
+///
+/// lambda_http_local::run_actix(
+///     || App::new().route("/", web::get().to(|| async { "Hello, world!" })),
+///     "localhost:3000",
+/// );
+/// ```
+///
+/// # Panics
+///
+/// If the application fails to initialize, and otherwise see [`run`](fn.run.html#panics).
+#[cfg(feature = "actix")]
+pub fn run_actix<F, S, B, T>(factory: F, listen_addr: T)
+where
+    F: FnOnce() -> ::actix_web::App<S> + Send + 'static,
+    S: ::actix_web::dev::ServiceFactory<
+            ::actix_web::dev::ServiceRequest,
+            Config = (),
+            Response = ::actix_web::dev::ServiceResponse<B>,
+            Error = ::actix_web::Error,
+            InitError = (),
+        > + 'static,
+    B: ::actix_web::body::MessageBody + 'static,
+    T: ToSocketAddrs,
+{
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .run_actix(factory);
+}
+
+/// Run an [actix-web] application like [`run_actix`](fn.run_actix.html), returning an error
+/// instead of panicking if the application fails to initialize or the local development server
+/// fails to start.
+///
+/// This function requires the `actix` feature.
+///
+/// [actix-web]: https://docs.rs/actix-web/4/actix_web/
+///
+/// # Errors
+///
+/// Returns [`Error::Init`](enum.Error.html#variant.Init) if the application fails to
+/// initialize, and otherwise see [`try_run`](fn.try_run.html#errors).
+#[cfg(feature = "actix")]
+pub fn try_run_actix<F, S, B, T>(factory: F, listen_addr: T) -> Result<(), Error>
+where
+    F: FnOnce() -> ::actix_web::App<S> + Send + 'static,
+    S: ::actix_web::dev::ServiceFactory<
+            ::actix_web::dev::ServiceRequest,
+            Config = (),
+            Response = ::actix_web::dev::ServiceResponse<B>,
+            Error = ::actix_web::Error,
+            InitError = (),
+        > + 'static,
+    B: ::actix_web::body::MessageBody + 'static,
+    T: ToSocketAddrs,
+{
+    Runtime::builder()
+        .listen_addr(listen_addr)
+        .build()
+        .try_run_actix(factory)
+}
+
+#[must_use]
 pub fn is_lambda() -> bool {
     #[cfg(feature = "local")]
     {
@@ -102,14 +504,3 @@ pub fn is_lambda() -> bool {
         true
     }
 }
-
-fn lambda<F>(handler: F)
-where
-    F: Fn(Request<&[u8]>) -> Response<Vec<u8>> + Send + Sync + 'static,
-{
-    lambda_http::lambda!(|request: Request<lambda_http::Body>, context| {
-        let (mut parts, body) = request.into_parts();
-        parts.extensions.insert(context);
-        Ok(handler(Request::from_parts(parts, body.as_ref())).map(lambda_http::Body::from))
-    })
-}
@@ -0,0 +1,1940 @@
+use crate::access_log::{self, AccessLogFormat};
+use crate::admin::{self, EventStream, History, Record, Stats};
+use crate::authorizer::{CognitoClaims, LambdaAuthorizer, Rejected};
+use crate::chaos::{self, Chaos, Disruption, InjectedError};
+use crate::conn::{self, Activity, TimedStream};
+use crate::event::{self, Connection};
+use crate::har::Har;
+#[cfg(feature = "http3")]
+use crate::http3;
+use crate::instances::{Instances, Lease};
+#[cfg(feature = "openapi")]
+use crate::openapi::{OpenApi, ResponseValidation};
+use crate::profile::INVOCATION_PAYLOAD_LIMIT;
+use crate::record;
+use crate::report::{self, Report};
+use crate::service::{self, Service};
+#[cfg(feature = "shadow")]
+use crate::shadow::{self, Shadow};
+#[cfg(feature = "tls")]
+use crate::tls;
+use crate::trace;
+use crate::xray;
+use crate::{
+    lambda, ClientCert, ContextBuilder, Error, InvocationError, JwtAuthorizer, Profile, Runtime,
+};
+use futures::{FutureExt, TryFutureExt};
+use futures01::future::{self as future01, Either};
+#[cfg(feature = "shadow")]
+use futures01::sync::oneshot;
+use futures01::{Future, Stream};
+#[cfg(feature = "http3")]
+use http::header::ALT_SVC;
+use http::header::{HeaderValue, ACCEPT, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use lambda_http::request::RequestContext;
+use lambda_runtime::Context;
+#[cfg(feature = "tls")]
+use rustls::Session;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+#[cfg(any(feature = "tls", unix))]
+use std::net::Ipv4Addr;
+#[cfg(feature = "http3")]
+use std::net::UdpSocket;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::reactor::Handle;
+use tokio::timer::{Delay, Timeout};
+#[cfg(feature = "tls")]
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+
+#[allow(clippy::struct_excessive_bools)]
+struct Config {
+    access_log: Option<AccessLogFormat>,
+    body_limit: Option<usize>,
+    context: ContextBuilder,
+    quiet: bool,
+    platform_log: bool,
+    shutdown_timeout: Duration,
+    connection_timeouts: conn::Timeouts,
+    max_concurrent_requests: Option<usize>,
+    /// The number of requests being handled, if the number of concurrent requests is limited.
+    requests_in_flight: AtomicUsize,
+    /// The latency and failures injected into responses, if any.
+    chaos: Option<Chaos>,
+    reserved_concurrency: Option<usize>,
+    /// The number of invocations in progress, if their number is limited.
+    executions: AtomicUsize,
+    buffered_body_limit: Option<usize>,
+    /// The total size of the request bodies being buffered or handled, if it is limited.
+    buffered_bytes: AtomicUsize,
+    timeout: Option<Duration>,
+    integration_timeout: Option<Duration>,
+    enforce_payload_limits: bool,
+    jwt_authorizer: Option<JwtAuthorizer>,
+    lambda_authorizer: Option<LambdaAuthorizer>,
+    /// The number of requests made with each API key, if API keys are required.
+    api_keys: HashMap<String, AtomicU64>,
+    api_key_quota: Option<u64>,
+    cognito_claims: CognitoClaims,
+    #[cfg(feature = "openapi")]
+    openapi: Option<OpenApi>,
+    xray_daemon: Option<SocketAddr>,
+    stats: Option<Stats>,
+    health_endpoint: bool,
+    started: Instant,
+    /// Whether the handler has finished initializing.
+    ready: AtomicBool,
+    /// The most execution environments, if they are simulated.
+    max_instances: Option<usize>,
+    /// How long a new execution environment waits before its first invocation.
+    cold_start_delay: Option<Duration>,
+    instance_idle_timeout: Option<Duration>,
+    /// How many execution environments to start before serving requests.
+    provisioned_concurrency: usize,
+    /// The simulated execution environments, if they are simulated.
+    instances: Option<Arc<Instances>>,
+    history: Option<History>,
+    events: Option<EventStream>,
+    record_dir: Option<PathBuf>,
+    har: Option<Har>,
+    #[cfg(feature = "dashboard")]
+    dashboard: bool,
+    #[cfg(feature = "shadow")]
+    shadow: Option<Shadow>,
+    #[cfg(feature = "http3")]
+    http3: bool,
+    event: event::Options,
+}
+
+/// The number of invocations the dashboard shows if no invocation history size is set.
+#[cfg(feature = "dashboard")]
+const DASHBOARD_HISTORY: usize = 100;
+
+/// The most TLS handshakes performed at once.
+#[cfg(feature = "tls")]
+const TLS_HANDSHAKE_CONCURRENCY: usize = 64;
+
+/// A bound local development server that has not started serving requests yet.
+pub(crate) struct Server {
+    listener: Listener,
+    addr: SocketAddr,
+    config: Config,
+    #[cfg(feature = "http3")]
+    http3: Option<(Vec<UdpSocket>, quinn::ServerConfig)>,
+}
+
+/// The socket the local development server accepts connections on.
+enum Listener {
+    Tcp(Vec<TcpListener>),
+    #[cfg(feature = "tls")]
+    Tls(Vec<TcpListener>, TlsAcceptor),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener, Option<PathBuf>),
+}
+
+pub(crate) fn start(runtime: Runtime, service: Service) -> Result<(), Error> {
+    let server = bind(runtime)?;
+    let signal = shutdown_signal(server.config.quiet);
+    server.serve(service, signal)
+}
+
+pub(crate) fn bind(mut runtime: Runtime) -> Result<Server, Error> {
+    if let Some(dir) = &runtime.record_dir {
+        fs::create_dir_all(dir).map_err(|err| Error::Record(dir.clone(), err))?;
+    }
+    #[cfg(feature = "shadow")]
+    if let Some(Err(url)) = &runtime.shadow {
+        return Err(Error::Shadow(url.clone()));
+    }
+    #[cfg(feature = "listenfd")]
+    let mut inherited = None;
+    #[cfg(feature = "listenfd")]
+    if runtime.socket_activation {
+        match inherited_listener()? {
+            Some(Inherited::Tcp(listener)) => inherited = Some(listener),
+            #[cfg(unix)]
+            Some(Inherited::Unix(listener)) => return Ok(unix_server(runtime, listener, None)),
+            None => {}
+        }
+    }
+    #[cfg(unix)]
+    if let Some(path) = runtime.unix_socket.take() {
+        return bind_unix(runtime, path);
+    }
+    #[cfg(feature = "tls")]
+    let client_ca = runtime.tls_client_ca.as_deref();
+    #[cfg(feature = "tls")]
+    let certificate = certificate(&runtime)?;
+    #[cfg(feature = "tls")]
+    let tls = certificate
+        .as_ref()
+        .map(|(cert, key)| tls::acceptor(cert, key, client_ca))
+        .transpose()?;
+    #[cfg(feature = "tls")]
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    #[cfg(not(feature = "tls"))]
+    let scheme = "http";
+    let listen_addr = std::mem::replace(&mut runtime.listen_addr, Ok(Vec::new()));
+    #[cfg(unix)]
+    let reuse_port = runtime.reuse_port;
+    #[cfg(not(unix))]
+    let reuse_port = false;
+    #[cfg(feature = "listenfd")]
+    let listeners = match inherited {
+        Some(listener) => {
+            let addr = listener.local_addr().map_err(Error::Inherit)?;
+            vec![(listener, addr)]
+        }
+        None => bind_tcp(listen_addr, runtime.port_retries, reuse_port, runtime.quiet)?,
+    };
+    #[cfg(not(feature = "listenfd"))]
+    let listeners = bind_tcp(listen_addr, runtime.port_retries, reuse_port, runtime.quiet)?;
+    let addr = listeners[0].1;
+    #[cfg(feature = "http3")]
+    let quic = match certificate.filter(|_| runtime.http3) {
+        Some((cert, key)) => {
+            let config = http3::server_config(&cert, &key, client_ca)?;
+            let sockets = listeners
+                .iter()
+                .map(|&(_, addr)| bind_udp(addr, reuse_port).map_err(|err| Error::Bind(addr, err)))
+                .collect::<Result<Vec<_>, _>>()?;
+            Some((sockets, config))
+        }
+        None => None,
+    };
+
+    for &(_, addr) in &listeners {
+        if !runtime.quiet {
+            diagnostic!(info, "Listening on {scheme}://{addr}");
+        }
+        trace::event!(info, %addr, "listening");
+    }
+    if !runtime.quiet {
+        #[cfg(feature = "http3")]
+        if quic.is_some() {
+            diagnostic!(info, "Serving HTTP/3 on UDP port {}", addr.port());
+        }
+        #[cfg(feature = "dashboard")]
+        if runtime.dashboard {
+            diagnostic!(info, "Dashboard at {scheme}://{addr}{}", admin::PREFIX);
+        }
+    }
+    if let Some(on_bind) = runtime.on_bind.take() {
+        on_bind(addr);
+    }
+
+    let listeners = listeners
+        .into_iter()
+        .map(|(listener, _)| listener)
+        .collect();
+    #[cfg(feature = "tls")]
+    let listener = match tls {
+        Some(acceptor) => Listener::Tls(listeners, acceptor),
+        None => Listener::Tcp(listeners),
+    };
+    #[cfg(not(feature = "tls"))]
+    let listener = Listener::Tcp(listeners);
+    Ok(Server {
+        listener,
+        addr,
+        config: Config::new(runtime),
+        #[cfg(feature = "http3")]
+        http3: quic,
+    })
+}
+
+/// The paths of the certificate and private key to serve HTTPS with, if enabled.
+#[cfg(feature = "tls")]
+fn certificate(runtime: &Runtime) -> Result<Option<(PathBuf, PathBuf)>, Error> {
+    let https = runtime.https;
+    // QUIC requires TLS.
+    #[cfg(feature = "http3")]
+    let https = https || runtime.http3;
+    match runtime.tls_certificate.clone() {
+        Some(certificate) => Ok(Some(certificate)),
+        None if https => {
+            let (cert, key, generated) = tls::development_certificate()?;
+            if generated && !runtime.quiet {
+                diagnostic!(
+                    info,
+                    "Generated a self-signed development certificate at {}",
+                    cert.display()
+                );
+            }
+            Ok(Some((cert, key)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Bind to the addresses the listen address resolves to, trying up to `port_retries` of the
+/// following ports if the port is already in use.
+fn bind_tcp(
+    listen_addr: io::Result<Vec<SocketAddr>>,
+    port_retries: u16,
+    reuse_port: bool,
+    quiet: bool,
+) -> Result<Vec<(TcpListener, SocketAddr)>, Error> {
+    let listen_addrs = listen_addr.map_err(Error::Resolve)?;
+    let mut offset = 0;
+    loop {
+        let addrs = listen_addrs.iter().map(|&addr| match addr.port() {
+            0 => Some(addr),
+            port => port
+                .checked_add(offset)
+                .map(|port| SocketAddr::new(addr.ip(), port)),
+        });
+        let addrs = addrs.collect::<Option<Vec<_>>>().unwrap_or_default();
+        match bind_addrs(addrs, reuse_port, quiet) {
+            Err(Error::Bind(_, err))
+                if err.kind() == io::ErrorKind::AddrInUse && offset < port_retries =>
+            {
+                offset += 1;
+            }
+            Ok(listeners) => {
+                if offset > 0 && !quiet {
+                    diagnostic!(
+                        warn,
+                        "Port {} is in use; using port {} instead",
+                        listeners[0].1.port() - offset,
+                        listeners[0].1.port()
+                    );
+                }
+                return Ok(listeners);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Bind to each of `listen_addrs`, such as both `127.0.0.1` and `::1` for `localhost`, returning
+/// the listeners and the addresses they are bound to.
+///
+/// If the port is 0, the ephemeral port chosen for the first address is used for the others.
+/// Binding only fails if no address can be bound, so that a name still works when one of the
+/// addresses it resolves to is unavailable, such as `::1` where IPv6 is disabled.
+fn bind_addrs(
+    listen_addrs: Vec<SocketAddr>,
+    reuse_port: bool,
+    quiet: bool,
+) -> Result<Vec<(TcpListener, SocketAddr)>, Error> {
+    let mut listeners = Vec::<(TcpListener, SocketAddr)>::new();
+    let mut errors = Vec::new();
+    for mut listen_addr in listen_addrs {
+        if let (0, Some((_, addr))) = (listen_addr.port(), listeners.first()) {
+            listen_addr.set_port(addr.port());
+        }
+        if listeners.iter().any(|(_, addr)| *addr == listen_addr) {
+            continue;
+        }
+        let listener = bind_listener(listen_addr, reuse_port)
+            .and_then(|listener| Ok((listener.local_addr()?, listener)));
+        match listener {
+            Ok((addr, listener)) => listeners.push((listener, addr)),
+            Err(err) => errors.push(Error::Bind(listen_addr, err)),
+        }
+    }
+    if listeners.is_empty() {
+        return Err(errors.into_iter().next().unwrap_or(Error::NoAddresses));
+    }
+    if !quiet {
+        for err in errors {
+            diagnostic!(warn, "Not listening on all addresses: {err}");
+        }
+    }
+    Ok(listeners)
+}
+
+/// Bind a TCP listener to `addr`, letting other sockets bind to the same port if `reuse_port` is
+/// set.
+fn bind_listener(addr: SocketAddr, reuse_port: bool) -> io::Result<TcpListener> {
+    #[cfg(unix)]
+    if reuse_port {
+        let socket = reuse_port_socket(addr, socket2::Type::STREAM)?;
+        socket.listen(1024)?;
+        return Ok(socket.into());
+    }
+    #[cfg(not(unix))]
+    let _ = reuse_port;
+    TcpListener::bind(addr)
+}
+
+/// Bind a socket to `addr` with `SO_REUSEPORT` set.
+#[cfg(unix)]
+fn reuse_port_socket(addr: SocketAddr, ty: socket2::Type) -> io::Result<socket2::Socket> {
+    let socket = socket2::Socket::new(socket2::Domain::for_address(addr), ty, None)?;
+    socket.set_reuse_address(true)?;
+    socket.set_reuse_port(true)?;
+    socket.bind(&addr.into())?;
+    Ok(socket)
+}
+
+/// Bind a UDP socket to `addr` for HTTP/3, letting other sockets bind to the same port if
+/// `reuse_port` is set.
+#[cfg(feature = "http3")]
+fn bind_udp(addr: SocketAddr, reuse_port: bool) -> io::Result<UdpSocket> {
+    #[cfg(unix)]
+    if reuse_port {
+        return Ok(reuse_port_socket(addr, socket2::Type::DGRAM)?.into());
+    }
+    #[cfg(not(unix))]
+    let _ = reuse_port;
+    UdpSocket::bind(addr)
+}
+
+/// A socket passed to the process by a service manager.
+#[cfg(feature = "listenfd")]
+enum Inherited {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+/// Take the first socket passed with the `LISTEN_FDS` protocol, as systemd's socket activation
+/// and `systemfd` do, if there is one.
+#[cfg(feature = "listenfd")]
+fn inherited_listener() -> Result<Option<Inherited>, Error> {
+    let mut fds = listenfd::ListenFd::from_env();
+    if fds.len() == 0 {
+        return Ok(None);
+    }
+    match fds.take_tcp_listener(0) {
+        Ok(listener) => Ok(listener.map(Inherited::Tcp)),
+        #[cfg(unix)]
+        Err(_) => Ok(fds
+            .take_unix_listener(0)
+            .map_err(Error::Inherit)?
+            .map(Inherited::Unix)),
+        #[cfg(not(unix))]
+        Err(err) => Err(Error::Inherit(err)),
+    }
+}
+
+/// Bind to the Unix socket at `path`, which is served plain HTTP.
+#[cfg(unix)]
+fn bind_unix(runtime: Runtime, path: PathBuf) -> Result<Server, Error> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket left behind by a server that did not shut down cleanly would keep it from binding.
+    if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let _ = fs::remove_file(&path);
+    }
+    let listener = std::os::unix::net::UnixListener::bind(&path)
+        .map_err(|err| Error::BindUnix(path.clone(), err))?;
+    Ok(unix_server(runtime, listener, Some(path)))
+}
+
+/// Serve plain HTTP from a Unix socket, which is removed on shutdown if it was bound at `path`
+/// rather than inherited.
+#[cfg(unix)]
+fn unix_server(
+    runtime: Runtime,
+    listener: std::os::unix::net::UnixListener,
+    path: Option<PathBuf>,
+) -> Server {
+    if !runtime.quiet {
+        match &path {
+            Some(path) => diagnostic!(info, "Listening on unix:{}", path.display()),
+            None => diagnostic!(info, "Listening on an inherited Unix socket"),
+        }
+    }
+    trace::event!(info, "listening");
+
+    Server {
+        listener: Listener::Unix(listener, path),
+        addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        config: Config::new(runtime),
+        #[cfg(feature = "http3")]
+        http3: None,
+    }
+}
+
+impl Config {
+    fn new(runtime: Runtime) -> Config {
+        #[cfg(feature = "shadow")]
+        let shadow = runtime.shadow.clone().and_then(Result::ok).map(|url| {
+            Shadow::new(
+                &url,
+                runtime.shadow_compare.then(|| shadow::Compare {
+                    ignore_headers: runtime.shadow_ignore_headers.clone(),
+                    ignore_fields: runtime.shadow_ignore_fields.clone(),
+                }),
+            )
+        });
+        let chaos = Chaos::new(&runtime);
+        let history = history(&runtime);
+        Config {
+            access_log: if runtime.access_log {
+                Some(runtime.access_log_format)
+            } else {
+                None
+            },
+            body_limit: body_limit(&runtime),
+            integration_timeout: runtime.integration_timeout,
+            enforce_payload_limits: runtime.enforce_payload_limits,
+            jwt_authorizer: match runtime.profile {
+                Profile::HttpApi => runtime.jwt_authorizer,
+                _ => None,
+            },
+            lambda_authorizer: match runtime.profile {
+                Profile::RestApi | Profile::HttpApi => runtime.lambda_authorizer,
+                _ => None,
+            },
+            api_keys: match runtime.profile {
+                Profile::RestApi => runtime
+                    .api_keys
+                    .into_iter()
+                    .map(|key| (key, AtomicU64::new(0)))
+                    .collect(),
+                _ => HashMap::new(),
+            },
+            api_key_quota: runtime.api_key_quota,
+            cognito_claims: match runtime.profile {
+                Profile::RestApi | Profile::HttpApi => runtime.cognito_claims,
+                _ => CognitoClaims::default(),
+            },
+            #[cfg(feature = "openapi")]
+            openapi: (runtime.openapi)
+                .zip(Some(runtime.response_validation))
+                .map(|(document, validation)| OpenApi::new(document, validation)),
+            xray_daemon: runtime.xray_daemon,
+            stats: if runtime.metrics_endpoint {
+                Some(Stats::default())
+            } else {
+                None
+            },
+            health_endpoint: runtime.health_endpoint,
+            ready: AtomicBool::new(false),
+            // A single execution environment handles one invocation at a time.
+            max_instances: runtime.sequential.then_some(1).or(runtime.max_instances),
+            cold_start_delay: runtime.cold_start_delay,
+            instance_idle_timeout: runtime.instance_idle_timeout,
+            provisioned_concurrency: runtime.provisioned_concurrency,
+            instances: None,
+            started: Instant::now(),
+            history,
+            events: if runtime.event_stream {
+                Some(EventStream::default())
+            } else {
+                None
+            },
+            record_dir: runtime.record_dir,
+            har: runtime.har_path.map(Har::new),
+            #[cfg(feature = "dashboard")]
+            dashboard: runtime.dashboard,
+            #[cfg(feature = "shadow")]
+            shadow,
+            #[cfg(feature = "http3")]
+            http3: runtime.http3,
+            event: event::Options {
+                profile: runtime.profile,
+                stage: runtime.stage,
+                binary_media_types: runtime.binary_media_types,
+                stage_variables: runtime.stage_variables,
+            },
+            timeout: runtime
+                .enforce_timeout
+                .then_some(runtime.context.function_timeout()),
+            context: runtime.context,
+            quiet: runtime.quiet,
+            platform_log: runtime.platform_log,
+            shutdown_timeout: runtime.shutdown_timeout,
+            max_concurrent_requests: runtime.max_concurrent_requests,
+            requests_in_flight: AtomicUsize::new(0),
+            chaos,
+            reserved_concurrency: runtime.reserved_concurrency,
+            executions: AtomicUsize::new(0),
+            buffered_body_limit: runtime.buffered_body_limit,
+            buffered_bytes: AtomicUsize::new(0),
+            connection_timeouts: conn::Timeouts {
+                keep_alive: runtime.keep_alive_timeout,
+                request_read: runtime.request_read_timeout,
+            },
+        }
+    }
+}
+
+/// The request body size limit, which is at most the emulated service's if payload limits are
+/// enforced.
+fn body_limit(runtime: &Runtime) -> Option<usize> {
+    if runtime.enforce_payload_limits {
+        let limit = runtime.profile.request_limit();
+        Some(
+            runtime
+                .body_limit
+                .map_or(limit, |body_limit| body_limit.min(limit)),
+        )
+    } else {
+        runtime.body_limit
+    }
+}
+
+/// The invocation history to keep, if any, which the dashboard needs.
+fn history(runtime: &Runtime) -> Option<History> {
+    match runtime.invocation_history {
+        #[cfg(feature = "dashboard")]
+        0 if runtime.dashboard => Some(History::new(DASHBOARD_HISTORY)),
+        0 => None,
+        capacity => Some(History::new(capacity)),
+    }
+}
+
+/// Simulate execution environments for `service`, if the number is limited, each has its own
+/// handler, or cold starts or provisioned concurrency are simulated.
+fn simulate_instances(config: &Config, service: &Service) -> Option<Arc<Instances>> {
+    if config.max_instances.is_none()
+        && config.cold_start_delay.is_none()
+        && config.instance_idle_timeout.is_none()
+        && config.provisioned_concurrency == 0
+        && !service.is_per_instance()
+    {
+        return None;
+    }
+    Some(Arc::new(Instances::new(
+        service.clone(),
+        config.max_instances,
+        config.instance_idle_timeout,
+    )))
+}
+
+/// Create the handler for the first execution environment and start the provisioned ones, if
+/// execution environments are simulated, marking the function ready once they have.
+///
+/// This happens on its own thread once the server is listening, as initializing the handler may
+/// be slow, so that health checks are answered in the meantime. The returned future resolves if
+/// the first handler fails to initialize, after storing the error in `init_error`.
+fn initialize(
+    config: &Arc<Config>,
+    init_error: &Arc<Mutex<Option<String>>>,
+) -> impl Future<Item = (), Error = ()> + Send {
+    let (sender, receiver) = futures01::sync::oneshot::channel();
+    let (config, init_error) = (config.clone(), init_error.clone());
+    thread::spawn(move || {
+        let Some(instances) = &config.instances else {
+            config.ready.store(true, Ordering::SeqCst);
+            return;
+        };
+        match instances.initialize(config.provisioned_concurrency, &config.context) {
+            Ok(provisioned) => {
+                if provisioned > 0 && !config.quiet {
+                    diagnostic!(
+                        info,
+                        "Started {} provisioned execution environments",
+                        provisioned
+                    );
+                }
+                config.ready.store(true, Ordering::SeqCst);
+            }
+            Err(message) => {
+                *lock(&init_error) = Some(message);
+                let _ = sender.send(());
+            }
+        }
+    });
+    // The sender is dropped without sending once initialization has succeeded.
+    receiver.or_else(|_| future01::empty())
+}
+
+impl Server {
+    pub(crate) fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Serve requests until `signal` resolves and in-flight requests have completed (or the
+    /// shutdown timeout has elapsed).
+    pub(crate) fn serve<S>(self, service: Service, signal: S) -> Result<(), Error>
+    where
+        S: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        let Server {
+            listener,
+            addr,
+            mut config,
+            #[cfg(feature = "http3")]
+            http3,
+        } = self;
+        config.instances = simulate_instances(&config, &service);
+        if let Some(chaos) = config.chaos.as_ref().filter(|_| !config.quiet) {
+            diagnostic!(info, "Injecting failures with seed {}", chaos.seed());
+        }
+        let jwt_warning = config
+            .jwt_authorizer
+            .as_ref()
+            .and_then(JwtAuthorizer::warning);
+        if let Some(warning) = jwt_warning.filter(|_| !config.quiet) {
+            diagnostic!(warn, "{}", warning);
+        }
+        let quiet = config.quiet;
+        let shutdown_timeout = config.shutdown_timeout;
+        let config = Arc::new(config);
+        let har_config = config.clone();
+        let init_error = Arc::new(Mutex::new(None));
+        let init_failed = initialize(&config, &init_error);
+
+        // Once the shutdown signal resolves, or the handler fails to initialize, the server stops
+        // accepting connections and waits for in-flight requests to complete, up to the shutdown
+        // timeout.
+        let signal = signal.select(init_failed).then(|_| Ok(())).shared();
+        let port = addr.port();
+        let mut runtime = tokio::runtime::Runtime::new().expect("failed to start Tokio runtime");
+        #[cfg(feature = "http3")]
+        let http3 = match http3 {
+            Some((sockets, http3_config)) => sockets
+                .into_iter()
+                .map(|socket| {
+                    let (sender, receiver) = futures::channel::oneshot::channel();
+                    let handler = http3_handler(&config, &service);
+                    let executor = runtime.executor();
+                    http3::spawn(socket, http3_config.clone(), executor, handler, receiver)
+                        .map(|thread| (sender, thread))
+                        .map_err(|err| Error::Bind(addr, err))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        #[cfg(unix)]
+        let socket_path = if let Listener::Unix(_, path) = &listener {
+            path.clone()
+        } else {
+            None
+        };
+        let server = serve_listener(listener, &config, service, port, &signal)?;
+
+        let deadline = signal.then(move |_| {
+            if !quiet {
+                diagnostic!(info, "Shutting down");
+            }
+            trace::event!(info, "shutting down");
+            Delay::new(Instant::now() + shutdown_timeout).then(|_| Ok::<_, ()>(()))
+        });
+
+        let result = runtime.block_on(server.select2(deadline));
+        #[cfg(feature = "http3")]
+        for (sender, thread) in http3 {
+            let _ = sender.send(());
+            let _ = thread.join();
+        }
+        runtime
+            .shutdown_now()
+            .wait()
+            .expect("failed to shut down Tokio runtime");
+        #[cfg(unix)]
+        if let Some(path) = socket_path {
+            let _ = fs::remove_file(path);
+        }
+        if let Some(har) = &har_config.har {
+            har.write()
+                .map_err(|err| Error::Har(har.path().to_owned(), err))?;
+            if !quiet {
+                diagnostic!(info, "Wrote HAR file {}", har.path().display());
+            }
+        }
+        if let Some(message) = lock(&init_error).take() {
+            return Err(Error::Init(message));
+        }
+        match result {
+            Ok(Either::A(_)) => Ok(()),
+            Ok(Either::B(_)) => {
+                if !quiet {
+                    diagnostic!(
+                        warn,
+                        "Shutdown timeout elapsed, closing remaining connections"
+                    );
+                }
+                trace::event!(
+                    warn,
+                    "shutdown timeout elapsed, closing remaining connections"
+                );
+                Ok(())
+            }
+            Err(Either::A((err, _))) => Err(Error::Server(err.into())),
+            Err(Either::B(((), _))) => unreachable!("shutdown deadline never fails"),
+        }
+    }
+}
+
+/// Serve connections from each socket of `listener` until `signal` resolves.
+fn serve_listener<S>(
+    listener: Listener,
+    config: &Arc<Config>,
+    service: Service,
+    port: u16,
+    signal: &future01::Shared<S>,
+) -> Result<impl Future<Item = (), Error = hyper::Error> + Send, Error>
+where
+    S: Future<Item = (), Error = ()> + Send + 'static,
+{
+    let shutdown = || signal.clone().then(|_| Ok::<_, ()>(()));
+    let servers = match listener {
+        Listener::Tcp(listeners) => listeners
+            .into_iter()
+            .map(|listener| serve_http(listener, config.clone(), service.clone(), port, shutdown()))
+            .collect::<Result<Vec<_>, _>>()?,
+        #[cfg(feature = "tls")]
+        Listener::Tls(listeners, acceptor) => listeners
+            .into_iter()
+            .map(|listener| {
+                let (config, service) = (config.clone(), service.clone());
+                serve_https(
+                    listener,
+                    acceptor.clone(),
+                    config,
+                    service,
+                    port,
+                    shutdown(),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        #[cfg(unix)]
+        Listener::Unix(listener, _) => {
+            vec![serve_unix(listener, config.clone(), service, shutdown())?]
+        }
+    };
+    Ok(future01::join_all(servers).map(|_| ()))
+}
+
+/// Serve plain HTTP connections from `listener` until `shutdown` resolves.
+fn serve_http<S>(
+    listener: TcpListener,
+    config: Arc<Config>,
+    service: Service,
+    port: u16,
+    shutdown: S,
+) -> Result<Box<dyn Future<Item = (), Error = hyper::Error> + Send>, Error>
+where
+    S: Future<Item = (), Error = ()> + Send + 'static,
+{
+    let timeouts = config.connection_timeouts;
+    let listener = tokio::net::TcpListener::from_std(listener, &Handle::default())
+        .map_err(|err| Error::Server(err.into()))?;
+    let incoming =
+        accept(listener.incoming()).map(move |stream| TimedStream::new(stream, timeouts));
+    let make_service = make_service_fn(move |stream: &TimedStream<TcpStream>| {
+        let connection = Connection {
+            client_ip: stream
+                .get_ref()
+                .peer_addr()
+                .map_or(Ipv4Addr::UNSPECIFIED.into(), |addr| addr.ip()),
+            port,
+            proto: "http",
+        };
+        let activity = stream.activity().clone();
+        Ok::<_, hyper::Error>(connection_service(
+            &config, &service, connection, activity, None,
+        ))
+    });
+    Ok(Box::new(
+        hyper::Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown),
+    ))
+}
+
+/// Serve plain HTTP connections from the Unix socket `listener` until `shutdown` resolves.
+#[cfg(unix)]
+fn serve_unix<S>(
+    listener: std::os::unix::net::UnixListener,
+    config: Arc<Config>,
+    service: Service,
+    shutdown: S,
+) -> Result<Box<dyn Future<Item = (), Error = hyper::Error> + Send>, Error>
+where
+    S: Future<Item = (), Error = ()> + Send + 'static,
+{
+    let listener = tokio::net::UnixListener::from_std(listener, &Handle::default())
+        .map_err(|err| Error::Server(err.into()))?;
+    let timeouts = config.connection_timeouts;
+    let incoming =
+        accept(listener.incoming()).map(move |stream| TimedStream::new(stream, timeouts));
+    let make_service = make_service_fn(move |stream: &TimedStream<tokio::net::UnixStream>| {
+        // Clients of a Unix socket are on the same machine, and usually a reverse proxy that
+        // adds its own forwarded headers.
+        let connection = Connection {
+            client_ip: Ipv4Addr::LOCALHOST.into(),
+            port: 80,
+            proto: "http",
+        };
+        let activity = stream.activity().clone();
+        Ok::<_, hyper::Error>(connection_service(
+            &config, &service, connection, activity, None,
+        ))
+    });
+    Ok(Box::new(
+        hyper::Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown),
+    ))
+}
+
+/// Serve HTTPS connections from `listener` until `shutdown` resolves.
+#[cfg(feature = "tls")]
+fn serve_https<S>(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    config: Arc<Config>,
+    service: Service,
+    port: u16,
+    shutdown: S,
+) -> Result<Box<dyn Future<Item = (), Error = hyper::Error> + Send>, Error>
+where
+    S: Future<Item = (), Error = ()> + Send + 'static,
+{
+    let quiet = config.quiet;
+    let timeouts = config.connection_timeouts;
+    let listener = tokio::net::TcpListener::from_std(listener, &Handle::default())
+        .map_err(|err| Error::Server(err.into()))?;
+    // Connections whose handshakes fail are dropped, rather than stopping the server.
+    let incoming = accept(listener.incoming())
+        .map(move |stream| {
+            acceptor
+                .accept(TimedStream::new(stream, timeouts))
+                .then(move |result| {
+                    if let (Err(err), false) = (&result, quiet) {
+                        diagnostic!(info, "TLS handshake failed: {err}");
+                    }
+                    Ok::<_, io::Error>(result.ok())
+                })
+        })
+        .buffer_unordered(TLS_HANDSHAKE_CONCURRENCY)
+        .filter_map(|stream| stream);
+    let make_service = make_service_fn(move |stream: &TlsStream<TimedStream<TcpStream>>| {
+        let client_ip = stream
+            .get_ref()
+            .0
+            .get_ref()
+            .peer_addr()
+            .map_or(Ipv4Addr::UNSPECIFIED.into(), |addr| addr.ip());
+        let connection = Connection {
+            client_ip,
+            port,
+            proto: "https",
+        };
+        let client_cert = stream
+            .get_ref()
+            .1
+            .get_peer_certificates()
+            .and_then(|certs| ClientCert::from_der(&certs.first()?.0));
+        let activity = stream.get_ref().0.activity().clone();
+        Ok::<_, hyper::Error>(connection_service(
+            &config,
+            &service,
+            connection,
+            activity,
+            client_cert,
+        ))
+    });
+    Ok(Box::new(
+        hyper::Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown),
+    ))
+}
+
+/// The handler for requests received over HTTP/3.
+#[cfg(feature = "http3")]
+fn http3_handler(config: &Arc<Config>, service: &Service) -> http3::Handler {
+    let config = config.clone();
+    let service = service.clone();
+    Arc::new(move |connection, request| {
+        Box::new(
+            handle(&config, &service, connection, request).and_then(|response| {
+                let (parts, body) = response.into_parts();
+                body.concat2()
+                    .map(|body| Response::from_parts(parts, body.to_vec()))
+            }),
+        )
+    })
+}
+
+/// The service handling requests from one connection, authenticated with `client_cert` if the
+/// client presented one.
+fn connection_service(
+    config: &Arc<Config>,
+    service: &Service,
+    connection: Connection,
+    activity: Activity,
+    client_cert: Option<ClientCert>,
+) -> impl hyper::service::Service<
+    ReqBody = hyper::Body,
+    ResBody = hyper::Body,
+    Error = BoxError,
+    Future = impl Future<Item = Response<hyper::Body>, Error = BoxError> + Send,
+> + Send {
+    let config = config.clone();
+    let service = service.clone();
+    service_fn(move |mut request: Request<hyper::Body>| {
+        if let Some(client_cert) = &client_cert {
+            request.extensions_mut().insert(client_cert.clone());
+        }
+        let (in_flight, deadline) = activity.start();
+        if let Some(deadline) = deadline {
+            request.extensions_mut().insert(deadline);
+        }
+        // Requests to the local development server's own endpoints are never disrupted.
+        let disruption = config
+            .chaos
+            .as_ref()
+            .filter(|_| !request.uri().path().starts_with(admin::PREFIX))
+            .map(|chaos| chaos.disrupt(&request));
+        if disruption
+            .as_ref()
+            .is_some_and(|disruption| disruption.error)
+        {
+            request.extensions_mut().insert(InjectedError);
+        }
+        let response = handle(&config, &service, connection, request).then(move |result| {
+            drop(in_flight);
+            result
+        });
+        match disruption {
+            Some(disruption) => Either::A(disrupt(disruption, response)),
+            None => Either::B(response.map_err(BoxError::from)),
+        }
+    })
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Delay, drop, or truncate `response` as `disruption` says.
+fn disrupt<F>(
+    disruption: Disruption,
+    response: F,
+) -> Box<dyn Future<Item = Response<hyper::Body>, Error = BoxError> + Send>
+where
+    F: Future<Item = Response<hyper::Body>, Error = hyper::Error> + Send + 'static,
+{
+    let response: Box<dyn Future<Item = _, Error = _> + Send> = match disruption.latency {
+        Some(latency) => Box::new(Delay::new(Instant::now() + latency).then(|_| response)),
+        None => Box::new(response),
+    };
+    Box::new(response.from_err().and_then(move |response| {
+        if disruption.drop {
+            trace::event!(debug, "dropping connection");
+            let err = io::Error::new(io::ErrorKind::ConnectionAborted, "connection dropped");
+            return Either::A(future01::err(err.into()));
+        }
+        if !disruption.truncate {
+            return Either::A(future01::ok(response));
+        }
+        trace::event!(debug, "truncating response");
+        let (parts, body) = response.into_parts();
+        Either::B(
+            body.concat2()
+                .from_err()
+                .map(move |body| chaos::truncate(Response::from_parts(parts, body.to_vec()))),
+        )
+    }))
+}
+
+/// Accept connections from `incoming`, waiting a moment after errors such as running out of file
+/// descriptors rather than stopping the server, as hyper does.
+fn accept<S>(incoming: S) -> impl Stream<Item = S::Item, Error = io::Error> + Send
+where
+    S: Stream<Error = io::Error> + Send,
+    S::Item: Send,
+{
+    incoming
+        .then(|result| match result {
+            Ok(stream) => Either::A(future01::ok(Some(stream))),
+            Err(_) => Either::B(
+                Delay::new(Instant::now() + Duration::from_millis(100)).then(|_| Ok(None)),
+            ),
+        })
+        .filter_map(|stream| stream)
+}
+
+/// Resolves when the process receives SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+fn shutdown_signal(quiet: bool) -> impl Future<Item = (), Error = ()> {
+    let signals = tokio_signal::ctrl_c().flatten_stream();
+    #[cfg(unix)]
+    let signals = signals.select(
+        tokio_signal::unix::Signal::new(tokio_signal::unix::SIGTERM)
+            .flatten_stream()
+            .map(|_| ()),
+    );
+    signals.into_future().then(move |result| match result {
+        Ok(_) => Either::A(future01::ok(())),
+        Err((err, _)) => {
+            if !quiet {
+                diagnostic!(warn, "Failed to listen for shutdown signals: {err}");
+            }
+            Either::B(future01::empty())
+        }
+    })
+}
+
+fn handle(
+    config: &Arc<Config>,
+    service: &Service,
+    connection: Connection,
+    request: Request<hyper::Body>,
+) -> impl Future<Item = Response<hyper::Body>, Error = hyper::Error> + Send {
+    if let Some(response) = serve_admin(config, &request) {
+        return Either::A(future01::ok(response));
+    }
+    let entry = config.access_log.map(|format| {
+        (
+            format,
+            access_log::Entry::new(connection.client_ip, &request),
+        )
+    });
+    // Advertise the HTTP/3 listener, which is on the UDP port with the same number.
+    #[cfg(feature = "http3")]
+    let alt_svc = config
+        .http3
+        .then(|| format!("h3=\":{}\"; ma=86400", connection.port))
+        .and_then(|alt_svc| HeaderValue::from_str(&alt_svc).ok());
+    let in_flight = InFlightRequest::start(config);
+    let response: Box<dyn Future<Item = _, Error = _> + Send> = if let Some(in_flight) = in_flight {
+        Box::new(
+            respond(config, service, connection, request).then(move |result| {
+                drop(in_flight);
+                result
+            }),
+        )
+    } else {
+        trace::event!(warn, "concurrent request limit reached");
+        Box::new(future01::ok(text_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Service Unavailable",
+        )))
+    };
+    Either::B(response.map(move |response| {
+        if let Some((format, entry)) = entry {
+            entry.write(format, &response);
+        }
+        #[cfg(feature = "http3")]
+        let response = {
+            let mut response = response;
+            if let Some(alt_svc) = alt_svc {
+                response.headers_mut().insert(ALT_SVC, alt_svc);
+            }
+            response
+        };
+        response.map(hyper::Body::from)
+    }))
+}
+
+/// Counts a request towards the limit on concurrent requests while it is held.
+struct InFlightRequest(Arc<Config>);
+
+impl InFlightRequest {
+    /// Start handling a request, unless the limit on concurrent requests has been reached.
+    fn start(config: &Arc<Config>) -> Option<InFlightRequest> {
+        let Some(limit) = config.max_concurrent_requests else {
+            return Some(InFlightRequest(config.clone()));
+        };
+        config
+            .requests_in_flight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |in_flight| {
+                (in_flight < limit).then_some(in_flight + 1)
+            })
+            .ok()
+            .map(|_| InFlightRequest(config.clone()))
+    }
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        if self.0.max_concurrent_requests.is_some() {
+            self.0.requests_in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Counts the bytes of a request body towards the limit on buffered bytes while it is held.
+struct BufferedBytes(Arc<Config>, usize);
+
+impl BufferedBytes {
+    /// Count `len` more bytes, unless that would exceed the limit on buffered bytes.
+    fn add(&mut self, len: usize) -> bool {
+        let Some(limit) = self.0.buffered_body_limit else {
+            return true;
+        };
+        let added = self
+            .0
+            .buffered_bytes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |buffered| {
+                buffered
+                    .checked_add(len)
+                    .filter(|&buffered| buffered <= limit)
+            })
+            .is_ok();
+        if added {
+            self.1 += len;
+        }
+        added
+    }
+}
+
+impl Drop for BufferedBytes {
+    fn drop(&mut self) {
+        self.0.buffered_bytes.fetch_sub(self.1, Ordering::SeqCst);
+    }
+}
+
+/// Respond to a request for one of the enabled `/__lambda/` endpoints.
+fn serve_admin<B>(config: &Config, request: &Request<B>) -> Option<Response<hyper::Body>> {
+    let path = request.uri().path().strip_prefix(admin::PREFIX)?;
+    if path == "events" {
+        return config.events.as_ref().map(EventStream::subscribe);
+    }
+    let response = match path {
+        "metrics" => config.stats.as_ref().map(Stats::response),
+        "invocations" => config.history.as_ref().map(History::response),
+        #[cfg(feature = "dashboard")]
+        "" if config.dashboard => Some(admin::dashboard()),
+        "health" if config.health_endpoint => Some(admin::health(
+            &config.context.build(),
+            config.event.profile,
+            config.started,
+        )),
+        "ready" if config.health_endpoint => {
+            Some(admin::ready(config.ready.load(Ordering::SeqCst)))
+        }
+        _ => None,
+    };
+    response.map(|response| response.map(hyper::Body::from))
+}
+
+/// Buffer the body of `request` and invoke the function with it.
+fn respond(
+    config: &Arc<Config>,
+    service: &Service,
+    connection: Connection,
+    request: Request<hyper::Body>,
+) -> Box<dyn Future<Item = Response<Vec<u8>>, Error = hyper::Error> + Send> {
+    let (mut parts, body) = request.into_parts();
+    let deadline = parts.extensions.remove::<conn::Deadline>();
+
+    if let Some(limit) = config.body_limit {
+        let content_length = parts
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok());
+        if content_length.is_some_and(|length| length > limit) {
+            return Box::new(future01::ok(too_large(config)));
+        }
+    }
+
+    // Buffer the body, dropping it once it exceeds the limit (if any), the server is buffering
+    // too much in total, or the client takes too long to send it.
+    let limit = config.body_limit;
+    let buffered = BufferedBytes(config.clone(), 0);
+    let body = body.fold(Ok((Vec::new(), buffered)), move |buffer, chunk| {
+        Ok::<_, hyper::Error>(buffer.and_then(|(mut buffer, mut buffered)| {
+            if limit.is_some_and(|limit| buffer.len() + chunk.len() > limit) {
+                Err(StatusCode::PAYLOAD_TOO_LARGE)
+            } else if !buffered.add(chunk.len()) {
+                Err(StatusCode::SERVICE_UNAVAILABLE)
+            } else {
+                buffer.extend_from_slice(&chunk);
+                Ok((buffer, buffered))
+            }
+        }))
+    });
+    let body: Box<dyn Future<Item = _, Error = _> + Send> = match deadline {
+        Some(conn::Deadline(deadline)) => Box::new(body.select2(Delay::new(deadline)).then(
+            |result| match result {
+                Ok(Either::A((body, _))) => Ok(body),
+                Ok(Either::B(_)) | Err(Either::B(_)) => Ok(Err(StatusCode::REQUEST_TIMEOUT)),
+                Err(Either::A((err, _))) => Err(err),
+            },
+        )),
+        None => Box::new(body),
+    };
+    let config = config.clone();
+    let service = service.clone();
+    Box::new(
+        body.and_then(move |body| -> Box<dyn Future<Item = _, Error = _> + Send> {
+            match body {
+                Ok((body, buffered)) => {
+                    let request = Request::from_parts(parts, body);
+                    #[cfg(feature = "shadow")]
+                    let remote = config
+                        .shadow
+                        .as_ref()
+                        .and_then(|shadow| mirror(&config, shadow, &request));
+                    let response = try_invoke(&config, &service, connection, request);
+                    #[cfg(feature = "shadow")]
+                    let response = match remote {
+                        Some((shadow, remote)) => compare(&config, shadow, remote, response),
+                        None => response,
+                    };
+                    // The body is kept in memory until the invocation completes.
+                    Box::new(response.then(move |result| {
+                        drop(buffered);
+                        result.map_err(|()| unreachable!("invocations never fail"))
+                    }))
+                }
+                Err(StatusCode::PAYLOAD_TOO_LARGE) => Box::new(future01::ok(too_large(&config))),
+                Err(status) => {
+                    // The rest of the body is not read, and may never arrive, so the connection
+                    // cannot be reused.
+                    if status == StatusCode::SERVICE_UNAVAILABLE {
+                        trace::event!(warn, "buffered request body limit reached");
+                    }
+                    let reason = status.canonical_reason().unwrap_or_default();
+                    let mut response = text_response(status, reason);
+                    response
+                        .headers_mut()
+                        .insert(CONNECTION, HeaderValue::from_static("close"));
+                    Box::new(future01::ok(response))
+                }
+            }
+        }),
+    )
+}
+
+/// Check a buffered request as the emulated service would, and build the event to invoke the
+/// function with, or the response the service returns instead.
+#[allow(clippy::result_large_err)]
+fn build_event(
+    config: &Config,
+    connection: Connection,
+    mut request: Request<Vec<u8>>,
+) -> Result<Value, Response<Vec<u8>>> {
+    let profile = config.event.profile;
+    if !config.event.matches_stage(request.uri().path()) {
+        return Err(profile.missing_route());
+    }
+    let authorizer = match authorize(config, &mut request) {
+        Ok(authorizer) => authorizer,
+        Err(err) => {
+            if let (false, Rejected::InvalidToken(reason)) = (config.quiet, &err) {
+                diagnostic!(info, "Unauthorized: {reason}");
+            }
+            trace::event!(debug, rejection = ?err, "request rejected by authorizer");
+            return Err(err.response(profile));
+        }
+    };
+    let api_key = match check_api_key(config, &request) {
+        Ok(api_key) => api_key,
+        Err(err) => return Err(err.response(profile)),
+    };
+    #[cfg(feature = "openapi")]
+    if let Some(openapi) = &config.openapi {
+        let path = config.event.resource_path(request.uri().path());
+        if let Err(invalid) = openapi.validate(&request, path) {
+            if !config.quiet {
+                diagnostic!(info, "Request validation failed: {invalid}");
+            }
+            trace::event!(debug, %invalid, "request failed validation");
+            return Err(invalid.response());
+        }
+    }
+    if !config.quiet && config.event.corrupts_request_body(&request) {
+        diagnostic!(
+            warn,
+            "Warning: the request body is not valid UTF-8, but its Content-Type is not a binary \
+             media type; API Gateway would corrupt it"
+        );
+    }
+    let mut event = event::to_event(&config.event, connection, request);
+    if let Some(authorizer) = authorizer {
+        event["requestContext"]["authorizer"] = authorizer;
+    }
+    if let Some(api_key) = api_key {
+        event["requestContext"]["identity"]["apiKey"] = api_key.into();
+    }
+    let event_size = event.to_string().len();
+    if config.enforce_payload_limits && event_size > INVOCATION_PAYLOAD_LIMIT {
+        // The body fits within the service's limit, but the event is too large to invoke the
+        // function with.
+        if !config.quiet {
+            diagnostic!(
+                warn,
+                "Request payload size ({event_size} bytes) exceeded maximum allowed payload size \
+                 ({INVOCATION_PAYLOAD_LIMIT} bytes)."
+            );
+        }
+        trace::event!(warn, event_size, "request payload size exceeded maximum");
+        return Err(profile.payload_too_large());
+    }
+    Ok(event)
+}
+
+/// Invoke the function, unless a service error is injected instead or its concurrency limit has
+/// been reached, in which case the request is throttled.
+fn try_invoke(
+    config: &Arc<Config>,
+    service: &Service,
+    connection: Connection,
+    request: Request<Vec<u8>>,
+) -> Box<dyn Future<Item = Response<Vec<u8>>, Error = ()> + Send> {
+    if request.extensions().get::<InjectedError>().is_some() {
+        if !config.quiet {
+            diagnostic!(
+                info,
+                "Injected a service error for {} {}",
+                request.method(),
+                request.uri().path()
+            );
+        }
+        trace::event!(debug, "injecting service error");
+        return Box::new(future01::ok(config.event.profile.service_error()));
+    }
+    let Some(execution) = Execution::start(config) else {
+        if !config.quiet {
+            diagnostic!(
+                warn,
+                "Throttled {} {}: the function's concurrency limit is reached",
+                request.method(),
+                request.uri().path()
+            );
+        }
+        trace::event!(warn, "invocation throttled");
+        return Box::new(future01::ok(config.event.profile.throttled()));
+    };
+    Box::new(
+        invoke_on_instance(config, service, connection, request).then(move |response| {
+            drop(execution);
+            response
+        }),
+    )
+}
+
+/// Counts an invocation towards the function's concurrency limit while it is held.
+struct Execution(Arc<Config>);
+
+impl Execution {
+    /// Start an invocation, unless the function's concurrency limit has been reached.
+    fn start(config: &Arc<Config>) -> Option<Execution> {
+        let Some(limit) = config.reserved_concurrency else {
+            return Some(Execution(config.clone()));
+        };
+        config
+            .executions
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |executions| {
+                (executions < limit).then_some(executions + 1)
+            })
+            .ok()
+            .map(|_| Execution(config.clone()))
+    }
+}
+
+impl Drop for Execution {
+    fn drop(&mut self) {
+        if self.0.reserved_concurrency.is_some() {
+            self.0.executions.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Invoke the function in one of its simulated execution environments, if they are simulated,
+/// waiting for one to become idle if the most are busy.
+fn invoke_on_instance(
+    config: &Arc<Config>,
+    service: &Service,
+    connection: Connection,
+    request: Request<Vec<u8>>,
+) -> Box<dyn Future<Item = Response<Vec<u8>>, Error = ()> + Send> {
+    let Some(instances) = &config.instances else {
+        return invoke(config, service, connection, request, None);
+    };
+    let config = config.clone();
+    let service = service.clone();
+    Box::new(
+        instances
+            .acquire(&config.context)
+            .and_then(move |mut instance| {
+                let cold_start: Box<dyn Future<Item = Lease, Error = ()> + Send> =
+                    match (instance.init_duration(), config.cold_start_delay) {
+                        (Some(_), Some(delay)) => {
+                            instance.add_init_duration(delay);
+                            Box::new(Delay::new(Instant::now() + delay).then(move |_| Ok(instance)))
+                        }
+                        _ => Box::new(future01::ok(instance)),
+                    };
+                cold_start.and_then(move |instance| {
+                    if instance.init_duration().is_some() && !config.quiet {
+                        diagnostic!(info, "Started execution environment {}", instance.id());
+                    }
+                    trace::event!(debug, instance = instance.id(), "invoking on instance");
+                    invoke(&config, &service, connection, request, Some(instance))
+                })
+            }),
+    )
+}
+
+/// Invoke the function with a buffered request, as the emulated service would, in the
+/// execution environment `instance` if they are simulated.
+fn invoke(
+    config: &Arc<Config>,
+    service: &Service,
+    connection: Connection,
+    request: Request<Vec<u8>>,
+    instance: Option<Lease>,
+) -> Box<dyn Future<Item = Response<Vec<u8>>, Error = ()> + Send> {
+    let profile = config.event.profile;
+    let accept = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    #[cfg(feature = "openapi")]
+    let operation = response_operation(config, &request);
+    let mut record = if config.history.is_some() || config.events.is_some() || config.har.is_some()
+    {
+        Some(Record::new(&request, config.har.is_some()))
+    } else {
+        None
+    };
+    let event = match build_event(config, connection, request) {
+        Ok(event) => event,
+        Err(response) => {
+            if let Some(record) = record {
+                add_to_history(config, record, &response);
+            }
+            return Box::new(future01::ok(response));
+        }
+    };
+    if let Some(record) = &mut record {
+        record.set_event(&event);
+    }
+    let recorded_event = config.record_dir.as_ref().map(|_| event.clone());
+    let request = event::from_event(event).expect("failed to parse synthesized event");
+    let service_request_id = match request.extensions().get::<RequestContext>() {
+        Some(RequestContext::ApiGateway { request_id, .. }) => Some(request_id.clone()),
+        _ => None,
+    };
+    let mut context = config.context.build();
+    if let Some(log_stream_name) = instance.as_ref().and_then(Lease::log_stream_name) {
+        log_stream_name.clone_into(&mut context.log_stream_name);
+    }
+    let request_id = context.aws_request_id.clone();
+    if let Some(record) = &mut record {
+        record.set_request_id(&request_id);
+    }
+    // The invocation is finished by whichever of the function and the timeout completes first.
+    let invocation = Arc::new(Mutex::new(Some(Invocation::start(
+        config,
+        &mut context,
+        &request,
+        recorded_event,
+        instance.as_ref().and_then(Lease::init_duration),
+    ))));
+
+    let mut response: Box<dyn Future<Item = _, Error = _> + Send> = {
+        let config = config.clone();
+        let invocation = invocation.clone();
+        Box::new(
+            call(service, request, context, instance, config.quiet).map(move |result| {
+                if let Some(invocation) = lock(&invocation).take() {
+                    invocation.finish(&config, Some(&result));
+                }
+                #[cfg(feature = "openapi")]
+                let result = result.map(|response| check_response(&config, operation, response));
+                let response = result.unwrap_or_else(|err| {
+                    text_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+                });
+                finish_response(
+                    &config,
+                    accept.as_deref(),
+                    service_request_id.as_deref(),
+                    response,
+                )
+            }),
+        )
+    };
+    if let Some(timeout) = config.timeout {
+        let config = config.clone();
+        response = Box::new(Timeout::new(response, timeout).or_else(move |_| {
+            if !config.quiet {
+                diagnostic!(
+                    error,
+                    "{} Task timed out after {:.2} seconds",
+                    request_id,
+                    timeout.as_secs_f64()
+                );
+            }
+            trace::event!(warn, %request_id, ?timeout, "task timed out");
+            if let Some(invocation) = lock(&invocation).take() {
+                invocation.finish(&config, None);
+            }
+            Ok(profile.function_error())
+        }));
+    }
+    if let Some(timeout) = config.integration_timeout {
+        response = Box::new(
+            Timeout::new(response, timeout).or_else(move |_| Ok(profile.integration_timed_out())),
+        );
+    }
+    if let Some(record) = record {
+        let config = config.clone();
+        response = Box::new(response.map(move |response| {
+            add_to_history(&config, record, &response);
+            response
+        }));
+    }
+    response
+}
+
+/// The number of the documented operation the response to `request` is checked against, if any.
+#[cfg(feature = "openapi")]
+fn response_operation(config: &Config, request: &Request<Vec<u8>>) -> Option<usize> {
+    let path = config.event.resource_path(request.uri().path());
+    config.openapi.as_ref()?.response_operation(request, path)
+}
+
+/// Check a response from the function against the API's document, if responses are checked and
+/// the request matched the operation numbered `operation`.
+#[cfg(feature = "openapi")]
+fn check_response(
+    config: &Config,
+    operation: Option<usize>,
+    response: Response<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let (Some(openapi), Some(operation)) = (&config.openapi, operation) else {
+        return response;
+    };
+    let Err(mismatch) = openapi.validate_response(operation, &response) else {
+        return response;
+    };
+    trace::event!(warn, %mismatch, "response failed validation");
+    if openapi.response_validation() == Some(ResponseValidation::Fail) {
+        if !config.quiet {
+            diagnostic!(error, "Response validation failed: {mismatch}");
+        }
+        return text_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Response validation failed: {mismatch}"),
+        );
+    }
+    if !config.quiet {
+        diagnostic!(warn, "Warning: response validation failed: {mismatch}");
+    }
+    response
+}
+
+/// Send a copy of a request to the deployed endpoint in the background, and log its response.
+///
+/// If responses are compared, returns a description of the request and the deployed response.
+#[cfg(feature = "shadow")]
+fn mirror(
+    config: &Config,
+    shadow: &Shadow,
+    request: &Request<Vec<u8>>,
+) -> Option<(String, oneshot::Receiver<Response<Vec<u8>>>)> {
+    let quiet = config.quiet;
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let label = format!("{method} {uri}");
+    let (tx, rx) = oneshot::channel();
+    let start = Instant::now();
+    tokio::spawn(
+        shadow
+            .forward(admin::copy_request(request))
+            .then(move |result| {
+                match result {
+                    Ok(response) => {
+                        let status = response.status().as_u16();
+                        let elapsed = start.elapsed();
+                        if !quiet {
+                            diagnostic!(
+                                info,
+                                "Shadow {method} {uri}: {status} in {:.2} ms",
+                                elapsed.as_secs_f64() * 1000.0
+                            );
+                        }
+                        trace::event!(info, %method, %uri, status, ?elapsed, "shadow response");
+                        // The receiver is dropped if responses are not compared.
+                        let _ = tx.send(response);
+                    }
+                    Err(err) => {
+                        if !quiet {
+                            diagnostic!(warn, "Shadow {method} {uri} failed: {err}");
+                        }
+                        trace::event!(warn, %method, %uri, error = %err, "shadow request failed");
+                    }
+                }
+                Ok(())
+            }),
+    );
+    if shadow.compares() {
+        Some((label, rx))
+    } else {
+        None
+    }
+}
+
+/// Once both the local and the deployed responses to a request are ready, report the differences
+/// between them.
+#[cfg(feature = "shadow")]
+fn compare(
+    config: &Arc<Config>,
+    label: String,
+    remote: oneshot::Receiver<Response<Vec<u8>>>,
+    response: Box<dyn Future<Item = Response<Vec<u8>>, Error = ()> + Send>,
+) -> Box<dyn Future<Item = Response<Vec<u8>>, Error = ()> + Send> {
+    let config = config.clone();
+    Box::new(response.map(move |response| {
+        let local = shadow::copy_response(&response);
+        tokio::spawn(remote.then(move |remote| {
+            // The deployed request failed, which has already been logged.
+            let Ok(remote) = remote else { return Ok(()) };
+            let Some(shadow) = &config.shadow else {
+                return Ok(());
+            };
+            let differences = shadow.diff(&local, &remote);
+            if !config.quiet {
+                if differences.is_empty() {
+                    diagnostic!(info, "Shadow {label}: responses match");
+                } else {
+                    diagnostic!(
+                        warn,
+                        "Shadow {label}: responses differ\n  {}",
+                        differences.join("\n  ")
+                    );
+                }
+            }
+            trace::event!(
+                info,
+                request = %label,
+                differences = ?differences,
+                "shadow responses compared"
+            );
+            Ok(())
+        }));
+        response
+    }))
+}
+
+/// Add the response to a request to the invocation history and the HAR file, and send it to event
+/// stream clients.
+fn add_to_history(config: &Config, record: Record, response: &Response<Vec<u8>>) {
+    if let Some(har) = &config.har {
+        record.add_to_har(har, response);
+    }
+    let entry = record.finish(response);
+    if let Some(events) = &config.events {
+        events.send(&entry);
+    }
+    if let Some(history) = &config.history {
+        history.push(entry);
+    }
+}
+
+/// The parts of an invocation that are reported once it completes or times out.
+struct Invocation {
+    request_id: String,
+    start: Instant,
+    report: Option<Report>,
+    segment: Option<(SocketAddr, xray::Segment)>,
+    /// The event, if invocations are recorded.
+    event: Option<Value>,
+}
+
+impl Invocation {
+    /// Start reporting an invocation with `context` and `request`, setting the trace ID of
+    /// `context` if X-Ray segments are sent. `init_duration` is how long the execution environment
+    /// took to start, if this is a cold start.
+    fn start(
+        config: &Config,
+        context: &mut Context,
+        request: &lambda_http::Request,
+        event: Option<Value>,
+        init_duration: Option<Duration>,
+    ) -> Invocation {
+        let segment = config.xray_daemon.map(|daemon| {
+            let segment = xray::Segment::start(&context.function_name, request);
+            context.xray_trace_id = Some(segment.trace_header());
+            (daemon, segment)
+        });
+        let report = if config.platform_log {
+            let report = Report::start(context, init_duration);
+            diagnostic!(info, "{}", report.start_line());
+            Some(report)
+        } else {
+            None
+        };
+        Invocation {
+            request_id: context.aws_request_id.clone(),
+            start: Instant::now(),
+            report,
+            segment,
+            event,
+        }
+    }
+
+    /// Report the result of the invocation, or that it timed out if there is none.
+    fn finish(self, config: &Config, result: Option<&Result<Response<Vec<u8>>, InvocationError>>) {
+        let failed = result.is_none_or(Result::is_err);
+        if let Some(stats) = &config.stats {
+            stats.record(self.start.elapsed(), failed);
+        }
+        if let Some(report) = &self.report {
+            write_report(config, report, result.is_none());
+        }
+        if let Some((daemon, segment)) = self.segment {
+            segment.send(daemon, result.and_then(|result| result.as_ref().ok()));
+        }
+        if let (Some(dir), Some(event), Some(result)) = (&config.record_dir, &self.event, result) {
+            let is_alb = config.event.profile.is_alb();
+            if let Err(err) = record::write(dir, &self.request_id, is_alb, event, result.as_ref()) {
+                if !config.quiet {
+                    diagnostic!(
+                        warn,
+                        "Failed to record invocation {}: {}",
+                        self.request_id,
+                        err
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn write_report(config: &Config, report: &Report, timed_out: bool) {
+    let max_memory_used = report::max_memory_used();
+    diagnostic!(info, "{}", report.end_line());
+    diagnostic!(info, "{}", report.report_line(timed_out, max_memory_used));
+    match max_memory_used {
+        Some(used) if !config.quiet && report.exceeds_memory_size(used) => diagnostic!(
+            warn,
+            "Max Memory Used of {} MB exceeds the function's memory size of {} MB",
+            used,
+            report.memory_size()
+        ),
+        _ => {}
+    }
+}
+
+/// Run the configured authorizers for `request`, returning the `authorizer` field of the request
+/// context if any allowed it.
+fn authorize(config: &Config, request: &mut Request<Vec<u8>>) -> Result<Option<Value>, Rejected> {
+    let mut context = None;
+    if let Some(authorizer) = &config.jwt_authorizer {
+        context = Some(authorizer.authorize(request.headers())?);
+    }
+    if let Some(authorizer) = &config.lambda_authorizer {
+        context = Some(authorizer(&service::request_head(request)).into_context()?);
+    }
+    if !config.cognito_claims.is_empty() {
+        context = Some(config.cognito_claims.authorize(request.headers_mut())?);
+    }
+    Ok(context)
+}
+
+/// Check the API key of `request` if API keys are required, returning the key.
+fn check_api_key(config: &Config, request: &Request<Vec<u8>>) -> Result<Option<String>, Rejected> {
+    if config.api_keys.is_empty() {
+        return Ok(None);
+    }
+    let key = request
+        .headers()
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok());
+    let (key, count) = key
+        .and_then(|key| config.api_keys.get_key_value(key))
+        .ok_or(Rejected::InvalidApiKey)?;
+    let count = count.fetch_add(1, Ordering::Relaxed);
+    if config.api_key_quota.is_some_and(|quota| count >= quota) {
+        return Err(Rejected::QuotaExceeded);
+    }
+    Ok(Some(key.clone()))
+}
+
+/// Convert a response from the function to what the emulated service returns to the client.
+fn finish_response(
+    config: &Config,
+    accept: Option<&str>,
+    service_request_id: Option<&str>,
+    mut response: Response<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let profile = config.event.profile;
+    if config.enforce_payload_limits {
+        let size = lambda::response_event(profile.is_alb(), &response)
+            .to_string()
+            .len();
+        let limit = profile.response_limit();
+        if size > limit {
+            if !config.quiet {
+                diagnostic!(
+                    warn,
+                    "Response payload size ({size} bytes) exceeded maximum allowed payload size \
+                     ({limit} bytes)."
+                );
+            }
+            trace::event!(warn, size, "response payload size exceeded maximum");
+            return profile.function_error();
+        }
+    }
+    if config.event.encode_response_body(accept, &mut response) && !config.quiet {
+        diagnostic!(
+            warn,
+            "Warning: the response body is binary, but neither the response's Content-Type nor \
+             the request's Accept header is a binary media type; API Gateway would return it \
+             base64-encoded"
+        );
+    }
+    profile.finish_response(&mut response, service_request_id.unwrap_or(""));
+    response
+}
+
+/// Call the handler, or that of the execution environment `instance` if there is one, with
+/// `request` as `lambda_http` received it.
+///
+/// Blocking handlers run on their own thread, so they neither stall the server nor keep it from
+/// responding once the function timeout has elapsed. The execution environment stays busy until
+/// the handler returns, even if the invocation times out first.
+fn call(
+    service: &Service,
+    request: lambda_http::Request,
+    context: Context,
+    instance: Option<Lease>,
+    quiet: bool,
+) -> impl Future<Item = Result<Response<Vec<u8>>, InvocationError>, Error = ()> + Send {
+    let request = lambda::into_request(request, context);
+    let service = instance.as_ref().map_or(service, Lease::service).clone();
+    if service.is_blocking() {
+        let (tx, rx) = futures::channel::oneshot::channel();
+        thread::spawn(move || {
+            // The receiver is gone if the invocation timed out.
+            let _ = tx.send(futures::executor::block_on(service.invoke(request, quiet)));
+            drop(instance);
+        });
+        Either::A(
+            rx.compat()
+                .or_else(|_| Ok(Err(InvocationError::new("handler panicked")))),
+        )
+    } else {
+        Either::B(
+            service
+                .invoke(request, quiet)
+                .map(move |result| {
+                    drop(instance);
+                    Ok(result)
+                })
+                .boxed()
+                .compat(),
+        )
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn too_large(config: &Config) -> Response<Vec<u8>> {
+    config.event.profile.payload_too_large()
+}
+
+fn text_response<T: Into<String>>(status: StatusCode, body: T) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(body.into().into_bytes())
+        .expect("failed to build response")
+}
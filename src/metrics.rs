@@ -0,0 +1,268 @@
+//! Custom metrics in the CloudWatch embedded metric format.
+//!
+//! Metrics recorded while handling a request are written when the invocation completes. In AWS
+//! Lambda, they are written to standard output as an [embedded metric format][emf] log event,
+//! which CloudWatch Logs turns into CloudWatch metrics without any API calls. Locally, they are
+//! written to standard error in a more readable form instead, or logged with the `log` feature,
+//! unless the local development server is [quiet](../struct.Builder.html#method.quiet).
+//!
+//! Metrics are published in the namespace set by the `AWS_EMF_NAMESPACE` environment variable,
+//! or `aws-embedded-metrics` if it is not set, with the function name as the `FunctionName`
+//! dimension.
+//!
+//! Metrics are collected for each invocation, so the local development server writes the
+//! metrics of concurrent requests separately. [`count`] and [`record`] record metrics for the
+//! invocation whose handler is running on the current thread. To record metrics from a thread or
+//! task the handler spawns, use the invocation's [`Metrics`], from the request's extensions,
+//! instead. Metrics recorded after the handler has responded are not written.
+//!
+//! [`count`]: fn.count.html
+//! [`record`]: fn.record.html
+//! [`Metrics`]: struct.Metrics.html
+//!
+//! [emf]: https://docs.aws.amazon.com/AmazonCloudWatch/latest/monitoring/CloudWatch_Embedded_Metric_Format_Specification.html
+//!
+//! # Example
+//!
+//! ```rust
+//! # use http::{Request, Response};
+//! use lambda_http_local::metrics;
+//!
+//! let handler = |request: Request<&[u8]>| {
+//!     metrics::count("orders", 1);
+//!     metrics::record("order_value", 42.5, "None");
+//!     Response::new("ordered")
+//! };
+//! # lambda_http_local::testing::invoke(handler, Request::get("/").body(Vec::new()).unwrap())
+//! #     .unwrap();
+//! ```
+
+#![allow(clippy::doc_markdown)]
+
+use crate::service::BoxFuture;
+use chrono::Utc;
+use futures::future::FutureExt;
+use http::Request;
+use lambda_runtime::Context;
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::env;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{self, Poll};
+
+struct Metric {
+    name: String,
+    unit: String,
+    values: Vec<f64>,
+}
+
+thread_local! {
+    /// The metrics of the invocation whose handler is running on this thread, if any.
+    static CURRENT: RefCell<Option<Metrics>> = const { RefCell::new(None) };
+}
+
+/// The metrics recorded during one invocation.
+///
+/// Each request passed to a handler has the `Metrics` of its invocation in its extensions. Use it
+/// to record metrics from threads or tasks the handler spawns, where [`count`] and [`record`] do
+/// not know which invocation they are for.
+///
+/// [`count`]: fn.count.html
+/// [`record`]: fn.record.html
+///
+/// ```rust
+/// # use http::{Request, Response};
+/// use lambda_http_local::metrics::Metrics;
+///
+/// let handler = |request: Request<&[u8]>| {
+///     let metrics = request.extensions().get::<Metrics>().unwrap().clone();
+///     std::thread::spawn(move || metrics.count("background_jobs", 1))
+///         .join()
+///         .unwrap();
+///     Response::new("started")
+/// };
+/// # lambda_http_local::testing::invoke(handler, Request::get("/").body(Vec::new()).unwrap())
+/// #     .unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct Metrics {
+    metrics: Arc<Mutex<Vec<Metric>>>,
+    /// Whether the local development server is quiet, so that the metrics are not written.
+    quiet: bool,
+}
+
+impl Metrics {
+    /// Add `value` to the count `name`.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn count(&self, name: &str, value: u64) {
+        self.record(name, value as f64, "Count");
+    }
+
+    /// Record `value` for the metric `name`, in `unit`.
+    ///
+    /// See [`record`](fn.record.html) for details.
+    pub fn record(&self, name: &str, value: f64, unit: &str) {
+        let mut metrics = self.lock();
+        match metrics.iter_mut().find(|metric| metric.name == name) {
+            Some(metric) if metric.unit != unit => {
+                if !self.quiet {
+                    diagnostic!(
+                        warn,
+                        "Warning: ignoring a value for the metric {} in {}, as it was already \
+                         recorded in {} during this invocation",
+                        name,
+                        unit,
+                        metric.unit
+                    );
+                }
+            }
+            Some(metric) => metric.values.push(value),
+            None => metrics.push(Metric {
+                name: name.to_owned(),
+                unit: unit.to_owned(),
+                values: vec![value],
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Metric>> {
+        self.metrics.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Call `f` with these as the metrics of the invocation running on this thread.
+    fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let result = f();
+        CURRENT.with(|current| current.replace(previous));
+        result
+    }
+}
+
+/// Add `value` to the count `name`, for the invocation whose handler is running on this thread.
+#[allow(clippy::cast_precision_loss)]
+pub fn count(name: &str, value: u64) {
+    record(name, value as f64, "Count");
+}
+
+/// Record `value` for the metric `name`, in `unit`, for the invocation whose handler is running
+/// on this thread.
+///
+/// `unit` is one of the units CloudWatch supports, such as `"Count"`, `"Milliseconds"`,
+/// `"Bytes"`, or `"None"`. Recording a metric several times in one invocation publishes each
+/// value. A metric has one unit per invocation: values recorded in another unit than the first
+/// are ignored, with a warning.
+///
+/// Outside of a handler, such as in a thread it spawned, metrics are ignored with a warning; use
+/// the invocation's [`Metrics`](struct.Metrics.html) there instead. As the warning is not for any
+/// invocation, it is written even if the local development server is quiet.
+pub fn record(name: &str, value: f64, unit: &str) {
+    let current = CURRENT.with(|current| current.borrow().clone());
+    match current {
+        Some(metrics) => metrics.record(name, value, unit),
+        None => diagnostic!(
+            warn,
+            "Warning: ignoring a value for the metric {name} recorded outside of a handler; record \
+             it with the invocation's Metrics instead"
+        ),
+    }
+}
+
+/// Call `call` with `request`, collecting the metrics recorded while it handles the request, and
+/// write them once the response is ready, unless `quiet` is set locally.
+pub(crate) fn scope<F>(mut request: Request<Vec<u8>>, quiet: bool, call: F) -> BoxFuture
+where
+    F: FnOnce(Request<Vec<u8>>) -> BoxFuture,
+{
+    let metrics = Metrics {
+        quiet,
+        ..Metrics::default()
+    };
+    let context = request.extensions().get::<Context>().cloned();
+    request.extensions_mut().insert(metrics.clone());
+    let response = metrics.in_scope(|| call(request));
+    Scoped {
+        metrics: metrics.clone(),
+        response,
+    }
+    .inspect(move |_| {
+        if let Some(context) = &context {
+            flush(&metrics, context);
+        }
+    })
+    .boxed()
+}
+
+/// A response future that is polled with its invocation's metrics in scope.
+struct Scoped {
+    metrics: Metrics,
+    response: BoxFuture,
+}
+
+impl Future for Scoped {
+    type Output = <BoxFuture as Future>::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let Scoped { metrics, response } = &mut *self;
+        metrics.in_scope(|| response.as_mut().poll(cx))
+    }
+}
+
+/// Write the metrics recorded during an invocation, if there are any.
+fn flush(metrics: &Metrics, context: &Context) {
+    let quiet = metrics.quiet;
+    let metrics = std::mem::take(&mut *metrics.lock());
+    if metrics.is_empty() {
+        return;
+    }
+    if crate::is_lambda() {
+        println!("{}", emf(&metrics, &context.function_name));
+    } else if !quiet {
+        for metric in &metrics {
+            let values = metric
+                .values
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            diagnostic!(
+                info,
+                "{} Metric {} = {} ({})",
+                context.aws_request_id,
+                metric.name,
+                values.join(", "),
+                metric.unit
+            );
+        }
+    }
+}
+
+/// Format `metrics` as an embedded metric format log event.
+fn emf(metrics: &[Metric], function_name: &str) -> Value {
+    let namespace =
+        env::var("AWS_EMF_NAMESPACE").unwrap_or_else(|_| "aws-embedded-metrics".to_owned());
+    let mut event = Map::new();
+    event.insert("FunctionName".to_owned(), function_name.into());
+    for metric in metrics {
+        let value = match metric.values.as_slice() {
+            [value] => json!(value),
+            values => json!(values),
+        };
+        event.insert(metric.name.clone(), value);
+    }
+    event.insert(
+        "_aws".to_owned(),
+        json!({
+            "Timestamp": Utc::now().timestamp_millis(),
+            "CloudWatchMetrics": [{
+                "Namespace": namespace,
+                "Dimensions": [["FunctionName"]],
+                "Metrics": metrics
+                    .iter()
+                    .map(|metric| json!({ "Name": metric.name, "Unit": metric.unit }))
+                    .collect::<Vec<_>>(),
+            }],
+        }),
+    );
+    Value::Object(event)
+}
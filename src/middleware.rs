@@ -0,0 +1,69 @@
+use crate::service::{self, BoxFuture, Service};
+use crate::InvocationError;
+use futures::future::FutureExt;
+use http::{Request, Response};
+use std::future::Future;
+use std::sync::Arc;
+
+pub(crate) type ErrorHandler =
+    Arc<dyn Fn(&Request<()>, &InvocationError) -> Response<Vec<u8>> + Send + Sync>;
+pub(crate) type Middleware = Arc<dyn Fn(Request<Vec<u8>>, Next) -> BoxFuture + Send + Sync>;
+
+/// The rest of the middleware chain, ending with the handler.
+///
+/// Middleware added with [`Builder::with_middleware`] receives a `Next` along with each request,
+/// and calls [`run`](#method.run) to pass the request on.
+///
+/// [`Builder::with_middleware`]: struct.Builder.html#method.with_middleware
+#[derive(Clone)]
+pub struct Next {
+    service: Service,
+}
+
+impl Next {
+    /// Pass `request` to the next middleware, or to the handler if this is the last middleware.
+    pub fn run(
+        self,
+        request: Request<Vec<u8>>,
+    ) -> impl Future<Output = Result<Response<Vec<u8>>, InvocationError>> + Send {
+        self.service.call(request)
+    }
+}
+
+/// Wrap `service` in `middleware`, with the first middleware outermost.
+pub(crate) fn apply(service: Service, middleware: &[Middleware]) -> Service {
+    middleware
+        .iter()
+        .rev()
+        .fold(service, |service, middleware| {
+            let middleware = middleware.clone();
+            // Middleware calls the service it wraps from its own future, so if that service blocks,
+            // the middleware does too.
+            let blocking = service.is_blocking();
+            Service::new(
+                move |request| {
+                    let next = Next {
+                        service: service.clone(),
+                    };
+                    middleware(request, next)
+                },
+                blocking,
+            )
+        })
+}
+
+/// Wrap `service` so that errors and panics are turned into responses by `handler`.
+pub(crate) fn handle_errors(service: Service, handler: ErrorHandler) -> Service {
+    let blocking = service.is_blocking();
+    Service::new(
+        move |request| {
+            let head = service::request_head(&request);
+            let handler = handler.clone();
+            service
+                .call_catching_panics(request)
+                .map(move |result| result.or_else(|err| Ok(handler(&head, &err))))
+                .boxed()
+        },
+        blocking,
+    )
+}
@@ -0,0 +1,410 @@
+//! Parsing `multipart/form-data` request bodies, such as file uploads from HTML forms.
+//!
+//! AWS Lambda receives the whole request body at once, and API Gateway sends binary bodies
+//! base64-encoded, which is decoded before the handler is called. Handlers therefore see the same
+//! raw body in AWS Lambda and locally, and [`Multipart`] parses it in place: each [`Field`]
+//! borrows its data from the body rather than copying it, so large uploads are not held in memory
+//! twice.
+//!
+//! API Gateway REST APIs only pass binary bodies through unchanged if `multipart/form-data` is
+//! one of the API's binary media types.
+//!
+//! [`Multipart`]: struct.Multipart.html
+//! [`Field`]: struct.Field.html
+//!
+//! # Example
+//!
+//! ```rust
+//! use http::Request;
+//! use lambda_http_local::{testing, Multipart, Rejection};
+//!
+//! let handler = |request: Request<&[u8]>| -> Result<String, Rejection> {
+//!     let mut uploaded = Vec::new();
+//!     for field in Multipart::from_request(&request)? {
+//!         let field = field?;
+//!         if let Some(file_name) = field.file_name() {
+//!             uploaded.push(format!("{} ({} bytes)", file_name, field.data().len()));
+//!         }
+//!     }
+//!     Ok(uploaded.join(", "))
+//! };
+//!
+//! let request = Request::post("/upload")
+//!     .header("content-type", "multipart/form-data; boundary=X")
+//!     .body(
+//!         b"--X\r\n\
+//!           Content-Disposition: form-data; name=\"file\"; filename=\"notes.txt\"\r\n\
+//!           Content-Type: text/plain\r\n\
+//!           \r\n\
+//!           Hello, world!\r\n\
+//!           --X--\r\n"
+//!             .to_vec(),
+//!     )
+//!     .unwrap();
+//! let response = testing::invoke(handler, request).unwrap();
+//! assert_eq!(response.body(), b"notes.txt (13 bytes)");
+//! ```
+
+use crate::extract::{has_content_type, parameter, Rejection};
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::{Request, StatusCode};
+
+/// The fields of a `multipart/form-data` request body, in order.
+///
+/// Fields are parsed as the iterator advances. After a malformed field, the iterator returns the
+/// rejection for it, and then ends.
+#[derive(Debug, Clone)]
+pub struct Multipart<'a> {
+    /// The boundary line that precedes each field, including the leading `\r\n--`.
+    delimiter: Vec<u8>,
+    /// The rest of the body, starting at the next delimiter, or `None` once parsing has ended.
+    remaining: Option<&'a [u8]>,
+}
+
+impl<'a> Multipart<'a> {
+    /// Parse the body of `request` as `multipart/form-data`.
+    ///
+    /// # Errors
+    ///
+    /// Rejects the request with `415 Unsupported Media Type` if its `Content-Type` is not
+    /// `multipart/form-data`, and with `400 Bad Request` if it does not give a boundary, or the
+    /// body does not contain it.
+    pub fn from_request<B: AsRef<[u8]>>(
+        request: &'a Request<B>,
+    ) -> Result<Multipart<'a>, Rejection> {
+        if !has_content_type(request, |media_type| media_type == "multipart/form-data") {
+            return Err(Rejection::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Expected a request body with Content-Type: multipart/form-data",
+            ));
+        }
+        let boundary = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parameter(value, "boundary"))
+            .filter(|boundary| !boundary.is_empty())
+            .ok_or_else(|| {
+                Rejection::new(
+                    StatusCode::BAD_REQUEST,
+                    "The multipart/form-data Content-Type has no boundary",
+                )
+            })?;
+        let delimiter = format!("\r\n--{boundary}").into_bytes();
+
+        // The first boundary has no line break before it if it starts the body, rather than
+        // following a preamble.
+        let body = request.body().as_ref();
+        let start = if body.starts_with(&delimiter[2..]) {
+            Some(0)
+        } else {
+            find(body, &delimiter).map(|start| start + 2)
+        };
+        match start {
+            Some(start) => Ok(Multipart {
+                remaining: Some(&body[start + delimiter.len() - 2..]),
+                delimiter,
+            }),
+            None => Err(malformed("the body does not contain the boundary")),
+        }
+    }
+
+    /// Parse the field at the start of `remaining`, returning it and what follows it.
+    fn parse(&self, remaining: &'a [u8]) -> Result<Option<(Field<'a>, &'a [u8])>, Rejection> {
+        if remaining.starts_with(b"--") {
+            return Ok(None);
+        }
+        // Transport padding may follow the boundary before the line break.
+        let line_end =
+            find(remaining, b"\r\n").ok_or_else(|| malformed("unterminated boundary"))?;
+        if !remaining[..line_end]
+            .iter()
+            .all(|byte| *byte == b' ' || *byte == b'\t')
+        {
+            return Err(malformed("unexpected text after a boundary"));
+        }
+        let remaining = &remaining[line_end + 2..];
+
+        let mut headers = HeaderMap::new();
+        let headers_end = if remaining.starts_with(b"\r\n") {
+            0
+        } else {
+            find(remaining, b"\r\n\r\n").ok_or_else(|| malformed("unterminated field headers"))? + 2
+        };
+        for line in remaining[..headers_end]
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+        {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            let colon = line
+                .iter()
+                .position(|byte| *byte == b':')
+                .ok_or_else(|| malformed("a field header has no colon"))?;
+            let name = HeaderName::from_bytes(&line[..colon])
+                .map_err(|_| malformed("a field header has an invalid name"))?;
+            let value = HeaderValue::from_bytes(trim(&line[colon + 1..]))
+                .map_err(|_| malformed("a field header has an invalid value"))?;
+            headers.append(name, value);
+        }
+        let remaining = &remaining[headers_end + 2..];
+
+        let data_end = find(remaining, &self.delimiter)
+            .ok_or_else(|| malformed("a field is not followed by a boundary"))?;
+        let field = Field {
+            headers,
+            data: &remaining[..data_end],
+        };
+        Ok(Some((field, &remaining[data_end + self.delimiter.len()..])))
+    }
+}
+
+impl<'a> Iterator for Multipart<'a> {
+    type Item = Result<Field<'a>, Rejection>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = self.remaining.take()?;
+        match self.parse(remaining) {
+            Ok(Some((field, remaining))) => {
+                self.remaining = Some(remaining);
+                Some(Ok(field))
+            }
+            Ok(None) => None,
+            Err(rejection) => Some(Err(rejection)),
+        }
+    }
+}
+
+/// A field of a `multipart/form-data` request body.
+#[derive(Debug, Clone)]
+pub struct Field<'a> {
+    headers: HeaderMap,
+    data: &'a [u8],
+}
+
+impl<'a> Field<'a> {
+    /// The name of the field, from its `Content-Disposition` header.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.disposition_parameter("name")
+    }
+
+    /// The name of the uploaded file, from its `Content-Disposition` header, if the field is a
+    /// file.
+    #[must_use]
+    pub fn file_name(&self) -> Option<&str> {
+        self.disposition_parameter("filename")
+    }
+
+    /// The `Content-Type` of the field, if it has one. Fields without one are `text/plain`.
+    #[must_use]
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// All of the field's headers.
+    #[must_use]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The contents of the field.
+    #[must_use]
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The contents of the field as text, or `None` if they are not valid UTF-8.
+    #[must_use]
+    pub fn text(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.data).ok()
+    }
+
+    fn disposition_parameter(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parameter(value, name))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace())
+        .map_or(start, |end| end + 1);
+    &bytes[start..end]
+}
+
+fn malformed(reason: &str) -> Rejection {
+    Rejection::new(
+        StatusCode::BAD_REQUEST,
+        format!("Failed to parse the multipart/form-data body: {reason}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Multipart;
+    use http::{Request, StatusCode};
+
+    fn request(content_type: &str, body: &'static [u8]) -> Request<&'static [u8]> {
+        Request::post("/")
+            .header("content-type", content_type)
+            .body(body)
+            .unwrap()
+    }
+
+    /// The data of each field of `body`, or the message of the rejection parsing it ended with.
+    fn parse(body: &'static [u8]) -> Vec<Result<Vec<u8>, String>> {
+        let request = request("multipart/form-data; boundary=X", body);
+        Multipart::from_request(&request)
+            .unwrap()
+            .map(|field| {
+                field
+                    .map(|field| field.data().to_vec())
+                    .map_err(|rejection| rejection.message().to_owned())
+            })
+            .collect()
+    }
+
+    fn malformed(reason: &str) -> Result<Vec<u8>, String> {
+        Err(format!(
+            "Failed to parse the multipart/form-data body: {reason}"
+        ))
+    }
+
+    #[test]
+    fn fields() {
+        let request = request(
+            "multipart/form-data; boundary=\"X\"",
+            b"--X\r\n\
+              Content-Disposition: form-data; name=\"a\"\r\n\
+              \r\n\
+              one\r\n\
+              --X\r\n\
+              Content-Disposition: form-data; name=\"b\"; filename=\"b.txt\"\r\n\
+              Content-Type: text/plain\r\n\
+              \r\n\
+              two\r\nlines\r\n\
+              --X--\r\n",
+        );
+        let fields = Multipart::from_request(&request)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].name(), Some("a"));
+        assert_eq!(fields[0].file_name(), None);
+        assert_eq!(fields[0].text(), Some("one"));
+        assert_eq!(fields[1].name(), Some("b"));
+        assert_eq!(fields[1].file_name(), Some("b.txt"));
+        assert_eq!(fields[1].content_type(), Some("text/plain"));
+        assert_eq!(fields[1].data(), b"two\r\nlines");
+    }
+
+    #[test]
+    fn preamble_and_epilogue() {
+        assert_eq!(
+            parse(b"ignored --X\r\n--X\r\n\r\none\r\n--X--\r\nignored --X\r\n"),
+            vec![Ok(b"one".to_vec())]
+        );
+    }
+
+    #[test]
+    fn fields_without_headers() {
+        assert_eq!(
+            parse(b"--X\r\n\r\none\r\n--X\r\n\r\n\r\n--X--"),
+            vec![Ok(b"one".to_vec()), Ok(b"".to_vec())]
+        );
+    }
+
+    #[test]
+    fn transport_padding() {
+        assert_eq!(
+            parse(b"--X \t\r\n\r\none\r\n--X--"),
+            vec![Ok(b"one".to_vec())]
+        );
+    }
+
+    #[test]
+    fn no_fields() {
+        assert_eq!(parse(b"--X--\r\n"), vec![]);
+    }
+
+    #[test]
+    fn malformed_fields() {
+        assert_eq!(parse(b"--X"), vec![malformed("unterminated boundary")]);
+        assert_eq!(
+            parse(b"--XY\r\n\r\none\r\n--X--"),
+            vec![malformed("unexpected text after a boundary")]
+        );
+        assert_eq!(
+            parse(b"--X\r\nContent-Type: text/plain\r\none\r\n--X--"),
+            vec![malformed("unterminated field headers")]
+        );
+        assert_eq!(
+            parse(b"--X\r\nno colon\r\n\r\none\r\n--X--"),
+            vec![malformed("a field header has no colon")]
+        );
+        assert_eq!(
+            parse(b"--X\r\nbad name: value\r\n\r\none\r\n--X--"),
+            vec![malformed("a field header has an invalid name")]
+        );
+        assert_eq!(
+            parse(b"--X\r\n\r\none"),
+            vec![malformed("a field is not followed by a boundary")]
+        );
+    }
+
+    #[test]
+    fn parsing_ends_after_a_malformed_field() {
+        assert_eq!(
+            parse(b"--X\r\n\r\none\r\n--X\r\nno colon\r\n\r\ntwo\r\n--X\r\n\r\nthree\r\n--X--"),
+            vec![
+                Ok(b"one".to_vec()),
+                malformed("a field header has no colon")
+            ]
+        );
+    }
+
+    #[test]
+    fn rejected_requests() {
+        let rejection = |request| Multipart::from_request(&request).unwrap_err();
+
+        let json = rejection(request("application/json", b"{}"));
+        assert_eq!(json.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let no_boundary = rejection(request("multipart/form-data", b"--X--"));
+        assert_eq!(no_boundary.status(), StatusCode::BAD_REQUEST);
+        let empty_boundary = rejection(request("multipart/form-data; boundary=", b"--X--"));
+        assert_eq!(empty_boundary.status(), StatusCode::BAD_REQUEST);
+
+        let missing = rejection(request(
+            "multipart/form-data; boundary=X",
+            b"--Y\r\n\r\none\r\n--Y--",
+        ));
+        assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            missing.message(),
+            "Failed to parse the multipart/form-data body: the body does not contain the boundary"
+        );
+        // A boundary is only a delimiter at the start of the body or of a line.
+        let inline = rejection(request(
+            "multipart/form-data; boundary=X",
+            b"text --X\r\n\r\none",
+        ));
+        assert_eq!(inline.status(), StatusCode::BAD_REQUEST);
+    }
+}
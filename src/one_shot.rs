@@ -0,0 +1,44 @@
+//! Invoking the function once with an event from standard input.
+
+use crate::service::Service;
+use crate::{event, lambda, ContextBuilder, Error};
+use futures::TryFutureExt;
+use lambda_http::request::RequestContext;
+use serde_json::json;
+use std::io::{self, Read, Write};
+use std::process;
+
+/// The environment variable that enables one-shot mode, as
+/// [`Builder::one_shot`](../struct.Builder.html#method.one_shot) does.
+pub(crate) const ONE_SHOT_VAR: &str = "LAMBDA_HTTP_LOCAL_ONE_SHOT";
+
+/// Read an event from standard input, invoke the function with it, and write the response to
+/// standard output. If the function fails, the error is written instead and the process exits
+/// with status 1.
+pub(crate) fn start(context: &ContextBuilder, service: &Service) -> Result<(), Error> {
+    let mut input = Vec::new();
+    io::stdin().read_to_end(&mut input).map_err(Error::Event)?;
+    let request = serde_json::from_slice(&input)
+        .and_then(event::from_saved_event)
+        .map_err(|err| Error::Event(err.into()))?;
+    let is_alb = request
+        .extensions()
+        .get::<RequestContext>()
+        .is_some_and(RequestContext::is_alb);
+
+    let mut tokio =
+        tokio::runtime::current_thread::Runtime::new().expect("failed to start Tokio runtime");
+    let result = tokio.block_on(lambda::invoke(service, request, context.build()).compat());
+    let (output, code) = match &result {
+        Ok(response) => (lambda::response_event(is_alb, response), 0),
+        Err(err) => (json!({ "errorMessage": err.message() }), 1),
+    };
+    let mut stdout = io::stdout();
+    writeln!(stdout, "{output}")
+        .and_then(|()| stdout.flush())
+        .map_err(Error::Event)?;
+    if code != 0 {
+        process::exit(code);
+    }
+    Ok(())
+}
@@ -0,0 +1,1311 @@
+//! Validating requests against an OpenAPI document, as API Gateway REST API request validators do.
+//!
+//! Requests are matched to the document's operations by method and path. Those that match none
+//! are passed to the function unchecked. For a matching operation, the request must have the
+//! operation's required query parameters and headers, and its body must have one of the
+//! operation's content types and match the JSON schema given for it.
+//!
+//! Responses can also be checked against the operation's documented responses, to catch
+//! handlers drifting from the document.
+
+#![allow(clippy::doc_markdown)]
+
+use crate::event::media_type_matches;
+use crate::extract::is_json;
+use http::header::{HeaderMap, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use regex_lite::Regex;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::ptr;
+
+/// The operations of an OpenAPI document.
+pub(crate) struct OpenApi {
+    document: Value,
+    /// The compiled `pattern` of each schema in the document, by pattern.
+    patterns: HashMap<String, Regex>,
+    operations: Vec<Operation>,
+    response_validation: Option<ResponseValidation>,
+}
+
+struct Operation {
+    /// The method in lowercase, or `None` for API Gateway's `x-amazon-apigateway-any-method`.
+    method: Option<String>,
+    /// The segments of the path template, such as `orders` and `{id}`.
+    segments: Vec<String>,
+    /// The method and path template, such as `PUT /orders/{id}`.
+    label: String,
+    /// The required query parameters and headers, as `(location, name)`.
+    required: Vec<(String, String)>,
+    body: Option<Body>,
+    responses: Vec<ResponseSpec>,
+}
+
+/// Media types, and the schema for each, if any.
+type Content = Vec<(String, Option<Value>)>;
+
+struct Body {
+    required: bool,
+    content: Content,
+}
+
+/// A documented response of an operation.
+struct ResponseSpec {
+    /// The status code, such as `200`, a range, such as `2XX`, or `default`.
+    status: String,
+    /// The headers the response must have.
+    required_headers: Vec<String>,
+    /// The media types the response can have, if they are documented.
+    content: Option<Content>,
+}
+
+/// What to do with responses that do not match the OpenAPI document set with
+/// [`Builder::openapi`].
+///
+/// [`Builder::openapi`]: struct.Builder.html#method.openapi
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseValidation {
+    /// Log a warning describing the mismatch, and send the response unchanged.
+    Log,
+    /// Log an error describing the mismatch, and send a `500 Internal Server Error` response
+    /// instead.
+    Fail,
+}
+
+/// Why a request does not match the document.
+#[derive(Debug)]
+pub(crate) enum Invalid {
+    MissingParameters(Vec<String>),
+    UnsupportedMediaType(String),
+    Body(String),
+}
+
+/// The HTTP methods an OpenAPI path item can have operations for.
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+impl OpenApi {
+    /// Read the operations of `document`, in either the OpenAPI 3 or Swagger 2 format.
+    pub(crate) fn new(document: Value, response_validation: Option<ResponseValidation>) -> OpenApi {
+        let mut operations = Vec::new();
+        for (path, item) in document["paths"].as_object().into_iter().flatten() {
+            let item = resolve(&document, item);
+            let segments = segments(path).map(str::to_owned).collect::<Vec<_>>();
+            for (key, operation) in item.as_object().into_iter().flatten() {
+                let method = match key.as_str() {
+                    "x-amazon-apigateway-any-method" => None,
+                    method if METHODS.contains(&method) => Some(method.to_owned()),
+                    _ => continue,
+                };
+                let operation = resolve(&document, operation);
+                operations.push(Operation {
+                    label: format!(
+                        "{} {path}",
+                        method.as_deref().unwrap_or("ANY").to_ascii_uppercase()
+                    ),
+                    method,
+                    segments: segments.clone(),
+                    required: required_parameters(&document, item, operation),
+                    body: request_body(&document, operation),
+                    responses: responses(&document, operation),
+                });
+            }
+        }
+        let mut patterns = HashMap::new();
+        compile_patterns(&document, &mut patterns);
+        OpenApi {
+            document,
+            patterns,
+            operations,
+            response_validation,
+        }
+    }
+
+    /// What to do with responses that do not match the document, if they are checked.
+    pub(crate) fn response_validation(&self) -> Option<ResponseValidation> {
+        self.response_validation
+    }
+
+    /// The number of the operation the response to `request`, whose path is `path` without any
+    /// stage, is checked against, if responses are checked and it matches one.
+    pub(crate) fn response_operation<B>(&self, request: &Request<B>, path: &str) -> Option<usize> {
+        self.response_validation?;
+        self.operation(request.method().as_str(), path)
+    }
+
+    /// Check `request`, whose path is `path` without any stage, against the operation it
+    /// matches, if any.
+    pub(crate) fn validate(&self, request: &Request<Vec<u8>>, path: &str) -> Result<(), Invalid> {
+        let Some(operation) = self.operation(request.method().as_str(), path) else {
+            return Ok(());
+        };
+        let operation = &self.operations[operation];
+
+        let query = serde_urlencoded::from_str::<Vec<(String, String)>>(
+            request.uri().query().unwrap_or_default(),
+        )
+        .unwrap_or_default();
+        let missing = operation
+            .required
+            .iter()
+            .filter(|(location, name)| match location.as_str() {
+                "query" => !query
+                    .iter()
+                    .any(|(key, value)| key == name && !value.is_empty()),
+                _ => request
+                    .headers()
+                    .get(name.as_str())
+                    .and_then(|value| value.to_str().ok())
+                    .is_none_or(|value| value.trim().is_empty()),
+            })
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Invalid::MissingParameters(missing));
+        }
+
+        match &operation.body {
+            Some(body) => self.validate_body(body, request),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_body(&self, body: &Body, request: &Request<Vec<u8>>) -> Result<(), Invalid> {
+        if request.body().is_empty() {
+            return if body.required {
+                Err(Invalid::Body("the request body is required".to_owned()))
+            } else {
+                Ok(())
+            };
+        }
+        let media_type = media_type(request.headers());
+        let Some((_, schema)) = body
+            .content
+            .iter()
+            .find(|(accepted, _)| media_type_matches(accepted, &media_type))
+        else {
+            return Err(Invalid::UnsupportedMediaType(media_type));
+        };
+        let Some(schema) = schema.as_ref().filter(|_| is_json(&media_type)) else {
+            return Ok(());
+        };
+        let value = serde_json::from_slice::<Value>(request.body())
+            .map_err(|err| Invalid::Body(format!("the request body is not valid JSON: {err}")))?;
+        self.check(schema, &value).map_err(Invalid::Body)
+    }
+
+    /// The index of the operation for `method` and `path`, whose path is without any stage,
+    /// preferring the path template with the most literal segments, and then one without a greedy
+    /// `{proxy+}` parameter.
+    fn operation(&self, method: &str, path: &str) -> Option<usize> {
+        let method = method.to_ascii_lowercase();
+        let path = segments(path).collect::<Vec<_>>();
+        self.operations
+            .iter()
+            .enumerate()
+            .filter(|(_, operation)| {
+                operation.method.as_ref().is_none_or(|m| *m == method)
+                    && template_matches(&operation.segments, &path)
+            })
+            .max_by_key(|(_, operation)| {
+                let literals = operation
+                    .segments
+                    .iter()
+                    .filter(|segment| !segment.starts_with('{'))
+                    .count();
+                let greedy = operation.segments.iter().any(|s| s.ends_with("+}"));
+                (literals, !greedy, operation.method.is_some())
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// Check `response` against the documented responses of the operation numbered `operation`,
+    /// returning a description of the first mismatch.
+    pub(crate) fn validate_response(
+        &self,
+        operation: usize,
+        response: &Response<Vec<u8>>,
+    ) -> Result<(), String> {
+        let operation = &self.operations[operation];
+        let mismatch = |problem: String| format!("{}: {problem}", operation.label);
+        if operation.responses.is_empty() {
+            return Ok(());
+        }
+        let status = response.status().as_u16().to_string();
+        let range = format!("{}XX", &status[..1]);
+        let documented = |expected: &str| {
+            operation
+                .responses
+                .iter()
+                .find(|spec| spec.status.eq_ignore_ascii_case(expected))
+        };
+        let spec = documented(&status)
+            .or_else(|| documented(&range))
+            .or_else(|| documented("default"))
+            .ok_or_else(|| mismatch(format!("status {status} is not documented")))?;
+
+        for name in &spec.required_headers {
+            if !response.headers().contains_key(name.as_str()) {
+                return Err(mismatch(format!("missing required header {name}")));
+            }
+        }
+        let (Some(content), false) = (&spec.content, response.body().is_empty()) else {
+            return Ok(());
+        };
+        let media_type = media_type(response.headers());
+        let Some((_, schema)) = content
+            .iter()
+            .find(|(documented, _)| media_type_matches(documented, &media_type))
+        else {
+            return Err(mismatch(format!(
+                "Content-Type {media_type} is not documented for status {status}"
+            )));
+        };
+        let Some(schema) = schema.as_ref().filter(|_| is_json(&media_type)) else {
+            return Ok(());
+        };
+        let value = serde_json::from_slice::<Value>(response.body())
+            .map_err(|err| mismatch(format!("the body is not valid JSON: {err}")))?;
+        self.check(schema, &value)
+            .map_err(|reason| mismatch(format!("the body does not match the schema: {reason}")))
+    }
+
+    /// Check `value` against `schema`, returning a description of the first mismatch.
+    fn check(&self, schema: &Value, value: &Value) -> Result<(), String> {
+        Schema {
+            document: &self.document,
+            patterns: &self.patterns,
+            checking: RefCell::default(),
+        }
+        .check(schema, value, "", 0)
+    }
+}
+
+impl Invalid {
+    /// The response API Gateway returns for the request.
+    pub(crate) fn response(&self) -> Response<Vec<u8>> {
+        let (status, error_type, message) = match self {
+            Invalid::MissingParameters(names) => (
+                StatusCode::BAD_REQUEST,
+                "BadRequestException",
+                format!(
+                    "Missing required request parameters: [{}]",
+                    names.join(", ")
+                ),
+            ),
+            Invalid::UnsupportedMediaType(_) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "UnsupportedMediaTypeException",
+                "Unsupported Media Type".to_owned(),
+            ),
+            Invalid::Body(_) => (
+                StatusCode::BAD_REQUEST,
+                "BadRequestException",
+                "Invalid request body".to_owned(),
+            ),
+        };
+        Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "application/json")
+            .header("x-amzn-errortype", error_type)
+            .body(
+                serde_json::json!({ "message": message })
+                    .to_string()
+                    .into_bytes(),
+            )
+            .expect("failed to build response")
+    }
+}
+
+impl Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Invalid::MissingParameters(names) => write!(
+                f,
+                "missing required request parameters: {}",
+                names.join(", ")
+            ),
+            Invalid::UnsupportedMediaType(media_type) => {
+                write!(
+                    f,
+                    "no request body is accepted with Content-Type {media_type}"
+                )
+            }
+            Invalid::Body(reason) => write!(f, "invalid request body: {reason}"),
+        }
+    }
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn template_matches(template: &[String], path: &[&str]) -> bool {
+    match (template.split_first(), path.split_first()) {
+        // A greedy parameter matches the rest of the path, and must be the last segment.
+        (Some((segment, template)), Some(_)) if segment.ends_with("+}") => template.is_empty(),
+        (Some((segment, template)), Some((actual, path))) => {
+            (segment.starts_with('{') || segment == actual) && template_matches(template, path)
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Follow `value` if it is a `$ref` to another part of the document.
+fn resolve<'a>(document: &'a Value, value: &'a Value) -> &'a Value {
+    let mut value = value;
+    // References may refer to other references, but not indefinitely.
+    for _ in 0..16 {
+        match value["$ref"]
+            .as_str()
+            .and_then(|reference| pointer(document, reference))
+        {
+            Some(target) => value = target,
+            None => break,
+        }
+    }
+    value
+}
+
+/// The part of `document` a `$ref` such as `#/components/schemas/Order` refers to.
+fn pointer<'a>(document: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    let pointer = percent_encoding::percent_decode(pointer.as_bytes()).decode_utf8_lossy();
+    document.pointer(&pointer)
+}
+
+/// The query parameters and headers an operation requires, including those of its path.
+fn required_parameters(document: &Value, item: &Value, operation: &Value) -> Vec<(String, String)> {
+    let mut seen = Vec::<(&str, &str)>::new();
+    let mut required = Vec::new();
+    let parameters = operation["parameters"]
+        .as_array()
+        .into_iter()
+        .chain(item["parameters"].as_array())
+        .flatten()
+        .map(|parameter| resolve(document, parameter));
+    for parameter in parameters {
+        let (Some(location), Some(name)) = (parameter["in"].as_str(), parameter["name"].as_str())
+        else {
+            continue;
+        };
+        // Operation parameters override path parameters with the same name and location, whether
+        // or not either is required. Only header names are case-insensitive.
+        let same_name = |n: &str| {
+            if location == "header" {
+                n.eq_ignore_ascii_case(name)
+            } else {
+                n == name
+            }
+        };
+        if seen.iter().any(|(l, n)| *l == location && same_name(n)) {
+            continue;
+        }
+        seen.push((location, name));
+        if matches!(location, "query" | "header") && parameter["required"] == true {
+            required.push((location.to_owned(), name.to_owned()));
+        }
+    }
+    required
+}
+
+/// The request body an operation accepts, from its OpenAPI 3 `requestBody` or Swagger 2 `body`
+/// parameter.
+fn request_body(document: &Value, operation: &Value) -> Option<Body> {
+    if let Some(request_body) = operation.get("requestBody") {
+        let request_body = resolve(document, request_body);
+        return Some(Body {
+            required: request_body["required"] == true,
+            content: content(document, request_body)?,
+        });
+    }
+    let parameter = operation["parameters"]
+        .as_array()?
+        .iter()
+        .map(|parameter| resolve(document, parameter))
+        .find(|parameter| parameter["in"] == "body")?;
+    Some(Body {
+        required: parameter["required"] == true,
+        content: media_types(document, operation, "consumes")
+            .into_iter()
+            .map(|media_type| (media_type, parameter.get("schema").cloned()))
+            .collect(),
+    })
+}
+
+/// The documented responses of an operation.
+fn responses(document: &Value, operation: &Value) -> Vec<ResponseSpec> {
+    let responses = resolve(document, &operation["responses"]);
+    responses
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(status, spec)| {
+            let spec = resolve(document, spec);
+            let required_headers = spec["headers"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(_, header)| resolve(document, header)["required"] == true)
+                .map(|(name, _)| name.clone())
+                .collect();
+            // Swagger 2 responses have one schema for all of the media types the operation
+            // produces.
+            let content = content(document, spec).or_else(|| {
+                spec.get("schema").map(|schema| {
+                    media_types(document, operation, "produces")
+                        .into_iter()
+                        .map(|media_type| (media_type, Some(schema.clone())))
+                        .collect()
+                })
+            });
+            ResponseSpec {
+                status: status.clone(),
+                required_headers,
+                content,
+            }
+        })
+        .collect()
+}
+
+/// The media types of an OpenAPI 3 request body or response, if it has any.
+fn content(document: &Value, value: &Value) -> Option<Content> {
+    let content = value["content"].as_object()?;
+    Some(
+        content
+            .iter()
+            .map(|(media_type, content)| {
+                let schema = resolve(document, content).get("schema").cloned();
+                (media_type.clone(), schema)
+            })
+            .collect(),
+    )
+}
+
+/// The media types a Swagger 2 operation `consumes` or `produces`.
+fn media_types(document: &Value, operation: &Value, key: &str) -> Vec<String> {
+    operation
+        .get(key)
+        .or_else(|| document.get(key))
+        .and_then(Value::as_array)
+        .map_or_else(
+            || vec!["application/json".to_owned()],
+            |media_types| {
+                media_types
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_owned)
+                    .collect()
+            },
+        )
+}
+
+/// The media type of a request or response, in lowercase and without parameters.
+///
+/// API Gateway uses the `application/json` model for bodies without a `Content-Type`.
+fn media_type(headers: &HeaderMap) -> String {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map_or_else(
+            || "application/json".to_owned(),
+            |media_type| media_type.trim().to_ascii_lowercase(),
+        )
+}
+
+/// Compile the `pattern` of every schema in `value`, so that each is compiled once, when the
+/// document is read, rather than for every string checked against it.
+fn compile_patterns(value: &Value, patterns: &mut HashMap<String, Regex>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(pattern)) = object.get("pattern") {
+                if !patterns.contains_key(pattern) {
+                    // Patterns the regex engine does not support are not checked.
+                    if let Ok(regex) = Regex::new(pattern) {
+                        patterns.insert(pattern.clone(), regex);
+                    }
+                }
+            }
+            for value in object.values() {
+                compile_patterns(value, patterns);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                compile_patterns(value, patterns);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A JSON schema validator for the subset of JSON Schema that OpenAPI and API Gateway models use.
+struct Schema<'a> {
+    /// The document `$ref`s are resolved in.
+    document: &'a Value,
+    /// The compiled patterns of the document's schemas.
+    patterns: &'a HashMap<String, Regex>,
+    /// The schemas being checked, and the values they are being checked against.
+    checking: RefCell<Vec<(*const Value, *const Value)>>,
+}
+
+/// How deeply schemas can nest, through references or otherwise.
+const MAX_DEPTH: usize = 64;
+
+impl Schema<'_> {
+    /// Check `value`, found at the JSON pointer `at`, against `schema`.
+    fn check(&self, schema: &Value, value: &Value, at: &str, depth: usize) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Ok(());
+        }
+        let schema = match schema {
+            Value::Bool(false) => return Err(mismatch(at, "no value is allowed")),
+            Value::Object(_) => resolve(self.document, schema),
+            _ => return Ok(()),
+        };
+        // A schema that refers back to itself for the same value, such as through `anyOf`, adds
+        // nothing to the check already in progress.
+        let key = (ptr::from_ref(schema), ptr::from_ref(value));
+        if self.checking.borrow().contains(&key) {
+            return Ok(());
+        }
+        self.checking.borrow_mut().push(key);
+        let result = self.check_resolved(schema, value, at, depth);
+        self.checking.borrow_mut().pop();
+        result
+    }
+
+    fn check_resolved(
+        &self,
+        schema: &Value,
+        value: &Value,
+        at: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        if value.is_null() && schema["nullable"] == true {
+            return Ok(());
+        }
+        check_type(schema, value, at)?;
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return Err(mismatch(at, &format!("expected one of {}", schema["enum"])));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                return Err(mismatch(at, &format!("expected {expected}")));
+            }
+        }
+        match value {
+            Value::String(string) => check_string(schema, string, at, self.patterns)?,
+            Value::Number(_) => check_number(schema, value.as_f64().unwrap_or_default(), at)?,
+            Value::Array(items) => self.check_array(schema, items, at, depth)?,
+            Value::Object(object) => self.check_object(schema, object, at, depth)?,
+            Value::Null | Value::Bool(_) => {}
+        }
+        self.check_combinators(schema, value, at, depth)
+    }
+
+    fn check_array(
+        &self,
+        schema: &Value,
+        items: &[Value],
+        at: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        if let Some(min) = schema["minItems"].as_u64() {
+            if (items.len() as u64) < min {
+                return Err(mismatch(at, &format!("expected at least {min} items")));
+            }
+        }
+        if let Some(max) = schema["maxItems"].as_u64() {
+            if items.len() as u64 > max {
+                return Err(mismatch(at, &format!("expected at most {max} items")));
+            }
+        }
+        if schema["uniqueItems"] == true
+            && items
+                .iter()
+                .enumerate()
+                .any(|(i, item)| items[..i].contains(item))
+        {
+            return Err(mismatch(at, "expected unique items"));
+        }
+        for (i, item) in items.iter().enumerate() {
+            let item_schema = match &schema["items"] {
+                Value::Array(schemas) => schemas.get(i).unwrap_or(&schema["additionalItems"]),
+                item_schema => item_schema,
+            };
+            if !item_schema.is_null() {
+                self.check(item_schema, item, &format!("{at}/{i}"), depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_object(
+        &self,
+        schema: &Value,
+        object: &Map<String, Value>,
+        at: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        for name in schema["required"].as_array().into_iter().flatten() {
+            let Some(name) = name.as_str() else { continue };
+            if !object.contains_key(name) {
+                return Err(mismatch(at, &format!("missing required property {name:?}")));
+            }
+        }
+        if let Some(min) = schema["minProperties"].as_u64() {
+            if (object.len() as u64) < min {
+                return Err(mismatch(at, &format!("expected at least {min} properties")));
+            }
+        }
+        if let Some(max) = schema["maxProperties"].as_u64() {
+            if object.len() as u64 > max {
+                return Err(mismatch(at, &format!("expected at most {max} properties")));
+            }
+        }
+        let properties = schema["properties"].as_object();
+        for (name, value) in object {
+            let at = format!("{at}/{}", name.replace('~', "~0").replace('/', "~1"));
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => self.check(property, value, &at, depth + 1)?,
+                None => match &schema["additionalProperties"] {
+                    Value::Bool(false) => return Err(mismatch(&at, "unexpected property")),
+                    additional @ Value::Object(_) => {
+                        self.check(additional, value, &at, depth + 1)?;
+                    }
+                    _ => {}
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn check_combinators(
+        &self,
+        schema: &Value,
+        value: &Value,
+        at: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        for sub in schema["allOf"].as_array().into_iter().flatten() {
+            self.check(sub, value, at, depth + 1)?;
+        }
+        let matching = |key: &str| {
+            schema[key].as_array().map(|schemas| {
+                schemas
+                    .iter()
+                    .filter(|sub| self.check(sub, value, at, depth + 1).is_ok())
+                    .count()
+            })
+        };
+        if matching("anyOf") == Some(0) {
+            return Err(mismatch(
+                at,
+                "expected a value matching any of the anyOf schemas",
+            ));
+        }
+        if matching("oneOf").is_some_and(|count| count != 1) {
+            return Err(mismatch(
+                at,
+                "expected a value matching exactly one of the oneOf schemas",
+            ));
+        }
+        if let Some(not) = schema.get("not") {
+            if self.check(not, value, at, depth + 1).is_ok() {
+                return Err(mismatch(at, "expected a value not matching the not schema"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_type(schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    let types = match &schema["type"] {
+        Value::String(ty) => vec![ty.as_str()],
+        Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+        _ => return Ok(()),
+    };
+    let actual = type_name(value);
+    let matches = types.iter().any(|&ty| {
+        ty == actual
+            || (ty == "number" && actual == "integer")
+            || (ty == "integer" && value.as_f64().is_some_and(|n| n.fract() == 0.0))
+    });
+    if matches {
+        Ok(())
+    } else {
+        Err(mismatch(
+            at,
+            &format!("expected {}, found {actual}", types.join(" or ")),
+        ))
+    }
+}
+
+fn check_string(
+    schema: &Value,
+    string: &str,
+    at: &str,
+    patterns: &HashMap<String, Regex>,
+) -> Result<(), String> {
+    let length = string.chars().count() as u64;
+    if let Some(min) = schema["minLength"].as_u64() {
+        if length < min {
+            return Err(mismatch(at, &format!("expected at least {min} characters")));
+        }
+    }
+    if let Some(max) = schema["maxLength"].as_u64() {
+        if length > max {
+            return Err(mismatch(at, &format!("expected at most {max} characters")));
+        }
+    }
+    if let Some(pattern) = schema["pattern"].as_str() {
+        // Patterns the regex engine does not support were not compiled, and are not checked.
+        if patterns
+            .get(pattern)
+            .is_some_and(|regex| !regex.is_match(string))
+        {
+            return Err(mismatch(
+                at,
+                &format!("expected a string matching {pattern:?}"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_number(schema: &Value, number: f64, at: &str) -> Result<(), String> {
+    // JSON Schema draft 4, which API Gateway models use, has boolean exclusive bounds, and later
+    // drafts have numeric ones.
+    let bound = |key: &str, exclusive: &str| match (schema[key].as_f64(), &schema[exclusive]) {
+        (_, Value::Number(bound)) => bound.as_f64().map(|bound| (bound, true)),
+        (Some(bound), exclusive) => Some((bound, *exclusive == true)),
+        (None, _) => None,
+    };
+    if let Some((min, exclusive)) = bound("minimum", "exclusiveMinimum") {
+        let below = if exclusive {
+            number <= min
+        } else {
+            number < min
+        };
+        if below {
+            let relation = if exclusive {
+                "greater than"
+            } else {
+                "at least"
+            };
+            return Err(mismatch(at, &format!("expected a number {relation} {min}")));
+        }
+    }
+    if let Some((max, exclusive)) = bound("maximum", "exclusiveMaximum") {
+        let above = if exclusive {
+            number >= max
+        } else {
+            number > max
+        };
+        if above {
+            let relation = if exclusive { "less than" } else { "at most" };
+            return Err(mismatch(at, &format!("expected a number {relation} {max}")));
+        }
+    }
+    if let Some(divisor) = schema["multipleOf"]
+        .as_f64()
+        .filter(|divisor| *divisor > 0.0)
+    {
+        let quotient = number / divisor;
+        if (quotient - quotient.round()).abs() > 1e-9 {
+            return Err(mismatch(at, &format!("expected a multiple of {divisor}")));
+        }
+    }
+    Ok(())
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Describe a mismatch at the JSON pointer `at`.
+fn mismatch(at: &str, problem: &str) -> String {
+    if at.is_empty() {
+        problem.to_owned()
+    } else {
+        format!("{at}: {problem}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OpenApi, ResponseValidation};
+    use http::{Request, Response};
+    use serde_json::{json, Value};
+
+    fn api(paths: &Value) -> OpenApi {
+        OpenApi::new(json!({ "openapi": "3.0.0", "paths": paths }), None)
+    }
+
+    fn validate(api: &OpenApi, request: &Request<Vec<u8>>) -> Result<(), String> {
+        let path = request.uri().path().to_owned();
+        api.validate(request, &path).map_err(|err| err.to_string())
+    }
+
+    fn get(uri: &str) -> Request<Vec<u8>> {
+        Request::get(uri).body(Vec::new()).unwrap()
+    }
+
+    fn post(content_type: &str, body: &str) -> Request<Vec<u8>> {
+        Request::post("/orders")
+            .header("content-type", content_type)
+            .body(body.as_bytes().to_vec())
+            .unwrap()
+    }
+
+    /// The label of the operation `method` and `path` match, if any.
+    fn operation<'a>(api: &'a OpenApi, method: &str, path: &str) -> Option<&'a str> {
+        api.operation(method, path)
+            .map(|operation| api.operations[operation].label.as_str())
+    }
+
+    /// Check `value` against `schema` in a document with the `schemas` components.
+    fn check(schemas: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+        let document = json!({ "components": { "schemas": schemas }, "x-schema": schema });
+        let api = OpenApi::new(document, None);
+        api.check(&api.document["x-schema"], value)
+    }
+
+    #[test]
+    fn operations() {
+        let api = api(&json!({
+            "/orders": { "get": {}, "post": {} },
+            "/orders/{id}": { "get": {} },
+            "/orders/new": { "get": {} },
+            "/files/{path+}": { "x-amazon-apigateway-any-method": {} },
+            "/files/{path+}/raw": { "get": {} },
+            "/{proxy+}": { "get": {} },
+        }));
+        assert_eq!(operation(&api, "POST", "/orders"), Some("POST /orders"));
+        assert_eq!(
+            operation(&api, "GET", "/orders/1"),
+            Some("GET /orders/{id}")
+        );
+        assert_eq!(
+            operation(&api, "GET", "/orders/new"),
+            Some("GET /orders/new")
+        );
+        assert_eq!(
+            operation(&api, "PUT", "/files/a/b"),
+            Some("ANY /files/{path+}")
+        );
+        assert_eq!(
+            operation(&api, "GET", "/files/a/raw"),
+            Some("ANY /files/{path+}")
+        );
+        assert_eq!(
+            operation(&api, "GET", "/orders/1/items"),
+            Some("GET /{proxy+}")
+        );
+        assert_eq!(operation(&api, "PUT", "/orders"), None);
+        assert_eq!(operation(&api, "PUT", "/files"), None);
+        assert_eq!(operation(&api, "GET", "/"), None);
+    }
+
+    #[test]
+    fn unmatched_requests_are_not_checked() {
+        let api = api(&json!({
+            "/orders": { "get": { "parameters": [{ "in": "query", "name": "id", "required": true }] } },
+        }));
+        assert_eq!(validate(&api, &get("/other")), Ok(()));
+        assert_eq!(validate(&api, &get("/orders/1")), Ok(()));
+    }
+
+    #[test]
+    fn required_parameters() {
+        let api = api(&json!({
+            "/orders": {
+                "parameters": [
+                    { "in": "header", "name": "X-Tenant", "required": true },
+                    { "in": "query", "name": "limit", "required": true },
+                ],
+                "get": {
+                    "parameters": [
+                        { "$ref": "#/paths/~1orders/x-parameters/status" },
+                        // Overrides the required path parameter.
+                        { "in": "query", "name": "limit" },
+                    ]
+                },
+                "x-parameters": { "status": { "in": "query", "name": "status", "required": true } },
+            },
+        }));
+        let missing = |uri: &str, tenant: Option<&str>| {
+            let mut request = Request::get(uri);
+            if let Some(tenant) = tenant {
+                request.header("x-tenant", tenant);
+            }
+            validate(&api, &request.body(Vec::new()).unwrap())
+        };
+        assert_eq!(missing("/orders?status=open", Some("a")), Ok(()));
+        assert_eq!(
+            missing("/orders", Some("a")),
+            Err("missing required request parameters: status".to_owned())
+        );
+        assert_eq!(
+            missing("/orders?status=", None),
+            Err("missing required request parameters: status, X-Tenant".to_owned())
+        );
+        assert_eq!(
+            missing("/orders?status=open", Some(" ")),
+            Err("missing required request parameters: X-Tenant".to_owned())
+        );
+    }
+
+    #[test]
+    fn parameter_overrides() {
+        let api = api(&json!({
+            "/orders": {
+                "parameters": [
+                    { "in": "header", "name": "X-Tenant", "required": true },
+                    { "in": "query", "name": "Limit", "required": true },
+                ],
+                "get": {
+                    "parameters": [
+                        // Overrides the header, whose name is case-insensitive.
+                        { "in": "header", "name": "x-tenant" },
+                        // Does not override the query parameter, whose name is case-sensitive.
+                        { "in": "query", "name": "limit" },
+                    ]
+                },
+            },
+        }));
+        assert_eq!(validate(&api, &get("/orders?Limit=1")), Ok(()));
+        assert_eq!(
+            validate(&api, &get("/orders?limit=1")),
+            Err("missing required request parameters: Limit".to_owned())
+        );
+    }
+
+    #[test]
+    fn request_bodies() {
+        let api = api(&json!({
+            "/orders": {
+                "post": {
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": { "schema": { "$ref": "#/components/schemas/Order" } },
+                            "text/*": { "schema": { "type": "integer" } },
+                        },
+                    },
+                },
+            },
+        }));
+        let api = OpenApi::new(
+            json!({
+                "paths": api.document["paths"],
+                "components": { "schemas": { "Order": { "type": "object", "required": ["id"] } } },
+            }),
+            None,
+        );
+        assert_eq!(
+            validate(&api, &post("application/json", r#"{"id":1}"#)),
+            Ok(())
+        );
+        assert_eq!(
+            validate(
+                &api,
+                &post("Application/JSON; charset=utf-8", r#"{"id":1}"#)
+            ),
+            Ok(())
+        );
+        // The schema is only checked for JSON bodies.
+        assert_eq!(validate(&api, &post("text/plain", "not a number")), Ok(()));
+        assert_eq!(
+            validate(&api, &post("application/json", "")),
+            Err("invalid request body: the request body is required".to_owned())
+        );
+        assert_eq!(
+            validate(&api, &post("application/json", "{}")),
+            Err("invalid request body: missing required property \"id\"".to_owned())
+        );
+        assert!(validate(&api, &post("application/json", "{"))
+            .unwrap_err()
+            .starts_with("invalid request body: the request body is not valid JSON"));
+        assert_eq!(
+            validate(&api, &post("application/xml", "<order/>")),
+            Err("no request body is accepted with Content-Type application/xml".to_owned())
+        );
+        let untyped = Request::post("/orders").body(b"[]".to_vec()).unwrap();
+        assert_eq!(
+            validate(&api, &untyped),
+            Err("invalid request body: expected object, found array".to_owned())
+        );
+    }
+
+    #[test]
+    fn swagger_body_parameters() {
+        let api = OpenApi::new(
+            json!({
+                "swagger": "2.0",
+                "consumes": ["application/json"],
+                "paths": {
+                    "/orders": {
+                        "post": {
+                            "parameters": [{
+                                "in": "body",
+                                "name": "order",
+                                "schema": { "type": "object", "additionalProperties": false },
+                            }],
+                        },
+                    },
+                },
+            }),
+            None,
+        );
+        assert_eq!(validate(&api, &post("application/json", "{}")), Ok(()));
+        assert_eq!(validate(&api, &post("application/json", "")), Ok(()));
+        assert_eq!(
+            validate(&api, &post("application/json", r#"{"a":1}"#)),
+            Err("invalid request body: /a: unexpected property".to_owned())
+        );
+        assert!(validate(&api, &post("text/plain", "{}")).is_err());
+    }
+
+    #[test]
+    fn types() {
+        let check = |schema, value| check(&json!({}), &schema, &value);
+        assert_eq!(check(json!({ "type": "integer" }), json!(1.0)), Ok(()));
+        assert_eq!(check(json!({ "type": "number" }), json!(1)), Ok(()));
+        assert_eq!(
+            check(json!({ "type": ["string", "null"] }), json!(null)),
+            Ok(())
+        );
+        assert_eq!(
+            check(json!({ "type": "string", "nullable": true }), json!(null)),
+            Ok(())
+        );
+        assert_eq!(
+            check(json!({ "type": "integer" }), json!(1.5)),
+            Err("expected integer, found number".to_owned())
+        );
+        assert_eq!(
+            check(json!({ "type": "string" }), json!(null)),
+            Err("expected string, found null".to_owned())
+        );
+        assert_eq!(
+            check(json!({ "enum": ["a", "b"] }), json!("c")),
+            Err(r#"expected one of ["a","b"]"#.to_owned())
+        );
+        assert_eq!(
+            check(json!({ "const": 1 }), json!(2)),
+            Err("expected 1".to_owned())
+        );
+        assert_eq!(
+            check(json!(false), json!(1)),
+            Err("no value is allowed".to_owned())
+        );
+    }
+
+    #[test]
+    fn strings_and_numbers() {
+        let check = |schema, value| check(&json!({}), &schema, &value);
+        let string = json!({ "minLength": 2, "maxLength": 3, "pattern": "^[a-z]+$" });
+        assert_eq!(check(string.clone(), json!("ab")), Ok(()));
+        assert!(check(string.clone(), json!("a")).is_err());
+        assert!(check(string.clone(), json!("abcd")).is_err());
+        assert!(check(string, json!("AB")).is_err());
+        // Patterns the regex engine can not compile are not checked.
+        assert_eq!(check(json!({ "pattern": "(" }), json!("a")), Ok(()));
+
+        let draft4 = json!({ "minimum": 0, "exclusiveMinimum": true, "maximum": 10 });
+        assert_eq!(check(draft4.clone(), json!(10)), Ok(()));
+        assert_eq!(
+            check(draft4.clone(), json!(0)),
+            Err("expected a number greater than 0".to_owned())
+        );
+        assert_eq!(
+            check(draft4, json!(11)),
+            Err("expected a number at most 10".to_owned())
+        );
+        let later = json!({ "exclusiveMaximum": 10 });
+        assert_eq!(
+            check(later, json!(10)),
+            Err("expected a number less than 10".to_owned())
+        );
+        assert_eq!(check(json!({ "multipleOf": 0.1 }), json!(0.3)), Ok(()));
+        assert!(check(json!({ "multipleOf": 2 }), json!(3)).is_err());
+    }
+
+    #[test]
+    fn arrays_and_objects() {
+        let check = |schema, value| check(&json!({}), &schema, &value);
+        let array = json!({ "items": { "type": "integer" }, "minItems": 1, "uniqueItems": true });
+        assert_eq!(check(array.clone(), json!([1, 2])), Ok(()));
+        assert!(check(array.clone(), json!([])).is_err());
+        assert_eq!(
+            check(array.clone(), json!([1, 1])),
+            Err("expected unique items".to_owned())
+        );
+        assert_eq!(
+            check(array, json!([1, "a"])),
+            Err("/1: expected integer, found string".to_owned())
+        );
+        let tuple = json!({ "items": [{ "type": "string" }], "additionalItems": false });
+        assert_eq!(check(tuple.clone(), json!(["a"])), Ok(()));
+        assert_eq!(
+            check(tuple, json!(["a", 1])),
+            Err("/1: no value is allowed".to_owned())
+        );
+
+        let object = json!({
+            "properties": { "a/b": { "type": "string" } },
+            "additionalProperties": { "type": "integer" },
+            "maxProperties": 2,
+        });
+        assert_eq!(check(object.clone(), json!({ "a/b": "x", "c": 1 })), Ok(()));
+        assert_eq!(
+            check(object.clone(), json!({ "a/b": 1 })),
+            Err("/a~1b: expected string, found integer".to_owned())
+        );
+        assert_eq!(
+            check(object.clone(), json!({ "c": "x" })),
+            Err("/c: expected integer, found string".to_owned())
+        );
+        assert!(check(object, json!({ "a/b": "x", "c": 1, "d": 2 })).is_err());
+    }
+
+    #[test]
+    fn combinators() {
+        let check = |schema, value| check(&json!({}), &schema, &value);
+        let any_of = json!({ "anyOf": [{ "type": "string" }, { "type": "integer" }] });
+        assert_eq!(check(any_of.clone(), json!(1)), Ok(()));
+        assert_eq!(
+            check(any_of, json!(null)),
+            Err("expected a value matching any of the anyOf schemas".to_owned())
+        );
+        let one_of = json!({ "oneOf": [{ "type": "number" }, { "type": "integer" }] });
+        assert_eq!(check(one_of.clone(), json!(1.5)), Ok(()));
+        assert_eq!(
+            check(one_of.clone(), json!(1)),
+            Err("expected a value matching exactly one of the oneOf schemas".to_owned())
+        );
+        assert!(check(one_of, json!("a")).is_err());
+        let all_of = json!({ "allOf": [{ "required": ["a"] }, { "required": ["b"] }] });
+        assert_eq!(check(all_of.clone(), json!({ "a": 1, "b": 2 })), Ok(()));
+        assert!(check(all_of, json!({ "a": 1 })).is_err());
+        assert!(check(json!({ "not": { "type": "null" } }), json!(null)).is_err());
+    }
+
+    #[test]
+    fn references() {
+        let schemas = json!({
+            "Tree": {
+                "type": "object",
+                "properties": {
+                    "value": { "type": "integer" },
+                    "children": { "type": "array", "items": { "$ref": "#/components/schemas/Tree" } },
+                },
+            },
+            "Loop": { "$ref": "#/components/schemas/Loop" },
+            "AnyLoop": { "anyOf": [{ "$ref": "#/components/schemas/AnyLoop" }, { "type": "string" }] },
+            "OneLoop": {
+                "oneOf": [
+                    { "$ref": "#/components/schemas/OneLoop" },
+                    { "$ref": "#/components/schemas/OneLoop" },
+                ],
+            },
+        });
+        let check = |name: &str, value| {
+            let reference = json!({ "$ref": format!("#/components/schemas/{name}") });
+            check(&schemas, &reference, &value)
+        };
+        let tree =
+            json!({ "value": 1, "children": [{ "value": 2, "children": [{ "value": 3 }] }] });
+        assert_eq!(check("Tree", tree), Ok(()));
+        assert_eq!(
+            check(
+                "Tree",
+                json!({ "children": [{ "children": [{ "value": "a" }] }] })
+            ),
+            Err("/children/0/children/0/value: expected integer, found string".to_owned())
+        );
+        assert_eq!(check("Missing", json!(1)), Ok(()));
+        // References back to the same schema for the same value end, rather than recursing.
+        assert_eq!(check("Loop", json!(1)), Ok(()));
+        assert_eq!(check("AnyLoop", json!(1)), Ok(()));
+        assert!(check("OneLoop", json!(1)).is_err());
+    }
+
+    #[test]
+    fn responses() {
+        let api = OpenApi::new(
+            json!({
+                "paths": {
+                    "/orders": {
+                        "get": {
+                            "responses": {
+                                "200": {
+                                    "headers": { "x-request-id": { "required": true } },
+                                    "content": {
+                                        "application/json": { "schema": { "type": "array" } },
+                                    },
+                                },
+                                "4XX": { "description": "client error" },
+                            },
+                        },
+                    },
+                },
+            }),
+            Some(ResponseValidation::Fail),
+        );
+        let operation = api.response_operation(&get("/orders"), "/orders").unwrap();
+        let check = |status: u16, headers: &[(&str, &str)], body: &str| {
+            let mut response = Response::builder();
+            response.status(status);
+            for (name, value) in headers {
+                response.header(*name, *value);
+            }
+            let response = response.body(body.as_bytes().to_vec()).unwrap();
+            api.validate_response(operation, &response)
+        };
+        let request_id = ("x-request-id", "1");
+        assert_eq!(check(200, &[request_id], "[]"), Ok(()));
+        assert_eq!(check(404, &[], "anything"), Ok(()));
+        assert_eq!(
+            check(500, &[], ""),
+            Err("GET /orders: status 500 is not documented".to_owned())
+        );
+        assert_eq!(
+            check(200, &[], "[]"),
+            Err("GET /orders: missing required header x-request-id".to_owned())
+        );
+        assert_eq!(
+            check(200, &[request_id, ("content-type", "text/html")], "<p>"),
+            Err("GET /orders: Content-Type text/html is not documented for status 200".to_owned())
+        );
+        assert_eq!(
+            check(200, &[request_id], "{}"),
+            Err(
+                "GET /orders: the body does not match the schema: expected array, found object"
+                    .to_owned()
+            )
+        );
+        assert!(api.response_operation(&get("/other"), "/other").is_none());
+        let unchecked = OpenApi::new(api.document.clone(), None);
+        assert!(unchecked
+            .response_operation(&get("/orders"), "/orders")
+            .is_none());
+    }
+}
@@ -0,0 +1,272 @@
+#[cfg(feature = "local")]
+use http::header::{HeaderValue, CONTENT_TYPE};
+#[cfg(feature = "local")]
+use http::{Response, StatusCode};
+use std::time::Duration;
+
+/// The largest payload a synchronous Lambda invocation accepts or returns, in bytes.
+#[cfg(feature = "local")]
+pub(crate) const INVOCATION_PAYLOAD_LIMIT: usize = 6_291_556;
+
+/// The AWS service in front of the function that the local development server emulates.
+///
+/// Each service converts HTTP requests into Lambda events, and Lambda responses back into HTTP
+/// responses, a little differently: how repeated headers and query parameters are passed, which
+/// headers are added to responses, what clients receive when the function fails or times out, and
+/// so on. Choosing the service your function is deployed behind makes the local development server
+/// behave the same way.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use http::Response;
+/// use lambda_http_local::{Profile, Runtime};
+///
+/// Runtime::builder()
+///     .profile(Profile::Alb)
+///     .build()
+///     .run(|request| Response::new("Hello, world!"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Profile {
+    /// An API Gateway REST API with a Lambda proxy integration. This is the default.
+    #[default]
+    RestApi,
+    /// An API Gateway HTTP API using payload format version 2.0, the default for new
+    /// integrations.
+    ///
+    /// Requests are delivered to the handler as the equivalent 1.0 event, the version
+    /// `lambda_http` understands: repeated headers and query parameters are combined into a
+    /// single comma-separated value.
+    HttpApi,
+    /// An Application Load Balancer target group, without multi-value headers enabled.
+    ///
+    /// Only the last value of repeated request headers and query parameters is passed to the
+    /// function, and only the first value of repeated response headers is returned.
+    Alb,
+    /// An Application Load Balancer target group with multi-value headers enabled.
+    ///
+    /// All values of repeated request headers and query parameters are passed to the function,
+    /// and all values of repeated response headers are returned.
+    AlbMultiValueHeaders,
+    /// A Lambda function URL.
+    ///
+    /// Function URLs send events in the 2.0 payload format. Requests are delivered to the handler
+    /// as the equivalent 1.0 event, which has the same semantics: repeated headers and query
+    /// parameters are combined into a single comma-separated value.
+    FunctionUrl,
+}
+
+impl Profile {
+    pub(crate) fn is_alb(self) -> bool {
+        matches!(self, Profile::Alb | Profile::AlbMultiValueHeaders)
+    }
+
+    /// The time after which the service gives up waiting for the function, if any.
+    pub(crate) fn integration_timeout(self) -> Option<Duration> {
+        match self {
+            Profile::RestApi => Some(Duration::from_secs(29)),
+            Profile::HttpApi => Some(Duration::from_secs(30)),
+            Profile::Alb | Profile::AlbMultiValueHeaders | Profile::FunctionUrl => None,
+        }
+    }
+
+    /// The largest request body the service accepts, in bytes.
+    #[cfg(feature = "local")]
+    pub(crate) fn request_limit(self) -> usize {
+        match self {
+            Profile::RestApi | Profile::HttpApi => 10 * 1024 * 1024,
+            // ALB limits requests to and responses from Lambda targets to 1 MB.
+            Profile::Alb | Profile::AlbMultiValueHeaders => 1024 * 1024,
+            Profile::FunctionUrl => INVOCATION_PAYLOAD_LIMIT,
+        }
+    }
+
+    /// The largest response payload the function can return, in bytes.
+    #[cfg(feature = "local")]
+    pub(crate) fn response_limit(self) -> usize {
+        if self.is_alb() {
+            1024 * 1024
+        } else {
+            INVOCATION_PAYLOAD_LIMIT
+        }
+    }
+
+    /// Add the headers the service adds to responses from the function.
+    #[cfg(feature = "local")]
+    pub(crate) fn finish_response(self, response: &mut Response<Vec<u8>>, request_id: &str) {
+        let headers = response.headers_mut();
+        let request_id = HeaderValue::from_str(request_id).expect("invalid request ID");
+        match self {
+            Profile::RestApi | Profile::FunctionUrl => {
+                headers.insert("x-amzn-requestid", request_id);
+            }
+            Profile::HttpApi => {
+                headers.insert("apigw-requestid", request_id);
+            }
+            Profile::AlbMultiValueHeaders => {}
+            Profile::Alb => {
+                // Without multi-value headers, only one value of each header is passed on.
+                let names = headers.keys().cloned().collect::<Vec<_>>();
+                for name in names {
+                    let first = headers[&name].clone();
+                    headers.insert(name, first);
+                }
+            }
+        }
+        if !self.is_alb() && !headers.contains_key(CONTENT_TYPE) {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        }
+    }
+
+    /// The response clients receive when the function fails or times out.
+    #[cfg(feature = "local")]
+    pub(crate) fn function_error(self) -> Response<Vec<u8>> {
+        match self {
+            Profile::RestApi => error_response(
+                StatusCode::BAD_GATEWAY,
+                "application/json",
+                r#"{"message": "Internal server error"}"#,
+            ),
+            Profile::HttpApi => error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "application/json",
+                r#"{"message":"Internal Server Error"}"#,
+            ),
+            Profile::Alb | Profile::AlbMultiValueHeaders => error_response(
+                StatusCode::BAD_GATEWAY,
+                "text/html",
+                "<html>\r\n<head><title>502 Bad Gateway</title></head>\r\n<body>\r\n\
+                 <center><h1>502 Bad Gateway</h1></center>\r\n</body>\r\n</html>\r\n",
+            ),
+            Profile::FunctionUrl => error_response(
+                StatusCode::BAD_GATEWAY,
+                "text/plain",
+                "Internal Server Error",
+            ),
+        }
+    }
+
+    /// The response clients receive for a path outside the API's stage.
+    #[cfg(feature = "local")]
+    pub(crate) fn missing_route(self) -> Response<Vec<u8>> {
+        match self {
+            Profile::HttpApi => error_response(
+                StatusCode::NOT_FOUND,
+                "application/json",
+                r#"{"message":"Not Found"}"#,
+            ),
+            _ => error_response(
+                StatusCode::FORBIDDEN,
+                "application/json",
+                r#"{"message":"Missing Authentication Token"}"#,
+            ),
+        }
+    }
+
+    /// The response clients receive when the request is too large.
+    #[cfg(feature = "local")]
+    pub(crate) fn payload_too_large(self) -> Response<Vec<u8>> {
+        match self {
+            Profile::RestApi | Profile::HttpApi => error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "application/json",
+                r#"{"message":"Request Too Long"}"#,
+            ),
+            Profile::Alb | Profile::AlbMultiValueHeaders => error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "text/html",
+                "<html>\r\n<head><title>413 Request Entity Too Large</title></head>\r\n<body>\r\n\
+                 <center><h1>413 Request Entity Too Large</h1></center>\r\n</body>\r\n</html>\r\n",
+            ),
+            Profile::FunctionUrl => error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "text/plain",
+                "Request Entity Too Large",
+            ),
+        }
+    }
+
+    /// The response clients receive when AWS Lambda fails to invoke the function.
+    #[cfg(feature = "local")]
+    pub(crate) fn service_error(self) -> Response<Vec<u8>> {
+        match self {
+            Profile::RestApi => error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "application/json",
+                r#"{"message": "Internal server error"}"#,
+            ),
+            Profile::HttpApi => error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "application/json",
+                r#"{"message":"Internal Server Error"}"#,
+            ),
+            Profile::Alb | Profile::AlbMultiValueHeaders => error_response(
+                StatusCode::BAD_GATEWAY,
+                "text/html",
+                "<html>\r\n<head><title>502 Bad Gateway</title></head>\r\n<body>\r\n\
+                 <center><h1>502 Bad Gateway</h1></center>\r\n</body>\r\n</html>\r\n",
+            ),
+            Profile::FunctionUrl => error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "text/plain",
+                "Internal Server Error",
+            ),
+        }
+    }
+
+    /// The response clients receive when the function is throttled.
+    #[cfg(feature = "local")]
+    pub(crate) fn throttled(self) -> Response<Vec<u8>> {
+        let mut response = match self {
+            Profile::RestApi | Profile::HttpApi => error_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                "application/json",
+                r#"{"message":"Too Many Requests"}"#,
+            ),
+            Profile::Alb | Profile::AlbMultiValueHeaders => error_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                "text/html",
+                "<html>\r\n<head><title>429 Too Many Requests</title></head>\r\n<body>\r\n\
+                 <center><h1>429 Too Many Requests</h1></center>\r\n</body>\r\n</html>\r\n",
+            ),
+            Profile::FunctionUrl => error_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                "application/json",
+                r#"{"Message":"Rate Exceeded."}"#,
+            ),
+        };
+        response.headers_mut().insert(
+            "x-amzn-errortype",
+            HeaderValue::from_static("TooManyRequestsException"),
+        );
+        response
+    }
+
+    /// The response clients receive when the integration timeout elapses.
+    #[cfg(feature = "local")]
+    pub(crate) fn integration_timed_out(self) -> Response<Vec<u8>> {
+        match self {
+            Profile::HttpApi => error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "application/json",
+                r#"{"message":"Service Unavailable"}"#,
+            ),
+            _ => error_response(
+                StatusCode::GATEWAY_TIMEOUT,
+                "application/json",
+                r#"{"message": "Endpoint request timed out"}"#,
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "local")]
+fn error_response(status: StatusCode, content_type: &'static str, body: &str) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(body.as_bytes().to_vec())
+        .expect("failed to build response")
+}
@@ -0,0 +1,32 @@
+//! Recording invocations of the local development server to disk.
+
+use crate::{lambda, InvocationError};
+use chrono::Utc;
+use http::Response;
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Write an invocation to a new file in `dir`, named for the time and the request ID so that
+/// the files sort in the order the invocations completed.
+pub(crate) fn write(
+    dir: &Path,
+    request_id: &str,
+    is_alb: bool,
+    event: &Value,
+    result: Result<&Response<Vec<u8>>, &InvocationError>,
+) -> io::Result<()> {
+    let mut record = json!({ "event": event });
+    match result {
+        Ok(response) => record["response"] = lambda::response_event(is_alb, response),
+        Err(err) => record["error"] = json!({ "errorMessage": err.message() }),
+    }
+    let name = format!(
+        "{}-{}.json",
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        request_id
+    );
+    let contents = serde_json::to_vec_pretty(&record).map_err(io::Error::from)?;
+    fs::write(dir.join(name), contents)
+}
@@ -0,0 +1,96 @@
+//! The platform log lines AWS Lambda writes for each invocation.
+
+use lambda_runtime::Context;
+use std::convert::TryFrom;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// An invocation in progress, for writing its `START`, `END`, and `REPORT` lines.
+#[derive(Clone)]
+pub(crate) struct Report {
+    request_id: String,
+    version: String,
+    memory_size: i32,
+    start: Instant,
+    /// How long the execution environment took to start, for cold starts.
+    init_duration: Option<Duration>,
+}
+
+impl Report {
+    pub(crate) fn start(context: &Context, init_duration: Option<Duration>) -> Report {
+        Report {
+            request_id: context.aws_request_id.clone(),
+            version: context.function_version.clone(),
+            memory_size: context.memory_limit_in_mb,
+            start: Instant::now(),
+            init_duration,
+        }
+    }
+
+    pub(crate) fn start_line(&self) -> String {
+        format!(
+            "START RequestId: {} Version: {}",
+            self.request_id, self.version
+        )
+    }
+
+    pub(crate) fn end_line(&self) -> String {
+        format!("END RequestId: {}", self.request_id)
+    }
+
+    /// The `REPORT` line for the invocation completing now. Like AWS Lambda, fields are separated
+    /// by tabs, the billed duration is rounded up to the millisecond, and the init duration is only
+    /// included for cold starts.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub(crate) fn report_line(&self, timed_out: bool, max_memory_used: Option<u64>) -> String {
+        let duration = self.start.elapsed().as_secs_f64() * 1000.0;
+        let max_memory_used = max_memory_used.map_or_else(String::new, |max_memory_used| {
+            format!("Max Memory Used: {max_memory_used} MB\t")
+        });
+        let init_duration = self
+            .init_duration
+            .map_or_else(String::new, |init_duration| {
+                format!(
+                    "Init Duration: {:.2} ms\t",
+                    init_duration.as_secs_f64() * 1000.0
+                )
+            });
+        format!(
+            "REPORT RequestId: {}\tDuration: {:.2} ms\tBilled Duration: {} ms\tMemory Size: {} MB\t{}{}{}",
+            self.request_id,
+            duration,
+            duration.ceil() as u64,
+            self.memory_size,
+            max_memory_used,
+            init_duration,
+            if timed_out { "Status: timeout\t" } else { "" }
+        )
+    }
+
+    /// Whether the process has used more memory than the function is configured with, in which
+    /// case AWS Lambda would have stopped it.
+    pub(crate) fn exceeds_memory_size(&self, max_memory_used: u64) -> bool {
+        u64::try_from(self.memory_size).is_ok_and(|size| size < max_memory_used)
+    }
+
+    pub(crate) fn memory_size(&self) -> i32 {
+        self.memory_size
+    }
+}
+
+/// The peak resident memory of the process so far, in megabytes, rounded up.
+///
+/// This is read from `/proc/self/status`, so it is only available on Linux, and includes the
+/// memory used by the local development server as well as the handler.
+pub(crate) fn max_memory_used() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kilobytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes.div_ceil(1024))
+}
@@ -0,0 +1,133 @@
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::{Response, StatusCode};
+use std::error::Error;
+use std::fmt::{self, Display};
+
+/// A value that can be returned from a handler.
+///
+/// This is implemented for:
+///
+/// * `Response<B>` with any body that converts into bytes, such as `Vec<u8>`, `String`, and
+///   `&'static str`.
+/// * `String` and `&'static str`, which are sent as `text/plain` with a `200 OK` status, and
+///   `Vec<u8>`, which is sent as `application/octet-stream`.
+/// * `StatusCode`, which is sent with an empty body.
+/// * `(StatusCode, R)`, which sends `R` with a different status.
+/// * [`Json<T>`], which is serialized as `application/json`.
+/// * `Result<R, E>` where `E` implements [`Display`], which allows handlers to use the `?`
+///   operator, and `Result<R, Rejection>`, which responds with the [`Rejection`].
+///
+/// ```rust,no_run
+/// use http::{Request, StatusCode};
+///
+/// lambda_http_local::run(
+///     |request: Request<&[u8]>| match request.uri().path() {
+///         "/" => (StatusCode::OK, "Hello, world!"),
+///         _ => (StatusCode::NOT_FOUND, "Not found"),
+///     },
+///     "localhost:3000",
+/// );
+/// ```
+///
+/// When a handler returns an error in AWS Lambda, the invocation is reported as a function error
+/// to the Runtime API. When running locally, a `500 Internal Server Error` response is sent with
+/// the error text as the body.
+///
+/// [`Display`]: https://doc.rust-lang.org/std/fmt/trait.Display.html
+/// [`Json<T>`]: struct.Json.html
+/// [`Rejection`]: struct.Rejection.html
+pub trait IntoResponse {
+    /// Convert this value into a response, or the error the invocation failed with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the handler failed to produce a response.
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError>;
+}
+
+impl<B: Into<Vec<u8>>> IntoResponse for Response<B> {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        Ok(self.map(Into::into))
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        Ok(with_content_type(
+            "text/plain; charset=utf-8",
+            self.into_bytes(),
+        ))
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        Ok(with_content_type("text/plain; charset=utf-8", self.into()))
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        Ok(with_content_type("application/octet-stream", self))
+    }
+}
+
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        let mut response = Response::new(Vec::new());
+        *response.status_mut() = self;
+        Ok(response)
+    }
+}
+
+impl<R: IntoResponse> IntoResponse for (StatusCode, R) {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        let (status, response) = self;
+        let mut response = response.into_response()?;
+        *response.status_mut() = status;
+        Ok(response)
+    }
+}
+
+impl<R: IntoResponse, E: Display> IntoResponse for Result<R, E> {
+    fn into_response(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        self.map_err(InvocationError::new)?.into_response()
+    }
+}
+
+fn with_content_type(content_type: &'static str, body: Vec<u8>) -> Response<Vec<u8>> {
+    let mut response = Response::new(body);
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response
+}
+
+/// The error a handler invocation failed with.
+#[derive(Debug, Clone)]
+pub struct InvocationError {
+    message: String,
+}
+
+impl InvocationError {
+    /// Create an error from any displayable value.
+    pub fn new<E: Display>(err: E) -> InvocationError {
+        InvocationError {
+            message: err.to_string(),
+        }
+    }
+
+    /// The error text.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for InvocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for InvocationError {}
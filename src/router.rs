@@ -0,0 +1,440 @@
+use crate::ext::PathParameters;
+use crate::service::{BoxFuture, Service};
+use crate::IntoResponse;
+use futures::future::{self, FutureExt};
+use http::header::CONTENT_TYPE;
+use http::{Method, Request, Response, StatusCode, Uri};
+use percent_encoding::percent_decode;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Dispatches requests to one of several handlers by method and path.
+///
+/// A router lets a single function serve several routes of an API. Routes are matched in the
+/// order they were added, against the path of the request URI, which for REST APIs served under a
+/// stage does not include the stage. Requests that match no route are passed to the
+/// [`fallback`](#method.fallback) handler, or receive a `404 Not Found` response if there is none.
+///
+/// Paths are templates like those of API Gateway resources: a segment like `{id}` matches any
+/// single path segment, and a final segment like `{proxy+}` matches the rest of the path. The
+/// decoded values are available from
+/// [`RequestExt::path_parameters`](trait.RequestExt.html#tymethod.path_parameters), replacing
+/// any path parameters from API Gateway. Since the first matching route is used, add routes with
+/// fixed segments before templates that would also match them.
+///
+/// Run a router with [`Runtime::run_router`](struct.Runtime.html#method.run_router); routing
+/// works the same way in AWS Lambda and locally.
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Method, Request, Response};
+/// use lambda_http_local::{testing, Router};
+///
+/// let router = Router::new()
+///     .route(Method::GET, "/users", |_| Response::new("all users"))
+///     .route(Method::POST, "/orders", |request| Response::new(request.body().to_vec()));
+///
+/// let request = Request::get("/users").body(Vec::new()).unwrap();
+/// let response = testing::invoke_router(router, request).unwrap();
+/// assert_eq!(response.body(), b"all users");
+/// ```
+///
+/// Path parameters:
+///
+/// ```rust
+/// # use http::{Method, Request, Response};
+/// use lambda_http_local::{testing, RequestExt, Router};
+///
+/// let router = Router::new().route(Method::GET, "/users/{id}/files/{path+}", |request| {
+///     let parameters = request.path_parameters();
+///     let id = parameters.get("id").unwrap();
+///     let path = parameters.get("path").unwrap();
+///     Response::new(format!("{} {}", id, path))
+/// });
+///
+/// let request = Request::get("/users/a%20b/files/c/d.txt").body(Vec::new()).unwrap();
+/// let response = testing::invoke_router(router, request).unwrap();
+/// assert_eq!(response.body(), b"a b c/d.txt");
+/// ```
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    mounts: Vec<Mount>,
+    fallback: Option<Service>,
+}
+
+#[derive(Clone)]
+struct Route {
+    method: Method,
+    path: String,
+    service: Service,
+}
+
+#[derive(Clone)]
+struct Mount {
+    prefix: String,
+    service: Service,
+}
+
+impl Router {
+    /// Create a router without any routes.
+    #[must_use]
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Add a route calling `handler` for requests with `method` to `path`.
+    #[must_use]
+    pub fn route<F, R>(self, method: Method, path: &str, handler: F) -> Router
+    where
+        F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add(method, path, Service::from_sync(handler))
+    }
+
+    /// Add a route calling an asynchronous `handler` for requests with `method` to `path`.
+    #[must_use]
+    pub fn route_async<F, R>(self, method: Method, path: &str, handler: F) -> Router
+    where
+        F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+        R: Future + Send + 'static,
+        R::Output: IntoResponse,
+    {
+        self.add(method, path, Service::from_async(handler))
+    }
+
+    /// Pass requests under `prefix` to `handler`, with the prefix removed from the path.
+    ///
+    /// This is intended for serving several functions from one local development server, such
+    /// as a development binary that mounts the handler of each function in a repository under
+    /// the base path it is deployed at, like an API Gateway custom domain's API mappings. Each
+    /// function is still deployed with its own `run` call. Mounts match requests with any
+    /// method, and are checked after routes.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use http::{Request, Response};
+    /// use lambda_http_local::{testing, Router};
+    ///
+    /// let auth = |request: Request<&[u8]>| {
+    ///     Response::new(format!("auth: {}", request.uri().path()))
+    /// };
+    /// let api = |request: Request<&[u8]>| {
+    ///     Response::new(format!("api: {}", request.uri().path()))
+    /// };
+    /// let router = Router::new().mount("/auth", auth).mount("/api", api);
+    ///
+    /// let request = Request::get("/api/users").body(Vec::new()).unwrap();
+    /// let response = testing::invoke_router(router, request).unwrap();
+    /// assert_eq!(response.body(), b"api: /users");
+    /// ```
+    #[must_use]
+    pub fn mount<F, R>(self, prefix: &str, handler: F) -> Router
+    where
+        F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_mount(prefix, Service::from_sync(handler))
+    }
+
+    /// Pass requests under `prefix` to an asynchronous `handler`, with the prefix removed from
+    /// the path.
+    ///
+    /// See [`mount`](#method.mount) for details.
+    #[must_use]
+    pub fn mount_async<F, R>(self, prefix: &str, handler: F) -> Router
+    where
+        F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+        R: Future + Send + 'static,
+        R::Output: IntoResponse,
+    {
+        self.add_mount(prefix, Service::from_async(handler))
+    }
+
+    /// Pass requests under `prefix` to `router`, with the prefix removed from the path.
+    ///
+    /// See [`mount`](#method.mount) for details.
+    #[must_use]
+    pub fn mount_router(self, prefix: &str, router: Router) -> Router {
+        self.add_mount(prefix, router.into_service())
+    }
+
+    /// Call `handler` for requests that match no route.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use http::{Method, Request, Response, StatusCode, Uri};
+    /// use lambda_http_local::{testing, Router};
+    ///
+    /// let router = Router::new()
+    ///     .route(Method::GET, "/users", |_| Response::new("all users"))
+    ///     .fallback(|_| {
+    ///         Response::builder()
+    ///             .status(StatusCode::NOT_FOUND)
+    ///             .header("content-type", "application/json")
+    ///             .body(br#"{"error":"not found"}"#.to_vec())
+    ///             .unwrap()
+    ///     });
+    ///
+    /// let request = Request::get("/orders").body(Vec::new()).unwrap();
+    /// let response = testing::invoke_router(router, request).unwrap();
+    /// assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// assert_eq!(response.body(), br#"{"error":"not found"}"#);
+    /// ```
+    #[must_use]
+    pub fn fallback<F, R>(mut self, handler: F) -> Router
+    where
+        F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.fallback = Some(Service::from_sync(handler));
+        self
+    }
+
+    /// Call an asynchronous `handler` for requests that match no route.
+    #[must_use]
+    pub fn fallback_async<F, R>(mut self, handler: F) -> Router
+    where
+        F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+        R: Future + Send + 'static,
+        R::Output: IntoResponse,
+    {
+        self.fallback = Some(Service::from_async(handler));
+        self
+    }
+
+    fn add(mut self, method: Method, path: &str, service: Service) -> Router {
+        self.routes.push(Route {
+            method,
+            path: path.to_owned(),
+            service,
+        });
+        self
+    }
+
+    fn add_mount(mut self, prefix: &str, service: Service) -> Router {
+        self.mounts.push(Mount {
+            prefix: prefix.trim_end_matches('/').to_owned(),
+            service,
+        });
+        self
+    }
+
+    /// Convert the router into a single service dispatching to its routes.
+    pub(crate) fn into_service(self) -> Service {
+        // A router with any blocking routes is called as a blocking handler, so those routes do
+        // not stall the server when running locally.
+        let blocking = self
+            .routes
+            .iter()
+            .map(|route| &route.service)
+            .chain(self.mounts.iter().map(|mount| &mount.service))
+            .chain(&self.fallback)
+            .any(Service::is_blocking);
+        let router = Arc::new(self);
+        let options_routes = router.clone();
+        Service::new(move |request| router.dispatch(request), blocking)
+            .with_options_routes(move |uri| options_routes.routes_options(uri))
+    }
+
+    /// Whether a request to `uri` with the `OPTIONS` method would be passed to a route for that
+    /// method, here or in a mounted router, rather than a mount for any method or the fallback.
+    fn routes_options(&self, uri: &Uri) -> bool {
+        let routed = self.routes.iter().any(|route| {
+            route.method == Method::OPTIONS && matches(&route.path, uri.path()).is_some()
+        });
+        if routed {
+            return true;
+        }
+        let Some((mount, uri)) = self
+            .mounts
+            .iter()
+            .find_map(|mount| Some((mount, strip_prefix(uri, &mount.prefix)?)))
+        else {
+            return false;
+        };
+        mount
+            .service
+            .options_routes()
+            .is_some_and(|routes| routes(&uri))
+    }
+
+    fn dispatch(&self, mut request: Request<Vec<u8>>) -> BoxFuture {
+        if let Some((route, parameters)) = self.find(&request) {
+            request
+                .extensions_mut()
+                .insert(PathParameters(parameters.into()));
+            return route.service.call(request);
+        }
+        for mount in &self.mounts {
+            if let Some(uri) = strip_prefix(request.uri(), &mount.prefix) {
+                *request.uri_mut() = uri;
+                return mount.service.call(request);
+            }
+        }
+        match &self.fallback {
+            Some(fallback) => fallback.call(request),
+            None => future::ready(Ok(not_found())).boxed(),
+        }
+    }
+
+    fn find<B>(&self, request: &Request<B>) -> Option<(&Route, HashMap<String, Vec<String>>)> {
+        self.routes
+            .iter()
+            .filter(|route| route.method == request.method())
+            .find_map(|route| Some((route, matches(&route.path, request.uri().path())?)))
+    }
+}
+
+/// Match `path` against a route template, returning the path parameters it captures.
+fn matches(template: &str, path: &str) -> Option<HashMap<String, Vec<String>>> {
+    let mut parameters = HashMap::new();
+    let mut path = path.trim_start_matches('/');
+    let mut segments = template.trim_start_matches('/').split('/').peekable();
+    while let Some(segment) = segments.next() {
+        let name = segment
+            .strip_prefix('{')
+            .and_then(|segment| segment.strip_suffix('}'));
+        if let Some(name) = name.and_then(|name| name.strip_suffix('+')) {
+            // A greedy parameter matches the rest of the path, and must be the last segment.
+            if segments.peek().is_some() || path.is_empty() {
+                return None;
+            }
+            parameters.insert(name.to_owned(), vec![decode(path)]);
+            return Some(parameters);
+        }
+
+        let (value, rest) = path.split_once('/').unwrap_or((path, ""));
+        match name {
+            Some(_) if value.is_empty() => return None,
+            Some(name) => {
+                parameters.insert(name.to_owned(), vec![decode(value)]);
+            }
+            None if value == segment => {}
+            None => return None,
+        }
+        path = rest;
+    }
+    if path.is_empty() {
+        Some(parameters)
+    } else {
+        None
+    }
+}
+
+/// Remove `prefix` from the path of `uri`, if the path is under it.
+fn strip_prefix(uri: &Uri, prefix: &str) -> Option<Uri> {
+    let path = uri.path().strip_prefix(prefix)?;
+    if !path.is_empty() && !path.starts_with('/') {
+        return None;
+    }
+    let path = if path.is_empty() { "/" } else { path };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_owned(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+fn decode(value: &str) -> String {
+    percent_decode(value.as_bytes())
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+fn not_found() -> Response<Vec<u8>> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(b"Not Found".to_vec())
+        .expect("failed to build response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, strip_prefix};
+    use http::Uri;
+    use std::collections::HashMap;
+
+    fn parameters(pairs: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        pairs
+            .iter()
+            .map(|(name, value)| ((*name).to_owned(), vec![(*value).to_owned()]))
+            .collect()
+    }
+
+    #[test]
+    fn fixed_segments() {
+        assert_eq!(matches("/users", "/users"), Some(parameters(&[])));
+        assert_eq!(matches("/users", "/users/"), Some(parameters(&[])));
+        assert_eq!(matches("/", "/"), Some(parameters(&[])));
+        assert_eq!(matches("/users", "/user"), None);
+        assert_eq!(matches("/users", "/users/1"), None);
+        assert_eq!(matches("/users/1", "/users"), None);
+        assert_eq!(matches("/", "/users"), None);
+    }
+
+    #[test]
+    fn parameters_match_one_segment() {
+        assert_eq!(
+            matches("/users/{id}/files/{name}", "/users/a%20b/files/c.txt"),
+            Some(parameters(&[("id", "a b"), ("name", "c.txt")]))
+        );
+        assert_eq!(matches("/users/{id}", "/users/1/files"), None);
+        assert_eq!(matches("/users/{id}/files", "/users//files"), None);
+        assert_eq!(matches("/users/{id}", "/users/"), None);
+        assert_eq!(matches("/{id}", "/"), None);
+    }
+
+    #[test]
+    fn greedy_parameters() {
+        assert_eq!(
+            matches("/files/{path+}", "/files/a/b%2Fc/d.txt"),
+            Some(parameters(&[("path", "a/b/c/d.txt")]))
+        );
+        assert_eq!(
+            matches("/{proxy+}", "/a/b"),
+            Some(parameters(&[("proxy", "a/b")]))
+        );
+        assert_eq!(matches("/files/{path+}", "/files"), None);
+        assert_eq!(matches("/files/{path+}", "/files/"), None);
+        assert_eq!(matches("/files/{path+}", "/other/a"), None);
+    }
+
+    #[test]
+    fn greedy_parameters_must_be_last() {
+        assert_eq!(matches("/files/{path+}/raw", "/files/a/raw"), None);
+        assert_eq!(matches("/{proxy+}/{id}", "/a/b"), None);
+    }
+
+    fn strip(uri: &str, prefix: &str) -> Option<String> {
+        strip_prefix(&uri.parse::<Uri>().unwrap(), prefix).map(|uri| uri.to_string())
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(
+            strip("/api/users?id=1", "/api"),
+            Some("/users?id=1".to_owned())
+        );
+        assert_eq!(strip("/api", "/api"), Some("/".to_owned()));
+        assert_eq!(strip("/api?id=1", "/api"), Some("/?id=1".to_owned()));
+        assert_eq!(strip("/api/", "/api"), Some("/".to_owned()));
+        assert_eq!(strip("/users", ""), Some("/users".to_owned()));
+    }
+
+    #[test]
+    fn prefixes_match_whole_segments() {
+        assert_eq!(strip("/apix", "/api"), None);
+        assert_eq!(strip("/apix/users", "/api"), None);
+        assert_eq!(strip("/ap", "/api"), None);
+        assert_eq!(strip("/other/api", "/api"), None);
+    }
+}
@@ -0,0 +1,2137 @@
+use crate::access_log::AccessLogFormat;
+use crate::authorizer::{CognitoClaims, LambdaAuthorizer};
+use crate::cors;
+use crate::middleware::{self, ErrorHandler, Middleware};
+use crate::service::Service;
+#[cfg(feature = "openapi")]
+use crate::ResponseValidation;
+use crate::{
+    Authorization, ContextBuilder, Cors, Error, Handler, IntoResponse, InvocationError,
+    JwtAuthorizer, Next, Profile, Router,
+};
+use futures::future::FutureExt;
+use http::{Method, Request, Response};
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use std::future::Future;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Decides from the method and path of a request whether a service error may be injected for it.
+pub(crate) type ErrorMatcher = Arc<dyn Fn(&Method, &str) -> bool + Send + Sync>;
+
+/// A configured runtime for an HTTP handler.
+///
+/// A `Runtime` is created with [`Runtime::builder`], which allows setting options for the local
+/// development server before calling [`run`] or [`run_async`] with a handler.
+///
+/// [`Runtime::builder`]: #method.builder
+/// [`run`]: #method.run
+/// [`run_async`]: #method.run_async
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use http::Response;
+/// use lambda_http_local::Runtime;
+///
+/// Runtime::builder()
+///     .listen_addr("localhost:8080")
+///     .body_limit(1024 * 1024)
+///     .build()
+///     .run(|request| Response::new("Hello, world!"));
+/// ```
+#[cfg_attr(not(feature = "local"), allow(dead_code))]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Runtime {
+    pub(crate) listen_addr: io::Result<Vec<SocketAddr>>,
+    pub(crate) port_retries: u16,
+    #[cfg(unix)]
+    pub(crate) reuse_port: bool,
+    #[cfg(unix)]
+    pub(crate) unix_socket: Option<PathBuf>,
+    #[cfg(feature = "listenfd")]
+    pub(crate) socket_activation: bool,
+    pub(crate) body_limit: Option<usize>,
+    pub(crate) max_concurrent_requests: Option<usize>,
+    pub(crate) reserved_concurrency: Option<usize>,
+    pub(crate) injected_latency: Option<(f64, RangeInclusive<Duration>)>,
+    pub(crate) dropped_connection_rate: f64,
+    pub(crate) truncated_response_rate: f64,
+    pub(crate) injected_errors: Option<(f64, ErrorMatcher)>,
+    pub(crate) simulation_seed: Option<u64>,
+    pub(crate) sequential: bool,
+    pub(crate) max_instances: Option<usize>,
+    pub(crate) cold_start_delay: Option<Duration>,
+    pub(crate) instance_idle_timeout: Option<Duration>,
+    pub(crate) provisioned_concurrency: usize,
+    pub(crate) buffered_body_limit: Option<usize>,
+    pub(crate) quiet: bool,
+    pub(crate) access_log: bool,
+    pub(crate) access_log_format: AccessLogFormat,
+    pub(crate) platform_log: bool,
+    pub(crate) shutdown_timeout: Duration,
+    pub(crate) keep_alive_timeout: Option<Duration>,
+    pub(crate) request_read_timeout: Option<Duration>,
+    pub(crate) on_bind: Option<Box<dyn FnOnce(SocketAddr) + Send>>,
+    pub(crate) context: ContextBuilder,
+    pub(crate) enforce_timeout: bool,
+    pub(crate) integration_timeout: Option<Duration>,
+    pub(crate) profile: Profile,
+    pub(crate) stage: Option<String>,
+    pub(crate) binary_media_types: Vec<String>,
+    pub(crate) stage_variables: Map<String, Value>,
+    pub(crate) enforce_payload_limits: bool,
+    pub(crate) jwt_authorizer: Option<JwtAuthorizer>,
+    pub(crate) lambda_authorizer: Option<LambdaAuthorizer>,
+    pub(crate) api_keys: Vec<String>,
+    pub(crate) api_key_quota: Option<u64>,
+    #[cfg(feature = "openapi")]
+    pub(crate) openapi: Option<serde_json::Value>,
+    #[cfg(feature = "openapi")]
+    pub(crate) response_validation: Option<ResponseValidation>,
+    pub(crate) cognito_claims: CognitoClaims,
+    pub(crate) xray_daemon: Option<SocketAddr>,
+    pub(crate) metrics_endpoint: bool,
+    pub(crate) health_endpoint: bool,
+    pub(crate) invocation_history: usize,
+    pub(crate) event_stream: bool,
+    pub(crate) record_dir: Option<PathBuf>,
+    pub(crate) har_path: Option<PathBuf>,
+    pub(crate) one_shot: bool,
+    #[cfg(feature = "dashboard")]
+    pub(crate) dashboard: bool,
+    #[cfg(feature = "tls")]
+    pub(crate) https: bool,
+    #[cfg(feature = "tls")]
+    pub(crate) tls_certificate: Option<(PathBuf, PathBuf)>,
+    #[cfg(feature = "tls")]
+    pub(crate) tls_client_ca: Option<PathBuf>,
+    #[cfg(feature = "http3")]
+    pub(crate) http3: bool,
+    /// The URL to mirror requests to, or the invalid URL given for it.
+    #[cfg(feature = "shadow")]
+    pub(crate) shadow: Option<Result<http::Uri, String>>,
+    #[cfg(feature = "shadow")]
+    pub(crate) shadow_compare: bool,
+    #[cfg(feature = "shadow")]
+    pub(crate) shadow_ignore_headers: Vec<String>,
+    #[cfg(feature = "shadow")]
+    pub(crate) shadow_ignore_fields: Vec<String>,
+    pub(crate) middleware: Vec<Middleware>,
+    pub(crate) error_handler: Option<ErrorHandler>,
+    pub(crate) cors: Option<Arc<Cors>>,
+}
+
+impl Runtime {
+    /// Create a builder for configuring a `Runtime`.
+    #[must_use]
+    pub fn builder() -> Builder {
+        Builder {
+            listen_addr: None,
+            port_retries: 0,
+            #[cfg(unix)]
+            reuse_port: false,
+            #[cfg(unix)]
+            unix_socket: None,
+            #[cfg(feature = "listenfd")]
+            socket_activation: false,
+            body_limit: None,
+            max_concurrent_requests: None,
+            reserved_concurrency: None,
+            injected_latency: None,
+            dropped_connection_rate: 0.0,
+            truncated_response_rate: 0.0,
+            injected_errors: None,
+            simulation_seed: None,
+            sequential: false,
+            max_instances: None,
+            cold_start_delay: None,
+            instance_idle_timeout: None,
+            provisioned_concurrency: 0,
+            buffered_body_limit: None,
+            quiet: false,
+            access_log: false,
+            platform_log: false,
+            access_log_format: AccessLogFormat::default(),
+            shutdown_timeout: Duration::from_secs(5),
+            keep_alive_timeout: None,
+            request_read_timeout: None,
+            on_bind: None,
+            context: ContextBuilder::new(),
+            enforce_timeout: true,
+            integration_timeout: None,
+            profile: Profile::default(),
+            stage: None,
+            binary_media_types: Vec::new(),
+            stage_variables: Map::new(),
+            enforce_payload_limits: true,
+            jwt_authorizer: None,
+            lambda_authorizer: None,
+            api_keys: Vec::new(),
+            api_key_quota: None,
+            #[cfg(feature = "openapi")]
+            openapi: None,
+            #[cfg(feature = "openapi")]
+            response_validation: None,
+            cognito_claims: CognitoClaims::default(),
+            xray_daemon: None,
+            metrics_endpoint: false,
+            health_endpoint: false,
+            invocation_history: 0,
+            event_stream: false,
+            record_dir: None,
+            har_path: None,
+            one_shot: false,
+            #[cfg(feature = "dashboard")]
+            dashboard: false,
+            #[cfg(feature = "tls")]
+            https: false,
+            #[cfg(feature = "tls")]
+            tls_certificate: None,
+            #[cfg(feature = "tls")]
+            tls_client_ca: None,
+            #[cfg(feature = "http3")]
+            http3: false,
+            #[cfg(feature = "shadow")]
+            shadow: None,
+            #[cfg(feature = "shadow")]
+            shadow_compare: false,
+            #[cfg(feature = "shadow")]
+            shadow_ignore_headers: Vec::new(),
+            #[cfg(feature = "shadow")]
+            shadow_ignore_fields: Vec::new(),
+            middleware: Vec::new(),
+            error_handler: None,
+            cors: None,
+        }
+    }
+
+    /// Run a handler with this configuration.
+    ///
+    /// See [`lambda_http_local::run`](fn.run.html) for details.
+    ///
+    /// # Panics
+    ///
+    /// If the local development server fails to start, this function will panic. Use
+    /// [`try_run`](#method.try_run) to handle these errors instead.
+    pub fn run<F, R>(self, handler: F)
+    where
+        F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.try_run(handler)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run an asynchronous handler with this configuration.
+    ///
+    /// See [`lambda_http_local::run_async`](fn.run_async.html) for details.
+    ///
+    /// # Panics
+    ///
+    /// If the local development server fails to start, this function will panic. Use
+    /// [`try_run_async`](#method.try_run_async) to handle these errors instead.
+    pub fn run_async<F, R>(self, handler: F)
+    where
+        F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+        R: Future + Send + 'static,
+        R::Output: IntoResponse,
+    {
+        self.try_run_async(handler)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a handler with this configuration, returning an error if the local development server
+    /// fails.
+    ///
+    /// See [`lambda_http_local::try_run`](fn.try_run.html) for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listen address cannot be resolved or bound, or if the server fails
+    /// while running.
+    pub fn try_run<F, R>(self, handler: F) -> Result<(), Error>
+    where
+        F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.start(Service::from_sync(handler))
+    }
+
+    /// Run an asynchronous handler with this configuration, returning an error if the local
+    /// development server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    pub fn try_run_async<F, R>(self, handler: F) -> Result<(), Error>
+    where
+        F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+        R: Future + Send + 'static,
+        R::Output: IntoResponse,
+    {
+        self.start(Service::from_async(handler))
+    }
+
+    /// Run a handler with any of the signatures [`Handler`](trait.Handler.html) is implemented
+    /// for with this configuration, such as one taking the Lambda `Context` as an argument.
+    ///
+    /// # Panics
+    ///
+    /// See [`run`](#method.run).
+    pub fn run_handler<H, Signature>(self, handler: H)
+    where
+        H: Handler<Signature>,
+    {
+        self.try_run_handler(handler)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a handler with any of the signatures [`Handler`](trait.Handler.html) is implemented
+    /// for with this configuration, returning an error if the local development server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    pub fn try_run_handler<H, Signature>(self, handler: H) -> Result<(), Error>
+    where
+        H: Handler<Signature>,
+    {
+        self.start(handler.into_service().0)
+    }
+
+    /// Run a handler created by `new_handler` for each execution environment, with this
+    /// configuration.
+    ///
+    /// In AWS Lambda, each execution environment is a separate process, so `new_handler` is
+    /// called once. The local development server simulates execution environments as
+    /// [`Builder::max_instances`](struct.Builder.html#method.max_instances) describes, calling
+    /// `new_handler` for each one it starts, so that state the handler keeps between invocations
+    /// behaves as it will when deployed.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use http::{Request, Response};
+    /// use lambda_http_local::Runtime;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// Runtime::builder().max_instances(4).build().run_per_instance(|| {
+    ///     // Each execution environment counts its own invocations.
+    ///     let invocations = AtomicUsize::new(0);
+    ///     move |_: Request<&[u8]>| {
+    ///         let count = invocations.fetch_add(1, Ordering::SeqCst) + 1;
+    ///         Response::new(format!("invocation {} of this environment", count))
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// See [`run`](#method.run).
+    pub fn run_per_instance<F, H, R>(self, new_handler: F)
+    where
+        F: Fn() -> H + Send + Sync + 'static,
+        H: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.try_run_per_instance(new_handler)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a handler created for each execution environment with this configuration, returning
+    /// an error if the local development server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    pub fn try_run_per_instance<F, H, R>(self, new_handler: F) -> Result<(), Error>
+    where
+        F: Fn() -> H + Send + Sync + 'static,
+        H: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.start(Service::per_instance(move || {
+            Ok(Service::from_sync(new_handler()))
+        }))
+    }
+
+    /// Run a handler that mutates its own state with this configuration.
+    ///
+    /// The handler is called for one request at a time, which AWS Lambda does anyway, and which
+    /// the local development server does by invoking the function
+    /// [sequentially](struct.Builder.html#method.sequential), so simple in-memory servers can keep
+    /// counters, caches, or mock stores without a `Mutex`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use http::{Request, Response};
+    /// use lambda_http_local::Runtime;
+    ///
+    /// let mut count = 0;
+    /// Runtime::builder().build().run_mut(move |_: Request<&[u8]>| {
+    ///     count += 1;
+    ///     Response::new(format!("request {}", count))
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// See [`run`](#method.run).
+    pub fn run_mut<F, R>(self, handler: F)
+    where
+        F: FnMut(Request<&[u8]>) -> R + Send + 'static,
+        R: IntoResponse,
+    {
+        self.try_run_mut(handler)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a handler that mutates its own state with this configuration, returning an error if
+    /// the local development server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    pub fn try_run_mut<F, R>(mut self, handler: F) -> Result<(), Error>
+    where
+        F: FnMut(Request<&[u8]>) -> R + Send + 'static,
+        R: IntoResponse,
+    {
+        self.sequential = true;
+        // Invocations are sequential, so the lock is never contended.
+        let handler = Mutex::new(handler);
+        self.try_run(move |request| {
+            let mut handler = handler.lock().unwrap_or_else(PoisonError::into_inner);
+            (*handler)(request)
+        })
+    }
+
+    /// Run a handler with this configuration, passing it `state` with each request.
+    ///
+    /// The state is shared by all invocations, and by all the execution environments the local
+    /// development server simulates, so it needs no `Arc` of its own. To mutate it, use types
+    /// that can be shared between threads, such as atomics or a `Mutex`. For state created
+    /// separately for each execution environment, use
+    /// [`run_with_init`](#method.run_with_init) instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use http::{Request, Response};
+    /// use lambda_http_local::Runtime;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut greetings = HashMap::new();
+    /// greetings.insert("/en", "Hello");
+    /// greetings.insert("/fr", "Bonjour");
+    ///
+    /// Runtime::builder().build().run_with_state(
+    ///     greetings,
+    ///     |greetings: &HashMap<&str, &str>, request: Request<&[u8]>| {
+    ///         let greeting = greetings.get(request.uri().path()).unwrap_or(&"Hi");
+    ///         Response::new(greeting.to_string())
+    ///     },
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// See [`run`](#method.run).
+    pub fn run_with_state<S, F, R>(self, state: S, handler: F)
+    where
+        S: Send + Sync + 'static,
+        F: Fn(&S, Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.try_run_with_state(state, handler)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a handler with this configuration, passing it `state` with each request, returning an
+    /// error if the local development server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    pub fn try_run_with_state<S, F, R>(self, state: S, handler: F) -> Result<(), Error>
+    where
+        S: Send + Sync + 'static,
+        F: Fn(&S, Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.try_run(move |request| handler(&state, request))
+    }
+
+    /// Run a handler with this configuration, initializing it with `init`, which runs once per
+    /// execution environment before its first invocation.
+    ///
+    /// `init` is for expensive setup, such as loading configuration or creating AWS clients; the
+    /// state it returns is passed to `handler` with each request. `init` runs before the handler
+    /// receives any requests, and if it fails, the error is reported as an init error to the
+    /// Runtime API in AWS Lambda, and returned by [`try_run_with_init`](#method.try_run_with_init)
+    /// locally. The local development server runs `init` once it is listening, so that its
+    /// [readiness check](struct.Builder.html#method.health_endpoint) can tell when `init` has
+    /// finished, and runs it again for each execution environment it simulates after the first, as
+    /// [`Builder::max_instances`](struct.Builder.html#method.max_instances) describes; if it fails
+    /// then, as in AWS Lambda, the invocation that started the environment fails with the error,
+    /// and the environment is stopped, so that the next invocation runs `init` again.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use http::{Request, Response};
+    /// use lambda_http_local::Runtime;
+    /// use std::env;
+    ///
+    /// struct Config {
+    ///     greeting: String,
+    /// }
+    ///
+    /// Runtime::builder().build().run_with_init(
+    ///     || env::var("GREETING").map(|greeting| Config { greeting }),
+    ///     |config: &Config, _: Request<&[u8]>| Response::new(config.greeting.clone()),
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `init` fails or the local development server fails to start, this function will panic.
+    /// Use [`try_run_with_init`](#method.try_run_with_init) to handle these errors instead.
+    pub fn run_with_init<I, S, E, F, R>(self, init: I, handler: F)
+    where
+        I: Fn() -> Result<S, E> + Send + Sync + 'static,
+        S: Send + Sync + 'static,
+        E: Display,
+        F: Fn(&S, Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.try_run_with_init(init, handler)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a handler initialized with `init` with this configuration, returning an error if
+    /// `init` or the local development server fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Init`](enum.Error.html#variant.Init) if `init` fails, and otherwise see
+    /// [`try_run`](#method.try_run).
+    pub fn try_run_with_init<I, S, E, F, R>(self, init: I, handler: F) -> Result<(), Error>
+    where
+        I: Fn() -> Result<S, E> + Send + Sync + 'static,
+        S: Send + Sync + 'static,
+        E: Display,
+        F: Fn(&S, Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        let handler = Arc::new(handler);
+        self.start(Service::per_instance(move || {
+            let state = init().map_err(|err| err.to_string())?;
+            let handler = handler.clone();
+            Ok(Service::from_sync(move |request| handler(&state, request)))
+        }))
+    }
+
+    /// Run a [`Router`](struct.Router.html) with this configuration.
+    ///
+    /// # Panics
+    ///
+    /// See [`run`](#method.run).
+    pub fn run_router(self, router: Router) {
+        self.try_run_router(router)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a [`Router`](struct.Router.html) with this configuration, returning an error if the
+    /// local development server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    pub fn try_run_router(self, router: Router) -> Result<(), Error> {
+        self.start(router.into_service())
+    }
+
+    /// Run a `tower::Service` with this configuration.
+    ///
+    /// See [`lambda_http_local::run_service`](fn.run_service.html) for details. This method
+    /// requires the `tower` feature.
+    ///
+    /// # Panics
+    ///
+    /// See [`run`](#method.run).
+    #[cfg(feature = "tower")]
+    pub fn run_service<S>(self, service: S)
+    where
+        S: tower_service::Service<Request<bytes::Bytes>, Response = http::Response<bytes::Bytes>>
+            + Clone
+            + Send
+            + 'static,
+        S::Error: std::fmt::Display,
+        S::Future: Send + 'static,
+    {
+        self.try_run_service(service)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a `tower::Service` with this configuration, returning an error if the local
+    /// development server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    #[cfg(feature = "tower")]
+    pub fn try_run_service<S>(self, service: S) -> Result<(), Error>
+    where
+        S: tower_service::Service<Request<bytes::Bytes>, Response = http::Response<bytes::Bytes>>
+            + Clone
+            + Send
+            + 'static,
+        S::Error: std::fmt::Display,
+        S::Future: Send + 'static,
+    {
+        self.start(crate::tower::service(service))
+    }
+
+    /// Run an `axum::Router` with this configuration.
+    ///
+    /// See [`lambda_http_local::run_axum`](fn.run_axum.html) for details. This method requires the
+    /// `axum` feature.
+    ///
+    /// # Panics
+    ///
+    /// See [`run`](#method.run).
+    #[cfg(feature = "axum")]
+    pub fn run_axum(self, router: ::axum::Router) {
+        self.try_run_axum(router)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run an `axum::Router` with this configuration, returning an error if the local
+    /// development server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    #[cfg(feature = "axum")]
+    pub fn try_run_axum(self, router: ::axum::Router) -> Result<(), Error> {
+        self.start(crate::http1::axum(router))
+    }
+
+    /// Run a warp filter with this configuration.
+    ///
+    /// See [`lambda_http_local::run_warp`](fn.run_warp.html) for details. This method requires the
+    /// `warp` feature.
+    ///
+    /// # Panics
+    ///
+    /// See [`run`](#method.run).
+    #[cfg(feature = "warp")]
+    pub fn run_warp<F>(self, filter: F)
+    where
+        F: ::warp::Filter<Error = ::warp::Rejection> + Clone + Send + Sync + 'static,
+        F::Extract: ::warp::Reply,
+        F::Future: Send,
+    {
+        self.try_run_warp(filter)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run a warp filter with this configuration, returning an error if the local development
+    /// server fails.
+    ///
+    /// # Errors
+    ///
+    /// See [`try_run`](#method.try_run).
+    #[cfg(feature = "warp")]
+    pub fn try_run_warp<F>(self, filter: F) -> Result<(), Error>
+    where
+        F: ::warp::Filter<Error = ::warp::Rejection> + Clone + Send + Sync + 'static,
+        F::Extract: ::warp::Reply,
+        F::Future: Send,
+    {
+        self.start(crate::http1::warp(filter))
+    }
+
+    /// Run an actix-web application with this configuration.
+    ///
+    /// See [`lambda_http_local::run_actix`](fn.run_actix.html) for details. This method requires
+    /// the `actix` feature.
+    ///
+    /// # Panics
+    ///
+    /// If the application fails to initialize or the local development server fails to start,
+    /// this function will panic. Use [`try_run_actix`](#method.try_run_actix) to handle these
+    /// errors instead.
+    #[cfg(feature = "actix")]
+    pub fn run_actix<F, S, B>(self, factory: F)
+    where
+        F: FnOnce() -> ::actix_web::App<S> + Send + 'static,
+        S: ::actix_web::dev::ServiceFactory<
+                ::actix_web::dev::ServiceRequest,
+                Config = (),
+                Response = ::actix_web::dev::ServiceResponse<B>,
+                Error = ::actix_web::Error,
+                InitError = (),
+            > + 'static,
+        B: ::actix_web::body::MessageBody + 'static,
+    {
+        self.try_run_actix(factory)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Run an actix-web application with this configuration, returning an error if the
+    /// application or the local development server fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Init`](enum.Error.html#variant.Init) if the application fails to
+    /// initialize, and otherwise see [`try_run`](#method.try_run).
+    #[cfg(feature = "actix")]
+    pub fn try_run_actix<F, S, B>(self, factory: F) -> Result<(), Error>
+    where
+        F: FnOnce() -> ::actix_web::App<S> + Send + 'static,
+        S: ::actix_web::dev::ServiceFactory<
+                ::actix_web::dev::ServiceRequest,
+                Config = (),
+                Response = ::actix_web::dev::ServiceResponse<B>,
+                Error = ::actix_web::Error,
+                InitError = (),
+            > + 'static,
+        B: ::actix_web::body::MessageBody + 'static,
+    {
+        self.start(crate::actix::service(factory))
+    }
+
+    /// Start the local development server on a background thread with this configuration.
+    ///
+    /// Unlike [`run`](#method.run), this always starts the local development server, even when
+    /// running in AWS Lambda. The server does not listen for shutdown signals; use the returned
+    /// [`ServerHandle`](struct.ServerHandle.html) to stop it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listen address cannot be resolved or bound.
+    #[cfg(feature = "local")]
+    pub fn spawn<F, R>(self, handler: F) -> Result<crate::ServerHandle, Error>
+    where
+        F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.spawn_service(Service::from_sync(handler))
+    }
+
+    /// Start the local development server on a background thread with an asynchronous handler.
+    ///
+    /// # Errors
+    ///
+    /// See [`spawn`](#method.spawn).
+    #[cfg(feature = "local")]
+    pub fn spawn_async<F, R>(self, handler: F) -> Result<crate::ServerHandle, Error>
+    where
+        F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+        R: Future + Send + 'static,
+        R::Output: IntoResponse,
+    {
+        self.spawn_service(Service::from_async(handler))
+    }
+
+    /// Start the local development server on a background thread with a handler created for each
+    /// execution environment, as [`run_per_instance`](#method.run_per_instance) runs it.
+    ///
+    /// # Errors
+    ///
+    /// See [`spawn`](#method.spawn).
+    #[cfg(feature = "local")]
+    pub fn spawn_per_instance<F, H, R>(self, new_handler: F) -> Result<crate::ServerHandle, Error>
+    where
+        F: Fn() -> H + Send + Sync + 'static,
+        H: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.spawn_service(Service::per_instance(move || {
+            Ok(Service::from_sync(new_handler()))
+        }))
+    }
+
+    /// Start the local development server on a background thread with a
+    /// [`Router`](struct.Router.html).
+    ///
+    /// # Errors
+    ///
+    /// See [`spawn`](#method.spawn).
+    #[cfg(feature = "local")]
+    pub fn spawn_router(self, router: Router) -> Result<crate::ServerHandle, Error> {
+        self.spawn_service(router.into_service())
+    }
+
+    #[cfg(feature = "local")]
+    fn spawn_service(self, service: Service) -> Result<crate::ServerHandle, Error> {
+        let service = self.wrap(service);
+        crate::server::spawn(self, service)
+    }
+
+    /// Wrap the handler in the configured middleware, error handler, and CORS headers.
+    fn wrap(&self, service: Service) -> Service {
+        let middleware = self.middleware.clone();
+        let error_handler = self.error_handler.clone();
+        let cors = self.cors.clone();
+        service.map(move |service| {
+            let options_routes = service.options_routes();
+            let service = middleware::apply(service, &middleware);
+            let service = match &error_handler {
+                Some(handler) => middleware::handle_errors(service, handler.clone()),
+                None => service,
+            };
+            match &cors {
+                Some(cors) => cors::apply(service, cors.clone(), options_routes.clone()),
+                None => service,
+            }
+        })
+    }
+
+    #[cfg_attr(not(feature = "local"), allow(clippy::unnecessary_wraps))]
+    fn start(self, service: Service) -> Result<(), Error> {
+        let service = self.wrap(service);
+        #[cfg(feature = "local")]
+        {
+            if crate::is_lambda() {
+                // AWS Lambda mode
+                crate::lambda::start(lambda_instance(service)?);
+                Ok(())
+            } else if self.one_shot || std::env::var_os(crate::one_shot::ONE_SHOT_VAR).is_some() {
+                let service = service.instantiate().map_err(Error::Init)?;
+                crate::one_shot::start(&self.context, &service)
+            } else {
+                // Hyper server mode
+                crate::local::start(self, service)
+            }
+        }
+
+        #[cfg(not(feature = "local"))]
+        {
+            crate::lambda::start(lambda_instance(service)?);
+            Ok(())
+        }
+    }
+}
+
+/// Create the handler for the execution environment this process runs in AWS Lambda, reporting
+/// an init error to the Runtime API if that fails.
+fn lambda_instance(service: Service) -> Result<Service, Error> {
+    service.instantiate().map_err(|message| {
+        crate::lambda::fail_init(&message);
+        Error::Init(message)
+    })
+}
+
+/// A builder for a [`Runtime`](struct.Runtime.html).
+///
+/// Options that only affect the local development server are ignored when running in AWS Lambda.
+#[allow(clippy::struct_excessive_bools)]
+pub struct Builder {
+    listen_addr: Option<io::Result<Vec<SocketAddr>>>,
+    port_retries: u16,
+    #[cfg(unix)]
+    reuse_port: bool,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+    #[cfg(feature = "listenfd")]
+    socket_activation: bool,
+    body_limit: Option<usize>,
+    max_concurrent_requests: Option<usize>,
+    reserved_concurrency: Option<usize>,
+    injected_latency: Option<(f64, RangeInclusive<Duration>)>,
+    dropped_connection_rate: f64,
+    truncated_response_rate: f64,
+    injected_errors: Option<(f64, ErrorMatcher)>,
+    simulation_seed: Option<u64>,
+    sequential: bool,
+    max_instances: Option<usize>,
+    cold_start_delay: Option<Duration>,
+    instance_idle_timeout: Option<Duration>,
+    provisioned_concurrency: usize,
+    buffered_body_limit: Option<usize>,
+    quiet: bool,
+    access_log: bool,
+    access_log_format: AccessLogFormat,
+    platform_log: bool,
+    shutdown_timeout: Duration,
+    keep_alive_timeout: Option<Duration>,
+    request_read_timeout: Option<Duration>,
+    on_bind: Option<Box<dyn FnOnce(SocketAddr) + Send>>,
+    context: ContextBuilder,
+    enforce_timeout: bool,
+    /// `None` if not set, in which case the profile's integration timeout is used.
+    #[allow(clippy::option_option)]
+    integration_timeout: Option<Option<Duration>>,
+    profile: Profile,
+    stage: Option<String>,
+    binary_media_types: Vec<String>,
+    stage_variables: Map<String, Value>,
+    enforce_payload_limits: bool,
+    jwt_authorizer: Option<JwtAuthorizer>,
+    lambda_authorizer: Option<LambdaAuthorizer>,
+    api_keys: Vec<String>,
+    api_key_quota: Option<u64>,
+    #[cfg(feature = "openapi")]
+    openapi: Option<serde_json::Value>,
+    #[cfg(feature = "openapi")]
+    response_validation: Option<ResponseValidation>,
+    cognito_claims: CognitoClaims,
+    xray_daemon: Option<SocketAddr>,
+    metrics_endpoint: bool,
+    health_endpoint: bool,
+    invocation_history: usize,
+    event_stream: bool,
+    record_dir: Option<PathBuf>,
+    har_path: Option<PathBuf>,
+    one_shot: bool,
+    #[cfg(feature = "dashboard")]
+    dashboard: bool,
+    #[cfg(feature = "tls")]
+    https: bool,
+    #[cfg(feature = "tls")]
+    tls_certificate: Option<(PathBuf, PathBuf)>,
+    #[cfg(feature = "tls")]
+    tls_client_ca: Option<PathBuf>,
+    #[cfg(feature = "http3")]
+    http3: bool,
+    #[cfg(feature = "shadow")]
+    shadow: Option<Result<http::Uri, String>>,
+    #[cfg(feature = "shadow")]
+    shadow_compare: bool,
+    #[cfg(feature = "shadow")]
+    shadow_ignore_headers: Vec<String>,
+    #[cfg(feature = "shadow")]
+    shadow_ignore_fields: Vec<String>,
+    middleware: Vec<Middleware>,
+    error_handler: Option<ErrorHandler>,
+    cors: Option<Arc<Cors>>,
+}
+
+impl Builder {
+    /// Set the address the local development server listens on.
+    ///
+    /// The server listens on every address `listen_addr` resolves to, so the default of
+    /// `localhost:3000` serves both `127.0.0.1` and `::1` where IPv6 is available. To listen on a
+    /// specific set of addresses, pass a slice of them, such as `&[v4, v6][..]`. Addresses that
+    /// fail to bind are skipped with a warning, as long as at least one succeeds.
+    ///
+    /// A port of 0 binds to an ephemeral port, shared by all of the addresses; use
+    /// [`on_bind`](#method.on_bind) to find out which port was chosen. It and
+    /// [`ServerHandle::addr`](struct.ServerHandle.html#method.addr) report the first address.
+    ///
+    /// The server speaks HTTP/1.1 and, to clients that connect with prior knowledge (such as
+    /// `curl --http2-prior-knowledge`), cleartext HTTP/2. As API Gateway does, it combines the
+    /// `Cookie` headers HTTP/2 clients send separately into one before building the event.
+    #[must_use]
+    pub fn listen_addr<T: ToSocketAddrs>(mut self, listen_addr: T) -> Builder {
+        self.listen_addr = Some(listen_addr.to_socket_addrs().map(Iterator::collect));
+        self
+    }
+
+    /// If the listen address's port is already in use, try up to `port_retries` of the ports
+    /// after it, such as 3001 through 3010 for `localhost:3000` and 10 retries, before giving up.
+    ///
+    /// This is handy when running several services side by side. The URL the server ends up
+    /// listening on is printed, and passed to [`on_bind`](#method.on_bind). To always bind to
+    /// whichever port is free instead, use a port of 0 in the listen address. Defaults to 0.
+    #[must_use]
+    pub fn port_retries(mut self, port_retries: u16) -> Builder {
+        self.port_retries = port_retries;
+        self
+    }
+
+    /// Set `SO_REUSEPORT` on the sockets the local development server binds, so that several
+    /// processes can listen on the same port at once.
+    ///
+    /// The operating system spreads incoming connections across the processes, which makes for
+    /// quick multi-process load testing, and lets an external supervisor start a new server
+    /// before stopping the old one for restarts without downtime. Every process bound to the port
+    /// must set this.
+    ///
+    /// This method is only available on Unix platforms. Defaults to `false`.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn reuse_port(mut self, reuse_port: bool) -> Builder {
+        self.reuse_port = reuse_port;
+        self
+    }
+
+    /// Listen on the Unix socket at `path` instead of a TCP address, such as when the local
+    /// development server sits behind a reverse proxy, or in a container where mapping ports is
+    /// awkward.
+    ///
+    /// A socket left at `path` by an earlier run is replaced, and the socket is removed when the
+    /// server shuts down. The socket is always served plain HTTP, as if from a client at
+    /// `127.0.0.1` on port 80, and [`on_bind`](#method.on_bind) is not called. This method is
+    /// only available on Unix.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn listen_unix<P: Into<PathBuf>>(mut self, path: P) -> Builder {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Serve the socket passed to the process by a service manager, if there is one, instead of
+    /// binding to the listen address.
+    ///
+    /// The first socket passed with the `LISTEN_FDS` protocol is used, which may be a TCP or Unix
+    /// socket. This supports systemd's socket activation, and running the server under
+    /// [`systemfd`](https://github.com/mitsuhiko/systemfd) so that the socket stays open, and
+    /// requests wait rather than fail, while `cargo watch` rebuilds and restarts it:
+    ///
+    /// ```text
+    /// systemfd --no-pid -s http::3000 -- cargo watch -x run
+    /// ```
+    ///
+    /// This method requires the `listenfd` feature. Defaults to `false`.
+    #[cfg(feature = "listenfd")]
+    #[must_use]
+    pub fn socket_activation(mut self, socket_activation: bool) -> Builder {
+        self.socket_activation = socket_activation;
+        self
+    }
+
+    /// Set the maximum size of a request body the local development server accepts.
+    ///
+    /// Requests with larger bodies receive a `413 Payload Too Large` response without the handler
+    /// being called. By default, request bodies of any size are accepted.
+    #[must_use]
+    pub fn body_limit(mut self, bytes: usize) -> Builder {
+        self.body_limit = Some(bytes);
+        self
+    }
+
+    /// Set the most requests the local development server handles at once.
+    ///
+    /// Requests past the limit receive a `503 Service Unavailable` response without the handler
+    /// being called, which emulates a constrained environment and keeps an accidental flood of
+    /// requests from overwhelming your machine. Requests to the `/__lambda/` endpoints do not
+    /// count towards the limit. By default, the number of concurrent requests is unlimited.
+    #[must_use]
+    pub fn max_concurrent_requests(mut self, limit: usize) -> Builder {
+        self.max_concurrent_requests = Some(limit);
+        self
+    }
+
+    /// Limit the function to `limit` concurrent invocations on the local development server, as
+    /// reserved concurrency does, throttling requests past it.
+    ///
+    /// Throttled requests receive a `429 Too Many Requests` response with an `x-amzn-ErrorType`
+    /// of `TooManyRequestsException`, in the format of the [profile](#method.profile), without the
+    /// handler being called, so client retry and backoff logic can be exercised. Unlike
+    /// [`max_instances`](#method.max_instances), which makes requests wait for an execution
+    /// environment, this rejects them. By default, the function is not throttled.
+    #[must_use]
+    pub fn reserved_concurrency(mut self, limit: usize) -> Builder {
+        self.reserved_concurrency = Some(limit);
+        self
+    }
+
+    /// Delay a fraction `rate` of responses from the local development server, between 0 and 1,
+    /// by a random duration in `latency`, for testing how clients cope with slow responses.
+    ///
+    /// Requests to the `/__lambda/` endpoints are not delayed. By default, no latency is
+    /// injected.
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    ///
+    /// // Delay one in ten responses by between 100 milliseconds and 2 seconds.
+    /// let builder = lambda_http_local::Runtime::builder()
+    ///     .inject_latency(0.1, Duration::from_millis(100)..=Duration::from_secs(2));
+    /// ```
+    #[must_use]
+    pub fn inject_latency(mut self, rate: f64, latency: RangeInclusive<Duration>) -> Builder {
+        self.injected_latency = Some((rate, latency));
+        self
+    }
+
+    /// Close the connection instead of responding to a fraction `rate` of requests to the local
+    /// development server, between 0 and 1, for testing how clients cope with network failures.
+    ///
+    /// The function is still invoked, so clients cannot tell whether their request was handled,
+    /// as with a real network failure. Requests to the `/__lambda/` endpoints are not affected.
+    /// By default, no connections are dropped.
+    #[must_use]
+    pub fn drop_connections(mut self, rate: f64) -> Builder {
+        self.dropped_connection_rate = rate;
+        self
+    }
+
+    /// Cut off the body of a fraction `rate` of responses from the local development server,
+    /// between 0 and 1, for testing how clients cope with incomplete responses.
+    ///
+    /// A truncated response declares its full length, but only half of its body is sent before
+    /// the connection is closed. Requests to the `/__lambda/` endpoints are not affected. By
+    /// default, no responses are truncated.
+    #[must_use]
+    pub fn truncate_responses(mut self, rate: f64) -> Builder {
+        self.truncated_response_rate = rate;
+        self
+    }
+
+    /// Respond to a fraction `rate` of requests to the local development server, between 0 and 1,
+    /// with the error clients receive when AWS Lambda fails to invoke the function, without
+    /// invoking it.
+    ///
+    /// This emulates Lambda service errors, for testing how clients cope with them. The error is
+    /// a `500 Internal Server Error` or `502 Bad Gateway` response in the format of the
+    /// [profile](#method.profile). To only inject errors for some requests, use
+    /// [`inject_errors_matching`](#method.inject_errors_matching). By default, no errors are
+    /// injected.
+    #[must_use]
+    pub fn inject_errors(self, rate: f64) -> Builder {
+        self.inject_errors_matching(rate, |_, _| true)
+    }
+
+    /// Respond to a fraction `rate` of the requests to the local development server for which
+    /// `matcher` returns `true`, given their method and path, with a service error, as
+    /// [`inject_errors`](#method.inject_errors) does for all requests.
+    ///
+    /// ```rust,no_run
+    /// use http::Method;
+    ///
+    /// // Fail half of the requests to create an order.
+    /// let builder = lambda_http_local::Runtime::builder()
+    ///     .inject_errors_matching(0.5, |method, path| {
+    ///         method == Method::POST && path.starts_with("/orders")
+    ///     });
+    /// ```
+    #[must_use]
+    pub fn inject_errors_matching<F>(mut self, rate: f64, matcher: F) -> Builder
+    where
+        F: Fn(&Method, &str) -> bool + Send + Sync + 'static,
+    {
+        self.injected_errors = Some((rate, Arc::new(matcher)));
+        self
+    }
+
+    /// Seed the random choices of which requests the local development server delays, fails, or
+    /// disrupts, so that failure scenarios can be reproduced in CI and bug reports.
+    ///
+    /// The same seed makes the same choices for the same sequence of requests, in the order they
+    /// arrive, even when they are handled concurrently. The seed in use is printed when the
+    /// server starts, unless it is [quiet](#method.quiet). The other simulations, such as
+    /// [cold starts](#method.cold_start_delay) and [throttling](#method.reserved_concurrency),
+    /// make no random choices. By default, a random seed is used.
+    #[must_use]
+    pub fn simulation_seed(mut self, seed: u64) -> Builder {
+        self.simulation_seed = Some(seed);
+        self
+    }
+
+    /// Invoke the function for one request at a time on the local development server, as a
+    /// single Lambda execution environment does.
+    ///
+    /// Requests that arrive while an invocation is in progress wait for it to complete, so that
+    /// code that relies on running alone, or breaks when it does not, behaves locally as it will
+    /// in AWS Lambda. The function timeout only starts once a request's invocation does. This is
+    /// the same as [`max_instances(1)`](#method.max_instances). To reject concurrent requests
+    /// rather than queue them, use [`max_concurrent_requests`](#method.max_concurrent_requests).
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn sequential(mut self, sequential: bool) -> Builder {
+        self.sequential = sequential;
+        self
+    }
+
+    /// Simulate up to `max` execution environments on the local development server, as AWS
+    /// Lambda scales a function.
+    ///
+    /// Each execution environment handles one invocation at a time. A request is invoked in an
+    /// idle environment if there is one, preferring the most recently used, and otherwise starts
+    /// a new environment, with a log stream name of its own. Once `max` environments are busy,
+    /// requests wait for one to become idle. To give each environment its own handler and state,
+    /// run a handler with [`run_per_instance`](struct.Runtime.html#method.run_per_instance),
+    /// which simulates as many environments as are needed unless a maximum is set. By default,
+    /// invocations are not limited to one per environment.
+    #[must_use]
+    pub fn max_instances(mut self, max: usize) -> Builder {
+        self.max_instances = Some(max.max(1));
+        self
+    }
+
+    /// Delay the first invocation in each execution environment on the local development server
+    /// by `delay`, to see the latency of cold starts.
+    ///
+    /// The delay is added to the time it takes to create the environment's handler, and reported
+    /// as the `Init Duration` of the invocation's platform log `REPORT` line, as AWS Lambda
+    /// reports cold starts. This simulates execution environments as
+    /// [`max_instances`](#method.max_instances) describes, without limiting their number unless a
+    /// maximum is set. By default, there is no delay.
+    #[must_use]
+    pub fn cold_start_delay(mut self, delay: Duration) -> Builder {
+        self.cold_start_delay = Some(delay);
+        self
+    }
+
+    /// Stop execution environments on the local development server once they have been idle for
+    /// `timeout`, so the next request starts a new one, with a cold start.
+    ///
+    /// AWS Lambda stops idle environments after an unspecified period, typically between a few
+    /// minutes and an hour. This simulates execution environments as
+    /// [`max_instances`](#method.max_instances) describes, without limiting their number unless a
+    /// maximum is set. By default, idle environments are kept until the server shuts down.
+    #[must_use]
+    pub fn instance_idle_timeout(mut self, timeout: Duration) -> Builder {
+        self.instance_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Start `count` execution environments on the local development server in advance, as
+    /// provisioned concurrency does, to compare tail latency with and without it.
+    ///
+    /// They are started once the server is listening, and its
+    /// [readiness check](#method.health_endpoint) succeeds once they have.
+    ///
+    /// Provisioned environments have already initialized, so their invocations never have a
+    /// [cold start delay](#method.cold_start_delay), and they are not stopped by the
+    /// [instance idle timeout](#method.instance_idle_timeout). Requests beyond them start
+    /// environments on demand. If [`max_instances`](#method.max_instances) is set, at most that
+    /// many are provisioned. Defaults to 0.
+    #[must_use]
+    pub fn provisioned_concurrency(mut self, count: usize) -> Builder {
+        self.provisioned_concurrency = count;
+        self
+    }
+
+    /// Set the most bytes of request bodies the local development server holds in memory at once,
+    /// across all requests.
+    ///
+    /// Request bodies are buffered in memory before the handler is called, and kept until it
+    /// returns. Requests whose bodies would take the total past the limit receive a `503 Service
+    /// Unavailable` response, so that a few large uploads at once cannot exhaust your machine's
+    /// memory; use [`body_limit`](#method.body_limit) to limit the size of each body. By default,
+    /// the total is unlimited.
+    #[must_use]
+    pub fn buffered_body_limit(mut self, bytes: usize) -> Builder {
+        self.buffered_body_limit = Some(bytes);
+        self
+    }
+
+    /// Suppress diagnostic messages the local development server writes to standard error,
+    /// including the [metrics](metrics/index.html) invocations record.
+    #[must_use]
+    pub fn quiet(mut self, quiet: bool) -> Builder {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Write a line to standard output for each request the local development server responds
+    /// to.
+    ///
+    /// By default, lines are in the Common Log Format used by web servers such as Apache and
+    /// nginx, followed by the time taken to respond in seconds:
+    ///
+    /// ```text
+    /// 127.0.0.1 - - [14/Oct/2026:10:41:15 +0000] "GET /users?limit=10 HTTP/1.1" 200 312 0.004
+    /// ```
+    ///
+    /// Use [`access_log_format`](#method.access_log_format) to choose another format. Defaults to
+    /// `false`.
+    #[must_use]
+    pub fn access_log(mut self, access_log: bool) -> Builder {
+        self.access_log = access_log;
+        self
+    }
+
+    /// Set the format of the access log enabled with [`access_log`](#method.access_log).
+    ///
+    /// Defaults to [`AccessLogFormat::Common`](enum.AccessLogFormat.html#variant.Common).
+    #[must_use]
+    pub fn access_log_format(mut self, format: AccessLogFormat) -> Builder {
+        self.access_log_format = format;
+        self
+    }
+
+    /// Write the `START`, `END`, and `REPORT` lines AWS Lambda writes to a function's logs for
+    /// each invocation, so tools that parse them work with the local development server too:
+    ///
+    /// ```text
+    /// START RequestId: 52fdfc07-2182-454f-963f-5f0f9a621d72 Version: $LATEST
+    /// END RequestId: 52fdfc07-2182-454f-963f-5f0f9a621d72
+    /// REPORT RequestId: 52fdfc07-2182-454f-963f-5f0f9a621d72 Duration: 3.39 ms Billed Duration: 4 ms Memory Size: 128 MB Max Memory Used: 12 MB
+    /// ```
+    ///
+    /// On Linux, `Max Memory Used` is the peak resident memory of the whole process, including the
+    /// local development server, and a warning is written when it exceeds the memory size set with
+    /// [`ContextBuilder::memory_limit_in_mb`](struct.ContextBuilder.html#method.memory_limit_in_mb).
+    ///
+    /// The lines are written to standard error, or logged with the `log` feature, even if
+    /// [`quiet`](#method.quiet) is set. Defaults to `false`.
+    #[must_use]
+    pub fn platform_log(mut self, platform_log: bool) -> Builder {
+        self.platform_log = platform_log;
+        self
+    }
+
+    /// Set how long the local development server waits for in-flight requests to complete after
+    /// receiving SIGINT or SIGTERM.
+    ///
+    /// When a shutdown signal is received, the server stops accepting new connections. Once all
+    /// in-flight requests have completed or the timeout has elapsed, `run` returns. Defaults to 5
+    /// seconds.
+    #[must_use]
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Builder {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Close connections to the local development server that sit idle, with no request in
+    /// flight, for `timeout`.
+    ///
+    /// This keeps clients that hold keep-alive connections open indefinitely, such as some
+    /// browsers and HTTP client pools, from accumulating dead connections over a long development
+    /// session. By default, idle connections are kept open until the client closes them.
+    #[must_use]
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Builder {
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how long clients have to send a whole request to the local development server, from
+    /// its first byte.
+    ///
+    /// Connections whose request headers take longer are closed, and requests whose bodies take
+    /// longer receive a `408 Request Timeout` response, so that slowloris-style clients that
+    /// trickle requests in cannot tie up the server. Over HTTPS, the TLS handshake counts towards
+    /// the first request. By default, clients can take as long as they like.
+    #[must_use]
+    pub fn request_read_timeout(mut self, timeout: Duration) -> Builder {
+        self.request_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a function to call with the address the local development server is bound to, before
+    /// it starts serving requests.
+    ///
+    /// This is useful when binding to port 0, as the port is chosen by the operating system.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use http::Response;
+    /// use lambda_http_local::Runtime;
+    /// use std::sync::mpsc;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// std::thread::spawn(move || {
+    ///     Runtime::builder()
+    ///         .listen_addr("localhost:0")
+    ///         .on_bind(move |addr| tx.send(addr).unwrap())
+    ///         .build()
+    ///         .run(|request| Response::new("Hello, world!"));
+    /// });
+    /// let addr = rx.recv().unwrap();
+    /// println!("server is listening on http://{}", addr);
+    /// ```
+    #[must_use]
+    pub fn on_bind<F>(mut self, f: F) -> Builder
+    where
+        F: FnOnce(SocketAddr) + Send + 'static,
+    {
+        self.on_bind = Some(Box::new(f));
+        self
+    }
+
+    /// Set how the simulated [`Context`] for local requests is created.
+    ///
+    /// [`Context`]: ../lambda_runtime_core/context/struct.Context.html
+    #[must_use]
+    pub fn context(mut self, context: ContextBuilder) -> Builder {
+        self.context = context;
+        self
+    }
+
+    /// Set whether the local development server enforces the function timeout.
+    ///
+    /// As in AWS Lambda, invocations that run longer than the timeout set with
+    /// [`ContextBuilder::timeout`] are abandoned, and the error response of the emulated
+    /// [profile](#method.profile) is returned instead. Defaults to `true`; disabling this can be
+    /// useful when stepping through a handler in a debugger.
+    ///
+    /// [`ContextBuilder::timeout`]: struct.ContextBuilder.html#method.timeout
+    #[must_use]
+    pub fn enforce_timeout(mut self, enforce_timeout: bool) -> Builder {
+        self.enforce_timeout = enforce_timeout;
+        self
+    }
+
+    /// Set the API Gateway integration timeout, or `None` to disable it.
+    ///
+    /// Separately from the function timeout, API Gateway gives up waiting for the function once
+    /// the integration timeout has elapsed and responds with an error, even if the function is
+    /// still running. Defaults to the [profile](#method.profile)'s timeout: 29 seconds for REST
+    /// APIs, 30 seconds for HTTP APIs, and none otherwise.
+    #[must_use]
+    pub fn integration_timeout(mut self, timeout: Option<Duration>) -> Builder {
+        self.integration_timeout = Some(timeout);
+        self
+    }
+
+    /// Set which AWS service in front of the function the local development server emulates.
+    ///
+    /// Defaults to [`Profile::RestApi`](enum.Profile.html#variant.RestApi).
+    #[must_use]
+    pub fn profile(mut self, profile: Profile) -> Builder {
+        self.profile = profile;
+        self
+    }
+
+    /// Serve the API under a stage, as API Gateway's default endpoints do.
+    ///
+    /// Requests must include the stage as the first path segment, such as `/prod/users` for a
+    /// stage named `prod`; other requests receive the error API Gateway responds with for
+    /// unknown routes. As in API Gateway, REST APIs remove the stage from the path passed to the
+    /// handler, while HTTP APIs keep it. By default, the API is served at the root, as if through
+    /// a custom domain name.
+    ///
+    /// This has no effect for profiles without stages.
+    #[must_use]
+    pub fn stage<T: Into<String>>(mut self, stage: T) -> Builder {
+        self.stage = Some(stage.into());
+        self
+    }
+
+    /// Set the stage variables passed to the function, as configured on an API Gateway stage.
+    ///
+    /// Handlers can read them with
+    /// [`RequestExt::stage_variables`](trait.RequestExt.html#tymethod.stage_variables). By
+    /// default, there are no stage variables.
+    ///
+    /// This has no effect for profiles without stages.
+    ///
+    /// ```rust,no_run
+    /// # use http::Response;
+    /// use lambda_http_local::{RequestExt, Runtime};
+    ///
+    /// Runtime::builder()
+    ///     .stage_variables(vec![("table", "users-dev")])
+    ///     .build()
+    ///     .run(|request| {
+    ///         let variables = request.stage_variables();
+    ///         Response::new(variables.get("table").unwrap_or_default().to_owned())
+    ///     });
+    /// ```
+    #[must_use]
+    pub fn stage_variables<I, K, V>(mut self, variables: I) -> Builder
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.stage_variables = string_map(variables);
+        self
+    }
+
+    /// Set whether the local development server enforces the payload size limits of AWS Lambda
+    /// and the emulated service.
+    ///
+    /// Requests with bodies larger than the emulated service accepts (10 MB for API Gateway, 6 MB
+    /// for function URLs, and 1 MB for ALB) receive a `413 Payload Too Large` response, as do
+    /// requests whose event would exceed Lambda's 6 MB invocation payload limit. Responses larger
+    /// than 6 MB (1 MB for ALB) fail in AWS Lambda; they are replaced with the error response the
+    /// emulated service returns, and a message is written to standard error. Defaults to `true`.
+    #[must_use]
+    pub fn enforce_payload_limits(mut self, enforce_payload_limits: bool) -> Builder {
+        self.enforce_payload_limits = enforce_payload_limits;
+        self
+    }
+
+    /// Set the binary media types of the emulated REST API, such as `image/png`, `image/*` or
+    /// `*/*`.
+    ///
+    /// REST APIs only pass binary bodies through unchanged for configured binary media types.
+    /// Request bodies with other content types are passed to the function as text, corrupting
+    /// anything that is not valid UTF-8, and binary response bodies are returned to the client
+    /// base64-encoded unless the request's `Accept` header or the response's `Content-Type`
+    /// matches. The local development server applies the same rules, and warns when a body would
+    /// be corrupted. By default, there are no binary media types.
+    ///
+    /// This has no effect for other profiles, which always pass binary bodies through.
+    #[must_use]
+    pub fn binary_media_types<I>(mut self, types: I) -> Builder
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.binary_media_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Protect the emulated HTTP API with a JWT authorizer.
+    ///
+    /// See [`JwtAuthorizer`](struct.JwtAuthorizer.html) for details. By default, requests are
+    /// not authorized. This has no effect for profiles other than
+    /// [`Profile::HttpApi`](enum.Profile.html#variant.HttpApi).
+    #[must_use]
+    pub fn jwt_authorizer(mut self, authorizer: JwtAuthorizer) -> Builder {
+        self.jwt_authorizer = Some(authorizer);
+        self
+    }
+
+    /// Protect the emulated API with a Lambda authorizer.
+    ///
+    /// In API Gateway, Lambda authorizers are separate functions. The local development server
+    /// calls `authorizer` in their place before invoking the handler, with the request's method,
+    /// URI and headers; it can act as a request authorizer, or as a token authorizer by only
+    /// reading the header containing the token. If it allows the request, the principal ID and
+    /// context it returns are passed to the function in the `authorizer` field of the request
+    /// context. Otherwise the client receives the error response API Gateway returns. The
+    /// authorizer is called on the server's event loop, so it should not block.
+    ///
+    /// By default, requests are not authorized. This has no effect for profiles other than
+    /// [`Profile::RestApi`](enum.Profile.html#variant.RestApi) and
+    /// [`Profile::HttpApi`](enum.Profile.html#variant.HttpApi).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use http::Response;
+    /// use lambda_http_local::{Authorization, Runtime};
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let server = Runtime::builder()
+    ///     .listen_addr("localhost:0")
+    ///     .quiet(true)
+    ///     .lambda_authorizer(|request| match request.headers().get("x-user") {
+    ///         Some(user) => Authorization::allow(user.to_str().unwrap()).context("plan", "free"),
+    ///         None => Authorization::unauthorized(),
+    ///     })
+    ///     .build()
+    ///     .spawn(|request| Response::new("Hello, world!"))
+    ///     .unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(server.addr()).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.0 401 Unauthorized"));
+    /// ```
+    #[must_use]
+    pub fn lambda_authorizer<F>(mut self, authorizer: F) -> Builder
+    where
+        F: Fn(&Request<()>) -> Authorization + Send + Sync + 'static,
+    {
+        self.lambda_authorizer = Some(Arc::new(authorizer));
+        self
+    }
+
+    /// Pass `claims` to the function for every request, as if it was authorized by a Cognito user
+    /// pool authorizer.
+    ///
+    /// The claims are passed in the `claims` field of the request context's `authorizer` field,
+    /// where both Cognito authorizers for REST APIs and JWT authorizers for HTTP APIs put them.
+    /// Requests can select a different set of claims configured with
+    /// [`cognito_user`](#method.cognito_user) instead. This has no effect for profiles other than
+    /// [`Profile::RestApi`](enum.Profile.html#variant.RestApi) and
+    /// [`Profile::HttpApi`](enum.Profile.html#variant.HttpApi).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use http::Response;
+    /// use lambda_http_local::Runtime;
+    ///
+    /// Runtime::builder()
+    ///     .cognito_claims(vec![("sub", "1234"), ("email", "alice@example.com")])
+    ///     .cognito_user("bob", vec![("sub", "5678"), ("email", "bob@example.com")])
+    ///     .build()
+    ///     .run(|request| Response::new("Hello, world!"));
+    /// ```
+    #[must_use]
+    pub fn cognito_claims<I, K, V>(mut self, claims: I) -> Builder
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.cognito_claims.default = Some(string_map(claims));
+        self
+    }
+
+    /// Add a set of Cognito user pool claims that requests select with the
+    /// `X-Local-Cognito-User` header.
+    ///
+    /// Requests with `X-Local-Cognito-User: user` receive `claims` as described for
+    /// [`cognito_claims`](#method.cognito_claims); the header itself is not passed to the
+    /// function. Once any claims are configured, requests without claims, or for an unknown user,
+    /// receive a `401 Unauthorized` response.
+    #[must_use]
+    pub fn cognito_user<T, I, K, V>(mut self, user: T, claims: I) -> Builder
+    where
+        T: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.cognito_claims
+            .users
+            .insert(user.into(), string_map(claims));
+        self
+    }
+
+    /// Require requests to the emulated REST API to include one of `keys` in the `x-api-key`
+    /// header, as API Gateway does for methods with API keys required.
+    ///
+    /// Requests without a valid key receive a `403 Forbidden` response. The key is passed to the
+    /// function in the `identity` field of the request context. By default, API keys are not
+    /// required. This has no effect for profiles other than
+    /// [`Profile::RestApi`](enum.Profile.html#variant.RestApi).
+    #[must_use]
+    pub fn api_keys<I>(mut self, keys: I) -> Builder
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.api_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Limit each API key to `requests` requests, as a usage plan's quota does.
+    ///
+    /// Requests over the quota receive a `429 Too Many Requests` response. Quotas are counted
+    /// from when the local development server starts, rather than per day, week or month. By
+    /// default, there is no quota. This has no effect unless
+    /// [`api_keys`](#method.api_keys) is set.
+    #[must_use]
+    pub fn api_key_quota(mut self, requests: u64) -> Builder {
+        self.api_key_quota = Some(requests);
+        self
+    }
+
+    /// Validate requests against the OpenAPI 3 or Swagger 2 `document` the API is defined with,
+    /// as API Gateway does for REST APIs with request validators.
+    ///
+    /// Requests for an operation in the document without its required query parameters or
+    /// headers, with a `Content-Type` it does not accept, or with a JSON body that does not match
+    /// its schema receive the same 400 or 415 response API Gateway returns, without invoking the
+    /// function, and the reason is logged. Requests that match no operation are passed to the
+    /// function. This method requires the `openapi` feature. By default, requests are not
+    /// validated.
+    ///
+    /// ```rust,no_run
+    /// # use http::{Request, Response};
+    /// let document = std::fs::read_to_string("openapi.json").unwrap();
+    /// let document = serde_json::from_str(&document).unwrap();
+    /// lambda_http_local::Runtime::builder()
+    ///     .openapi(document)
+    ///     .build()
+    ///     .run(|_: Request<&[u8]>| Response::new("Hello, world!"));
+    /// ```
+    #[cfg(feature = "openapi")]
+    #[allow(clippy::doc_markdown)]
+    #[must_use]
+    pub fn openapi(mut self, document: serde_json::Value) -> Builder {
+        self.openapi = Some(document);
+        self
+    }
+
+    /// Check the function's responses against the responses documented in the
+    /// [`openapi`](#method.openapi) document, to catch handlers drifting from it during
+    /// development.
+    ///
+    /// A response must have a status code the operation documents, explicitly, as a range such
+    /// as `2XX`, or with `default`, the headers documented as required, and, if it has a body, a
+    /// documented `Content-Type`, and a JSON body must match the schema given for it. Operations
+    /// without documented responses are not checked. With [`ResponseValidation::Log`], mismatches
+    /// are logged as warnings; with [`ResponseValidation::Fail`], they are logged as errors and
+    /// the client receives a `500 Internal Server Error` response describing the mismatch instead.
+    /// API Gateway does not check responses, so neither does AWS Lambda. This method requires the
+    /// `openapi` feature. By default, responses are not checked.
+    ///
+    /// ```rust,no_run
+    /// # use http::{Request, Response};
+    /// use lambda_http_local::ResponseValidation;
+    ///
+    /// let document = std::fs::read_to_string("openapi.json").unwrap();
+    /// let document = serde_json::from_str(&document).unwrap();
+    /// lambda_http_local::Runtime::builder()
+    ///     .openapi(document)
+    ///     .validate_responses(ResponseValidation::Fail)
+    ///     .build()
+    ///     .run(|_: Request<&[u8]>| Response::new("Hello, world!"));
+    /// ```
+    ///
+    /// [`ResponseValidation::Log`]: enum.ResponseValidation.html#variant.Log
+    /// [`ResponseValidation::Fail`]: enum.ResponseValidation.html#variant.Fail
+    #[cfg(feature = "openapi")]
+    #[must_use]
+    pub fn validate_responses(mut self, validation: ResponseValidation) -> Builder {
+        self.response_validation = Some(validation);
+        self
+    }
+
+    /// Send a segment for each invocation to the AWS X-Ray daemon listening on `addr`, as AWS
+    /// Lambda does for functions with active tracing.
+    ///
+    /// The daemon's default address is `127.0.0.1:2000`. Requests continue the trace of their
+    /// `X-Amzn-Trace-Id` or `traceparent` header, or start a new one. As in AWS Lambda, the
+    /// trace header with the invocation's segment as the parent is passed to the function in
+    /// `Context::xray_trace_id` and is returned by
+    /// [`RequestExt::trace_context`](trait.RequestExt.html#tymethod.trace_context), so segments
+    /// the function sends appear beneath it. By default, no segments are sent.
+    #[must_use]
+    pub fn xray_daemon(mut self, addr: SocketAddr) -> Builder {
+        self.xray_daemon = Some(addr);
+        self
+    }
+
+    /// Serve Prometheus metrics for the function at `/__lambda/metrics` on the local development
+    /// server, instead of invoking the function for requests to that path.
+    ///
+    /// The metrics are `lambda_invocations_total`, `lambda_errors_total` (invocations that
+    /// returned an error or timed out), and the `lambda_duration_seconds` histogram. Defaults to
+    /// `false`.
+    #[must_use]
+    pub fn metrics_endpoint(mut self, metrics_endpoint: bool) -> Builder {
+        self.metrics_endpoint = metrics_endpoint;
+        self
+    }
+
+    /// Respond to requests for `/__lambda/health` on the local development server with `200 OK`,
+    /// instead of invoking the function, so health checks can wait for the server to be up.
+    ///
+    /// The response is a JSON object with the function's name, version, and memory size, the
+    /// [`Profile`](enum.Profile.html), and the server's uptime in seconds.
+    ///
+    /// This also serves a readiness check at `/__lambda/ready`, which responds with `503 Service
+    /// Unavailable` until the handler has finished initializing and `200 OK` after, so that
+    /// orchestrated environments such as Docker Compose or Kubernetes do not route requests to a
+    /// handler that cannot serve them yet. Defaults to `false`.
+    #[must_use]
+    pub fn health_endpoint(mut self, health_endpoint: bool) -> Builder {
+        self.health_endpoint = health_endpoint;
+        self
+    }
+
+    /// Keep the last `invocations` requests to the local development server and their responses,
+    /// and serve them as a JSON array, most recent first, at `/__lambda/invocations`.
+    ///
+    /// Each entry has the request's method, URI, and headers, the response's status and headers,
+    /// the first 4 KiB of both bodies, the event the function was invoked with, the Lambda request
+    /// ID, and how long the response took.
+    /// Defaults to 0, which disables the endpoint.
+    #[must_use]
+    pub fn invocation_history(mut self, invocations: usize) -> Builder {
+        self.invocation_history = invocations;
+        self
+    }
+
+    /// Stream a summary of each request to the local development server as it completes, as
+    /// server-sent events from `/__lambda/events`.
+    ///
+    /// Each event is named `invocation`, and its data is a JSON object with the request's method
+    /// and URI, the response status, the Lambda request ID, and how long the response took:
+    ///
+    /// ```text
+    /// event: invocation
+    /// data: {"duration_ms":1.2,"method":"GET","request_id":"52fdfc07-2182-454f-963f-5f0f9a621d72","status":200,"time":"2026-10-14T10:41:15.123Z","uri":"/users"}
+    /// ```
+    ///
+    /// Open streams keep the server from shutting down until the
+    /// [`shutdown_timeout`](#method.shutdown_timeout) has elapsed. Defaults to `false`.
+    #[must_use]
+    pub fn event_stream(mut self, event_stream: bool) -> Builder {
+        self.event_stream = event_stream;
+        self
+    }
+
+    /// Record each invocation the local development server makes to a JSON file in `dir`, which
+    /// is created if it does not exist.
+    ///
+    /// Each file is named for the time the invocation completed and its request ID, and contains
+    /// an object with the `event` the function was invoked with, and either the `response` it
+    /// returned as the Lambda proxy integration response, or the `error` it failed with. The files
+    /// can be used as test fixtures.
+    #[must_use]
+    pub fn record<P: Into<PathBuf>>(mut self, dir: P) -> Builder {
+        self.record_dir = Some(dir.into());
+        self
+    }
+
+    /// Capture each request the local development server receives, and the response it sends,
+    /// and write them to an HTTP Archive (HAR) file at `path` on shutdown, replacing the file if it
+    /// exists.
+    ///
+    /// HAR files can be opened in browser developer tools, and replayed with
+    /// [`testing::replay_har`](testing/fn.replay_har.html). Requests are captured with their whole
+    /// bodies, so a long session uses memory in proportion to the traffic it serves.
+    #[must_use]
+    pub fn har<P: Into<PathBuf>>(mut self, path: P) -> Builder {
+        self.har_path = Some(path.into());
+        self
+    }
+
+    /// Instead of starting the local development server, read a single event from standard
+    /// input, invoke the function with it, write the response to standard output, and exit.
+    ///
+    /// The event is an API Gateway or ALB proxy integration event, or an invocation saved with
+    /// [`record`](#method.record). The output is the payload AWS Lambda would return: a proxy
+    /// integration response, or an object with the `errorMessage` the function failed with, in
+    /// which case the process exits with status 1.
+    ///
+    /// One-shot mode is also enabled by setting the `LAMBDA_HTTP_LOCAL_ONE_SHOT` environment
+    /// variable. Defaults to `false`.
+    ///
+    /// ```sh
+    /// cargo run < event.json
+    /// ```
+    #[must_use]
+    pub fn one_shot(mut self, one_shot: bool) -> Builder {
+        self.one_shot = one_shot;
+        self
+    }
+
+    /// Serve a web dashboard of recent invocations at `/__lambda/` on the local development
+    /// server, where each request can be expanded to show its headers, bodies, and the event the
+    /// function was invoked with.
+    ///
+    /// The dashboard shows the [`invocation_history`](#method.invocation_history), which is set to
+    /// 100 invocations if it is not set. Defaults to `false`. This method requires the
+    /// `dashboard` feature.
+    #[cfg(feature = "dashboard")]
+    #[must_use]
+    pub fn dashboard(mut self, dashboard: bool) -> Builder {
+        self.dashboard = dashboard;
+        self
+    }
+
+    /// Serve HTTPS instead of HTTP from the local development server.
+    ///
+    /// Unless a certificate is set with [`tls_certificate`](#method.tls_certificate), a
+    /// self-signed certificate for `localhost`, `127.0.0.1`, and `::1` is generated the first time
+    /// the server starts, and kept in `~/.lambda-http-local` for later runs. Clients need to be
+    /// told to trust it, such as with `curl --cacert ~/.lambda-http-local/localhost.pem`. This
+    /// method requires the `tls` feature. Defaults to `false`.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn https(mut self, https: bool) -> Builder {
+        self.https = https;
+        self
+    }
+
+    /// Serve HTTPS instead of HTTP from the local development server, with the PEM-encoded
+    /// certificate chain at `cert` and private key (in PKCS #8 or PKCS #1 format) at `key`.
+    ///
+    /// This is needed to test `Secure` cookies, browser features only available in secure
+    /// contexts, and clients that refuse plain HTTP. HTTP/2 is offered to clients with ALPN. This
+    /// method requires the `tls` feature.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn tls_certificate<C: Into<PathBuf>, K: Into<PathBuf>>(
+        mut self,
+        cert: C,
+        key: K,
+    ) -> Builder {
+        self.tls_certificate = Some((cert.into(), key.into()));
+        self
+    }
+
+    /// Require clients of the local development server to present a certificate signed by one of
+    /// the PEM-encoded CA certificates at `path`, as API Gateway and ALB do with mutual TLS.
+    ///
+    /// Connections without a valid client certificate fail during the TLS handshake. The
+    /// certificate's details are sent to the function in the `clientCert` field of the request
+    /// context, or for ALB profiles in `X-Amzn-Mtls-Clientcert-*` headers, and handlers can read
+    /// them with [`RequestExt::client_cert`](trait.RequestExt.html#tymethod.client_cert). This
+    /// only takes effect when serving HTTPS, with [`https`](#method.https) or
+    /// [`tls_certificate`](#method.tls_certificate). This method requires the `tls` feature.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn tls_client_ca<P: Into<PathBuf>>(mut self, path: P) -> Builder {
+        self.tls_client_ca = Some(path.into());
+        self
+    }
+
+    /// Also serve HTTP/3 from the local development server, over QUIC on the UDP port with the
+    /// same number as its TCP port, as `CloudFront` does with HTTP/3 enabled.
+    ///
+    /// Responses over HTTP/1.1 and HTTP/2 advertise HTTP/3 with an `Alt-Svc` header, so clients
+    /// that support it switch to it. QUIC requires TLS, so this implies [`https`](#method.https)
+    /// unless a certificate is set with [`tls_certificate`](#method.tls_certificate). HTTP/3
+    /// support is experimental: responses are buffered, so the event stream endpoint is not
+    /// available over it. This method requires the `http3` feature. Defaults to `false`.
+    #[cfg(feature = "http3")]
+    #[must_use]
+    pub fn http3(mut self, http3: bool) -> Builder {
+        self.http3 = http3;
+        self
+    }
+
+    /// Send a copy of each request the local development server receives to the deployed function
+    /// at `url`, such as a function URL or an API Gateway stage, and log the status it responds
+    /// with.
+    ///
+    /// Copies are sent in the background, and do not delay the local response. The request path
+    /// is appended to `url`, and the `Host` header is set to its host. This method requires the
+    /// `shadow` feature.
+    ///
+    /// If `url` is not an absolute `http` or `https` URL, the local development server fails to
+    /// start with [`Error::Shadow`](enum.Error.html#variant.Shadow).
+    #[cfg(feature = "shadow")]
+    #[must_use]
+    pub fn shadow(mut self, url: &str) -> Builder {
+        let parsed = url.parse::<http::Uri>().ok().filter(|parsed| {
+            matches!(parsed.scheme_str(), Some("http" | "https"))
+                && parsed.authority_part().is_some()
+        });
+        self.shadow = Some(parsed.ok_or_else(|| url.to_owned()));
+        self
+    }
+
+    /// Compare the response of the deployed function to each request sent with
+    /// [`shadow`](#method.shadow) with the local response, and log the differences in status,
+    /// headers, and body.
+    ///
+    /// Headers that differ between any two responses, like `Date` and `X-Amzn-RequestId`, are not
+    /// compared, nor are those added with [`shadow_ignore_header`](#method.shadow_ignore_header).
+    /// JSON bodies are compared field by field, except for the fields added with
+    /// [`shadow_ignore_field`](#method.shadow_ignore_field). This method requires the `shadow`
+    /// feature. Defaults to `false`.
+    #[cfg(feature = "shadow")]
+    #[must_use]
+    pub fn shadow_compare(mut self, shadow_compare: bool) -> Builder {
+        self.shadow_compare = shadow_compare;
+        self
+    }
+
+    /// Do not compare the header `name` when comparing responses with
+    /// [`shadow_compare`](#method.shadow_compare). This method requires the `shadow` feature.
+    #[cfg(feature = "shadow")]
+    #[must_use]
+    pub fn shadow_ignore_header<T: Into<String>>(mut self, name: T) -> Builder {
+        self.shadow_ignore_headers
+            .push(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Do not compare the field of JSON bodies at `pointer`, a JSON pointer such as
+    /// `/metadata/generated_at`, when comparing responses with
+    /// [`shadow_compare`](#method.shadow_compare). This method requires the `shadow` feature.
+    #[cfg(feature = "shadow")]
+    #[must_use]
+    pub fn shadow_ignore_field<T: Into<String>>(mut self, pointer: T) -> Builder {
+        self.shadow_ignore_fields.push(pointer.into());
+        self
+    }
+
+    /// Add middleware that is called with every request before the handler.
+    ///
+    /// Middleware receives the request and a [`Next`](struct.Next.html), and returns a future
+    /// resolving to the response. It can change the request before passing it on with
+    /// [`Next::run`](struct.Next.html#method.run), change the response after, or respond without
+    /// calling the handler at all. Middleware is called in the order it was added, in AWS Lambda
+    /// and locally alike.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use http::Response;
+    /// use lambda_http_local::Runtime;
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let server = Runtime::builder()
+    ///     .listen_addr("localhost:0")
+    ///     .quiet(true)
+    ///     .with_middleware(|request, next| async move {
+    ///         let mut response = next.run(request).await?;
+    ///         response
+    ///             .headers_mut()
+    ///             .insert("x-powered-by", "lambda-http-local".parse().unwrap());
+    ///         Ok::<_, lambda_http_local::InvocationError>(response)
+    ///     })
+    ///     .build()
+    ///     .spawn(|request| Response::new("Hello, world!"))
+    ///     .unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(server.addr()).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.contains("x-powered-by: lambda-http-local"));
+    /// ```
+    #[must_use]
+    pub fn with_middleware<F, R>(mut self, middleware: F) -> Builder
+    where
+        F: Fn(Request<Vec<u8>>, Next) -> R + Send + Sync + 'static,
+        R: Future + Send + 'static,
+        R::Output: IntoResponse,
+    {
+        self.middleware.push(Arc::new(move |request, next| {
+            middleware(request, next)
+                .map(IntoResponse::into_response)
+                .boxed()
+        }));
+        self
+    }
+
+    /// Set a function that builds the response for requests the handler fails to respond to.
+    ///
+    /// The function is called with the request, without its body, and the error the handler or
+    /// middleware failed with, including panics. This replaces the default behavior of reporting a
+    /// function error in AWS Lambda, which API Gateway turns into a `502 Bad Gateway` response, and
+    /// responding with a `500 Internal Server Error` locally.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use http::{Request, Response, StatusCode};
+    /// use lambda_http_local::Runtime;
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let server = Runtime::builder()
+    ///     .listen_addr("localhost:0")
+    ///     .quiet(true)
+    ///     .on_error(|request, err| {
+    ///         Response::builder()
+    ///             .status(StatusCode::INTERNAL_SERVER_ERROR)
+    ///             .header("content-type", "application/json")
+    ///             .body(format!(r#"{{"error":"{}"}}"#, err.message()).into_bytes())
+    ///             .unwrap()
+    ///     })
+    ///     .build()
+    ///     .spawn(|request| -> Response<Vec<u8>> { panic!("oh no") })
+    ///     .unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(server.addr()).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.ends_with(r#"{"error":"handler panicked: oh no"}"#));
+    /// ```
+    #[must_use]
+    pub fn on_error<F>(mut self, handler: F) -> Builder
+    where
+        F: Fn(&Request<()>, &InvocationError) -> Response<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Add CORS headers to responses to cross-origin requests, as an HTTP API or function URL
+    /// with the CORS configuration `cors` does.
+    ///
+    /// See [`Cors`](struct.Cors.html) for details. The headers are added to every response from
+    /// the handler, including those from middleware and [`on_error`](#method.on_error), and
+    /// preflight requests are answered automatically, in AWS Lambda and locally alike. By
+    /// default, no CORS headers are added, any the handler sets are kept, and preflight requests
+    /// are passed to the handler.
+    #[must_use]
+    pub fn cors(mut self, cors: Cors) -> Builder {
+        self.cors = Some(Arc::new(cors));
+        self
+    }
+
+    /// Create the configured `Runtime`.
+    #[must_use]
+    pub fn build(self) -> Runtime {
+        Runtime {
+            listen_addr: self
+                .listen_addr
+                .unwrap_or_else(|| "localhost:3000".to_socket_addrs().map(Iterator::collect)),
+            port_retries: self.port_retries,
+            #[cfg(unix)]
+            reuse_port: self.reuse_port,
+            #[cfg(unix)]
+            unix_socket: self.unix_socket,
+            #[cfg(feature = "listenfd")]
+            socket_activation: self.socket_activation,
+            body_limit: self.body_limit,
+            max_concurrent_requests: self.max_concurrent_requests,
+            reserved_concurrency: self.reserved_concurrency,
+            injected_latency: self.injected_latency,
+            dropped_connection_rate: self.dropped_connection_rate,
+            truncated_response_rate: self.truncated_response_rate,
+            injected_errors: self.injected_errors,
+            simulation_seed: self.simulation_seed,
+            sequential: self.sequential,
+            max_instances: self.max_instances,
+            cold_start_delay: self.cold_start_delay,
+            instance_idle_timeout: self.instance_idle_timeout,
+            provisioned_concurrency: self.provisioned_concurrency,
+            buffered_body_limit: self.buffered_body_limit,
+            quiet: self.quiet,
+            access_log: self.access_log,
+            platform_log: self.platform_log,
+            access_log_format: self.access_log_format,
+            shutdown_timeout: self.shutdown_timeout,
+            keep_alive_timeout: self.keep_alive_timeout,
+            request_read_timeout: self.request_read_timeout,
+            on_bind: self.on_bind,
+            context: self.context,
+            enforce_timeout: self.enforce_timeout,
+            integration_timeout: self
+                .integration_timeout
+                .unwrap_or(self.profile.integration_timeout()),
+            profile: self.profile,
+            stage: self.stage,
+            binary_media_types: self.binary_media_types,
+            stage_variables: self.stage_variables,
+            enforce_payload_limits: self.enforce_payload_limits,
+            jwt_authorizer: self.jwt_authorizer,
+            lambda_authorizer: self.lambda_authorizer,
+            api_keys: self.api_keys,
+            api_key_quota: self.api_key_quota,
+            #[cfg(feature = "openapi")]
+            openapi: self.openapi,
+            #[cfg(feature = "openapi")]
+            response_validation: self.response_validation,
+            cognito_claims: self.cognito_claims,
+            xray_daemon: self.xray_daemon,
+            metrics_endpoint: self.metrics_endpoint,
+            health_endpoint: self.health_endpoint,
+            invocation_history: self.invocation_history,
+            event_stream: self.event_stream,
+            record_dir: self.record_dir,
+            har_path: self.har_path,
+            one_shot: self.one_shot,
+            #[cfg(feature = "dashboard")]
+            dashboard: self.dashboard,
+            #[cfg(feature = "tls")]
+            https: self.https,
+            #[cfg(feature = "tls")]
+            tls_certificate: self.tls_certificate,
+            #[cfg(feature = "tls")]
+            tls_client_ca: self.tls_client_ca,
+            #[cfg(feature = "http3")]
+            http3: self.http3,
+            #[cfg(feature = "shadow")]
+            shadow: self.shadow,
+            #[cfg(feature = "shadow")]
+            shadow_compare: self.shadow_compare,
+            #[cfg(feature = "shadow")]
+            shadow_ignore_headers: self.shadow_ignore_headers,
+            #[cfg(feature = "shadow")]
+            shadow_ignore_fields: self.shadow_ignore_fields,
+            middleware: self.middleware,
+            error_handler: self.error_handler,
+            cors: self.cors,
+        }
+    }
+}
+
+fn string_map<I, K, V>(pairs: I) -> Map<String, Value>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<String>,
+{
+    pairs
+        .into_iter()
+        .map(|(key, value)| (key.into(), Value::String(value.into())))
+        .collect()
+}
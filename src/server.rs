@@ -0,0 +1,131 @@
+use crate::service::Service;
+use crate::{Error, IntoResponse, Runtime};
+use futures01::sync::oneshot;
+use futures01::Future;
+use http::Request;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::thread::{self, JoinHandle};
+
+/// A local development server running on a background thread.
+///
+/// This is intended for integration tests, which can start the server, send it requests with a
+/// real HTTP client, and shut it down once they are done.
+///
+/// # Example
+///
+/// ```rust
+/// # use http::Response;
+/// use lambda_http_local::LocalServer;
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// let mut server = LocalServer::spawn(
+///     |request| Response::new("Hello, world!"),
+///     "localhost:0",
+/// );
+///
+/// let mut stream = TcpStream::connect(server.addr()).unwrap();
+/// stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).unwrap();
+/// assert!(response.ends_with("Hello, world!"));
+///
+/// server.shutdown();
+/// server.join().unwrap();
+/// ```
+pub struct LocalServer;
+
+impl LocalServer {
+    /// Start a local development server for `handler` on a background thread.
+    ///
+    /// To configure the server further, use [`Runtime::spawn`](struct.Runtime.html#method.spawn).
+    ///
+    /// # Panics
+    ///
+    /// If `listen_addr` cannot be resolved or bound, this function will panic.
+    pub fn spawn<F, R, T>(handler: F, listen_addr: T) -> ServerHandle
+    where
+        F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+        T: ToSocketAddrs,
+    {
+        Runtime::builder()
+            .listen_addr(listen_addr)
+            .quiet(true)
+            .build()
+            .spawn(handler)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+/// A handle to a local development server running on a background thread.
+///
+/// Dropping the handle shuts the server down and waits for it to exit.
+pub struct ServerHandle {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl ServerHandle {
+    /// The address the server is bound to (the first, if it listens on several), or the
+    /// unspecified address `0.0.0.0:0` if it listens on a Unix socket.
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Signal the server to shut down.
+    ///
+    /// The server stops accepting new connections and exits once in-flight requests have
+    /// completed or the shutdown timeout has elapsed. Use [`join`](#method.join) to wait for it.
+    pub fn shutdown(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+
+    /// Wait for the server to exit.
+    ///
+    /// If [`shutdown`](#method.shutdown) has not been called, this waits indefinitely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server failed while running.
+    ///
+    /// # Panics
+    ///
+    /// If the server thread panicked, this function will panic.
+    pub fn join(mut self) -> Result<(), Error> {
+        self.join_thread()
+    }
+
+    fn join_thread(&mut self) -> Result<(), Error> {
+        match self.thread.take() {
+            Some(thread) => thread.join().expect("server thread panicked"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+        self.join_thread().ok();
+    }
+}
+
+pub(crate) fn spawn(runtime: Runtime, service: Service) -> Result<ServerHandle, Error> {
+    let server = crate::local::bind(runtime)?;
+    let addr = server.addr();
+    let (shutdown, signal) = oneshot::channel();
+    let thread = thread::Builder::new()
+        .name(format!("lambda-http-local {addr}"))
+        .spawn(move || server.serve(service, signal.then(|_| Ok(()))))
+        .expect("failed to spawn server thread");
+    Ok(ServerHandle {
+        addr,
+        shutdown: Some(shutdown),
+        thread: Some(thread),
+    })
+}
@@ -0,0 +1,211 @@
+use crate::{metrics, trace};
+use crate::{IntoResponse, InvocationError, TraceContext};
+use futures::future::{self, FutureExt};
+use http::{Request, Response, Uri};
+use lambda_http::request::RequestContext;
+use lambda_runtime::Context;
+use std::any::Any;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub(crate) type BoxFuture =
+    Pin<Box<dyn Future<Output = Result<Response<Vec<u8>>, InvocationError>> + Send>>;
+
+type NewInstance = Arc<dyn Fn() -> Result<Service, String> + Send + Sync>;
+
+/// Whether a router has an `OPTIONS` route for a URI.
+pub(crate) type OptionsRoutes = Arc<dyn Fn(&Uri) -> bool + Send + Sync>;
+
+/// A handler with its request and response types normalized.
+#[derive(Clone)]
+pub(crate) struct Service {
+    call: Arc<dyn Fn(Request<Vec<u8>>) -> BoxFuture + Send + Sync>,
+    /// Whether calling the handler blocks until the response is ready.
+    blocking: bool,
+    /// Creates the handler for each new execution environment, if they do not share one.
+    new_instance: Option<NewInstance>,
+    /// Whether the handler has an `OPTIONS` route for a URI, if it is a router.
+    options_routes: Option<OptionsRoutes>,
+}
+
+impl Service {
+    pub(crate) fn new<F>(call: F, blocking: bool) -> Service
+    where
+        F: Fn(Request<Vec<u8>>) -> BoxFuture + Send + Sync + 'static,
+    {
+        Service {
+            call: Arc::new(call),
+            blocking,
+            new_instance: None,
+            options_routes: None,
+        }
+    }
+
+    pub(crate) fn from_sync<F, R>(handler: F) -> Service
+    where
+        F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        Service {
+            call: Arc::new(move |request| {
+                let (parts, body) = request.into_parts();
+                future::ready(handler(Request::from_parts(parts, &body)).into_response()).boxed()
+            }),
+            blocking: true,
+            new_instance: None,
+            options_routes: None,
+        }
+    }
+
+    pub(crate) fn from_async<F, R>(handler: F) -> Service
+    where
+        F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+        R: Future + Send + 'static,
+        R::Output: IntoResponse,
+    {
+        Service {
+            call: Arc::new(move |request| {
+                handler(request).map(IntoResponse::into_response).boxed()
+            }),
+            blocking: false,
+            new_instance: None,
+            options_routes: None,
+        }
+    }
+
+    /// A handler that fails every invocation with `message`.
+    pub(crate) fn failing(message: String) -> Service {
+        Service::new(
+            move |_| future::ready(Err(InvocationError::new(message.clone()))).boxed(),
+            false,
+        )
+    }
+
+    /// A handler created anew by `new_instance` for each execution environment, which fails
+    /// with the message of an init error if it can not be created.
+    ///
+    /// No handler is created until an execution environment is started, so the returned one only
+    /// creates them, and fails any invocation it is called for itself.
+    pub(crate) fn per_instance<F>(new_instance: F) -> Service
+    where
+        F: Fn() -> Result<Service, String> + Send + Sync + 'static,
+    {
+        let mut service = Service::failing("no execution environment was started".to_owned());
+        service.new_instance = Some(Arc::new(new_instance));
+        service
+    }
+
+    /// Whether each execution environment has its own handler.
+    #[cfg_attr(not(feature = "local"), allow(dead_code))]
+    pub(crate) fn is_per_instance(&self) -> bool {
+        self.new_instance.is_some()
+    }
+
+    /// Create the handler for a new execution environment, if they do not share this one, or
+    /// the message of the init error it failed with.
+    #[cfg_attr(not(feature = "local"), allow(dead_code))]
+    pub(crate) fn new_instance(&self) -> Option<Result<Service, String>> {
+        self.new_instance
+            .as_ref()
+            .map(|new_instance| new_instance())
+    }
+
+    /// Create the handler for the only execution environment, as when each runs in its own
+    /// process, or the message of the init error it failed with.
+    pub(crate) fn instantiate(self) -> Result<Service, String> {
+        self.new_instance().unwrap_or(Ok(self))
+    }
+
+    /// Wrap this handler with `wrap`, along with each one created for a new execution
+    /// environment.
+    pub(crate) fn map<F>(mut self, wrap: F) -> Service
+    where
+        F: Fn(Service) -> Service + Send + Sync + 'static,
+    {
+        let wrap = Arc::new(wrap);
+        let new_instance = self.new_instance.take().map(|new_instance| {
+            let wrap = wrap.clone();
+            Arc::new(move || new_instance().map(|service| wrap(service))) as NewInstance
+        });
+        let mut service = wrap(self);
+        service.new_instance = new_instance;
+        service
+    }
+
+    /// Mark this handler as a router with an `OPTIONS` route for the URIs `options_routes`
+    /// returns `true` for.
+    pub(crate) fn with_options_routes<F>(mut self, options_routes: F) -> Service
+    where
+        F: Fn(&Uri) -> bool + Send + Sync + 'static,
+    {
+        self.options_routes = Some(Arc::new(options_routes));
+        self
+    }
+
+    /// The function telling whether this handler has an `OPTIONS` route for a URI, if it is a
+    /// router.
+    pub(crate) fn options_routes(&self) -> Option<OptionsRoutes> {
+        self.options_routes.clone()
+    }
+
+    pub(crate) fn call(&self, request: Request<Vec<u8>>) -> BoxFuture {
+        (self.call)(request)
+    }
+
+    /// Call the handler for an invocation, in a span with the `tracing` feature, converting
+    /// panics into errors, and writing the metrics it recorded once it completes, along with any
+    /// warnings about them unless `quiet` is set.
+    pub(crate) fn invoke(&self, request: Request<Vec<u8>>, quiet: bool) -> BoxFuture {
+        trace::invocation(request, |request| {
+            metrics::scope(request, quiet, |request| self.call_catching_panics(request))
+        })
+    }
+
+    /// Call the handler, converting a panic while handling the request into an error so that it
+    /// does not take down the server.
+    pub(crate) fn call_catching_panics(&self, request: Request<Vec<u8>>) -> BoxFuture {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.call(request))) {
+            Ok(response) => AssertUnwindSafe(response)
+                .catch_unwind()
+                .map(|result| result.unwrap_or_else(|payload| Err(panicked(&*payload))))
+                .boxed(),
+            Err(payload) => future::ready(Err(panicked(&*payload))).boxed(),
+        }
+    }
+
+    pub(crate) fn is_blocking(&self) -> bool {
+        self.blocking
+    }
+}
+
+/// Copy the method, URI, version, headers, and contexts of `request`, for callbacks that
+/// are given the request without its body.
+pub(crate) fn request_head<B>(request: &Request<B>) -> Request<()> {
+    let mut head = Request::builder();
+    head.method(request.method().clone())
+        .uri(request.uri().clone())
+        .version(request.version());
+    let mut head = head.body(()).expect("failed to build request");
+    *head.headers_mut() = request.headers().clone();
+    if let Some(context) = request.extensions().get::<Context>() {
+        head.extensions_mut().insert(context.clone());
+    }
+    if let Some(context) = request.extensions().get::<RequestContext>() {
+        head.extensions_mut().insert(context.clone());
+    }
+    if let Some(context) = request.extensions().get::<TraceContext>() {
+        head.extensions_mut().insert(context.clone());
+    }
+    head
+}
+
+fn panicked(payload: &(dyn Any + Send)) -> InvocationError {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    InvocationError::new(format!("handler panicked: {message}"))
+}
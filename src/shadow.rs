@@ -0,0 +1,225 @@
+//! Mirroring the local development server's requests to a deployed endpoint.
+
+use futures01::{Future, Stream};
+use http::header::{HeaderName, HOST};
+use http::{HeaderMap, HeaderValue, Request, Response, Uri};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client};
+use hyper_rustls::HttpsConnector;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Headers that differ between any two responses, and are never compared.
+const IGNORED_HEADERS: &[&str] = &[
+    "apigw-requestid",
+    "connection",
+    "content-length",
+    "date",
+    "server",
+    "via",
+    "x-amz-apigw-id",
+    "x-amz-cf-id",
+    "x-amz-cf-pop",
+    "x-amzn-remapped-date",
+    "x-amzn-requestid",
+    "x-amzn-trace-id",
+    "x-cache",
+];
+
+/// The most differences reported between two JSON bodies.
+const JSON_DIFFERENCE_LIMIT: usize = 10;
+
+/// The deployed endpoint to send copies of requests to.
+pub(crate) struct Shadow {
+    base: String,
+    host: Option<HeaderValue>,
+    client: Client<HttpsConnector<HttpConnector>>,
+    compare: Option<Compare>,
+}
+
+/// The parts of responses that are not compared, beyond the ignored headers.
+pub(crate) struct Compare {
+    /// Lowercase header names.
+    pub(crate) ignore_headers: Vec<String>,
+    /// JSON pointers to fields of JSON bodies.
+    pub(crate) ignore_fields: Vec<String>,
+}
+
+impl Shadow {
+    /// Mirror requests to the endpoint at `base`, which may include a path (such as an API
+    /// Gateway stage) to prefix request paths with, comparing responses if `compare` is set.
+    pub(crate) fn new(base: &Uri, compare: Option<Compare>) -> Shadow {
+        let host = base
+            .authority_part()
+            .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok());
+        Shadow {
+            base: base.to_string().trim_end_matches('/').to_owned(),
+            host,
+            client: Client::builder().build(HttpsConnector::new(1)),
+            compare,
+        }
+    }
+
+    /// Whether the deployed responses are compared with the local responses.
+    pub(crate) fn compares(&self) -> bool {
+        self.compare.is_some()
+    }
+
+    /// Describe the differences between the `local` and `remote` responses to a request.
+    pub(crate) fn diff(
+        &self,
+        local: &Response<Vec<u8>>,
+        remote: &Response<Vec<u8>>,
+    ) -> Vec<String> {
+        let mut differences = Vec::new();
+        let Some(compare) = &self.compare else {
+            return differences;
+        };
+        if local.status() != remote.status() {
+            differences.push(format!(
+                "status: {} locally, {} deployed",
+                local.status().as_u16(),
+                remote.status().as_u16()
+            ));
+        }
+
+        let names = local
+            .headers()
+            .keys()
+            .chain(remote.headers().keys())
+            .map(HeaderName::as_str)
+            .filter(|name| {
+                !IGNORED_HEADERS.contains(name) && !compare.ignore_headers.iter().any(|n| n == name)
+            })
+            .collect::<BTreeSet<_>>();
+        for name in names {
+            let (local_value, remote_value) = (
+                header(local.headers(), name),
+                header(remote.headers(), name),
+            );
+            if local_value != remote_value {
+                differences.push(format!(
+                    "header {}: {} locally, {} deployed",
+                    name,
+                    local_value.as_deref().unwrap_or("missing"),
+                    remote_value.as_deref().unwrap_or("missing")
+                ));
+            }
+        }
+
+        let json = serde_json::from_slice::<Value>(local.body())
+            .and_then(|local| Ok((local, serde_json::from_slice::<Value>(remote.body())?)));
+        if let Ok((mut local_json, mut remote_json)) = json {
+            for pointer in &compare.ignore_fields {
+                for json in [&mut local_json, &mut remote_json] {
+                    if let Some(field) = json.pointer_mut(pointer) {
+                        *field = Value::Null;
+                    }
+                }
+            }
+            let mut json_differences = Vec::new();
+            diff_json("", &local_json, &remote_json, &mut json_differences);
+            let omitted = json_differences.len().saturating_sub(JSON_DIFFERENCE_LIMIT);
+            json_differences.truncate(JSON_DIFFERENCE_LIMIT);
+            differences.extend(json_differences);
+            if omitted > 0 {
+                differences.push(format!("body: {omitted} more differences"));
+            }
+        } else if local.body() != remote.body() {
+            differences.push(format!(
+                "body: {} bytes locally, {} bytes deployed, contents differ",
+                local.body().len(),
+                remote.body().len()
+            ));
+        }
+        differences
+    }
+
+    /// Send `request` to the endpoint, resolving to the response it returns.
+    pub(crate) fn forward(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> impl Future<Item = Response<Vec<u8>>, Error = String> + Send {
+        let (mut parts, body) = request.into_parts();
+        let path = parts.uri.path_and_query().map_or("/", |path| path.as_str());
+        let uri = format!("{}{}", self.base, path).parse::<Uri>();
+        if let Some(host) = &self.host {
+            parts.headers.insert(HOST, host.clone());
+        }
+        let client = self.client.clone();
+        futures01::future::result(uri.map_err(|err| err.to_string()))
+            .and_then(move |uri| {
+                parts.uri = uri;
+                client
+                    .request(Request::from_parts(parts, Body::from(body)))
+                    .map_err(|err| err.to_string())
+            })
+            .and_then(|response| {
+                let (parts, body) = response.into_parts();
+                body.concat2()
+                    .map(|body| Response::from_parts(parts, body.to_vec()))
+                    .map_err(|err| err.to_string())
+            })
+    }
+}
+
+/// Copy a response, without its extensions.
+pub(crate) fn copy_response(response: &Response<Vec<u8>>) -> Response<Vec<u8>> {
+    let mut copy = Response::new(response.body().clone());
+    *copy.status_mut() = response.status();
+    *copy.version_mut() = response.version();
+    *copy.headers_mut() = response.headers().clone();
+    copy
+}
+
+/// The values of a header, joined by commas.
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values = headers
+        .get_all(name)
+        .iter()
+        .map(|value| String::from_utf8_lossy(value.as_bytes()))
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.join(", "))
+    }
+}
+
+/// Describe the differences between two JSON values, identifying each by its JSON pointer.
+fn diff_json(pointer: &str, local: &Value, remote: &Value, differences: &mut Vec<String>) {
+    match (local, remote) {
+        (Value::Object(local), Value::Object(remote)) => {
+            let keys = local.keys().chain(remote.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                let pointer = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                match (local.get(key), remote.get(key)) {
+                    (Some(local), Some(remote)) => diff_json(&pointer, local, remote, differences),
+                    (local, remote) => differences.push(json_difference(&pointer, local, remote)),
+                }
+            }
+        }
+        (Value::Array(local_items), Value::Array(remote_items))
+            if local_items.len() == remote_items.len() =>
+        {
+            for (index, (local, remote)) in local_items.iter().zip(remote_items).enumerate() {
+                diff_json(&format!("{pointer}/{index}"), local, remote, differences);
+            }
+        }
+        _ if local != remote => {
+            differences.push(json_difference(pointer, Some(local), Some(remote)));
+        }
+        _ => {}
+    }
+}
+
+fn json_difference(pointer: &str, local: Option<&Value>, remote: Option<&Value>) -> String {
+    let describe =
+        |value: Option<&Value>| value.map_or_else(|| "missing".to_owned(), Value::to_string);
+    format!(
+        "body {}: {} locally, {} deployed",
+        if pointer.is_empty() { "/" } else { pointer },
+        describe(local),
+        describe(remote)
+    )
+}
@@ -0,0 +1,564 @@
+//! Helpers for testing handlers without starting a server.
+
+use crate::event::{self, Connection, Options};
+use crate::service::Service;
+use crate::{har, lambda, ContextBuilder, IntoResponse, InvocationError, Router};
+use futures::TryFutureExt;
+use http::header::CONTENT_TYPE;
+use http::{Method, Request, Response, Uri};
+use lambda_http::request::RequestContext;
+use serde_json::Value;
+use std::fmt::{self, Display, Write};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{fs, io};
+
+/// Call `handler` with `request`, applying the same conversions used in AWS Lambda.
+///
+/// The request is converted into an API Gateway proxy integration event, which is then parsed the
+/// same way as events received from the Lambda Runtime API. No socket is opened.
+///
+/// As in AWS Lambda, the URI the handler receives is built from the `Host` header and the path,
+/// and does not include the query string. The request appears to come from `127.0.0.1` over HTTPS,
+/// as reported by the `X-Forwarded-*` headers. Bodies are converted as by a REST API without binary
+/// media types, so request bodies that are not valid UTF-8 are corrupted.
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Method, Request, Response, Uri};
+/// use lambda_http_local::testing;
+///
+/// let handler = |request: Request<&[u8]>| {
+///     Response::new(format!("Hello from {}", request.uri().path()))
+/// };
+/// let response = testing::invoke(handler, Request::get("/test").body(Vec::new()).unwrap());
+/// assert_eq!(response.unwrap().body(), b"Hello from /test");
+/// ```
+///
+/// # Errors
+///
+/// Returns the error the handler failed with, which in AWS Lambda would be reported as a function
+/// error. A handler that panics fails with an error including the panic message.
+///
+/// ```rust
+/// # use http::{Method, Request, Response, Uri};
+/// use lambda_http_local::testing;
+///
+/// let handler = |_: Request<&[u8]>| -> Response<Vec<u8>> { panic!("oh no") };
+/// let err = testing::invoke(handler, Request::get("/").body(Vec::new()).unwrap()).unwrap_err();
+/// assert_eq!(err.message(), "handler panicked: oh no");
+/// ```
+pub fn invoke<F, R>(
+    handler: F,
+    request: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, InvocationError>
+where
+    F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    invoke_service(&Service::from_sync(handler), request)
+}
+
+/// Call an asynchronous `handler` with `request`, applying the same conversions used in AWS Lambda.
+///
+/// See [`invoke`](fn.invoke.html) for details.
+///
+/// # Errors
+///
+/// Returns the error the handler failed with.
+pub fn invoke_async<F, R>(
+    handler: F,
+    request: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, InvocationError>
+where
+    F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+    R: Future + Send + 'static,
+    R::Output: IntoResponse,
+{
+    invoke_service(&Service::from_async(handler), request)
+}
+
+/// Call `router` with `request`, applying the same conversions used in AWS Lambda.
+///
+/// See [`invoke`](fn.invoke.html) for details.
+///
+/// # Errors
+///
+/// Returns the error the matched handler failed with.
+pub fn invoke_router(
+    router: Router,
+    request: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, InvocationError> {
+    invoke_service(&router.into_service(), request)
+}
+
+/// Call `handler` with each event saved in `path`, without opening a socket.
+///
+/// `path` is either a JSON file or a directory, in which case each `.json` file in it is replayed
+/// in order of file name. Each file contains an API Gateway or ALB proxy integration event, as
+/// received by the function in AWS Lambda, or an invocation saved by the local development server
+/// with [`Builder::record`](../struct.Builder.html#method.record).
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Method, Request, Response, Uri};
+/// use lambda_http_local::testing;
+/// # let dir = std::env::temp_dir().join("lambda-http-local-replay-doctest");
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// # std::fs::write(dir.join("event.json"), r#"{
+/// #     "httpMethod": "GET", "path": "/test", "headers": {"host": "example.com"},
+/// #     "requestContext": {"httpMethod": "GET", "path": "/test", "stage": "prod",
+/// #         "accountId": "", "resourceId": "", "resourcePath": "/test", "requestId": "",
+/// #         "apiId": "", "identity": {"sourceIp": "127.0.0.1"}}
+/// # }"#).unwrap();
+///
+/// let handler = |request: Request<&[u8]>| {
+///     Response::new(format!("Hello from {}", request.uri().path()))
+/// };
+/// for replay in testing::replay(handler, &dir).unwrap() {
+///     println!("{}: {}", replay.path().display(), replay.response_event());
+///     assert_eq!(replay.result().as_ref().unwrap().body(), b"Hello from /test");
+/// }
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `path` could not be read, or a file does not contain a valid event.
+pub fn replay<F, R, P>(handler: F, path: P) -> io::Result<Vec<Replay>>
+where
+    F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+    P: AsRef<Path>,
+{
+    replay_service(&Service::from_sync(handler), path.as_ref())
+}
+
+/// Call an asynchronous `handler` with each event saved in `path`, without opening a socket.
+///
+/// See [`replay`](fn.replay.html) for details.
+///
+/// # Errors
+///
+/// Returns an error if `path` could not be read, or a file does not contain a valid event.
+pub fn replay_async<F, R, P>(handler: F, path: P) -> io::Result<Vec<Replay>>
+where
+    F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+    R: Future + Send + 'static,
+    R::Output: IntoResponse,
+    P: AsRef<Path>,
+{
+    replay_service(&Service::from_async(handler), path.as_ref())
+}
+
+/// Call `handler` with each request in the HTTP Archive (HAR) file at `path`, without opening a
+/// socket, and compare its responses with those recorded in the file.
+///
+/// HAR files can be exported from browser developer tools, or written by the local development
+/// server with [`Builder::har`](../struct.Builder.html#method.har), so a browser session captured
+/// against a deployed function can be re-run through new handler code. Requests are converted
+/// as by [`invoke`](fn.invoke.html).
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Request, Response, StatusCode};
+/// use lambda_http_local::testing;
+/// # let path = std::env::temp_dir().join("lambda-http-local-replay-har-doctest.har");
+/// # std::fs::write(&path, r#"{"log": {"version": "1.2", "entries": [{
+/// #     "startedDateTime": "2024-01-01T00:00:00.000Z", "time": 120.5,
+/// #     "request": {"method": "GET", "url": "https://api.example.com/missing", "headers": []},
+/// #     "response": {"status": 200}
+/// # }]}}"#).unwrap();
+///
+/// let handler = |_: Request<&[u8]>| {
+///     let mut response = Response::new(Vec::new());
+///     *response.status_mut() = StatusCode::NOT_FOUND;
+///     response
+/// };
+/// for replay in testing::replay_har(handler, &path).unwrap() {
+///     if replay.status_changed() {
+///         println!("{}", replay);
+///     }
+///     assert_eq!(replay.recorded_status(), 200);
+///     assert_eq!(replay.status(), Some(404));
+/// }
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `path` could not be read, or is not a valid HAR file.
+pub fn replay_har<F, R, P>(handler: F, path: P) -> io::Result<Vec<HarReplay>>
+where
+    F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+    P: AsRef<Path>,
+{
+    replay_har_service(&Service::from_sync(handler), path.as_ref())
+}
+
+/// Call an asynchronous `handler` with each request in the HAR file at `path`, without opening a
+/// socket, and compare its responses with those recorded in the file.
+///
+/// See [`replay_har`](fn.replay_har.html) for details.
+///
+/// # Errors
+///
+/// Returns an error if `path` could not be read, or is not a valid HAR file.
+pub fn replay_har_async<F, R, P>(handler: F, path: P) -> io::Result<Vec<HarReplay>>
+where
+    F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+    R: Future + Send + 'static,
+    R::Output: IntoResponse,
+    P: AsRef<Path>,
+{
+    replay_har_service(&Service::from_async(handler), path.as_ref())
+}
+
+/// Call `handler` with a proxy integration `event` and describe the response in a canonical,
+/// diff-friendly form, for comparing against golden files or with snapshot testing tools such as
+/// `insta`.
+///
+/// The description starts with the status line, followed by the response headers sorted by name
+/// (repeated headers keep their order), a blank line, and the body. JSON bodies are
+/// pretty-printed, and bodies that are not valid UTF-8 are base64-encoded. If the handler fails,
+/// the description is the error message instead.
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Method, Request, Response, Uri};
+/// use lambda_http_local::fixtures::EventBuilder;
+/// use lambda_http_local::testing;
+///
+/// let handler = |_: Request<&[u8]>| {
+///     Response::builder()
+///         .header("x-request-count", "1")
+///         .header("content-type", "application/json")
+///         .body(br#"{"id":42,"tags":["new"]}"#.to_vec())
+///         .unwrap()
+/// };
+/// let snapshot = testing::snapshot(handler, EventBuilder::http_api_v2().path("/users/42").build());
+/// assert_eq!(
+///     snapshot,
+///     r#"HTTP 200 OK
+/// content-type: application/json
+/// x-request-count: 1
+///
+/// {
+///   "id": 42,
+///   "tags": [
+///     "new"
+///   ]
+/// }
+/// "#
+/// );
+/// ```
+///
+/// # Panics
+///
+/// If `event` is not a valid event, this function will panic.
+pub fn snapshot<F, R>(handler: F, event: Value) -> String
+where
+    F: Fn(Request<&[u8]>) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    snapshot_service(&Service::from_sync(handler), event)
+}
+
+/// Call an asynchronous `handler` with a proxy integration `event` and describe the response in
+/// a canonical, diff-friendly form.
+///
+/// See [`snapshot`](fn.snapshot.html) for details.
+///
+/// # Panics
+///
+/// If `event` is not a valid event, this function will panic.
+pub fn snapshot_async<F, R>(handler: F, event: Value) -> String
+where
+    F: Fn(Request<Vec<u8>>) -> R + Send + Sync + 'static,
+    R: Future + Send + 'static,
+    R::Output: IntoResponse,
+{
+    snapshot_service(&Service::from_async(handler), event)
+}
+
+/// The result of calling a handler with a saved event.
+///
+/// See [`replay`](fn.replay.html).
+#[derive(Debug)]
+pub struct Replay {
+    path: PathBuf,
+    is_alb: bool,
+    result: Result<Response<Vec<u8>>, InvocationError>,
+}
+
+impl Replay {
+    /// The file the event was read from.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The response the handler returned, or the error it failed with.
+    pub fn result(&self) -> &Result<Response<Vec<u8>>, InvocationError> {
+        &self.result
+    }
+
+    /// Convert into the response the handler returned, or the error it failed with.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the handler failed with.
+    pub fn into_result(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        self.result
+    }
+
+    /// The payload AWS Lambda would return for the invocation: a proxy integration response, or
+    /// an object with the `errorMessage` the handler failed with.
+    #[must_use]
+    pub fn response_event(&self) -> Value {
+        match &self.result {
+            Ok(response) => lambda::response_event(self.is_alb, response),
+            Err(err) => serde_json::json!({ "errorMessage": err.message() }),
+        }
+    }
+
+    /// Describe the response in a canonical, diff-friendly form, as
+    /// [`snapshot`](fn.snapshot.html) does.
+    #[must_use]
+    pub fn snapshot(&self) -> String {
+        describe(&self.result)
+    }
+}
+
+fn snapshot_service(service: &Service, event: Value) -> String {
+    let request = event::from_saved_event(event).expect("invalid event");
+    describe(&call(service, request))
+}
+
+/// Describe a response for [`snapshot`](fn.snapshot.html).
+fn describe(result: &Result<Response<Vec<u8>>, InvocationError>) -> String {
+    let response = match result {
+        Ok(response) => response,
+        Err(err) => return format!("error: {}\n", err.message()),
+    };
+    let status = response.status();
+    let mut snapshot = format!(
+        "HTTP {} {}\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+    let mut names = response.headers().keys().collect::<Vec<_>>();
+    names.sort_by_key(|name| name.as_str());
+    for name in names {
+        for value in response.headers().get_all(name) {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            let _ = writeln!(snapshot, "{name}: {value}");
+        }
+    }
+    snapshot.push('\n');
+
+    let body = response.body();
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.contains("json"));
+    let pretty = serde_json::from_slice::<Value>(body)
+        .ok()
+        .filter(|_| is_json)
+        .and_then(|json| serde_json::to_string_pretty(&json).ok());
+    if let Some(pretty) = pretty {
+        snapshot.push_str(&pretty);
+    } else if let Ok(text) = std::str::from_utf8(body) {
+        snapshot.push_str(text);
+    } else {
+        let _ = write!(snapshot, "base64: {}", base64::encode(body));
+    }
+    if !body.is_empty() && !snapshot.ends_with('\n') {
+        snapshot.push('\n');
+    }
+    snapshot
+}
+
+fn replay_service(service: &Service, path: &Path) -> io::Result<Vec<Replay>> {
+    let paths = if path.is_dir() {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths
+    } else {
+        vec![path.to_owned()]
+    };
+    paths
+        .into_iter()
+        .map(|path| {
+            let invalid = |err: serde_json::Error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), err),
+                )
+            };
+            let event = serde_json::from_slice(&fs::read(&path)?).map_err(invalid)?;
+            let request = event::from_saved_event(event).map_err(invalid)?;
+            let is_alb = request
+                .extensions()
+                .get::<RequestContext>()
+                .is_some_and(RequestContext::is_alb);
+            Ok(Replay {
+                path,
+                is_alb,
+                result: call(service, request),
+            })
+        })
+        .collect()
+}
+
+/// The result of calling a handler with a request from a HAR file, compared with the response
+/// recorded for it.
+///
+/// See [`replay_har`](fn.replay_har.html). The `Display` implementation summarizes the request
+/// and the differences in status and latency.
+#[derive(Debug)]
+pub struct HarReplay {
+    method: Method,
+    uri: Uri,
+    recorded_status: u16,
+    recorded_time: Duration,
+    time: Duration,
+    result: Result<Response<Vec<u8>>, InvocationError>,
+}
+
+impl HarReplay {
+    /// The method of the request.
+    #[must_use]
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// The path and query string of the request.
+    #[must_use]
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// The status of the response recorded in the HAR file, or 0 if none was recorded.
+    #[must_use]
+    pub fn recorded_status(&self) -> u16 {
+        self.recorded_status
+    }
+
+    /// The status of the response the handler returned, or `None` if it failed.
+    #[must_use]
+    pub fn status(&self) -> Option<u16> {
+        self.result
+            .as_ref()
+            .ok()
+            .map(|response| response.status().as_u16())
+    }
+
+    /// Whether the handler failed, or returned a response with a different status than the one
+    /// recorded.
+    #[must_use]
+    pub fn status_changed(&self) -> bool {
+        self.status() != Some(self.recorded_status)
+    }
+
+    /// How long the recorded request took, including the network.
+    #[must_use]
+    pub fn recorded_time(&self) -> Duration {
+        self.recorded_time
+    }
+
+    /// How long the handler took.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// The response the handler returned, or the error it failed with.
+    pub fn result(&self) -> &Result<Response<Vec<u8>>, InvocationError> {
+        &self.result
+    }
+
+    /// Convert into the response the handler returned, or the error it failed with.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the handler failed with.
+    pub fn into_result(self) -> Result<Response<Vec<u8>>, InvocationError> {
+        self.result
+    }
+}
+
+impl Display for HarReplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {} -> ",
+            self.method, self.uri, self.recorded_status
+        )?;
+        match self.status() {
+            Some(status) => write!(f, "{status}")?,
+            None => f.write_str("error")?,
+        }
+        write!(
+            f,
+            ", {:.1} ms -> {:.1} ms",
+            self.recorded_time.as_secs_f64() * 1000.0,
+            self.time.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+fn replay_har_service(service: &Service, path: &Path) -> io::Result<Vec<HarReplay>> {
+    Ok(har::read(path)?
+        .into_iter()
+        .map(|entry| {
+            let method = entry.request.method().clone();
+            let uri = entry.request.uri().clone();
+            let start = Instant::now();
+            let result = invoke_service(service, entry.request);
+            HarReplay {
+                method,
+                uri,
+                recorded_status: entry.status,
+                recorded_time: entry.time,
+                time: start.elapsed(),
+                result,
+            }
+        })
+        .collect())
+}
+
+fn invoke_service(
+    service: &Service,
+    request: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, InvocationError> {
+    call(
+        service,
+        event::to_lambda_request(&Options::default(), Connection::default(), request),
+    )
+}
+
+fn call(
+    service: &Service,
+    request: lambda_http::Request,
+) -> Result<Response<Vec<u8>>, InvocationError> {
+    let mut runtime =
+        tokio::runtime::current_thread::Runtime::new().expect("failed to start Tokio runtime");
+    runtime.block_on(lambda::invoke(service, request, ContextBuilder::new().build()).compat())
+}
@@ -0,0 +1,120 @@
+//! Serving HTTPS from the local development server.
+
+use crate::Error;
+use rcgen::{Certificate, CertificateParams, DistinguishedName, DnType, SanType};
+use rustls::internal::pemfile;
+use rustls::{AllowAnyAuthenticatedClient, NoClientAuth, RootCertStore, ServerConfig};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_rustls::TlsAcceptor;
+
+/// The directory, in the home directory, the development certificate is kept in.
+const CERTIFICATE_DIR: &str = ".lambda-http-local";
+
+/// Build an acceptor for TLS connections from a PEM-encoded certificate chain and private key,
+/// requiring clients to present a certificate signed by the PEM-encoded CA certificates at
+/// `client_ca_path`, if set.
+pub(crate) fn acceptor(
+    cert_path: &Path,
+    key_path: &Path,
+    client_ca_path: Option<&Path>,
+) -> Result<TlsAcceptor, Error> {
+    let certs = pemfile::certs(&mut open(cert_path)?)
+        .map_err(|()| invalid(cert_path, "invalid PEM certificate"))?;
+    if certs.is_empty() {
+        return Err(invalid(cert_path, "no certificates found"));
+    }
+    let mut keys = pemfile::pkcs8_private_keys(&mut open(key_path)?)
+        .map_err(|()| invalid(key_path, "invalid PEM private key"))?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut open(key_path)?)
+            .map_err(|()| invalid(key_path, "invalid PEM private key"))?;
+    }
+    let key = keys
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid(key_path, "no private key found"))?;
+
+    let verifier = match client_ca_path {
+        Some(client_ca_path) => {
+            let mut roots = RootCertStore::empty();
+            let (added, _) = roots
+                .add_pem_file(&mut open(client_ca_path)?)
+                .map_err(|()| invalid(client_ca_path, "invalid PEM certificate"))?;
+            if added == 0 {
+                return Err(invalid(client_ca_path, "no certificates found"));
+            }
+            AllowAnyAuthenticatedClient::new(roots)
+        }
+        None => NoClientAuth::new(),
+    };
+    let mut config = ServerConfig::new(verifier);
+    config
+        .set_single_cert(certs, key)
+        .map_err(|err| invalid(cert_path, &err.to_string()))?;
+    config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, Error> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| Error::Tls(path.to_owned(), err))
+}
+
+pub(crate) fn invalid(path: &Path, message: &str) -> Error {
+    Error::Tls(
+        path.to_owned(),
+        io::Error::new(io::ErrorKind::InvalidData, message),
+    )
+}
+
+/// The paths of the self-signed development certificate for `localhost` and its private key,
+/// generating them if they do not exist yet, and whether they were generated.
+///
+/// The certificate is kept in `~/.lambda-http-local`, so that once it is trusted, it stays
+/// trusted across runs and projects.
+pub(crate) fn development_certificate() -> Result<(PathBuf, PathBuf, bool), Error> {
+    let dir = env::var_os("HOME")
+        .map_or_else(PathBuf::new, PathBuf::from)
+        .join(CERTIFICATE_DIR);
+    let cert_path = dir.join("localhost.pem");
+    let key_path = dir.join("localhost-key.pem");
+    if cert_path.exists() && key_path.exists() {
+        return Ok((cert_path, key_path, false));
+    }
+
+    let mut params = CertificateParams::default();
+    let mut name = DistinguishedName::new();
+    name.push(
+        DnType::CommonName,
+        "lambda-http-local development certificate",
+    );
+    params.distinguished_name = name;
+    params.subject_alt_names = vec![
+        SanType::DnsName("localhost".to_owned()),
+        SanType::IpAddress(Ipv4Addr::LOCALHOST.into()),
+        SanType::IpAddress(Ipv6Addr::LOCALHOST.into()),
+    ];
+    let generated = Certificate::from_params(params)
+        .and_then(|cert| Ok((cert.serialize_pem()?, cert.serialize_private_key_pem())))
+        .map_err(|err| invalid(&cert_path, &err.to_string()))?;
+    fs::create_dir_all(&dir).map_err(|err| Error::Tls(dir.clone(), err))?;
+    fs::write(&cert_path, generated.0).map_err(|err| Error::Tls(cert_path.clone(), err))?;
+    write_private(&key_path, generated.1.as_bytes())
+        .map_err(|err| Error::Tls(key_path.clone(), err))?;
+    Ok((cert_path, key_path, true))
+}
+
+/// Write a file only the current user can read.
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
@@ -0,0 +1,35 @@
+use crate::service::Service;
+use crate::InvocationError;
+use bytes::Bytes;
+use futures::future::{self, FutureExt};
+use http::{Request, Response};
+use std::fmt::Display;
+use std::sync::Mutex;
+
+/// Wrap a `tower::Service` as a handler. Each request is sent to a clone of `service`, as tower
+/// services are generally used from several tasks at once.
+pub(crate) fn service<S>(service: S) -> Service
+where
+    S: tower_service::Service<Request<Bytes>, Response = Response<Bytes>> + Clone + Send + 'static,
+    S::Error: Display,
+    S::Future: Send + 'static,
+{
+    let service = Mutex::new(service);
+    Service::new(
+        move |request: Request<Vec<u8>>| {
+            let mut service = service.lock().expect("service mutex poisoned").clone();
+            async move {
+                future::poll_fn(|cx| service.poll_ready(cx))
+                    .await
+                    .map_err(InvocationError::new)?;
+                let response = service
+                    .call(request.map(Bytes::from))
+                    .await
+                    .map_err(InvocationError::new)?;
+                Ok(response.map(|body| body.to_vec()))
+            }
+            .boxed()
+        },
+        false,
+    )
+}
@@ -0,0 +1,65 @@
+//! Spans and events for the `tracing` feature.
+
+use crate::service::BoxFuture;
+use http::Request;
+
+/// Emit a `tracing` event at `$level`, if the `tracing` feature is enabled.
+#[cfg(feature = "local")]
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+#[cfg(feature = "local")]
+pub(crate) use event;
+
+/// Call `call` with `request` in a span for the invocation, recording the response status or the
+/// error the invocation failed with.
+#[cfg(feature = "tracing")]
+pub(crate) fn invocation<F>(request: Request<Vec<u8>>, call: F) -> BoxFuture
+where
+    F: FnOnce(Request<Vec<u8>>) -> BoxFuture,
+{
+    use futures::FutureExt;
+    use lambda_runtime::Context;
+    use tracing::Instrument;
+
+    let span = tracing::info_span!(
+        "invocation",
+        request_id = request
+            .extensions()
+            .get::<Context>()
+            .map(|context| context.aws_request_id.as_str()),
+        method = request.method().as_str(),
+        path = request.uri().path(),
+        trace_id = request
+            .extensions()
+            .get::<crate::TraceContext>()
+            .map(crate::TraceContext::trace_id),
+        status = tracing::field::Empty,
+    );
+    let response = span.in_scope(|| call(request));
+    let record = span.clone();
+    response
+        .map(move |result| {
+            match &result {
+                Ok(response) => {
+                    record.record("status", response.status().as_u16());
+                }
+                Err(err) => tracing::error!(parent: &record, error = %err, "invocation failed"),
+            }
+            result
+        })
+        .instrument(span)
+        .boxed()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn invocation<F>(request: Request<Vec<u8>>, call: F) -> BoxFuture
+where
+    F: FnOnce(Request<Vec<u8>>) -> BoxFuture,
+{
+    call(request)
+}
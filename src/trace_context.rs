@@ -0,0 +1,200 @@
+use http::HeaderMap;
+use std::fmt;
+
+const TRACEPARENT: &str = "traceparent";
+const X_AMZN_TRACE_ID: &str = "x-amzn-trace-id";
+
+/// The distributed tracing context a request was sent with, from its W3C `traceparent` header or
+/// its AWS X-Ray `X-Amzn-Trace-Id` header.
+///
+/// Handlers can get it with
+/// [`RequestExt::trace_context`](trait.RequestExt.html#tymethod.trace_context); it is also a
+/// request extension, so frameworks run with this crate can extract it. Trace IDs are always in
+/// the W3C format of 32 lowercase hex digits, so X-Ray trace IDs like
+/// `1-5759e988-bd862e3fe1be46a994272793` become `5759e988bd862e3fe1be46a994272793`.
+///
+/// # Example
+///
+/// ```rust
+/// # use http::{Request, Response};
+/// use lambda_http_local::{testing, RequestExt};
+///
+/// let handler = |request: Request<&[u8]>| {
+///     let trace = request.trace_context().unwrap();
+///     Response::new(trace.child("b7ad6b7169203331").traceparent())
+/// };
+/// let request = Request::get("/")
+///     .header("traceparent", "00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01")
+///     .body(Vec::new())
+///     .unwrap();
+/// let response = testing::invoke(handler, request).unwrap();
+/// assert_eq!(
+///     response.body(),
+///     b"00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: String,
+    parent_id: Option<String>,
+    sampled: Option<bool>,
+}
+
+impl TraceContext {
+    /// Read the trace context from request headers, preferring `traceparent` over
+    /// `X-Amzn-Trace-Id`. Returns `None` if neither header is present and valid.
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Option<TraceContext> {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        header(TRACEPARENT)
+            .and_then(TraceContext::parse_traceparent)
+            .or_else(|| header(X_AMZN_TRACE_ID).and_then(TraceContext::parse_xray))
+    }
+
+    /// Parse a W3C `traceparent` header, such as
+    /// `00-0af7651916cd43dd8448eb211c80319c-00f067aa0ba902b7-01`.
+    #[must_use]
+    pub fn parse_traceparent(value: &str) -> Option<TraceContext> {
+        let mut fields = value.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let parent_id = fields.next()?;
+        let flags = fields.next()?;
+        // Later versions may add fields, but version 00 has exactly four.
+        if !is_hex(version, 2) || version == "ff" || (version == "00" && fields.next().is_some()) {
+            return None;
+        }
+        if !is_id(trace_id, 32) || !is_id(parent_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        Some(TraceContext {
+            trace_id: trace_id.to_owned(),
+            parent_id: Some(parent_id.to_owned()),
+            sampled: Some(flags & 1 == 1),
+        })
+    }
+
+    /// Parse an AWS X-Ray `X-Amzn-Trace-Id` header, such as
+    /// `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`.
+    #[must_use]
+    pub fn parse_xray(value: &str) -> Option<TraceContext> {
+        let mut root = None;
+        let mut parent_id = None;
+        let mut sampled = None;
+        for field in value.split(';') {
+            match field.trim().split_once('=') {
+                Some(("Root", value)) => root = Some(value),
+                Some(("Parent", value)) if is_id(value, 16) => parent_id = Some(value.to_owned()),
+                Some(("Sampled", "1")) => sampled = Some(true),
+                Some(("Sampled", "0")) => sampled = Some(false),
+                _ => {}
+            }
+        }
+        let mut root = root?.split('-');
+        let (version, time, id) = (root.next()?, root.next()?, root.next()?);
+        if version != "1" || !is_hex(time, 8) || !is_hex(id, 24) || root.next().is_some() {
+            return None;
+        }
+        let trace_id = format!("{time}{id}");
+        if !is_id(&trace_id, 32) {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id,
+            parent_id,
+            sampled,
+        })
+    }
+
+    /// The ID of the trace, as 32 lowercase hex digits.
+    #[must_use]
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// The ID of the span the request was sent from, as 16 lowercase hex digits, if known.
+    #[must_use]
+    pub fn parent_id(&self) -> Option<&str> {
+        self.parent_id.as_deref()
+    }
+
+    /// Whether the caller sampled the trace, if it decided.
+    #[must_use]
+    pub fn sampled(&self) -> Option<bool> {
+        self.sampled
+    }
+
+    /// The trace context for a span with `span_id` in the same trace, for propagating the trace
+    /// to requests made while handling this one.
+    ///
+    /// # Panics
+    ///
+    /// If `span_id` is not 16 lowercase hex digits, this function will panic.
+    #[must_use]
+    pub fn child(&self, span_id: &str) -> TraceContext {
+        assert!(is_id(span_id, 16), "invalid span ID: {:?}", span_id);
+        TraceContext {
+            trace_id: self.trace_id.clone(),
+            parent_id: Some(span_id.to_owned()),
+            sampled: self.sampled,
+        }
+    }
+
+    /// Format the trace context as a W3C `traceparent` header value. An unknown parent ID is
+    /// formatted as all zeros, which receivers treat as invalid.
+    #[must_use]
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{}",
+            self.trace_id,
+            self.parent_id.as_deref().unwrap_or("0000000000000000"),
+            if self.sampled == Some(true) {
+                "01"
+            } else {
+                "00"
+            }
+        )
+    }
+
+    /// Format the trace context as an AWS X-Ray `X-Amzn-Trace-Id` header value.
+    #[must_use]
+    pub fn xray_header(&self) -> String {
+        let mut header = format!("Root={}", self.xray_trace_id());
+        if let Some(parent_id) = &self.parent_id {
+            header.push_str(";Parent=");
+            header.push_str(parent_id);
+        }
+        match self.sampled {
+            Some(true) => header.push_str(";Sampled=1"),
+            Some(false) => header.push_str(";Sampled=0"),
+            None => {}
+        }
+        header
+    }
+}
+
+impl TraceContext {
+    /// The trace ID in the X-Ray format, like `1-5759e988-bd862e3fe1be46a994272793`.
+    pub(crate) fn xray_trace_id(&self) -> String {
+        format!("1-{}-{}", &self.trace_id[..8], &self.trace_id[8..])
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.traceparent())
+    }
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Whether `value` is a valid trace or span ID of `len` hex digits, which must not be all zeros.
+fn is_id(value: &str, len: usize) -> bool {
+    is_hex(value, len) && value.bytes().any(|b| b != b'0')
+}
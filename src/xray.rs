@@ -0,0 +1,104 @@
+//! Segments for a local AWS X-Ray daemon.
+
+use crate::context::random;
+use crate::TraceContext;
+use http::{Request, Response};
+use serde_json::json;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The segment AWS Lambda records for an invocation, sent to the daemon once it completes.
+pub(crate) struct Segment {
+    name: String,
+    id: String,
+    trace: TraceContext,
+    parent_id: Option<String>,
+    start_time: f64,
+    method: String,
+    url: String,
+}
+
+impl Segment {
+    /// Start a segment for an invocation with `request`, continuing the trace of its
+    /// `X-Amzn-Trace-Id` or `traceparent` header or starting a new one.
+    pub(crate) fn start<B>(name: &str, request: &Request<B>) -> Segment {
+        let id = format!("{:016x}", random());
+        let caller = TraceContext::from_headers(request.headers()).unwrap_or_else(new_trace);
+        Segment {
+            name: name.to_owned(),
+            trace: caller.child(&id),
+            parent_id: caller.parent_id().map(str::to_owned),
+            id,
+            start_time: now(),
+            method: request.method().to_string(),
+            url: request.uri().to_string(),
+        }
+    }
+
+    /// The trace header for the invocation, with the segment as the parent, as AWS Lambda passes
+    /// to functions.
+    pub(crate) fn trace_header(&self) -> String {
+        self.trace.xray_header()
+    }
+
+    /// Send the completed segment to the daemon at `daemon`.
+    ///
+    /// Like the X-Ray SDKs, this ignores errors, so tracing does not break the function when
+    /// the daemon is not running.
+    pub(crate) fn send(self, daemon: SocketAddr, response: Option<&Response<Vec<u8>>>) {
+        let mut segment = json!({
+            "name": self.name,
+            "id": self.id,
+            "trace_id": self.trace.xray_trace_id(),
+            "start_time": self.start_time,
+            "end_time": now(),
+            "origin": "AWS::Lambda::Function",
+            "http": {
+                "request": {
+                    "method": self.method,
+                    "url": self.url,
+                },
+            },
+        });
+        if let Some(parent_id) = self.parent_id {
+            segment["parent_id"] = parent_id.into();
+        }
+        match response {
+            Some(response) => {
+                let status = response.status();
+                segment["http"]["response"] = json!({ "status": status.as_u16() });
+                segment["error"] = status.is_client_error().into();
+                segment["fault"] = status.is_server_error().into();
+            }
+            None => segment["fault"] = true.into(),
+        }
+        let packet = format!("{{\"format\":\"json\",\"version\":1}}\n{segment}");
+        let bind_addr: SocketAddr = if daemon.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0; 16], 0).into()
+        };
+        if let Ok(socket) = UdpSocket::bind(bind_addr) {
+            socket.send_to(packet.as_bytes(), daemon).ok();
+        }
+    }
+}
+
+/// Start a new, sampled trace.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn new_trace() -> TraceContext {
+    // X-Ray trace IDs start with the time the trace started, in seconds.
+    let header = format!(
+        "Root=1-{:08x}-{:016x}{:08x};Sampled=1",
+        now() as u32,
+        random(),
+        random() as u32
+    );
+    TraceContext::parse_xray(&header).expect("generated an invalid trace ID")
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}